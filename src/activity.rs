@@ -0,0 +1,85 @@
+use ratatui::layout::Rect;
+use ratatui::style::Style;
+use ratatui::text::Line;
+use ratatui::widgets::{Bar, BarChart, BarGroup, Block, Borders};
+use ratatui::Frame;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::sniffer::{self, Activity};
+use crate::theme::Theme;
+
+/// 2.4 GHz channels
+const CHANNELS: usize = 14;
+
+/// Packets per channel over a sliding window, fed from the packet monitor output
+pub struct ChannelActivity {
+    window: Duration,
+    samples: VecDeque<(Instant, Activity)>,
+}
+
+impl ChannelActivity {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Count the line if it reports channel activity
+    pub fn record(&mut self, line: &str) {
+        if let Some(activity) = sniffer::activity(line) {
+            self.samples.push_back((Instant::now(), activity));
+        }
+        self.expire();
+    }
+
+    fn expire(&mut self) {
+        while let Some((time, _)) = self.samples.front() {
+            if time.elapsed() <= self.window {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// Packets and deauth frames per channel within the window
+    fn counts(&self) -> [(u64, u64); CHANNELS] {
+        let mut counts = [(0, 0); CHANNELS];
+        for (_, activity) in self.samples.iter() {
+            let count = &mut counts[activity.channel as usize - 1];
+            count.0 += activity.packets;
+            count.1 += activity.deauths;
+        }
+        counts
+    }
+
+    pub fn render(&mut self, f: &mut Frame, area: Rect, theme: &Theme) {
+        self.expire();
+
+        // Channels with deauth frames stand out
+        let bars: Vec<Bar> = self
+            .counts()
+            .iter()
+            .enumerate()
+            .map(|(i, (packets, deauths))| {
+                let color = if *deauths > 0 { theme.focus } else { theme.accent };
+                Bar::default()
+                    .value(*packets)
+                    .label(Line::from(format!("{}", i + 1)))
+                    .style(Style::default().fg(color))
+            })
+            .collect();
+
+        // Make the bars use the available width
+        let width = (area.width.saturating_sub(2) / CHANNELS as u16).saturating_sub(1).max(1);
+
+        let title = format!("Channels, last {} s", self.window.as_secs());
+        let chart = BarChart::default()
+            .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(theme.border)).title(title))
+            .data(BarGroup::default().bars(&bars))
+            .bar_width(width)
+            .bar_gap(1);
+        f.render_widget(chart, area);
+    }
+}
@@ -1,6 +1,7 @@
 use crossterm::{
+    cursor::Show,
     event::{
-        self, Event, KeyCode, KeyEventKind, KeyModifiers,
+        self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEventKind, KeyModifiers,
     },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
@@ -8,46 +9,100 @@ use crossterm::{
 use ratatui::widgets::{Scrollbar, ScrollbarOrientation, ScrollbarState};
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
-    style::{Color, Modifier, Style},
-    text::Line,
-    widgets::{Block, Borders, Paragraph},
-    Frame, Terminal,
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{
+        block::{Position, Title},
+        Block, Borders, Clear, Padding, Paragraph, Widget, Wrap,
+    },
+    Frame, Terminal, TerminalOptions, Viewport,
 };
-use regex::RegexSet;
+use chrono::{DateTime, Local, TimeZone};
+use serde::{Deserialize, Serialize};
 use std::{
     collections::VecDeque,
-    io::{self, Stdout},
+    fs::{self, OpenOptions},
+    io::{self, IsTerminal, Stdout, Write},
+    path::PathBuf,
+    thread,
     time::{Duration, Instant},
 };
-use std::io::ErrorKind;
 use crossterm::event::KeyEvent;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
-
-lazy_static::lazy_static! {
-    static ref REGSET: RegexSet = RegexSet::new([
-        r"^(\x60|\.|:|/|-|\+|o|s|h|d|y| ){50,}",      // ASCII Chicken
-        r"^# ",                                       // # command
-        r"(?m)^\s*(-|=|#)+\s*$",                      // ================
-        r"^\[ =+ ?.* ?=+ \]",                         // [ ===== Headline ====== ]
-        r"^> \w+",                                    // > Finished job
-        r"^(ERROR)|(WARNING): ",                      // ERROR: something went wrong :(
-        r"^.*: +.*",                                  // -arg: value
-        r"^\[.*\]",                                   // [default=something]
-        r"(?m)^\S+( \[?-\S*( <\S*>)?\]?)*\s*$",       // command [-arg <value>] [-flag]
-    ]).unwrap();
-
-    static ref COLORSET: [(Color, Modifier);9] = [
-        (Color::White, Modifier::empty()),  // # command
-        (Color::White, Modifier::BOLD),   // # command
-        (Color::Blue, Modifier::empty()),   // ================
-        (Color::Yellow, Modifier::BOLD),  // [ ===== Headline ====== ]
-        (Color::Cyan, Modifier::empty()),   // > Finished job
-        (Color::Red, Modifier::empty()),    // ERROR: something went wrong :(
-        (Color::Green, Modifier::empty()),  // -arg value
-        (Color::Green, Modifier::BOLD),   // [default=something]
-        (Color::Yellow, Modifier::empty()), // command [-arg <value>] [-flag]
-    ];
+use tokio::sync::mpsc::{error::TryRecvError, UnboundedSender};
+
+use crate::activity::ChannelActivity;
+use crate::attack::{self, Tracker};
+use crate::bell::{Alert, Bell};
+use crate::chain;
+use crate::countdown::Timers;
+use crate::channel;
+use crate::command::{self, Command};
+use crate::config::Config;
+use crate::database::{self, Browser, Database};
+use crate::device_settings::{self, SettingsEditor};
+use crate::error::{Error, Result};
+use crate::events;
+use crate::firmware;
+use crate::gps::{self, Gps};
+use crate::handler::{self, LogAction, Meta};
+use crate::highlight;
+use crate::i18n;
+use crate::killring::KillRing;
+use crate::legend;
+use crate::output;
+use crate::paste;
+use crate::picker::{self, PortPicker};
+use crate::pipe::Pipe;
+use crate::port;
+use crate::preferences::{self, Preferences};
+use crate::presenter::Presenter;
+use crate::protocol::{BackendEvent, UiEvent};
+use crate::raw;
+use crate::redact::Redactor;
+use crate::scans::{self, Scans};
+use crate::session::{self, SavedLine, Snapshot};
+use crate::shell::{self, Leave};
+use crate::ssids::{self, SsidCommand, SsidManager};
+use crate::status::{self, SharedStatus};
+use crate::template::{self, Form, RecentMacs};
+use crate::theme::{ColorDepth, Theme};
+use crate::title::{self, TerminalTitle};
+use crate::undo::{Edit, Undo};
+use crate::watches::{self, WatchCommand, Watches};
+
+/// How often the plain UI follows the state of the backend
+const PLAIN_TICK: Duration = Duration::from_millis(100);
+
+/// How often the TUI looks for output and keys unless the config sets another
+pub const TICK_RATE: Duration = Duration::from_millis(15);
+
+/// Without output or keys the screen is still redrawn this often, for countdowns, durations and the status bar
+const IDLE_REDRAW: Duration = Duration::from_secs(1);
+
+/// Lines taken from the backend per frame, a burst shows up at once without blocking the keys
+const LINES_PER_FRAME: usize = 1000;
+
+/// Countdowns shown at once, `:timers` lists all of them
+const MAX_TIMERS: usize = 3;
+
+/// How often output is taken in while the shell or editor has the terminal
+const AWAY_POLL: Duration = Duration::from_millis(50);
+
+/// Height of the input line in inline mode
+const INLINE_HEIGHT: u16 = 1;
+
+/// Columns to move per horizontal scroll step
+const HSCROLL_STEP: usize = 8;
+
+/// Commands loaded from a device's history file
+const HISTORY_SIZE: usize = 1000;
+
+/// History file of a device, e.g. `~/.local/share/huhnitor/history/10c4-ea60-0001`
+fn history_file(port_name: &str) -> Option<PathBuf> {
+    let dir = dirs::data_dir()?.join("huhnitor").join("history");
+    fs::create_dir_all(&dir).ok()?;
+    Some(dir.join(port::device_key(port_name)))
 }
 
 struct InterruptHandler {
@@ -79,6 +134,8 @@ impl InterruptHandler {
 struct History {
     hist: Vec<String>,
     index: usize,
+    /// File the commands are saved to
+    file: Option<PathBuf>,
 }
 
 impl History {
@@ -86,8 +143,22 @@ impl History {
         Self {
             hist: vec!["".to_string()],
             index: 0,
+            file: None,
         }
     }
+
+    /// History saved in the file, new commands are appended to it
+    fn load(file: PathBuf) -> Self {
+        let mut history = Self::new();
+        let text = fs::read_to_string(&file).unwrap_or_default();
+        let lines: Vec<&str> = text.lines().filter(|line| !line.is_empty()).collect();
+        for line in &lines[lines.len().saturating_sub(HISTORY_SIZE)..] {
+            history.hist.insert(history.hist.len() - 1, line.to_string());
+        }
+        history.file = Some(file);
+        history.reset();
+        history
+    }
     fn prev_cmd(&mut self) -> String {
         if self.index > 0 {
             self.index -= 1;
@@ -100,14 +171,238 @@ impl History {
         }
         self.hist[self.index].to_string()
     }
-    fn add(&mut self, entry: String) {
-        self.hist.insert(self.hist.len() - 1, entry)
+    fn add(&mut self, entry: String) -> io::Result<()> {
+        let save = !entry.trim().is_empty();
+        self.hist.insert(self.hist.len() - 1, entry);
+
+        match &self.file {
+            Some(file) if save => {
+                let mut file = OpenOptions::new().create(true).append(true).open(file)?;
+                writeln!(file, "{}", self.hist[self.hist.len() - 2])
+            }
+            _ => Ok(()),
+        }
     }
     fn reset(&mut self) {
         self.index = self.hist.len() - 1
     }
 }
 
+/// How sent commands show up in the Messages box
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Echo {
+    /// Don't show sent commands, for firmware that echoes input itself
+    Off,
+    /// Show sent commands like received lines
+    Plain,
+    /// Show sent commands in a distinct style
+    Highlight,
+}
+
+impl Echo {
+    pub fn next(self) -> Self {
+        match self {
+            Echo::Off => Echo::Plain,
+            Echo::Plain => Echo::Highlight,
+            Echo::Highlight => Echo::Off,
+        }
+    }
+}
+
+impl std::str::FromStr for Echo {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(Echo::Off),
+            "plain" | "on" => Ok(Echo::Plain),
+            "highlight" => Ok(Echo::Highlight),
+            _ => Err(format!("Unknown echo mode '{}', expected off, plain or highlight", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Echo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Echo::Off => "off",
+            Echo::Plain => "plain",
+            Echo::Highlight => "highlight",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What Ctrl-C does
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CtrlC {
+    /// Only send `stop` to the device
+    Stop,
+    /// Ask whether to quit Huhnitor
+    Prompt,
+    /// Quit Huhnitor right away
+    Quit,
+    /// Send `stop`, quit when pressed 3 times within 3 seconds
+    Spam,
+}
+
+/// Preferences that can be changed while Huhnitor is running
+#[derive(Clone, Copy, Debug)]
+pub struct Settings {
+    /// How sent commands are echoed
+    pub echo: Echo,
+    /// Wrap long lines instead of scrolling horizontally
+    pub wrap: bool,
+    /// Show the time each line arrived
+    pub timestamps: bool,
+    /// Number the lines of the Messages box
+    pub line_numbers: bool,
+    /// Lines kept in the Messages box, `None` keeps all
+    pub scrollback: Option<usize>,
+    /// Name of the color theme
+    pub theme: &'static str,
+    /// Colors the terminal can show, themes picked while running are fitted to it
+    pub color_depth: ColorDepth,
+    /// Ask before quitting
+    pub confirm_exit: bool,
+    /// What Ctrl-C does
+    pub ctrl_c: CtrlC,
+    /// Show the channel activity chart
+    pub channels: bool,
+    /// Time span of the channel activity chart
+    pub channel_window: Duration,
+    /// Save the command history per device
+    pub history: bool,
+    /// Ask before sending destructive commands
+    pub confirm_commands: bool,
+    /// Mark lines as sent or received
+    pub direction_markers: bool,
+    /// Show a separator when nothing was received or sent for this long
+    pub gap: Option<Duration>,
+    /// Save the session this often so it can be restored after a crash
+    pub autosave: Option<Duration>,
+    /// Show the port and connection state in the terminal title
+    pub title: bool,
+    /// Add the running attack to the terminal title
+    pub title_attack: bool,
+    /// Plain line based UI for screen readers
+    pub accessible: bool,
+    /// Pastes longer than this many bytes are confirmed first
+    pub paste_limit: usize,
+    /// Key that leaves raw mode
+    pub raw_escape: raw::Escape,
+    /// How often the TUI looks for output and keys
+    pub tick_rate: Duration,
+}
+
+/// What became of a command sent to the device
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Delivery {
+    /// Nothing received since it was sent
+    Pending,
+    /// The device echoed or responded
+    Acked,
+    /// Writing it to the port failed, or it was never sent
+    Failed,
+}
+
+/// Where a line in the Messages box comes from
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Origin {
+    /// Output of the device
+    Received,
+    /// Typed by the user
+    Sent,
+    /// Information and errors of Huhnitor itself
+    Local,
+    /// Separator for a pause in the output of the device
+    Gap,
+    /// Note or numbered mark of the user, added with `:note` or `:mark`
+    Note,
+}
+
+impl Origin {
+    /// Origin of a line without context, e.g. when restoring. The Huhnitor's own messages have a prefix.
+    fn of(line: &str) -> Self {
+        if line.starts_with("> ") || line.starts_with("ERROR: ") || line.starts_with("WARNING: ") {
+            Origin::Local
+        } else if line.starts_with("NOTE: ") || line.starts_with("MARK #") {
+            Origin::Note
+        } else {
+            Origin::Received
+        }
+    }
+
+    /// Marker shown in front of the line
+    fn marker(self) -> &'static str {
+        match self {
+            Origin::Received => "← ",
+            Origin::Sent => "→ ",
+            Origin::Local | Origin::Gap | Origin::Note => "  ",
+        }
+    }
+}
+
+/// A line in the Messages box
+struct Message {
+    text: String,
+    origin: Origin,
+    /// State of a sent command, `None` for everything else
+    delivery: Option<Delivery>,
+    /// Highlight rule the line matches, classified once instead of on every frame
+    category: Option<usize>,
+    /// When the line arrived
+    time: DateTime<Local>,
+}
+
+impl Message {
+    fn new(text: String, origin: Origin) -> Self {
+        let category = match origin {
+            Origin::Gap | Origin::Note => None,
+            _ => highlight::classify(&text),
+        };
+        Self { text, origin, delivery: None, category, time: Local::now() }
+    }
+}
+
+/// Rendered size of the lines in the Messages box, kept between frames so only new lines are measured
+#[derive(Default)]
+struct Measured {
+    /// Box width, wrapping, direction markers, annotations, timestamps and width of the line numbers the lines were measured with
+    key: (u16, bool, bool, bool, bool, usize),
+    /// Rows of each line
+    heights: Vec<usize>,
+    /// Columns of each line
+    widths: Vec<usize>,
+    max_width: usize,
+}
+
+impl Measured {
+    fn push(&mut self, height: usize, width: usize) {
+        self.heights.push(height);
+        self.widths.push(width);
+        self.max_width = self.max_width.max(width);
+    }
+
+    /// Forget the sizes of the first lines, after they were dropped from the scrollback
+    fn drop_front(&mut self, count: usize) {
+        let count = count.min(self.heights.len());
+        self.heights.drain(..count);
+        self.widths.drain(..count);
+        self.max_width = self.widths.iter().copied().max().unwrap_or(0);
+    }
+
+    /// Measure the lines from `index` on again, e.g. after their text changed
+    fn forget(&mut self, index: usize) {
+        self.heights.truncate(index);
+        self.widths.truncate(index);
+        self.max_width = self.widths.iter().copied().max().unwrap_or(0);
+    }
+}
+
 #[derive(PartialEq)]
 enum InputMode {
     Normal,
@@ -119,37 +414,409 @@ pub struct App {
     /// Current value of the input box
     input: String,
     /// All application output
-    output: Vec<String>,
+    output: Vec<Message>,
     /// History of commands entered
     cmd_history: History,
     /// User-controlled scrolling
     manual_scroll: bool,
+    /// Lines added below the view while scrolled up
+    unseen: usize,
     /// Scrollbar State
     scrollbar: ScrollbarState,
-    /// Scroll position
-    scroll_pos: usize,
-    /// Cursor Position
+    /// First visible line and the row within that line, so the view stays put when lines reflow
+    scroll_anchor: (usize, usize),
+    /// Rows to scroll by on the next frame
+    scroll_delta: isize,
+    /// Size of the lines in the Messages box
+    measured: Measured,
+    /// Lines dropped from the top of the Messages box, so the remaining ones keep their numbers
+    dropped: usize,
+    /// Cursor Position, a byte offset into the input
     cursor_pos: usize,
     /// Input Mode
     input_mode: InputMode,
+    /// Port selection popup, if open
+    picker: Option<PortPicker>,
+    /// Runtime preferences
+    settings: Settings,
+    /// Horizontal scroll position, when wrapping is off
+    hscroll_pos: usize,
+    /// Horizontal scrollbar State
+    hscrollbar: ScrollbarState,
+    /// Colors
+    theme: Theme,
+    /// Print output into the normal terminal scrollback instead of the alternate screen
+    inline: bool,
+    /// Number of messages already printed to the scrollback in inline mode
+    printed: usize,
+    /// Terminal title, while the App sets it
+    title: Option<TerminalTitle>,
+    /// When the session was last saved
+    last_save: Instant,
+    /// Autosave writing in the background
+    saving: Option<thread::JoinHandle<io::Result<()>>>,
+    /// Output or history changed since the session was last saved
+    unsaved: bool,
+    /// When a line was last received or sent, for the gap separators
+    last_activity: Option<Instant>,
+    /// Echoed commands nothing has been received for yet
+    unacked: Vec<usize>,
+    /// Waiting for the user to confirm quitting
+    confirm_quit: bool,
+    /// Command waiting for the user to confirm sending it, with the reason
+    confirm_send: Option<(String, &'static str)>,
+    /// Pasted text waiting for the user to confirm its size
+    confirm_paste: Option<String>,
+    /// Connection state reported by the backend
+    status: SharedStatus,
+    /// Packets per channel from the packet monitor
+    activity: ChannelActivity,
+    /// Attack the deauther is running
+    attack: Tracker,
+    /// Countdowns of commands sent with a duration, e.g. `scan -t 30`
+    timers: Timers,
+    /// SSID list of the deauther
+    ssids: SsidManager,
+    /// SSID list popup is open
+    show_ssids: bool,
+    /// Earlier versions of the input line
+    undo: Undo,
+    /// The last key was an undo, so Ctrl-Z pressed once too often doesn't suspend
+    undoing: bool,
+    /// Text deleted with the kill commands
+    kill_ring: KillRing,
+    /// Port whose device the command history belongs to
+    history_port: Option<String>,
+    /// Settings of the deauther
+    device_settings: SettingsEditor,
+    /// Settings editor popup is open
+    show_settings: bool,
+    /// Preferences popup, if open
+    preferences: Option<Preferences>,
+    /// Config file the preferences are written to, the default one if not given
+    config_path: Option<PathBuf>,
+    /// Rules for what to hide in scan exports, the error if they don't parse
+    redactor: std::result::Result<Redactor, String>,
+    /// Legend of the highlight rules is open
+    show_legend: bool,
+    /// Annotate lines with the highlight rule they matched
+    show_matches: bool,
+    /// Placeholders of a submitted command still to fill in
+    template: Option<Form>,
+    /// MAC addresses offered for placeholders
+    recent_macs: RecentMacs,
+    /// Results of the last two scans
+    scans: Scans,
+    /// Diff of the last two scans is open
+    show_scans: bool,
+    /// Scan results of all sessions
+    database: Option<Database>,
+    /// Panel browsing the scan database
+    db_browser: Option<Browser>,
+    /// Position scan results are tagged with
+    gps: Option<Gps>,
+    /// Command fed with the received lines
+    pipe: Option<Pipe>,
+    /// Values extracted from the output for the sidebar
+    watches: Watches,
+    /// Alerts for unattended sessions
+    bell: Bell,
+    /// Keystrokes go to the device right away instead of into the input line
+    raw: bool,
+    /// Overlay of the pressed keys and a bigger input line, for screen recordings
+    presenter: Option<Presenter>,
+    /// Attached to a session of `huhnitor daemon`, which goes on without the UI
+    attached: bool,
+    /// The user left the session to the daemon
+    detached: bool,
+    /// The terminal is handed to the shell once the key or command is handled
+    leave: Option<Leave>,
+    /// Unfinished line of the device, shown in the input box in raw mode
+    partial: String,
 }
 
 impl<'a> App {
-    pub fn new() -> Self {
+    pub fn new(settings: Settings, theme: Theme) -> Self {
         Self {
             input: String::default(),
             output: Vec::new(),
             cmd_history: History::new(),
             manual_scroll: false,
+            unseen: 0,
             scrollbar: ScrollbarState::default(),
-            scroll_pos: 0,
+            scroll_anchor: (0, 0),
+            scroll_delta: 0,
+            measured: Measured::default(),
+            dropped: 0,
             cursor_pos: 0,
             input_mode: InputMode::Insert,
+            picker: None,
+            settings,
+            hscroll_pos: 0,
+            hscrollbar: ScrollbarState::default(),
+            theme,
+            inline: false,
+            printed: 0,
+            title: None,
+            last_save: Instant::now(),
+            saving: None,
+            unsaved: false,
+            last_activity: None,
+            unacked: Vec::new(),
+            confirm_quit: false,
+            confirm_send: None,
+            confirm_paste: None,
+            status: SharedStatus::default(),
+            activity: ChannelActivity::new(settings.channel_window),
+            attack: Tracker::default(),
+            timers: Timers::default(),
+            ssids: SsidManager::new(),
+            show_ssids: false,
+            undo: Undo::default(),
+            undoing: false,
+            kill_ring: KillRing::default(),
+            history_port: None,
+            device_settings: SettingsEditor::new(),
+            show_settings: false,
+            preferences: None,
+            config_path: None,
+            redactor: Ok(Redactor::default()),
+            show_legend: false,
+            show_matches: false,
+            template: None,
+            recent_macs: RecentMacs::default(),
+            scans: Scans::default(),
+            show_scans: false,
+            database: None,
+            db_browser: None,
+            gps: None,
+            pipe: None,
+            watches: Watches::default(),
+            bell: Bell::default(),
+            raw: false,
+            presenter: None,
+            attached: false,
+            detached: false,
+            leave: None,
+            partial: String::new(),
+        }
+    }
+
+    /// Share the connection state with the backend
+    pub fn set_status(&mut self, status: SharedStatus) {
+        self.status = status;
+    }
+
+    /// Hide what the redaction rules match in scan exports, invalid rules refuse any export
+    pub fn set_redactor(&mut self, redactor: std::result::Result<Redactor, String>) {
+        self.redactor = redactor;
+    }
+
+    /// Write preferences changed with `:settings` to this config file instead of the default one
+    pub fn set_config_path(&mut self, path: Option<PathBuf>) {
+        self.config_path = path;
+    }
+
+    /// Run without the alternate screen, see `flush_inline`
+    pub fn set_inline(&mut self, inline: bool) {
+        self.inline = inline;
+    }
+
+    /// Add a message of the Huhnitor to the Messages box
+    fn push(&mut self, text: String) {
+        let origin = Origin::of(&text);
+        self.add(text, origin);
+    }
+
+    /// Add a line to the Messages box
+    fn add(&mut self, text: String, origin: Origin) {
+        let msg = Message::new(text, origin);
+        if origin != Origin::Sent {
+            self.bell.line(msg.category);
+        }
+        self.output.push(msg);
+        self.unsaved = true;
+        if self.manual_scroll {
+            self.unseen += 1;
+        }
+    }
+
+    /// Start the Messages box with some lines, e.g. the banner
+    pub fn greet(&mut self, lines: Vec<String>) {
+        for line in lines {
+            self.push(line);
+        }
+        // A session without anything else doesn't replace the saved one
+        self.unsaved = false;
+    }
+
+    /// Show the connection state in the terminal title, turned off if the terminal can't take it
+    fn update_title(&mut self) {
+        let status = status::get(&self.status);
+        let attack = self.attack.kind().filter(|_| self.settings.title_attack);
+        let text = title::text(status.port.as_deref(), status.stale, attack);
+        if let Some(Err(e)) = self.title.as_mut().map(|title| title.set(text)) {
+            self.title = None;
+            self.report(Error::Io(e));
+        }
+    }
+
+    /// Scrollback and history for the session file, without the gap separators
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            saved: 0,
+            port: status::get(&self.status).port,
+            lines: self
+                .output
+                .iter()
+                .filter(|msg| msg.origin != Origin::Gap)
+                .map(|msg| SavedLine { text: msg.text.clone(), origin: Some(msg.origin), sent: false })
+                .collect(),
+            history: self.cmd_history.hist.iter().filter(|cmd| !cmd.is_empty()).cloned().collect(),
+        }
+    }
+
+    /// Save the session in the background, writing a long scrollback would hold up the UI
+    fn save_session(&mut self) {
+        let mut snapshot = self.snapshot();
+        self.saving = Some(thread::spawn(move || session::save(&mut snapshot)));
+        self.last_save = Instant::now();
+        self.unsaved = false;
+    }
+
+    /// Collect the result of the background save, `wait` for it to be done. Autosaving stops if it failed.
+    fn saved(&mut self, wait: bool) {
+        if !wait && !self.saving.as_ref().is_some_and(|saving| saving.is_finished()) {
+            return;
+        }
+        let result = match self.saving.take().map(thread::JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err(io::Error::other("the save panicked")),
+            None => return,
+        };
+        if let Err(e) = result {
+            self.settings.autosave = None;
+            self.push(format!("ERROR: {}", i18n::format("save_failed", &[&e])));
+        }
+    }
+
+    /// Attach to a session of `huhnitor daemon` instead of owning the port, `:detach` leaves it running.
+    /// The scan database, GPS, `:pipe` and autosave stay in the UI, so they only work while one is attached.
+    #[cfg(unix)]
+    pub fn set_attached(&mut self, attached: bool) {
+        self.attached = attached;
+    }
+
+    /// Turn presenter mode on or off, it needs the room of the full terminal UI
+    pub fn set_presenter(&mut self, on: bool) {
+        if on && (self.inline || self.settings.accessible) {
+            return self.push(format!("ERROR: {}", i18n::text("presenter_plain")));
+        }
+        self.presenter = on.then(Presenter::default);
+    }
+
+    /// Enter or leave raw mode
+    pub fn set_raw(&mut self, raw: bool) {
+        // Plain mode reads whole lines from stdin
+        if raw && self.settings.accessible {
+            return self.push(format!("ERROR: {}", i18n::text("raw_plain")));
+        }
+        self.raw = raw;
+        let text = if raw { i18n::format("raw_on", &[&self.settings.raw_escape]) } else { i18n::text("raw_off").to_string() };
+        self.push(format!("> {}", text));
+    }
+
+    /// Ring the bell for these alerts, as set in the config file
+    pub fn set_bell(&mut self, bell: Bell) {
+        self.bell = bell;
+    }
+
+    /// Follow the position of a GPS to tag scan results with
+    pub fn start_gps(&mut self, source: gps::Source) {
+        self.gps = Some(Gps::start(source));
+    }
+
+    /// Keep scan results in the database at this path
+    pub fn open_database(&mut self, path: Option<PathBuf>) {
+        let path = match path {
+            Some(path) => path,
+            None => return,
+        };
+        match Database::open(&path) {
+            Ok(db) => self.database = Some(db),
+            Err(e) => self.push(format!("ERROR: {}", i18n::format("database_failed", &[&path.display(), &e]))),
+        }
+    }
+
+    /// Load the scrollback and history of the last session
+    pub fn restore(&mut self, port: Option<&str>) {
+        let snapshot = match session::load(port) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                self.push(format!("ERROR: {}", i18n::format("restore_failed", &[&e])));
+                return;
+            }
+        };
+
+        let saved = Local.timestamp_opt(snapshot.saved as i64, 0).single();
+        for line in snapshot.lines.iter() {
+            // Files of older versions only know whether a line was typed
+            let origin = match line.origin {
+                Some(origin) => origin,
+                None if line.sent => Origin::Sent,
+                None => Origin::of(&line.text),
+            };
+            let mut msg = Message::new(line.text.clone(), origin);
+            msg.time = saved.unwrap_or(msg.time);
+            self.output.push(msg);
+        }
+        for cmd in snapshot.history.iter() {
+            let len = self.cmd_history.hist.len();
+            self.cmd_history.hist.insert(len - 1, cmd.clone());
+        }
+        self.cmd_history.reset();
+
+        let port = snapshot.port.as_deref().map_or(String::new(), |port| i18n::format("restored_port", &[&port]));
+        let restored = i18n::format("restored", &[&snapshot.lines.len(), &port, &output::duration(snapshot.age())]);
+        self.push(format!("> {}", restored));
+    }
+
+    /// Show the port picker, in auto mode newly plugged in devices are connected right away
+    pub fn open_port_picker(&mut self, auto: bool) {
+        let picker = PortPicker::new(auto, self.theme);
+
+        if self.inline {
+            // No room for a popup, but the picker still watches for new devices
+            for line in picker.lines() {
+                self.push(line);
+            }
+            self.push(format!("> {}", i18n::format("pick_port", &[&command::PREFIX])));
+        }
+
+        self.picker = Some(picker);
+    }
+
+    fn connect(&mut self, port: &str, input_tx: &UnboundedSender<UiEvent>) {
+        self.picker = None;
+        self.send(input_tx, format!("{}connect {}\r\n", command::PREFIX, port));
+    }
+
+    /// Pass a line on to the backend
+    fn send(&mut self, input_tx: &UnboundedSender<UiEvent>, line: String) {
+        if input_tx.send(UiEvent::SendLine(line)).is_err() {
+            self.report(Error::ChannelClosed("input"));
+        }
+    }
+
+    fn send_bytes(&mut self, input_tx: &UnboundedSender<UiEvent>, bytes: Vec<u8>) {
+        if input_tx.send(UiEvent::SendBytes(bytes)).is_err() {
+            self.report(Error::ChannelClosed("input"));
         }
     }
 
     fn delete_char(&mut self) {
         if self.cursor_pos != 0 {
+            self.undo.record(Edit::Delete, &self.input, self.cursor_pos);
             self.remove_char(self.cursor_pos)
         }
     }
@@ -157,225 +824,1811 @@ impl<'a> App {
     fn submit(&mut self) -> String {
         let entr_txt: String = self.input.drain(..).collect();
 
-        self.output.push(entr_txt.clone());
-        self.cmd_history.add(entr_txt.clone());
+        if self.settings.echo != Echo::Off {
+            self.output.push(Message::new(entr_txt.clone(), Origin::Sent));
+        }
+        self.last_activity = Some(Instant::now());
+        self.unsaved = true;
+        if let Err(e) = self.cmd_history.add(entr_txt.clone()) {
+            // Keep the history in memory only
+            self.cmd_history.file = None;
+            self.report(Error::Io(e));
+        }
         self.cmd_history.reset();
         self.cursor_reset();
+        self.undo.clear();
 
         entr_txt
     }
 
     fn put_char(&mut self, c: char) {
+        // Undo typing word by word
+        if c.is_whitespace() {
+            self.undo.split();
+        }
+        self.undo.record(Edit::Insert, &self.input, self.cursor_pos);
         self.input.insert(self.cursor_pos, c);
         self.cursor_right();
     }
 
-    fn cursor_left(&mut self) {
-        self.cursor_pos = self.cursor_pos.saturating_sub(1).clamp(0, self.input.len());
+    /// Put other text into the input line, e.g. from the history
+    fn replace_input(&mut self, text: String) {
+        if text != self.input {
+            self.undo.record(Edit::Replace, &self.input, self.cursor_pos);
+        }
+        self.input = text;
+        self.cursor_pos = self.input.len();
     }
 
-    fn cursor_right(&mut self) {
-        self.cursor_pos = self.cursor_pos.saturating_add(1).clamp(0, self.input.len());
+    fn undo(&mut self) {
+        if let Some((input, cursor_pos)) = self.undo.undo(&self.input, self.cursor_pos) {
+            self.input = input;
+            self.cursor_pos = cursor_pos;
+        }
     }
 
-    fn cursor_reset(&mut self) {
-        self.cursor_pos = 0
+    fn redo(&mut self) {
+        if let Some((input, cursor_pos)) = self.undo.redo(&self.input, self.cursor_pos) {
+            self.input = input;
+            self.cursor_pos = cursor_pos;
+        }
     }
 
-    fn scroll_up(&mut self) {
-        self.scroll_pos = self.scroll_pos.saturating_sub(1);
-        self.scrollbar = self.scrollbar.position(self.scroll_pos);
-        self.manual_scroll = true;
+    /// Start of the word before the cursor, words end at whitespace or, if `alnum`, at anything but letters and digits
+    fn word_start(&self, alnum: bool) -> usize {
+        let is_word = |c: char| if alnum { c.is_alphanumeric() } else { !c.is_whitespace() };
+        let before = self.input.get(..self.cursor_pos).unwrap_or_default();
+        before.trim_end_matches(|c| !is_word(c)).trim_end_matches(is_word).len()
     }
 
-    fn scroll_down(&mut self) {
-        self.scroll_pos = self.scroll_pos.saturating_add(1);
-        self.scrollbar = self.scrollbar.position(self.scroll_pos);
+    /// End of the word after the cursor
+    fn word_end(&self) -> usize {
+        let after = self.input.get(self.cursor_pos..).unwrap_or_default();
+        let rest = after.trim_start_matches(|c: char| !c.is_alphanumeric()).trim_start_matches(char::is_alphanumeric);
+        self.input.len() - rest.len()
     }
 
-    fn remove_char(&mut self, idx: usize) {
-        let left_idx = self.cursor_pos - 1;
+    /// Cut the text between `start` and `end` into the kill ring
+    fn kill(&mut self, start: usize, end: usize) {
+        if start >= end || !self.input.is_char_boundary(start) || !self.input.is_char_boundary(end) {
+            return;
+        }
+        self.undo.record(Edit::Replace, &self.input, self.cursor_pos);
+        let text = self.input.drain(start..end).collect();
+        self.kill_ring.kill(text, start < self.cursor_pos);
+        self.cursor_pos = start;
+    }
 
-        let left_part = self.input.chars().take(left_idx);
-        let right_part = self.input.chars().skip(idx);
+    /// Insert the latest killed text at the cursor
+    fn yank(&mut self) {
+        if !self.input.is_char_boundary(self.cursor_pos) {
+            return;
+        }
+        if let Some(text) = self.kill_ring.yank(self.cursor_pos) {
+            self.undo.record(Edit::Replace, &self.input, self.cursor_pos);
+            self.input.insert_str(self.cursor_pos, &text);
+            self.cursor_pos += text.len();
+        }
+    }
 
-        self.input = left_part.chain(right_part).collect();
-        self.cursor_left();
+    /// Take in pasted text, cleaned up and confirmed first if it is long
+    fn paste(&mut self, text: &str) {
+        let text = paste::sanitize(text);
+        if text.len() > self.settings.paste_limit {
+            self.confirm_paste = Some(text);
+        } else {
+            self.insert_text(&text);
+        }
     }
 
-    fn parse<S: AsRef<str>>(s: S) -> Line<'a> {
-        let matches: Vec<_> = REGSET.matches(s.as_ref()).into_iter().collect();
+    /// Insert text at the cursor, undone in one step
+    fn insert_text(&mut self, text: &str) {
+        if text.is_empty() || !self.input.is_char_boundary(self.cursor_pos) {
+            return;
+        }
+        self.undo.record(Edit::Replace, &self.input, self.cursor_pos);
+        self.input.insert_str(self.cursor_pos, text);
+        self.cursor_pos += text.len();
+    }
 
-        let (color, modf) = if !matches.is_empty() {
-            COLORSET[matches[0]]
-        } else {
-            (Color::White, Modifier::empty())
-        };
-        Line::styled(
-            s.as_ref().to_string(),
-            Style::default().fg(color).add_modifier(modf),
-        )
+    /// Paste the text of the system clipboard
+    fn paste_clipboard(&mut self) {
+        if self.settings.accessible {
+            self.push(format!("ERROR: {}", i18n::format("paste_needs_tui", &[&command::PREFIX])));
+            return;
+        }
+        match paste::clipboard() {
+            Ok(text) => self.paste(&text),
+            Err(e) => self.push(format!("ERROR: {}", e)),
+        }
     }
 
-    fn event_handler(&mut self, key: KeyEvent, spam_handler: &mut InterruptHandler, input_tx: &UnboundedSender<String>) -> io::Result<bool> {
-        if key.kind == KeyEventKind::Press && self.input_mode == InputMode::Insert {
-            match key.code {
-                KeyCode::Enter => {
-                    let entr_txt: String = self.submit();
-                    input_tx.send(format!("{}\r\n", entr_txt.clone())).unwrap();
-                    if entr_txt.to_uppercase() == "EXIT" {
-                        return Ok(false);
-                    }
-                }
-                KeyCode::Char('c')
-                if key.modifiers == KeyModifiers::from_name("CONTROL").unwrap() => {
-                    if input_tx.send("stop\n".to_string()).is_err() {
-                        self.output.push("Couldn't stop!".to_string());
-                    }
-                    if spam_handler.interrupted() {
-                        let res: io::Result<bool> = match input_tx.send("EXIT".to_string()) {
-                            Ok(_) => Ok(false),
-                            Err(e) => Err(io::Error::new(ErrorKind::Other, e.0))
-                        };
-                        return res;
-                    }
-                }
-                KeyCode::Char(c) => self.put_char(c),
-                KeyCode::Backspace => self.delete_char(),
-                KeyCode::Up => {
-                    self.input = self.cmd_history.prev_cmd();
-                    self.cursor_pos = self.input.len();
-                }
-                KeyCode::Down => {
-                    self.input = self.cmd_history.next_cmd();
-                    self.cursor_pos = self.input.len();
-                }
-                KeyCode::Left => self.cursor_left(),
-                KeyCode::Right => self.cursor_right(),
-                KeyCode::PageUp => self.scroll_up(),
-                KeyCode::PageDown => self.scroll_down(),
-                KeyCode::Esc => self.input_mode = InputMode::Normal,
+    /// Swap the text yanked right before for the next older kill
+    fn yank_pop(&mut self) {
+        if let Some((start, text)) = self.kill_ring.yank_pop() {
+            self.undo.record(Edit::Replace, &self.input, self.cursor_pos);
+            self.input.replace_range(start..self.cursor_pos, &text);
+            self.cursor_pos = start + text.len();
+        }
+    }
 
-                _ => (),
-            }
-        } else if key.kind == KeyEventKind::Press && self.input_mode == InputMode::Normal {
-            match key.code {
-                KeyCode::Up | KeyCode::PageUp => self.scroll_up(),
-                KeyCode::Down | KeyCode::PageDown => self.scroll_down(),
-                KeyCode::Esc => self.input_mode = InputMode::Insert,
-                _ => ()
+    /// Input line highlighted with the commands of the firmware
+    fn input_spans(&self) -> Vec<Span<'_>> {
+        let profile = status::get(&self.status).firmware.unwrap_or_else(firmware::generic);
+        let known = |word: &str| match word.strip_prefix(command::PREFIX) {
+            Some(name) => Some(command::exists(name)),
+            None if word.eq_ignore_ascii_case("exit") || word.eq_ignore_ascii_case(handler::PREFIX) => Some(true),
+            None if profile.commands().is_empty() => None,
+            None => Some(profile.commands().iter().any(|(n, _)| n.eq_ignore_ascii_case(word))),
+        };
+        highlight::tokenize(&self.input, known)
+            .into_iter()
+            .map(|(text, token)| Span::styled(text, self.theme.token(token)))
+            .collect()
+    }
+
+    /// Send a typed command to the device, its echo stays pending until the device responds
+    fn send_line(&mut self, input_tx: &UnboundedSender<UiEvent>, line: String) {
+        // Meta and local commands are carried out by the backend, the device won't answer them
+        let for_device = !handler::is_meta(&line) && command::parse(&line).is_none();
+        if let Some(index) = self.echo_of(&line).filter(|_| for_device) {
+            self.output[index].delivery = Some(Delivery::Pending);
+            self.unacked.push(index);
+        }
+        let profile = status::get(&self.status).firmware.unwrap_or_else(firmware::generic);
+        if line.trim().eq_ignore_ascii_case(profile.stop_command()) {
+            self.timers.clear();
+        }
+        if for_device {
+            for command in chain::split(&line) {
+                self.timers.start(&command);
             }
         }
-        Ok(true)
+        self.send(input_tx, format!("{}\r\n", line));
     }
 
-    /// Start render loop
-    pub async fn run(
-        mut self,
-        input_tx: UnboundedSender<String>,
-        mut output_rx: UnboundedReceiver<String>,
-        tick_rate: Duration,
-    ) -> io::Result<()> {
-        let mut spam_handler = InterruptHandler::new(2);
-        let stdout = io::stdout();
-        let backend = CrosstermBackend::new(stdout);
-        let mut terminal = Terminal::new(backend)?;
-        let mut prev_tick = Instant::now();
-        let mut res: io::Result<()> = Ok(());
-
-        // setup terminal
-        enable_raw_mode()?;
-        let mut stdout = io::stdout();
-        execute!(stdout, EnterAlternateScreen)?;
+    /// Latest echo of a typed command
+    fn echo_of(&self, line: &str) -> Option<usize> {
+        self.output.iter().rposition(|msg| msg.origin == Origin::Sent && msg.text.trim_end() == line.trim_end())
+    }
 
-        loop {
-            terminal.draw(|f| self.ui(f))?;
+    fn mark_failed(&mut self, line: &str) {
+        if let Some(index) = self.echo_of(line) {
+            self.output[index].delivery = Some(Delivery::Failed);
+            // The line got longer
+            self.measured.forget(index);
+            self.unacked.retain(|i| *i != index);
+        }
+    }
 
-            if let Ok(str) = output_rx.try_recv() {
-                self.output.push(str)
+    /// Separate output that arrives after a long pause, e.g. a stalled scan or a reboot
+    fn mark_gap(&mut self) {
+        let now = Instant::now();
+        let gap = self.last_activity.replace(now).map(|last| now - last);
+        if let (Some(gap), Some(min)) = (gap, self.settings.gap) {
+            if gap >= min {
+                let text = i18n::format("gap", &[&output::duration(gap)]);
+                let text = if self.settings.accessible { text } else { format!("── {} ──", text) };
+                self.output.push(Message::new(text, Origin::Gap));
             }
+        }
+    }
 
-            let timeout = tick_rate.saturating_sub(prev_tick.elapsed());
-            if event::poll(timeout)? {
-                if let Event::Key(key) = event::read()? {
-                    match self.event_handler(key, &mut spam_handler, &input_tx) {
-                        Ok(false) => break,
-                        Err(e) => {
-                            res = Err(e);
-                            break;
-                        }
-                        _ => ()
-                    }
-                }
+    /// Mark the pending commands as answered once the device sends something
+    fn acknowledge(&mut self) {
+        for index in self.unacked.drain(..) {
+            if let Some(msg) = self.output.get_mut(index) {
+                msg.delivery = Some(Delivery::Acked);
             }
+        }
+    }
 
-            if prev_tick.elapsed() >= tick_rate {
-                prev_tick = Instant::now();
+    /// Check a typed command before sending it, it may have to be confirmed first
+    fn send_checked(&mut self, input_tx: &UnboundedSender<UiEvent>, line: String) {
+        // Local and meta commands are carried out by the backend, not the firmware
+        if handler::is_meta(&line) || command::parse(&line).is_some() {
+            return self.send_line(input_tx, line);
+        }
+        let profile = status::get(&self.status).firmware.unwrap_or_else(firmware::generic);
+        let mut confirm = None;
+        for command in chain::split(&line) {
+            let check = firmware::check(profile, &command);
+            for warning in check.warnings {
+                self.push(format!("WARNING: {}", warning));
             }
+            confirm = confirm.or(check.confirm);
+        }
+        match confirm {
+            Some(reason) if self.settings.confirm_commands => self.confirm_send = Some((line, reason)),
+            _ => self.send_line(input_tx, line),
         }
-        Self::shutdown(terminal)?;
+    }
 
-        res
+    /// Question of the open confirmation prompt
+    fn prompt(&self) -> Option<String> {
+        if self.confirm_quit {
+            Some(i18n::text("quit_prompt").to_string())
+        } else if let Some(text) = &self.confirm_paste {
+            Some(i18n::format("paste_prompt", &[&output::bytes(text.len() as u64)]))
+        } else {
+            self.confirm_send
+                .as_ref()
+                .map(|(line, reason)| i18n::format("send_prompt", &[reason, line]))
+        }
     }
 
-    fn ui(&mut self, f: &mut Frame) {
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .margin(2)
-            .constraints([Constraint::Min(1), Constraint::Length(3)].as_ref())
-            .split(f.size());
+    /// Stop the running attack with the command of the firmware, dropping whatever is still queued to be sent
+    fn stop_attack(&mut self, input_tx: &UnboundedSender<UiEvent>) {
+        if input_tx.send(UiEvent::Interrupt).is_err() {
+            self.report(Error::ChannelClosed("input"));
+        }
+        self.timers.clear();
+    }
 
-        let (msg_color, input_color) = match self.input_mode {
-            InputMode::Insert => (Color::Yellow, Color::White),
-            InputMode::Normal => (Color::White, Color::Yellow)
-        };
+    /// Complete the command name in the input line from the commands of the firmware
+    fn complete(&mut self) {
+        if self.input.contains(' ') {
+            return;
+        }
 
-        // Set scroll position
-        let lines: Vec<Line> = self.output.iter().map(Self::parse).collect();
-        let box_height = chunks[0].height as usize;
-        let visible_len = (lines.len() as isize - box_height as isize + 2).clamp(0, lines.len() as isize);
-        if !self.manual_scroll {
-            self.scroll_pos = visible_len as usize;
-        } else if self.scroll_pos >= visible_len as usize {
-            self.manual_scroll = false;
+        let profile = status::get(&self.status).firmware.unwrap_or_else(firmware::generic);
+        match firmware::complete(profile, &self.input).as_slice() {
+            [] => (),
+            [name] => self.replace_input(format!("{} ", name)),
+            names => {
+                // Complete as far as all candidates agree and list them
+                let common = names.iter().skip(1).fold(names[0].to_string(), |common, name| {
+                    common.chars().zip(name.chars()).take_while(|(a, b)| a == b).map(|(a, _)| a).collect()
+                });
+                self.replace_input(common);
+                self.push(format!("> {}", names.join("  ")));
+            }
         }
-        self.scrollbar = self.scrollbar.content_length(lines.len());
+    }
 
-        // Message Box
-        let messages = Paragraph::new(lines)
-            .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(msg_color)).title("Messages"))
-            .scroll((self.scroll_pos as u16, 0));
-        f.render_widget(messages, chunks[0]);
-        f.render_stateful_widget(
-            Scrollbar::new(ScrollbarOrientation::VerticalRight)
-                .begin_symbol(Some("^"))
-                .end_symbol(Some("v")),
-            chunks[0],
-            &mut self.scrollbar,
-        );
+    /// The cursor is a byte offset into the input and moves by whole chars
+    fn cursor_left(&mut self) {
+        let before = self.input.get(..self.cursor_pos).unwrap_or_default();
+        self.cursor_pos -= before.chars().next_back().map_or(0, char::len_utf8);
+    }
 
-        // Input Box
-        let input = Paragraph::new(self.input.as_str())
-            .style(Style::default().fg(Color::Yellow))
-            .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(input_color)).title("Input"));
-        f.render_widget(input, chunks[1]);
-        // Show cursor
-        f.set_cursor(
-            // Put cursor after input text
-            chunks[1].x + self.cursor_pos as u16 + 1,
-            // Leave room for border
-            chunks[1].y + 1,
-        );
+    fn cursor_right(&mut self) {
+        let after = self.input.get(self.cursor_pos..).unwrap_or_default();
+        self.cursor_pos += after.chars().next().map_or(0, char::len_utf8);
     }
 
-    /// restore terminal
-    fn shutdown(mut terminal: Terminal<CrosstermBackend<Stdout>>) -> io::Result<()> {
-        disable_raw_mode()?;
-        execute!(
-            terminal.backend_mut(),
-            LeaveAlternateScreen
-        )?;
-        terminal.show_cursor()?;
-        Ok(())
+    /// Column of the cursor in the input line
+    fn cursor_column(&self) -> usize {
+        self.input.get(..self.cursor_pos).unwrap_or_default().chars().count()
+    }
+
+    fn cursor_reset(&mut self) {
+        self.cursor_pos = 0
+    }
+
+    fn scroll_up(&mut self) {
+        self.scroll_delta -= 1;
+        self.manual_scroll = true;
+    }
+
+    fn scroll_down(&mut self) {
+        self.scroll_delta += 1;
+    }
+
+    /// Go back to following new output
+    fn scroll_to_bottom(&mut self) {
+        self.manual_scroll = false;
+        self.scroll_delta = 0;
+        self.unseen = 0;
+    }
+
+    /// Scroll to a line, counted from the start of the session like the line numbers
+    fn goto(&mut self, n: usize) {
+        if self.inline || self.settings.accessible {
+            self.push(format!("ERROR: {}", i18n::text("goto_inline")));
+            return;
+        }
+        let first = self.dropped + 1;
+        let last = self.dropped + self.output.len();
+        if n < first || n > last {
+            self.push(format!("ERROR: {}", i18n::format("no_line", &[&n, &first, &last])));
+            return;
+        }
+        self.manual_scroll = true;
+        self.scroll_anchor = (n - first, 0);
+        self.scroll_delta = 0;
+    }
+
+    /// Width of the line numbers, enough for the last line
+    fn number_width(&self) -> usize {
+        (self.dropped + self.output.len()).to_string().len().max(3)
+    }
+
+    /// Number of rows a line takes up in a box of the given width
+    fn rows(line: &Line, width: u16, wrap: bool) -> usize {
+        if wrap {
+            Paragraph::new(line.clone()).wrap(Wrap { trim: false }).line_count(width).max(1)
+        } else {
+            1
+        }
+    }
+
+    /// Turn a row offset from the top into a (line, row within line) anchor
+    fn anchor_at(row: usize, heights: &[usize]) -> (usize, usize) {
+        let mut start = 0;
+        for (i, height) in heights.iter().enumerate() {
+            if row < start + height {
+                return (i, row - start);
+            }
+            start += height;
+        }
+        (heights.len(), 0)
+    }
+
+    /// Row offset from the top of an anchor, with the current line heights
+    fn anchor_row(anchor: (usize, usize), heights: &[usize]) -> usize {
+        let (line, row) = anchor;
+        let above: usize = heights.iter().take(line).sum();
+        above + heights.get(line).map_or(0, |height| row.min(height - 1))
+    }
+
+    fn scroll_left(&mut self) {
+        self.hscroll_pos = self.hscroll_pos.saturating_sub(HSCROLL_STEP);
+    }
+
+    fn scroll_right(&mut self) {
+        self.hscroll_pos = self.hscroll_pos.saturating_add(HSCROLL_STEP);
+    }
+
+    fn set_wrap(&mut self, wrap: bool) {
+        self.settings.wrap = wrap;
+        self.hscroll_pos = 0;
+        let state = i18n::text(if wrap { "on" } else { "off" });
+        self.push(format!("> {}", i18n::format("line_wrap", &[&state])));
+    }
+
+    /// Remove the char before the byte offset `idx`, which is the cursor
+    fn remove_char(&mut self, idx: usize) {
+        self.cursor_left();
+        self.input.replace_range(self.cursor_pos..idx, "");
+    }
+
+    /// Show an error inside the Messages box instead of printing over the TUI
+    fn report(&mut self, e: Error) {
+        self.push(format!("ERROR: {}", e));
+    }
+
+    /// Change the SSID list through the deauther's own commands
+    fn run_ssid_command(&mut self, cmd: SsidCommand, input_tx: &UnboundedSender<UiEvent>) {
+        self.show_ssids = !self.inline;
+        self.ssids.reset();
+        for line in ssids::firmware_commands(&cmd) {
+            self.send(input_tx, format!("{}\r\n", line));
+        }
+    }
+
+    /// Read the settings of the deauther into the editor
+    fn open_device_settings(&mut self, input_tx: &UnboundedSender<UiEvent>) {
+        // Without room for the editor, the settings are only listed
+        self.show_settings = !self.inline;
+        let commands = self.device_settings.refresh();
+        self.send_device_commands(commands, input_tx);
+    }
+
+    /// Follow preferences changed in the panel
+    fn apply_preferences(&mut self, before: Settings) {
+        if self.settings.wrap != before.wrap {
+            self.hscroll_pos = 0;
+        }
+        if self.settings.theme != before.theme {
+            self.theme = Theme::by_name(self.settings.theme).unwrap_or_default().fit(self.settings.color_depth);
+        }
+        if self.settings.scrollback != before.scrollback {
+            self.trim_scrollback();
+        }
+    }
+
+    /// Write the preferences changed in the panel to the config file
+    fn save_preferences(&mut self) {
+        let path = match self.config_path.clone().or_else(Config::default_path) {
+            Some(path) => path,
+            None => return self.push(format!("ERROR: {}", i18n::text("no_config_dir"))),
+        };
+        let changes = self.preferences.as_ref().map(|panel| panel.changes(&self.settings)).unwrap_or_default();
+        if changes.is_empty() {
+            return self.push(format!("> {}", i18n::text("settings_unchanged")));
+        }
+        match Config::store(&path, &changes) {
+            Ok(()) => {
+                if let Some(panel) = self.preferences.as_mut() {
+                    panel.saved(&self.settings);
+                }
+                self.push(format!("> {}", i18n::format("settings_saved", &[&path.display()])));
+            }
+            Err(e) => self.push(format!("ERROR: {}", i18n::format("settings_failed", &[&path.display(), &e]))),
+        }
+    }
+
+    /// Drop the oldest lines beyond the scrollback size.
+    /// Inline and plain mode only drop lines already printed.
+    fn trim_scrollback(&mut self) {
+        let mut excess = self.settings.scrollback.map_or(0, |max| self.output.len().saturating_sub(max));
+        if self.inline {
+            excess = excess.min(self.printed);
+        }
+        if excess == 0 {
+            return;
+        }
+        self.output.drain(..excess);
+        self.dropped += excess;
+        self.unacked = self.unacked.iter().filter(|i| **i >= excess).map(|i| i - excess).collect();
+        self.printed = self.printed.saturating_sub(excess);
+        self.measured.drop_front(excess);
+        self.scroll_anchor = match self.scroll_anchor {
+            (line, offset) if line >= excess => (line - excess, offset),
+            _ => (0, 0),
+        };
+    }
+
+    /// Send commands of the settings editor to the deauther
+    fn send_device_commands(&mut self, commands: Vec<String>, input_tx: &UnboundedSender<UiEvent>) {
+        for line in commands {
+            self.send(input_tx, format!("{}\r\n", line));
+        }
+    }
+
+    /// Handle local commands that only concern the UI.
+    /// Returns true if the command was consumed and must not be sent on.
+    fn local_command(&mut self, text: &str, input_tx: &UnboundedSender<UiEvent>) -> bool {
+        match command::parse(text) {
+            Some(Ok(Command::Connect(None))) => {
+                self.open_port_picker(false);
+                true
+            }
+            Some(Ok(Command::Connect(Some(_)))) => {
+                // Picked by hand, the picker would connect the next plugged in device as well
+                self.picker = None;
+                false
+            }
+            Some(Ok(Command::Echo(echo))) => {
+                self.settings.echo = echo.unwrap_or_else(|| self.settings.echo.next());
+                self.push(format!("> {}", i18n::format("echo", &[&self.settings.echo])));
+                true
+            }
+            Some(Ok(Command::Wrap(wrap))) => {
+                self.set_wrap(wrap.unwrap_or(!self.settings.wrap));
+                true
+            }
+            Some(Ok(Command::LineNumbers(show))) => {
+                self.settings.line_numbers = show.unwrap_or(!self.settings.line_numbers);
+                let state = i18n::text(if self.settings.line_numbers { "on" } else { "off" });
+                self.push(format!("> {}", i18n::format("line_numbers", &[&state])));
+                true
+            }
+            Some(Ok(Command::Goto(n))) => {
+                self.goto(n);
+                true
+            }
+            Some(Ok(Command::Presenter(on))) => {
+                self.set_presenter(on.unwrap_or(self.presenter.is_none()));
+                let state = i18n::text(if self.presenter.is_some() { "on" } else { "off" });
+                self.push(format!("> {}", i18n::format("presenter", &[&state])));
+                true
+            }
+            Some(Ok(Command::Channels(show))) => {
+                self.settings.channels = show.unwrap_or(!self.settings.channels);
+                true
+            }
+            Some(Ok(Command::Ssid(cmd))) => {
+                self.run_ssid_command(cmd, input_tx);
+                true
+            }
+            Some(Ok(Command::Prefs)) => {
+                // Without room for the panel, the preferences are only listed
+                if self.inline {
+                    for line in preferences::lines(&self.settings) {
+                        self.push(line);
+                    }
+                } else {
+                    self.preferences = Some(Preferences::new(&self.settings));
+                }
+                true
+            }
+            Some(Ok(Command::Settings)) => {
+                self.open_device_settings(input_tx);
+                true
+            }
+            Some(Ok(Command::Stop)) => {
+                self.stop_attack(input_tx);
+                true
+            }
+            Some(Ok(Command::Watch(cmd))) => {
+                self.run_watch_command(cmd);
+                true
+            }
+            Some(Ok(Command::Timers)) => {
+                let lines = self.timers.lines();
+                if lines.is_empty() {
+                    self.push(format!("> {}", i18n::text("no_timers")));
+                }
+                for line in lines {
+                    self.push(format!("> {}", line));
+                }
+                true
+            }
+            Some(Ok(Command::Diff)) => {
+                if self.inline {
+                    for line in self.scans.lines() {
+                        self.push(line);
+                    }
+                } else {
+                    self.show_scans = true;
+                }
+                true
+            }
+            Some(Ok(Command::Database(search))) => {
+                match &self.database {
+                    None => self.push(format!("> {}", i18n::text("no_database"))),
+                    Some(db) if self.inline => {
+                        for line in db.lines(&search) {
+                            self.push(line);
+                        }
+                    }
+                    Some(db) => self.db_browser = Some(Browser::new(db, &search)),
+                }
+                true
+            }
+            Some(Ok(Command::ExportScan(path))) => {
+                // Invalid rules would leak what they're meant to hide
+                let exported = match &self.redactor {
+                    Ok(redactor) => self
+                        .scans
+                        .export(&path, redactor)
+                        .map_err(|e| i18n::format("export_failed", &[&path.display(), &e])),
+                    Err(e) => Err(i18n::format("export_refused", &[e])),
+                };
+                match exported {
+                    Ok(count) => self.push(format!("> {}", i18n::format("exported_scan", &[&count, &path.display()]))),
+                    Err(e) => self.push(format!("ERROR: {}", e)),
+                }
+                true
+            }
+            Some(Ok(Command::Raw)) => {
+                self.set_raw(true);
+                true
+            }
+            Some(Ok(Command::Paste)) => {
+                self.paste_clipboard();
+                true
+            }
+            Some(Ok(Command::Legend)) => {
+                if self.inline {
+                    for line in legend::lines() {
+                        self.push(line);
+                    }
+                } else {
+                    self.show_legend = true;
+                }
+                true
+            }
+            Some(Ok(Command::Classify(show))) => {
+                self.show_matches = show.unwrap_or(!self.show_matches);
+                true
+            }
+            Some(Ok(Command::Mute(mute))) => {
+                self.bell.set_muted(mute.unwrap_or(!self.bell.is_muted()));
+                let text = if self.bell.is_muted() { "bell_muted" } else { "bell_unmuted" };
+                self.push(format!("> {}", i18n::text(text)));
+                true
+            }
+            Some(Ok(Command::Pipe(Some(command)))) => {
+                match Pipe::start(&command) {
+                    Ok(pipe) => {
+                        self.pipe = Some(pipe);
+                        self.push(format!("> {}", i18n::format("pipe_started", &[&command])));
+                    }
+                    Err(e) => self.push(format!("ERROR: {}", i18n::format("pipe_failed", &[&command, &e]))),
+                }
+                true
+            }
+            Some(Ok(Command::Pipe(None))) => {
+                match self.pipe.take() {
+                    Some(pipe) => self.push(format!("> {}", i18n::format("pipe_stopped", &[&pipe.command()]))),
+                    None => self.push(format!("> {}", i18n::text("no_pipe"))),
+                }
+                true
+            }
+            Some(Ok(Command::Edit)) => {
+                self.open_editor();
+                true
+            }
+            Some(Ok(Command::Shell(command))) => {
+                self.leave = Some(Leave::Run(command));
+                true
+            }
+            Some(Ok(Command::Detach)) if self.attached => {
+                self.detached = true;
+                true
+            }
+            Some(Ok(Command::Detach)) => {
+                self.push(format!("ERROR: {}", i18n::text("not_attached")));
+                true
+            }
+            Some(Ok(Command::Help)) => {
+                for line in command::help() {
+                    self.push(line);
+                }
+                true
+            }
+            Some(Err(e)) => {
+                self.push(format!("ERROR: {}", e));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Tell the backend to end the session, returns false to stop the render loop
+    fn quit(&mut self, input_tx: &UnboundedSender<UiEvent>) -> Result<bool> {
+        match input_tx.send(UiEvent::Quit) {
+            Ok(_) => Ok(false),
+            Err(_) => Err(Error::ChannelClosed("input")),
+        }
+    }
+
+    /// Quit, or ask first if the user wants to be asked
+    fn request_quit(&mut self, input_tx: &UnboundedSender<UiEvent>) -> Result<bool> {
+        if self.settings.confirm_exit {
+            self.confirm_quit = true;
+            Ok(true)
+        } else {
+            self.quit(input_tx)
+        }
+    }
+
+    /// Answer the open confirmation prompt, returns false to stop the render loop
+    fn answer(&mut self, yes: bool, input_tx: &UnboundedSender<UiEvent>) -> Result<bool> {
+        if std::mem::take(&mut self.confirm_quit) {
+            return if yes { self.quit(input_tx) } else { Ok(true) };
+        }
+        if let Some(text) = self.confirm_paste.take() {
+            if yes {
+                self.insert_text(&text);
+            }
+            return Ok(true);
+        }
+        if let Some((line, _)) = self.confirm_send.take() {
+            if yes {
+                self.send_line(input_tx, line);
+            } else {
+                self.mark_failed(&line);
+                self.push(format!("> {}", i18n::format("not_sent", &[&line])));
+            }
+        }
+        Ok(true)
+    }
+
+    /// Handle an entered line, returns false to stop the render loop
+    fn enter(&mut self, text: String, input_tx: &UnboundedSender<UiEvent>) -> Result<bool> {
+        // Without the popup, the values of a template are typed like commands
+        if let Some(form) = self.template.as_mut() {
+            if let Some(line) = form.answer(&text) {
+                self.template = None;
+                self.send_filled(line, input_tx);
+            }
+            return Ok(true);
+        }
+        if text.trim().eq_ignore_ascii_case("CLEAR") {
+            self.dropped += self.output.len();
+            self.output.clear();
+            self.unacked.clear();
+            self.measured = Measured::default();
+            return Ok(true);
+        }
+        if self.local_command(&text, input_tx) {
+            // The daemon keeps the session, only the UI ends
+            return Ok(!self.detached);
+        }
+        if text.trim().eq_ignore_ascii_case("EXIT") {
+            return self.request_quit(input_tx);
+        }
+        // A log started without a name asks for one, an empty answer leaves it unnamed
+        if let Some(Ok(Meta::Log(LogAction::Start(_, None)))) = handler::parse(&text) {
+            self.template = Form::new(&format!("{} {{session_name}}", template::escape(text.trim())), &self.recent_macs);
+            return Ok(true);
+        }
+        if let Some(form) = Form::new(&text, &self.recent_macs) {
+            self.template = Some(form);
+            return Ok(true);
+        }
+        self.send_checked(input_tx, template::literal(&text));
+        Ok(true)
+    }
+
+    /// Send a template once its placeholders are filled in, echoed like a typed command
+    fn send_filled(&mut self, line: String, input_tx: &UnboundedSender<UiEvent>) {
+        if self.settings.echo != Echo::Off {
+            self.add(line.clone(), Origin::Sent);
+        }
+        self.send_checked(input_tx, line);
+    }
+
+    /// Take in an event from the backend
+    fn receive(&mut self, event: BackendEvent) {
+        match event {
+            BackendEvent::Partial(line) => self.partial = raw::visible(&line),
+            BackendEvent::Rx(line) => {
+                self.partial.clear();
+                self.activity.record(&line);
+                let attacking = self.attack.is_running();
+                self.attack.update(&line);
+                if attacking && !self.attack.is_running() {
+                    self.bell.alert(Alert::AttackDone);
+                }
+                self.ssids.update(&line);
+                self.device_settings.update(&line);
+                self.recent_macs.update(&line);
+                let profile = status::get(&self.status).firmware.unwrap_or_else(firmware::generic);
+                // The database gets each result once per scan
+                let fix = self.gps.as_ref().and_then(Gps::fix);
+                let found = profile.parse_event(&line).filter(|event| self.scans.update(event, fix));
+                if let (Some(db), Some(events::Event::ScanResult { mac, rssi, channel, line })) = (&self.database, found) {
+                    if let Err(e) = db.record(&mac, rssi, channel, &line) {
+                        self.push(format!("ERROR: {}", e));
+                    }
+                }
+                if let Some(pipe) = &self.pipe {
+                    pipe.push(&line);
+                }
+                self.watches.update(&line);
+                self.acknowledge();
+                self.mark_gap();
+                self.add(line, Origin::Received);
+            }
+            BackendEvent::Status(text) => self.push(text),
+            // Scans follow what the backend sent, whoever asked for it
+            BackendEvent::Sent(command) => self.scans.sent(&command),
+        }
+    }
+
+    fn run_watch_command(&mut self, cmd: WatchCommand) {
+        // Without the sidebar every change lists them
+        let list = cmd == WatchCommand::List || self.settings.accessible || self.inline;
+        let result = match cmd {
+            WatchCommand::List => Ok(()),
+            WatchCommand::Add(pattern) => self.watches.add(&pattern),
+            WatchCommand::Remove(number) => self.watches.remove(number),
+            WatchCommand::Clear => {
+                self.watches.clear();
+                Ok(())
+            }
+        };
+        match result {
+            Err(e) => self.push(format!("ERROR: {}", e)),
+            Ok(()) if list => {
+                for line in self.watches.lines() {
+                    self.push(line);
+                }
+            }
+            Ok(()) => (),
+        }
+    }
+
+    /// Take in the output of the piped command, shown in its pane or, without one, as messages
+    fn poll_pipe(&mut self) {
+        let (lines, end) = match self.pipe.as_mut() {
+            Some(pipe) => pipe.poll(),
+            None => return,
+        };
+        if self.settings.accessible || self.inline {
+            for line in lines {
+                self.push(format!("> | {}", line));
+            }
+        }
+        if let (Some(end), Some(pipe)) = (end, &self.pipe) {
+            self.push(format!("> {}", i18n::format("pipe_ended", &[&pipe.command(), &end])));
+        }
+    }
+
+    /// Follow the state of the backend and do what is due, e.g. autosaving
+    fn tick(&mut self, input_tx: &UnboundedSender<UiEvent>) {
+        self.update_title();
+
+        for text in self.timers.finished() {
+            self.bell.alert(Alert::Timer);
+            self.push(format!("> {}", text));
+        }
+        if let Some(e) = self.gps.as_ref().and_then(Gps::take_error) {
+            self.push(format!("ERROR: {}", i18n::format("gps_failed", &[&e])));
+        }
+        self.poll_pipe();
+        // Once per tick, so a burst of lines isn't trimmed line by line
+        self.trim_scrollback();
+
+        // Every device has its own history, which stays while disconnected
+        let port = status::get(&self.status).port;
+        if self.settings.history && port.is_some() && port != self.history_port {
+            if let Some(file) = port.as_deref().and_then(history_file) {
+                self.cmd_history = History::load(file);
+            }
+            self.history_port = port;
+        }
+
+        // Back to the port selection when the port couldn't be opened
+        let mut pick_port = false;
+        let mut failed = Vec::new();
+        status::update(&self.status, |status| {
+            pick_port = std::mem::take(&mut status.pick_port);
+            failed = std::mem::take(&mut status.failed);
+        });
+        for line in failed {
+            self.mark_failed(&line);
+        }
+        if pick_port && self.picker.is_none() {
+            self.open_port_picker(false);
+        }
+
+        self.saved(false);
+        if self.saving.is_none() && self.settings.autosave.is_some_and(|every| self.unsaved && self.last_save.elapsed() >= every) {
+            self.save_session();
+        }
+
+        if let Some(port) = self.picker.as_mut().and_then(PortPicker::tick) {
+            self.connect(&port, input_tx);
+        }
+    }
+
+    fn event_handler(&mut self, key: KeyEvent, spam_handler: &mut InterruptHandler, input_tx: &UnboundedSender<UiEvent>) -> Result<bool> {
+        if let Some(presenter) = self.presenter.as_mut() {
+            presenter.key(key);
+        }
+        if self.raw && key.kind == KeyEventKind::Press {
+            if self.settings.raw_escape.matches(&key) {
+                self.set_raw(false);
+            } else if let Some(bytes) = raw::bytes(key) {
+                self.send_bytes(input_tx, bytes);
+            }
+            return Ok(true);
+        }
+
+        if self.prompt().is_some() && key.kind == KeyEventKind::Press {
+            return self.answer(matches!(key.code, KeyCode::Char(c) if i18n::is_yes(&c.to_string())), input_tx);
+        }
+
+        let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL;
+        let picker_keys = key.kind == KeyEventKind::Press && !ctrl_c && !self.inline;
+        if let Some(picker) = self.picker.as_mut().filter(|_| picker_keys) {
+            match picker.handle_key(key) {
+                picker::Action::Select(port) => self.connect(&port, input_tx),
+                picker::Action::Close => self.picker = None,
+                picker::Action::None => (),
+            }
+            return Ok(true);
+        }
+
+        if let Some(form) = self.template.as_mut().filter(|_| picker_keys) {
+            match form.handle_key(key) {
+                template::Action::Done(line) => {
+                    self.template = None;
+                    self.send_filled(line, input_tx);
+                }
+                template::Action::Cancel => self.template = None,
+                template::Action::None => (),
+            }
+            return Ok(true);
+        }
+
+        if self.show_ssids && picker_keys {
+            match self.ssids.handle_key(key) {
+                ssids::Action::Run(cmd) => self.run_ssid_command(cmd, input_tx),
+                ssids::Action::Edit(text) => {
+                    self.show_ssids = false;
+                    self.replace_input(text);
+                }
+                ssids::Action::Close => self.show_ssids = false,
+                ssids::Action::None => (),
+            }
+            return Ok(true);
+        }
+
+        if let Some(panel) = self.preferences.as_mut().filter(|_| picker_keys) {
+            let before = self.settings;
+            match panel.handle_key(key, &mut self.settings) {
+                preferences::Action::Save => self.save_preferences(),
+                preferences::Action::DeviceSettings => {
+                    self.preferences = None;
+                    self.open_device_settings(input_tx);
+                }
+                preferences::Action::Close => self.preferences = None,
+                preferences::Action::None => (),
+            }
+            self.apply_preferences(before);
+            return Ok(true);
+        }
+
+        if self.show_settings && picker_keys {
+            match self.device_settings.handle_key(key) {
+                device_settings::Action::Send(commands) => self.send_device_commands(commands, input_tx),
+                device_settings::Action::Close => self.show_settings = false,
+                device_settings::Action::None => (),
+            }
+            return Ok(true);
+        }
+
+        if let Some(browser) = self.db_browser.as_mut().filter(|_| picker_keys) {
+            match browser.handle_key(key) {
+                database::Action::Search => {
+                    if let Some(db) = &self.database {
+                        browser.refresh(db);
+                    }
+                }
+                database::Action::Close => self.db_browser = None,
+                database::Action::None => (),
+            }
+            return Ok(true);
+        }
+
+        if self.show_scans && picker_keys {
+            match self.scans.handle_key(key) {
+                scans::Action::Close => self.show_scans = false,
+                scans::Action::None => (),
+            }
+            return Ok(true);
+        }
+
+        if self.show_legend && picker_keys {
+            match legend::handle_key(key) {
+                legend::Action::ToggleDebug => self.show_matches = !self.show_matches,
+                legend::Action::Close => self.show_legend = false,
+                legend::Action::None => (),
+            }
+            return Ok(true);
+        }
+
+        if key.kind == KeyEventKind::Press && self.input_mode == InputMode::Insert {
+            self.kill_ring.next_key();
+            let undo_key = matches!(key.code, KeyCode::Char('z' | '_' | '7')) && key.modifiers == KeyModifiers::CONTROL;
+            let undoing = std::mem::replace(&mut self.undoing, undo_key);
+            match key.code {
+                KeyCode::Enter => {
+                    let entr_txt: String = self.submit();
+                    return self.enter(entr_txt, input_tx);
+                }
+                KeyCode::Char('c') if ctrl_c => match self.settings.ctrl_c {
+                    CtrlC::Prompt => self.confirm_quit = true,
+                    CtrlC::Quit => return self.quit(input_tx),
+                    CtrlC::Stop | CtrlC::Spam => {
+                        if input_tx.send(UiEvent::Interrupt).is_err() {
+                            self.push(i18n::text("stop_failed").to_string());
+                        }
+                        if self.settings.ctrl_c == CtrlC::Spam && spam_handler.interrupted() {
+                            return self.request_quit(input_tx);
+                        }
+                    }
+                },
+                KeyCode::Char('b') if key.modifiers == KeyModifiers::CONTROL => {
+                    self.send(input_tx, format!("{}break\r\n", command::PREFIX));
+                }
+                KeyCode::Char(attack::STOP_KEY) if key.modifiers == KeyModifiers::CONTROL && self.attack.is_running() => {
+                    self.stop_attack(input_tx);
+                }
+                // Ctrl-Z suspends like in other programs with an empty input line, unless it was just undoing
+                KeyCode::Char('z') if key.modifiers == KeyModifiers::CONTROL && shell::CAN_SUSPEND && self.input.is_empty() && self.undo.is_empty() && !undoing => {
+                    self.leave = Some(Leave::Suspend);
+                }
+                KeyCode::Char('z') | KeyCode::Char('_') | KeyCode::Char('7') if key.modifiers == KeyModifiers::CONTROL => self.undo(),
+                KeyCode::Char('z') if key.modifiers == KeyModifiers::ALT => self.redo(),
+                KeyCode::Char('Z') if key.modifiers.contains(KeyModifiers::CONTROL) => self.redo(),
+                KeyCode::Char('o') if key.modifiers == KeyModifiers::CONTROL => self.open_editor(),
+                KeyCode::Char('w') if key.modifiers == KeyModifiers::CONTROL => self.kill(self.word_start(false), self.cursor_pos),
+                KeyCode::Char('u') if key.modifiers == KeyModifiers::CONTROL => self.kill(0, self.cursor_pos),
+                KeyCode::Char('k') if key.modifiers == KeyModifiers::CONTROL => self.kill(self.cursor_pos, self.input.len()),
+                KeyCode::Char('d') if key.modifiers == KeyModifiers::ALT => self.kill(self.cursor_pos, self.word_end()),
+                KeyCode::Backspace if key.modifiers == KeyModifiers::ALT => self.kill(self.word_start(true), self.cursor_pos),
+                KeyCode::Char('y') if key.modifiers == KeyModifiers::CONTROL => self.yank(),
+                KeyCode::Char('v') | KeyCode::Char('V') if key.modifiers == KeyModifiers::CONTROL | KeyModifiers::SHIFT => self.paste_clipboard(),
+                KeyCode::Char('y') if key.modifiers == KeyModifiers::ALT => self.yank_pop(),
+                KeyCode::Char(c) => self.put_char(c),
+                KeyCode::Backspace => self.delete_char(),
+                KeyCode::Tab => self.complete(),
+                KeyCode::Up => {
+                    let text = self.cmd_history.prev_cmd();
+                    self.replace_input(text);
+                }
+                KeyCode::Down => {
+                    let text = self.cmd_history.next_cmd();
+                    self.replace_input(text);
+                }
+                KeyCode::Left => {
+                    self.undo.split();
+                    self.cursor_left();
+                }
+                KeyCode::Right => {
+                    self.undo.split();
+                    self.cursor_right();
+                }
+                KeyCode::PageUp => self.scroll_up(),
+                KeyCode::PageDown => self.scroll_down(),
+                KeyCode::End => self.scroll_to_bottom(),
+                KeyCode::Esc => self.input_mode = InputMode::Normal,
+
+                _ => (),
+            }
+        } else if key.kind == KeyEventKind::Press && self.input_mode == InputMode::Normal {
+            match key.code {
+                KeyCode::Up | KeyCode::PageUp => self.scroll_up(),
+                KeyCode::Down | KeyCode::PageDown => self.scroll_down(),
+                KeyCode::Left => self.scroll_left(),
+                KeyCode::Right => self.scroll_right(),
+                KeyCode::Char('z') if key.modifiers == KeyModifiers::CONTROL && shell::CAN_SUSPEND => {
+                    self.leave = Some(Leave::Suspend);
+                }
+                KeyCode::Char('w') => self.set_wrap(!self.settings.wrap),
+                KeyCode::Char(attack::STOP_KEY) if self.attack.is_running() => self.stop_attack(input_tx),
+                KeyCode::End | KeyCode::Char('G') => self.scroll_to_bottom(),
+                KeyCode::Esc => self.input_mode = InputMode::Insert,
+                _ => ()
+            }
+        }
+        Ok(true)
+    }
+
+    /// Start render loop
+    pub async fn run(mut self, input_tx: UnboundedSender<UiEvent>, mut output_rx: channel::Receiver) -> Result<()> {
+        if self.settings.title && io::stdout().is_terminal() {
+            self.title = Some(TerminalTitle::save());
+        }
+
+        let res = if self.settings.accessible {
+            self.run_plain(&input_tx, &mut output_rx).await
+        } else {
+            self.run_tui(&input_tx, &mut output_rx)
+        };
+
+        if let Some(title) = self.title.take() {
+            title.restore();
+        }
+        self.saved(true);
+        if self.settings.autosave.is_some() && self.unsaved {
+            self.save_session();
+            self.saved(true);
+        }
+
+        res
+    }
+
+    fn run_tui(&mut self, input_tx: &UnboundedSender<UiEvent>, output_rx: &mut channel::Receiver) -> Result<()> {
+        let stdout = io::stdout();
+        let backend = CrosstermBackend::new(stdout);
+        let mut terminal = if self.inline {
+            Terminal::with_options(backend, TerminalOptions { viewport: Viewport::Inline(INLINE_HEIGHT) })?
+        } else {
+            Terminal::new(backend)?
+        };
+
+        // setup terminal
+        enable_raw_mode()?;
+        execute!(io::stdout(), EnableBracketedPaste)?;
+        if !self.inline {
+            let mut stdout = io::stdout();
+            execute!(stdout, EnterAlternateScreen)?;
+        }
+
+        // Always restore the terminal, even when the loop fails
+        let res = self.event_loop(&mut terminal, input_tx, output_rx);
+        Self::shutdown(terminal, self.inline)?;
+        res
+    }
+
+    /// Line based UI for screen readers. New lines are printed one after another without colors or borders,
+    /// the terminal edits the input line and everything is done with typed commands instead of keys.
+    async fn run_plain(&mut self, input_tx: &UnboundedSender<UiEvent>, output_rx: &mut channel::Receiver) -> Result<()> {
+        // Reading the terminal blocks, so it gets its own thread
+        let (line_tx, mut line_rx) = tokio::sync::mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            for line in io::stdin().lines() {
+                if line_tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        self.push(format!("> {}", i18n::format("help_hint", &[&command::PREFIX])));
+        let mut ticker = tokio::time::interval(PLAIN_TICK);
+        loop {
+            self.print_plain()?;
+            tokio::select! {
+                line = output_rx.recv() => match line {
+                    Some(line) => self.receive(line),
+                    // The session has ended
+                    None => return Ok(()),
+                },
+                line = line_rx.recv() => {
+                    let mut running = match line {
+                        Some(Ok(line)) if self.prompt().is_some() => self.answer(i18n::is_yes(&line), input_tx)?,
+                        Some(Ok(line)) => {
+                            self.input = line;
+                            let text = self.submit();
+                            self.enter(text, input_tx)?
+                        }
+                        // The terminal is gone
+                        _ => self.quit(input_tx)?,
+                    };
+                    // The terminal is already in its normal state, and Ctrl-Z suspends on its own
+                    if let Some(leave) = self.leave.take() {
+                        self.print_plain()?;
+                        if let Some(text) = self.away(leave, output_rx) {
+                            running = self.send_edited(&text, input_tx)?;
+                        }
+                    }
+                    if !running {
+                        return self.print_plain();
+                    }
+                    if let Some(prompt) = self.prompt() {
+                        self.print_plain()?;
+                        println!("{}", prompt);
+                    }
+                    if let Some(question) = self.template.as_ref().map(Form::question) {
+                        self.print_plain()?;
+                        println!("{}", question);
+                    }
+                }
+                _ = ticker.tick() => self.tick(input_tx),
+            }
+        }
+    }
+
+    /// Print new messages as plain text, typed lines are already on the screen
+    fn print_plain(&mut self) -> Result<()> {
+        self.printed = self.printed.min(self.output.len());
+        let mut stdout = io::stdout().lock();
+        for (index, msg) in self.output.iter().enumerate().skip(self.printed) {
+            if msg.origin == Origin::Sent {
+                continue;
+            }
+            if self.settings.line_numbers {
+                write!(stdout, "{} ", self.dropped + index + 1)?;
+            }
+            if self.settings.timestamps {
+                write!(stdout, "{} ", msg.time.format("%H:%M:%S"))?;
+            }
+            writeln!(stdout, "{}", msg.text)?;
+        }
+        stdout.flush()?;
+        self.printed = self.output.len();
+        Ok(())
+    }
+
+    /// Print new messages above the input line, into the terminal's scrollback
+    fn flush_inline(&mut self, terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+        // The buffer was cleared
+        self.printed = self.printed.min(self.output.len());
+
+        let size = terminal.size()?;
+        let max_rows = size.height.saturating_sub(INLINE_HEIGHT).max(1) as usize;
+
+        while self.printed < self.output.len() {
+            // Insert at most a screen at once
+            let mut lines = Vec::new();
+            let mut rows = 0;
+            for index in self.printed..self.output.len() {
+                let line = self.message_line(index);
+                let height = Self::rows(&line, size.width, self.settings.wrap);
+                if rows + height > max_rows && !lines.is_empty() {
+                    break;
+                }
+                rows += height;
+                lines.push(line);
+            }
+            self.printed += lines.len();
+
+            let mut paragraph = Paragraph::new(lines);
+            if self.settings.wrap {
+                paragraph = paragraph.wrap(Wrap { trim: false });
+            }
+            terminal.insert_before(rows.min(max_rows) as u16, |buf| paragraph.render(buf.area, buf))?;
+        }
+
+        Ok(())
+    }
+
+    fn ui_inline(&self, f: &mut Frame) {
+        if let Some(prompt) = self.prompt() {
+            f.render_widget(Paragraph::new(prompt).style(Style::default().fg(self.theme.focus)), f.size());
+            return;
+        }
+
+        if self.raw {
+            let area = f.size();
+            f.render_widget(Paragraph::new(self.partial.as_str()), area);
+            f.set_cursor(area.x + self.partial.chars().count() as u16, area.y);
+            return;
+        }
+
+        let prompt = self.template.as_ref().map_or("> ".to_string(), Form::question);
+        let mut spans = vec![Span::styled(prompt.clone(), Style::default().fg(self.theme.focus))];
+        spans.extend(self.input_spans());
+        let input = Paragraph::new(Line::from(spans));
+        let area = f.size();
+        f.render_widget(input, area);
+        f.set_cursor(area.x + (prompt.chars().count() + self.cursor_column()) as u16, area.y);
+    }
+
+    /// Draw, take in output and handle keys until the user quits.
+    /// The screen is only drawn again once something changed, or after a while for the clocks on it.
+    fn event_loop(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+        input_tx: &UnboundedSender<UiEvent>,
+        output_rx: &mut channel::Receiver,
+    ) -> Result<()> {
+        let tick_rate = self.settings.tick_rate;
+        let mut spam_handler = InterruptHandler::new(2);
+        let mut prev_tick = Instant::now();
+        let mut changed = true;
+        let mut last_draw = Instant::now();
+
+        loop {
+            if changed || last_draw.elapsed() >= IDLE_REDRAW {
+                if self.inline {
+                    self.flush_inline(terminal)?;
+                    terminal.draw(|f| self.ui_inline(f))?;
+                } else {
+                    terminal.draw(|f| self.ui(f))?;
+                }
+                changed = false;
+                last_draw = Instant::now();
+            }
+
+            for _ in 0..LINES_PER_FRAME {
+                match output_rx.try_recv() {
+                    Ok(str) => {
+                        self.receive(str);
+                        changed = true;
+                    }
+                    // The session has ended
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                    Err(TryRecvError::Empty) => break,
+                }
+            }
+
+            let lines = (self.output.len(), self.pipe.as_ref().map(Pipe::count));
+            self.tick(input_tx);
+            changed |= (self.output.len(), self.pipe.as_ref().map(Pipe::count)) != lines;
+            changed |= self.presenter.as_mut().is_some_and(Presenter::expire);
+
+            let timeout = tick_rate.saturating_sub(prev_tick.elapsed());
+            if event::poll(timeout)? {
+                // Keys, pastes and resizes all change what is shown
+                changed = true;
+                match event::read()? {
+                    Event::Key(key) if !self.event_handler(key, &mut spam_handler, input_tx)? => return Ok(()),
+                    // Bracketed paste, so pasted lines aren't sent one by one as if typed
+                    Event::Paste(text) if self.raw => self.send_bytes(input_tx, text.into_bytes()),
+                    Event::Paste(text) if self.prompt().is_none() && self.input_mode == InputMode::Insert => self.paste(&text),
+                    _ => (),
+                }
+            }
+
+            if let Some(leave) = self.leave.take() {
+                if let Some(text) = self.step_out(terminal, leave, output_rx)? {
+                    if !self.send_edited(&text, input_tx)? {
+                        return Ok(());
+                    }
+                }
+            }
+
+            if prev_tick.elapsed() >= tick_rate {
+                prev_tick = Instant::now();
+            }
+        }
+    }
+
+    fn message_line(&self, index: usize) -> Line<'a> {
+        let msg = &self.output[index];
+        let sent = msg.origin == Origin::Sent;
+        let mut line = if sent && self.settings.echo == Echo::Highlight {
+            Line::styled(msg.text.clone(), self.theme.echo)
+        } else if msg.origin == Origin::Gap {
+            Line::styled(msg.text.clone(), Style::default().fg(self.theme.border).add_modifier(Modifier::DIM))
+        } else if msg.origin == Origin::Note {
+            Line::styled(msg.text.clone(), Style::default().fg(self.theme.focus).add_modifier(Modifier::BOLD | Modifier::REVERSED))
+        } else if self.show_matches {
+            let mut line = Line::styled(msg.text.clone(), self.theme.category(msg.category));
+            let annotation = Style::default().fg(self.theme.border).add_modifier(Modifier::DIM);
+            line.spans.push(Span::styled(legend::annotation(msg.category), annotation));
+            line
+        } else {
+            Line::styled(msg.text.clone(), self.theme.category(msg.category))
+        };
+        match msg.delivery {
+            Some(Delivery::Pending) => line = line.patch_style(Style::default().add_modifier(Modifier::DIM)),
+            Some(Delivery::Failed) => {
+                line = line.patch_style(Style::default().add_modifier(Modifier::CROSSED_OUT));
+                line.spans.push(Span::styled(i18n::text("not_sent_mark"), self.theme.categories[highlight::ERROR]));
+            }
+            _ => (),
+        }
+        if self.settings.direction_markers {
+            let marker = Span::styled(msg.origin.marker(), Style::default().fg(self.theme.border).add_modifier(Modifier::DIM));
+            line.spans.insert(0, marker);
+        }
+        if self.settings.timestamps {
+            let time = Span::styled(msg.time.format("%H:%M:%S ").to_string(), Style::default().fg(self.theme.border).add_modifier(Modifier::DIM));
+            line.spans.insert(0, time);
+        }
+        if self.settings.line_numbers {
+            let number = format!("{:>width$} ", self.dropped + index + 1, width = self.number_width());
+            line.spans.insert(0, Span::styled(number, Style::default().fg(self.theme.border)));
+        }
+        line
+    }
+
+    fn status_bar(&self) -> Paragraph<'a> {
+        let status = status::get(&self.status);
+        let port = match &status.port {
+            Some(port) => Span::styled(format!(" {} ", port), Style::default().fg(self.theme.focus).add_modifier(Modifier::BOLD)),
+            None => Span::styled(format!(" {} ", i18n::text("not_connected")), Style::default().fg(self.theme.border)),
+        };
+
+        let mut spans = vec![
+            port,
+            Span::styled(format!("| {} ", status.link), Style::default().fg(self.theme.accent)),
+        ];
+        if let Some(profile) = status.firmware {
+            spans.push(Span::styled(format!("| {} ", profile.title()), Style::default().fg(self.theme.accent)));
+        }
+        if status.dry_run {
+            spans.push(Span::styled(format!("| {} ", i18n::text("dry_run")), Style::default().fg(self.theme.focus).add_modifier(Modifier::BOLD)));
+        }
+        if status.broadcast {
+            spans.push(Span::styled(format!("| {} ", i18n::text("broadcast")), Style::default().fg(self.theme.focus).add_modifier(Modifier::BOLD)));
+        }
+        if status.skip_broadcasts {
+            spans.push(Span::styled(format!("| {} ", i18n::text("skip_broadcasts")), Style::default().fg(self.theme.border)));
+        }
+        if self.bell.is_muted() {
+            spans.push(Span::styled(format!("| {} ", i18n::text("muted")), Style::default().fg(self.theme.border)));
+        }
+        if status.stale {
+            spans.push(Span::styled(format!("| {} ", i18n::text("stale")), Style::default().fg(self.theme.focus).add_modifier(Modifier::BOLD)));
+        }
+        if let Some(gps) = &self.gps {
+            let (text, color) = match gps.fix() {
+                Some(_) => (i18n::text("gps"), self.theme.accent),
+                None => (i18n::text("no_fix"), self.theme.border),
+            };
+            spans.push(Span::styled(format!("| {} ", text), Style::default().fg(color)));
+        }
+        if status.resets > 0 {
+            let resets = i18n::format(if status.resets == 1 { "reset" } else { "resets" }, &[&status.resets]);
+            let resets = format!("| {} ", resets);
+            spans.push(Span::styled(resets, Style::default().fg(self.theme.focus).add_modifier(Modifier::BOLD)));
+        }
+        Paragraph::new(Line::from(spans))
+    }
+
+    fn ui(&mut self, f: &mut Frame) {
+        let chart_height = if self.settings.channels { 10 } else { 0 };
+        let pipe_height = if self.pipe.is_some() { 10 } else { 0 };
+        let attack_height = if self.attack.is_running() { 3 } else { 0 };
+        let timers_height = match self.timers.len() {
+            0 => 0,
+            n => n.min(MAX_TIMERS) as u16 + 2,
+        };
+        // Presenter mode gives the input line room above and below, so it stands out in recordings
+        let input_padding = if self.presenter.is_some() { 1 } else { 0 };
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(2)
+            .constraints(
+                [
+                    Constraint::Min(1),
+                    Constraint::Length(attack_height),
+                    Constraint::Length(timers_height),
+                    Constraint::Length(chart_height),
+                    Constraint::Length(pipe_height),
+                    Constraint::Length(3 + 2 * input_padding),
+                    Constraint::Length(1),
+                ]
+                .as_ref(),
+            )
+            .split(f.size());
+
+        let (msg_color, input_color) = match self.input_mode {
+            InputMode::Insert => (self.theme.focus, self.theme.border),
+            InputMode::Normal => (self.theme.border, self.theme.focus)
+        };
+
+        // Scroll in rendered rows, but remember the position as a line so resizing doesn't move the view
+        // Watches get a sidebar next to the messages
+        let sidebar_width = if self.watches.is_empty() { 0 } else { watches::WIDTH };
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(1), Constraint::Length(sidebar_width)])
+            .split(chunks[0]);
+        let messages_area = columns[0];
+
+        let box_height = (messages_area.height as usize).saturating_sub(2);
+        let box_width = (messages_area.width as usize).saturating_sub(2).max(1);
+
+        // Only lines that weren't measured with this layout yet are built to find their size
+        let numbers = if self.settings.line_numbers { self.number_width() } else { 0 };
+        let key = (box_width as u16, self.settings.wrap, self.settings.direction_markers, self.show_matches, self.settings.timestamps, numbers);
+        if self.measured.key != key || self.measured.heights.len() > self.output.len() {
+            self.measured = Measured { key, ..Measured::default() };
+        }
+        while self.measured.heights.len() < self.output.len() {
+            let line = self.message_line(self.measured.heights.len());
+            let height = Self::rows(&line, box_width as u16, self.settings.wrap);
+            self.measured.push(height, line.width());
+        }
+
+        // Set scroll position
+        let rows: usize = self.measured.heights.iter().sum();
+        let max_scroll = rows.saturating_sub(box_height);
+
+        let scroll_pos = if self.manual_scroll {
+            Self::anchor_row(self.scroll_anchor, &self.measured.heights) as isize
+        } else {
+            max_scroll as isize
+        };
+        let scroll_pos = (scroll_pos + self.scroll_delta).clamp(0, max_scroll as isize) as usize;
+        self.scroll_delta = 0;
+        if self.manual_scroll && scroll_pos >= max_scroll {
+            self.scroll_to_bottom();
+        }
+        self.scroll_anchor = Self::anchor_at(scroll_pos, &self.measured.heights);
+        self.scrollbar = self.scrollbar.content_length(max_scroll).position(scroll_pos);
+
+        // Build only the lines in view, starting with the one cut off at the top
+        let (first, offset) = self.scroll_anchor;
+        let mut lines = Vec::new();
+        let mut shown = 0;
+        for (index, height) in self.measured.heights.iter().enumerate().skip(first) {
+            if shown >= offset + box_height {
+                break;
+            }
+            lines.push(self.message_line(index));
+            shown += height;
+        }
+
+        let overflow = self.measured.max_width.saturating_sub(box_width);
+        self.hscroll_pos = self.hscroll_pos.min(overflow);
+        self.hscrollbar = self.hscrollbar.content_length(overflow).position(self.hscroll_pos);
+
+        // Message Box
+        let mut block = Block::default().borders(Borders::ALL).border_style(Style::default().fg(msg_color)).title(i18n::text("messages"));
+        if self.manual_scroll && self.unseen > 0 {
+            let badge = i18n::format(if self.unseen == 1 { "new_line" } else { "new_lines" }, &[&self.unseen]);
+            block = block.title(
+                Title::from(Span::styled(badge, Style::default().fg(self.theme.accent).add_modifier(Modifier::BOLD)))
+                    .position(Position::Bottom)
+                    .alignment(Alignment::Right),
+            );
+        }
+        let mut messages = Paragraph::new(lines).block(block);
+        if self.settings.wrap {
+            messages = messages.wrap(Wrap { trim: false }).scroll((offset as u16, 0));
+        } else {
+            messages = messages.scroll((offset as u16, self.hscroll_pos as u16));
+        }
+        f.render_widget(messages, messages_area);
+        f.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(Some("^"))
+                .end_symbol(Some("v")),
+            messages_area,
+            &mut self.scrollbar,
+        );
+        if !self.settings.wrap && overflow > 0 {
+            f.render_stateful_widget(
+                Scrollbar::new(ScrollbarOrientation::HorizontalBottom)
+                    .begin_symbol(Some("<"))
+                    .end_symbol(Some(">")),
+                messages_area,
+                &mut self.hscrollbar,
+            );
+        }
+
+        // Input Box, in raw mode it shows the line the device is writing
+        let (spans, title, cursor) = if self.raw {
+            let title = i18n::format("raw", &[&self.settings.raw_escape]);
+            (vec![Span::raw(self.partial.clone())], title, self.partial.chars().count())
+        } else {
+            (self.input_spans(), i18n::text("input").to_string(), self.cursor_column())
+        };
+        let mut input_style = Style::default().fg(self.theme.input);
+        if self.presenter.is_some() {
+            input_style = input_style.add_modifier(Modifier::BOLD);
+        }
+        let input = Paragraph::new(Line::from(spans)).style(input_style).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(input_color))
+                .title(title)
+                .padding(Padding::vertical(input_padding)),
+        );
+        f.render_widget(input, chunks[5]);
+        // Show cursor
+        f.set_cursor(
+            // Put cursor after input text
+            chunks[5].x + cursor as u16 + 1,
+            // Leave room for border
+            chunks[5].y + 1 + input_padding,
+        );
+
+        // Attack dashboard
+        self.attack.render(f, chunks[1], &self.theme);
+
+        // Countdowns
+        self.timers.render(f, chunks[2], &self.theme);
+
+        // Channel activity
+        if self.settings.channels {
+            self.activity.render(f, chunks[3], &self.theme);
+        }
+
+        if !self.watches.is_empty() {
+            self.watches.render(f, columns[1], &self.theme);
+        }
+
+        // Output of the piped command
+        if let Some(pipe) = &self.pipe {
+            pipe.render(f, chunks[4], &self.theme);
+        }
+
+        // Status bar
+        f.render_widget(self.status_bar(), chunks[6]);
+
+        if self.show_ssids {
+            self.ssids.render(f, f.size(), &self.theme);
+        }
+
+        if self.show_settings {
+            self.device_settings.render(f, f.size(), &self.theme);
+        }
+
+        if let Some(panel) = self.preferences.as_mut() {
+            panel.render(f, f.size(), &self.theme, &self.settings);
+        }
+
+        if self.show_scans {
+            self.scans.render(f, f.size(), &self.theme);
+        }
+
+        if let Some(browser) = self.db_browser.as_mut() {
+            browser.render(f, f.size(), &self.theme);
+        }
+
+        if self.show_legend {
+            legend::render(f, f.size(), &self.theme, self.show_matches);
+        }
+
+        if let Some(picker) = self.picker.as_mut() {
+            picker.render(f, f.size());
+        }
+
+        if let Some(form) = self.template.as_mut() {
+            form.render(f, f.size(), &self.theme);
+        }
+
+        if let Some(presenter) = &self.presenter {
+            presenter.render(f, messages_area, &self.theme);
+        }
+
+        if let Some(prompt) = self.prompt() {
+            let area = picker::centered(f.size(), 40, 20);
+            let popup = Paragraph::new(prompt)
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: true })
+                .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(self.theme.focus)));
+            f.render_widget(Clear, area);
+            f.render_widget(popup, area);
+        }
+    }
+
+    /// Hand the terminal to the shell and take it back afterwards, with the text written in the editor.
+    /// The connection keeps running, output that arrives meanwhile is shown on return.
+    fn step_out(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+        leave: Leave,
+        output_rx: &mut channel::Receiver,
+    ) -> Result<Option<String>> {
+        disable_raw_mode()?;
+        execute!(terminal.backend_mut(), DisableBracketedPaste)?;
+        if self.inline {
+            terminal.clear()?;
+        } else {
+            execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+        }
+        terminal.show_cursor()?;
+
+        let edited = self.away(leave, output_rx);
+
+        enable_raw_mode()?;
+        execute!(terminal.backend_mut(), EnableBracketedPaste)?;
+        if self.inline {
+            // The shell moved the cursor, so the input line starts over below its output
+            let backend = CrosstermBackend::new(io::stdout());
+            *terminal = Terminal::with_options(backend, TerminalOptions { viewport: Viewport::Inline(INLINE_HEIGHT) })?;
+        } else {
+            execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+            terminal.clear()?;
+        }
+        Ok(edited)
+    }
+
+    /// Do what the terminal was handed over for, returns the text written in the editor
+    fn away(&mut self, leave: Leave, output_rx: &mut channel::Receiver) -> Option<String> {
+        match leave {
+            Leave::Suspend => {
+                if let Err(e) = shell::suspend() {
+                    self.push(format!("ERROR: {}", i18n::format("shell_failed", &[&e])));
+                }
+            }
+            Leave::Run(command) => {
+                let status = self.meanwhile(output_rx, move || shell::run(command.as_deref()));
+                self.shell_done(status);
+            }
+            Leave::Edit(text) => match self.meanwhile(output_rx, move || shell::edit(&text)) {
+                Ok(text) => return Some(text),
+                Err(e) => self.push(format!("ERROR: {}", i18n::format("editor_failed", &[&e]))),
+            },
+        }
+        None
+    }
+
+    /// Run the shell or editor in its own thread and take in output until it is done,
+    /// so a full output channel doesn't hold up the connection meanwhile
+    fn meanwhile<T: std::marker::Send + 'static>(
+        &mut self,
+        output_rx: &mut channel::Receiver,
+        work: impl FnOnce() -> T + std::marker::Send + 'static,
+    ) -> T {
+        let handle = thread::spawn(work);
+        while !handle.is_finished() {
+            while let Ok(event) = output_rx.try_recv() {
+                self.receive(event);
+            }
+            thread::sleep(AWAY_POLL);
+        }
+        handle.join().unwrap_or_else(|e| std::panic::resume_unwind(e))
+    }
+
+    /// Open the editor with the input line, one command of its chain per line
+    fn open_editor(&mut self) {
+        let mut text = chain::split(&self.input).join("\n");
+        text.push('\n');
+        self.leave = Some(Leave::Edit(text));
+    }
+
+    /// Send the lines written in the editor like a typed chain of commands, so they go out one by one.
+    /// Returns false if the user quit.
+    fn send_edited(&mut self, text: &str, input_tx: &UnboundedSender<UiEvent>) -> Result<bool> {
+        let line = paste::sanitize(text);
+        if line.is_empty() {
+            return Ok(true);
+        }
+        self.input = line;
+        let text = self.submit();
+        self.enter(text, input_tx)
+    }
+
+    /// Report how the shell or command ended
+    fn shell_done(&mut self, status: io::Result<std::process::ExitStatus>) {
+        match status {
+            Ok(status) if status.success() => (),
+            Ok(status) => self.push(format!("> {}", i18n::format("shell_exit", &[&status]))),
+            Err(e) => self.push(format!("ERROR: {}", i18n::format("shell_failed", &[&e]))),
+        }
+    }
+
+    /// restore terminal
+    fn shutdown(mut terminal: Terminal<CrosstermBackend<Stdout>>, inline: bool) -> Result<()> {
+        disable_raw_mode()?;
+        execute!(terminal.backend_mut(), DisableBracketedPaste)?;
+        if inline {
+            // Keep the scrollback, only remove the input line
+            terminal.clear()?;
+        } else {
+            execute!(
+                terminal.backend_mut(),
+                LeaveAlternateScreen
+            )?;
+        }
+        terminal.show_cursor()?;
+        Ok(())
+    }
+}
+
+/// Leave raw mode and the alternate screen, ignoring errors.
+/// Used from the panic hook and on exit paths where the TUI can't clean up itself.
+pub fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(io::stdout(), DisableBracketedPaste, LeaveAlternateScreen, Show);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app() -> App {
+        let settings = Settings {
+            echo: Echo::Plain,
+            wrap: false,
+            timestamps: false,
+            line_numbers: false,
+            scrollback: None,
+            theme: "default",
+            color_depth: ColorDepth::Ansi16,
+            confirm_exit: false,
+            ctrl_c: CtrlC::Spam,
+            channels: false,
+            channel_window: Duration::from_secs(10),
+            history: false,
+            confirm_commands: true,
+            direction_markers: false,
+            gap: None,
+            autosave: None,
+            title: false,
+            title_attack: false,
+            accessible: false,
+            paste_limit: paste::LIMIT,
+            raw_escape: raw::Escape::parse(raw::ESCAPE).unwrap(),
+            tick_rate: TICK_RATE,
+        };
+        App::new(settings, Theme::default())
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            app.put_char(c);
+        }
+    }
+
+    #[test]
+    fn edits_around_non_ascii_chars() {
+        let mut app = app();
+        type_text(&mut app, "Grüße Welt");
+        app.kill(app.word_start(false), app.cursor_pos);
+        assert_eq!(app.input, "Grüße ");
+
+        app.cursor_left();
+        app.cursor_left();
+        assert_eq!(app.cursor_column(), 4);
+        app.put_char('s');
+        app.delete_char();
+        app.delete_char();
+        assert_eq!(app.input, "Grüe ");
+
+        app.yank();
+        assert_eq!(app.input, "GrüWelte ");
+        app.cursor_left();
+        app.cursor_left();
+        app.cursor_left();
+        app.cursor_left();
+        app.cursor_left();
+        app.kill(app.cursor_pos, app.input.len());
+        assert_eq!(app.input, "Gr");
+        app.cursor_right();
+        assert_eq!(app.cursor_column(), 2);
+    }
+
+    #[test]
+    fn types_after_a_non_ascii_paste() {
+        let mut app = app();
+        app.paste("Café");
+        app.cursor_left();
+        app.put_char('f');
+        assert_eq!(app.input, "Caffé");
+        assert_eq!(app.cursor_column(), 4);
+
+        app.paste("\u{1f414}\n\u{1f95a}");
+        assert_eq!(app.input, "Caff\u{1f414}; \u{1f95a}é");
+        app.delete_char();
+        app.undo();
+        assert_eq!(app.input, "Caff\u{1f414}; \u{1f95a}é");
     }
 }
@@ -0,0 +1,117 @@
+use ratatui::layout::{Alignment, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::block::Title;
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::Frame;
+use regex::Regex;
+use std::time::Instant;
+
+use crate::i18n;
+use crate::output;
+use crate::theme::Theme;
+
+// The deauther reports attacks in a few different shapes, e.g.
+// `Starting deauth attack`, `Deauth attack: 3 targets, 120 pkts/s, 4512 packets sent` and `Attack stopped`
+lazy_static::lazy_static! {
+    static ref STARTED: Regex = Regex::new(r"(?i)\b(?:start\w*|running) +(deauth|beacon|probe)\w*|\b(deauth|beacon|probe)\w* +attack +(?:start|running)").unwrap();
+    static ref STOPPED: Regex = Regex::new(r"(?i)\b(?:attack\w* +(?:stopped|finished|done)|stopped +(?:all +)?attacks?)\b").unwrap();
+    static ref KIND: Regex = Regex::new(r"(?i)\b(deauth|beacon|probe)\w*\b").unwrap();
+    static ref TARGETS: Regex = Regex::new(r"(?i)\b(\d+) *targets?\b").unwrap();
+    static ref RATE: Regex = Regex::new(r"(?i)\b(\d+) *(?:pkts|packets)/s\b").unwrap();
+    static ref SENT: Regex = Regex::new(r"(?i)\b(\d+) *(?:pkts|packets)(?: sent)?\b(?:[^/]|$)").unwrap();
+}
+
+/// Key that stops the attack from the dashboard
+pub const STOP_KEY: char = 'x';
+
+/// The attack the deauther is currently running
+struct Attack {
+    kind: String,
+    targets: Option<u64>,
+    packets: Option<u64>,
+    rate: Option<u64>,
+    started: Instant,
+}
+
+/// Keeps track of the attack status from the deauther output
+#[derive(Default)]
+pub struct Tracker {
+    current: Option<Attack>,
+}
+
+impl Tracker {
+    pub fn is_running(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Type of the running attack, e.g. `deauth`
+    pub fn kind(&self) -> Option<&str> {
+        self.current.as_ref().map(|attack| attack.kind.as_str())
+    }
+
+    /// Update the status from a line of output
+    pub fn update(&mut self, line: &str) {
+        if STOPPED.is_match(line) {
+            self.current = None;
+            return;
+        }
+
+        if let Some(caps) = STARTED.captures(line) {
+            let kind = caps.get(1).or_else(|| caps.get(2)).map_or("", |kind| kind.as_str());
+            self.current = Some(Attack {
+                kind: kind.to_lowercase(),
+                targets: None,
+                packets: None,
+                rate: None,
+                started: Instant::now(),
+            });
+        }
+
+        // Progress lines only count while an attack is known to run
+        let attack = match self.current.as_mut() {
+            Some(attack) if KIND.is_match(line) || line.to_lowercase().contains("attack") => attack,
+            _ => return,
+        };
+
+        let number = |regex: &Regex| regex.captures(line).and_then(|caps| caps[1].parse().ok());
+        attack.targets = number(&TARGETS).or(attack.targets);
+        attack.rate = number(&RATE).or(attack.rate);
+        attack.packets = number(&SENT).or(attack.packets);
+    }
+
+    /// One line summary with the stop key
+    pub fn render(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let attack = match &self.current {
+            Some(attack) => attack,
+            None => return,
+        };
+
+        let unknown = || "?".to_string();
+        let mut spans = vec![
+            Span::styled(format!(" {} ", attack.kind), Style::default().fg(theme.focus).add_modifier(Modifier::BOLD)),
+            Span::styled(
+                format!(
+                    "| targets: {} | packets: {}",
+                    attack.targets.map_or_else(unknown, |n| n.to_string()),
+                    attack.packets.map_or_else(unknown, |n| n.to_string()),
+                ),
+                theme.text,
+            ),
+        ];
+        if let Some(rate) = attack.rate {
+            spans.push(Span::styled(format!(" ({}/s)", rate), theme.text));
+        }
+        spans.push(Span::styled(
+            format!(" | {} ", output::duration(attack.started.elapsed())),
+            theme.text,
+        ));
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.accent))
+            .title(i18n::text("attack"))
+            .title(Title::from(i18n::format("attack_stop", &[&STOP_KEY.to_ascii_uppercase()])).alignment(Alignment::Right));
+        f.render_widget(Paragraph::new(Line::from(spans)).block(block), area);
+    }
+}
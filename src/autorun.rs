@@ -0,0 +1,168 @@
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Pause before each command of the `on-connect` list, unless the config sets another
+pub const DELAY: Duration = Duration::from_millis(500);
+
+/// Longest wait for the prompt before the next command is sent anyway
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Entry of the `on-connect` list, a command or a table with its own delay in milliseconds
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum Entry {
+    Command(String),
+    Delayed { command: String, delay: u64 },
+}
+
+/// Command sent after connecting and how long to wait before sending it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub command: String,
+    pub delay: Duration,
+}
+
+/// Steps of the config entries, `delay` is used where an entry doesn't have its own
+pub fn steps(entries: &[Entry], delay: Duration) -> Vec<Step> {
+    entries
+        .iter()
+        .map(|entry| match entry {
+            Entry::Command(command) => Step { command: command.clone(), delay },
+            Entry::Delayed { command, delay } => Step { command: command.clone(), delay: Duration::from_millis(*delay) },
+        })
+        .collect()
+}
+
+/// Steps still to run in the current session
+pub struct Queue {
+    steps: VecDeque<Step>,
+    due: Instant,
+    /// Wait for the prompt of the device after each command
+    wait: bool,
+    /// Sent a command and the prompt didn't show up yet
+    waiting: bool,
+}
+
+impl Default for Queue {
+    fn default() -> Self {
+        Self::waiting_for_prompt(false)
+    }
+}
+
+impl Queue {
+    pub fn waiting_for_prompt(wait: bool) -> Self {
+        Self { steps: VecDeque::new(), due: Instant::now(), wait, waiting: false }
+    }
+
+    /// Start over with all steps, the first one is due after its delay
+    pub fn start(&mut self, steps: &[Step]) {
+        self.steps = steps.iter().cloned().collect();
+        self.waiting = false;
+        self.schedule();
+    }
+
+    /// Add steps after the ones still waiting
+    pub fn extend(&mut self, steps: Vec<Step>) {
+        let idle = self.steps.is_empty();
+        self.steps.extend(steps);
+        if idle {
+            self.waiting = false;
+            self.schedule();
+        }
+    }
+
+    /// Drop the steps that weren't sent yet
+    pub fn clear(&mut self) {
+        self.steps.clear();
+        self.waiting = false;
+    }
+
+    /// The device showed its prompt, the next step is due after its delay
+    pub fn prompt(&mut self) {
+        if std::mem::take(&mut self.waiting) {
+            self.schedule();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// When the next command is due
+    pub fn due(&self) -> Instant {
+        self.due
+    }
+
+    /// Take the next command and schedule the one after it
+    pub fn pop(&mut self) -> Option<String> {
+        let step = self.steps.pop_front()?;
+        if self.wait && !self.steps.is_empty() {
+            self.waiting = true;
+            self.due = Instant::now() + PROMPT_TIMEOUT;
+        } else {
+            self.waiting = false;
+            self.schedule();
+        }
+        Some(step.command)
+    }
+
+    fn schedule(&mut self) {
+        if let Some(step) = self.steps.front() {
+            self.due = Instant::now() + step.delay;
+        }
+    }
+}
+
+/// Settings commands sent during the session, the last one of each setting
+#[derive(Default)]
+pub struct Applied {
+    commands: Vec<(String, String)>,
+    /// Port of the device they were sent to
+    port: Option<String>,
+}
+
+impl Applied {
+    /// Remember a command, replacing the earlier one that changed the same setting
+    pub fn record(&mut self, setting: String, command: &str) {
+        self.commands.retain(|(name, _)| *name != setting);
+        self.commands.push((setting, command.to_string()));
+    }
+
+    /// Forget the commands when a different device is connected, they don't apply to it
+    pub fn connected(&mut self, port: &str) {
+        if self.port.as_deref() != Some(port) {
+            self.commands.clear();
+            self.port = Some(port.to_string());
+        }
+    }
+
+    /// Steps sending the commands again in the order they were first sent
+    pub fn steps(&self, delay: Duration) -> Vec<Step> {
+        self.commands.iter().map(|(_, command)| Step { command: command.clone(), delay }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(applied: &Applied) -> Vec<String> {
+        applied.steps(Duration::ZERO).into_iter().map(|step| step.command).collect()
+    }
+
+    #[test]
+    fn keeps_settings_across_reconnects_to_the_same_port() {
+        let mut applied = Applied::default();
+        applied.connected("/dev/ttyUSB0");
+        applied.record("channel".to_string(), "set channel 1");
+        applied.record("channel".to_string(), "set channel 6");
+
+        applied.connected("/dev/ttyUSB0");
+        assert_eq!(commands(&applied), ["set channel 6"]);
+
+        applied.connected("/dev/ttyUSB1");
+        assert!(commands(&applied).is_empty());
+    }
+}
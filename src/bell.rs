@@ -0,0 +1,98 @@
+use std::io::{self, Write};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use crate::highlight::{self, RULES};
+use crate::shell;
+
+/// Rings closer together than this are one, so a flood of errors doesn't turn into a buzz
+const QUIET: Duration = Duration::from_secs(1);
+
+/// Things that can ring besides lines of a highlight category
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Alert {
+    /// A running attack ended
+    AttackDone,
+    /// The countdown of a timed command ran out
+    Timer,
+}
+
+impl Alert {
+    const NAMES: [(&'static str, Alert); 2] = [("attack-done", Alert::AttackDone), ("timer", Alert::Timer)];
+}
+
+/// Rings the terminal bell, or plays a sound with a command, when something worth noticing happens
+#[derive(Default)]
+pub struct Bell {
+    /// Highlight categories whose lines ring
+    categories: [bool; highlight::COUNT],
+    alerts: Vec<Alert>,
+    /// Run instead of the terminal bell, e.g. `paplay alert.oga`
+    command: Option<String>,
+    muted: bool,
+    last: Option<Instant>,
+}
+
+impl Bell {
+    /// Ring for the given highlight categories, e.g. `error`, and alerts, e.g. `attack-done`
+    pub fn new(names: &[String], command: Option<String>) -> Result<Self, String> {
+        let mut bell = Self { command, ..Self::default() };
+        for name in names {
+            let name = name.trim().to_lowercase();
+            if let Some(i) = RULES.iter().position(|rule| rule.name == name) {
+                bell.categories[i] = true;
+            } else if let Some((_, alert)) = Alert::NAMES.iter().find(|(alert, _)| *alert == name) {
+                bell.alerts.push(*alert);
+            } else {
+                let known: Vec<&str> = RULES.iter().map(|rule| rule.name).chain(Alert::NAMES.iter().map(|(name, _)| *name)).collect();
+                return Err(format!("Unknown bell '{}', expected one of {}", name, known.join(", ")));
+            }
+        }
+        Ok(bell)
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    /// A line was shown in the given highlight category
+    pub fn line(&mut self, category: Option<usize>) {
+        if category.is_some_and(|category| self.categories[category]) {
+            self.ring();
+        }
+    }
+
+    pub fn alert(&mut self, alert: Alert) {
+        if self.alerts.contains(&alert) {
+            self.ring();
+        }
+    }
+
+    fn ring(&mut self) {
+        if self.muted || self.last.is_some_and(|last| last.elapsed() < QUIET) {
+            return;
+        }
+        self.last = Some(Instant::now());
+        match &self.command {
+            Some(command) => {
+                // Not waited for, the sound plays while the session goes on
+                let (shell, flag) = shell::shell();
+                let _ = tokio::process::Command::new(shell)
+                    .arg(flag)
+                    .arg(command)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn();
+            }
+            None => {
+                let mut stdout = io::stdout();
+                let _ = stdout.write_all(b"\x07").and_then(|_| stdout.flush());
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use std::io;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::channel;
+use crate::i18n;
+use crate::output;
+
+/// Serial output buffered per client before a slow client starts missing data
+const BACKLOG: usize = 1024;
+
+/// Shares the serial session with TCP clients
+pub struct Bridge {
+    /// Serial output, mirrored to every client
+    clients: broadcast::Sender<Vec<u8>>,
+}
+
+impl Bridge {
+    /// Start listening, client connections are announced on `notify_tx`.
+    /// Input from clients is only accepted with an `input_tx`, otherwise they can just watch.
+    pub async fn bind(addr: SocketAddr, input_tx: Option<UnboundedSender<Vec<u8>>>, notify_tx: &channel::Sender) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let _ = notify_tx.notify(format!("> {}", i18n::format("serving", &[&listener.local_addr()?])));
+
+        let notify_tx = notify_tx.downgrade();
+
+        let (clients, _) = broadcast::channel(BACKLOG);
+
+        let output = clients.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, peer)) = listener.accept().await {
+                output::notify(&notify_tx, format!("> {}", i18n::format("bridge_connected", &[&peer])));
+                let input_tx = input_tx.clone();
+                let notify_tx = notify_tx.clone();
+                let output_rx = output.subscribe();
+                tokio::spawn(async move {
+                    serve(stream, output_rx, input_tx).await;
+                    output::notify(&notify_tx, format!("> {}", i18n::format("bridge_disconnected", &[&peer])));
+                });
+            }
+        });
+
+        Ok(Self { clients })
+    }
+
+    /// Mirror serial output to the clients
+    pub fn send(&self, bytes: &[u8]) {
+        // Fails when nobody is connected, which is fine
+        let _ = self.clients.send(bytes.to_vec());
+    }
+}
+
+/// Talk to one client until it disconnects
+async fn serve(stream: TcpStream, mut output_rx: broadcast::Receiver<Vec<u8>>, input_tx: Option<UnboundedSender<Vec<u8>>>) {
+    let (mut reader, mut writer) = stream.into_split();
+    let mut buf = [0; 1024];
+
+    loop {
+        tokio::select! {
+            bytes = output_rx.recv() => match bytes {
+                Ok(bytes) => {
+                    if writer.write_all(&bytes).await.is_err() {
+                        return;
+                    }
+                }
+                // The client was too slow, skip what it missed
+                Err(RecvError::Lagged(_)) => (),
+                Err(RecvError::Closed) => return,
+            },
+
+            len = reader.read(&mut buf) => match len {
+                Ok(0) | Err(_) => return,
+                Ok(len) => {
+                    if let Some(input_tx) = &input_tx {
+                        if input_tx.send(buf[..len].to_vec()).is_err() {
+                            return;
+                        }
+                    }
+                }
+            },
+        }
+    }
+}
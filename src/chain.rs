@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+/// Separates commands typed on one line, `\;` is sent as a semicolon
+pub const SEPARATOR: char = ';';
+
+/// Pause between the commands of a line unless the config sets another
+pub const DELAY: Duration = Duration::from_millis(500);
+
+/// Commands of a line, without blank ones. A separator inside double quotes belongs to the
+/// argument, e.g. an SSID like `add ssid "a;b"`.
+pub fn split(line: &str) -> Vec<String> {
+    let mut commands = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&SEPARATOR) => current.push(chars.next().unwrap_or(SEPARATOR)),
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            SEPARATOR if !quoted => commands.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    commands.push(current);
+    commands.into_iter().map(|command| command.trim().to_string()).filter(|command| !command.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_at_separators() {
+        assert_eq!(split("scan -t 5; select -a 3;attack -da"), ["scan -t 5", "select -a 3", "attack -da"]);
+        assert_eq!(split(" ; scan ;; "), ["scan"]);
+        assert!(split("").is_empty());
+    }
+
+    #[test]
+    fn keeps_escaped_and_quoted_separators() {
+        assert_eq!(split(r"add ssid a\;b; show ssids"), ["add ssid a;b", "show ssids"]);
+        assert_eq!(split(r#"add ssid "a;b" -wpa2; show ssids"#), [r#"add ssid "a;b" -wpa2"#, "show ssids"]);
+        assert_eq!(split(r#"add ssid "a;b"#), [r#"add ssid "a;b"#]);
+    }
+}
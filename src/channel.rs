@@ -0,0 +1,270 @@
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::Notify;
+
+use crate::protocol::BackendEvent;
+
+/// Lines waiting for the UI by default
+pub const CAPACITY: usize = 10_000;
+
+/// What happens to device output when the UI falls behind
+#[derive(Clone, Copy, PartialEq, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Overflow {
+    /// Throw away the oldest waiting lines and say how many were lost
+    #[default]
+    DropOldest,
+    /// Stop reading from the device until the UI catches up
+    Block,
+}
+
+/// The UI is gone
+#[derive(Debug)]
+pub struct Closed;
+
+struct State {
+    queue: VecDeque<BackendEvent>,
+    /// Lines thrown away since the receiver last looked
+    dropped: usize,
+    senders: usize,
+    /// Receiver is gone
+    closed: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    capacity: usize,
+    overflow: Overflow,
+    /// Wakes the receiver when a line arrives or the last sender is gone
+    readable: Notify,
+    /// Wakes a blocked sender when there is room again or the receiver is gone
+    writable: Notify,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Bounded channel of events from the serial side to the UI
+pub fn bounded(capacity: usize, overflow: Overflow) -> (Sender, Receiver) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State { queue: VecDeque::new(), dropped: 0, senders: 1, closed: false }),
+        capacity: capacity.max(1),
+        overflow,
+        readable: Notify::new(),
+        writable: Notify::new(),
+    });
+    (Sender { shared: shared.clone() }, Receiver { shared })
+}
+
+pub struct Sender {
+    shared: Arc<Shared>,
+}
+
+impl Sender {
+    /// Queue an event without waiting, only device output waits for room
+    pub fn send(&self, event: BackendEvent) -> Result<(), Closed> {
+        let mut state = self.shared.lock();
+        if state.closed {
+            return Err(Closed);
+        }
+        if state.queue.len() >= self.shared.capacity && self.shared.overflow == Overflow::DropOldest {
+            state.queue.pop_front();
+            state.dropped += 1;
+        }
+        state.queue.push_back(event);
+        self.shared.readable.notify_one();
+        Ok(())
+    }
+
+    /// Whether device output can be queued without waiting, or the UI is gone and it fails right away
+    pub fn has_room(&self) -> bool {
+        let state = self.shared.lock();
+        state.closed || self.shared.overflow == Overflow::DropOldest || state.queue.len() < self.shared.capacity
+    }
+
+    /// Wait until `has_room`
+    pub async fn room(&self) {
+        while !self.has_room() {
+            self.shared.writable.notified().await;
+        }
+    }
+
+    /// Queue a line of device output, with `Overflow::Block` this waits until the UI took some lines
+    pub async fn send_output(&self, line: String) -> Result<(), Closed> {
+        self.room().await;
+        self.send(BackendEvent::Rx(line))
+    }
+
+    /// Show a message of the Huhnitor itself
+    pub fn notify(&self, text: String) -> Result<(), Closed> {
+        self.send(BackendEvent::Status(text))
+    }
+
+    /// Sender that doesn't keep the channel open, for background tasks. The UI quits once
+    /// the last `Sender` is gone, a task holding one would keep it running after the session ended.
+    pub fn downgrade(&self) -> WeakSender {
+        WeakSender { shared: Arc::downgrade(&self.shared) }
+    }
+}
+
+impl Clone for Sender {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        Self { shared: self.shared.clone() }
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.shared.readable.notify_one();
+        }
+    }
+}
+
+/// Sender that doesn't keep the channel open, see `Sender::downgrade`
+#[derive(Clone)]
+pub struct WeakSender {
+    shared: Weak<Shared>,
+}
+
+impl WeakSender {
+    /// A sender, unless all others are gone already
+    pub fn upgrade(&self) -> Option<Sender> {
+        let shared = self.shared.upgrade()?;
+        let mut state = shared.lock();
+        if state.senders == 0 {
+            return None;
+        }
+        state.senders += 1;
+        drop(state);
+        Some(Sender { shared })
+    }
+}
+
+pub struct Receiver {
+    shared: Arc<Shared>,
+}
+
+impl Receiver {
+    /// Next event, a warning comes first if lines were dropped
+    pub fn try_recv(&mut self) -> Result<BackendEvent, TryRecvError> {
+        let mut state = self.shared.lock();
+        if state.dropped > 0 {
+            let dropped = std::mem::take(&mut state.dropped);
+            let warning = format!("WARNING: {} lines dropped, the output came in faster than it could be shown", dropped);
+            return Ok(BackendEvent::Status(warning));
+        }
+        match state.queue.pop_front() {
+            Some(event) => {
+                self.shared.writable.notify_one();
+                Ok(event)
+            }
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Wait for the next event, `None` once all senders are gone
+    pub async fn recv(&mut self) -> Option<BackendEvent> {
+        loop {
+            match self.try_recv() {
+                Ok(event) => return Some(event),
+                Err(TryRecvError::Disconnected) => return None,
+                Err(TryRecvError::Empty) => self.shared.readable.notified().await,
+            }
+        }
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.writable.notify_waiters();
+        self.shared.writable.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn rx(line: &str) -> BackendEvent {
+        BackendEvent::Rx(line.to_string())
+    }
+
+    #[tokio::test]
+    async fn drops_the_oldest_lines_and_says_so() {
+        let (tx, mut rx_) = bounded(2, Overflow::DropOldest);
+        for line in ["a", "b", "c"] {
+            tx.send_output(line.to_string()).await.unwrap();
+        }
+        assert!(matches!(rx_.try_recv(), Ok(BackendEvent::Status(warning)) if warning.starts_with("WARNING: 1 lines dropped")));
+        assert_eq!(rx_.try_recv().unwrap(), rx("b"));
+        assert_eq!(rx_.try_recv().unwrap(), rx("c"));
+        assert!(matches!(rx_.try_recv(), Err(TryRecvError::Empty)));
+    }
+
+    #[tokio::test]
+    async fn blocks_until_the_receiver_takes_a_line() {
+        let (tx, mut rx_) = bounded(1, Overflow::Block);
+        tx.send_output("a".to_string()).await.unwrap();
+        assert!(!tx.has_room());
+        assert!(tokio::time::timeout(Duration::from_millis(50), tx.send_output("b".to_string())).await.is_err());
+
+        let sender = tokio::spawn(async move {
+            tx.send_output("b".to_string()).await.unwrap();
+            tx
+        });
+        assert_eq!(rx_.recv().await, Some(rx("a")));
+        let tx = sender.await.unwrap();
+        assert_eq!(rx_.recv().await, Some(rx("b")));
+        // Messages of the Huhnitor never wait
+        tx.send_output("c".to_string()).await.unwrap();
+        tx.notify("note".to_string()).unwrap();
+        assert_eq!(rx_.try_recv().unwrap(), rx("c"));
+    }
+
+    #[tokio::test]
+    async fn fails_once_the_receiver_is_gone() {
+        let (tx, rx_) = bounded(1, Overflow::Block);
+        tx.send_output("a".to_string()).await.unwrap();
+        let blocked = tokio::spawn(async move { tx.send_output("b".to_string()).await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        drop(rx_);
+        assert!(blocked.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn ends_once_every_sender_is_gone() {
+        let (tx, mut rx_) = bounded(4, Overflow::DropOldest);
+        let clone = tx.clone();
+        drop(tx);
+        clone.notify("last".to_string()).unwrap();
+        drop(clone);
+        assert_eq!(rx_.recv().await, Some(BackendEvent::Status("last".to_string())));
+        assert_eq!(rx_.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn weak_senders_dont_keep_it_open() {
+        let (tx, mut rx_) = bounded(4, Overflow::DropOldest);
+        let weak = tx.downgrade();
+        let upgraded = weak.upgrade().unwrap();
+        drop(tx);
+        upgraded.notify("still open".to_string()).unwrap();
+        drop(upgraded);
+
+        assert!(weak.upgrade().is_none());
+        assert_eq!(rx_.recv().await, Some(BackendEvent::Status("still open".to_string())));
+        assert_eq!(rx_.recv().await, None);
+    }
+}
@@ -0,0 +1,382 @@
+use clap::builder::FalseyValueParser;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use clap_complete::Shell;
+use serialport::FlowControl;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use crate::app;
+use crate::gps;
+use crate::logfile;
+use crate::serial;
+
+/// Command line of the Huhnitor. Without a subcommand it runs the monitor, so `huhnitor -p COM3` keeps working.
+#[derive(Parser)]
+#[command(name = "Huhnitor", bin_name = "huhnitor", about = env!("CARGO_PKG_DESCRIPTION"), version)]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: Global,
+
+    #[command(subcommand)]
+    pub cmd: Option<Cmd>,
+
+    #[command(flatten)]
+    pub monitor: Monitor,
+}
+
+impl Cli {
+    /// Parse the command line. Options of the monitor given along with another subcommand are an error, not ignored.
+    pub fn parse_checked() -> Self {
+        let mut command = Cli::command();
+        let matches = command.get_matches_mut();
+        let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        if let Some(name) = matches.subcommand_name() {
+            let monitor = Monitor::augment_args(clap::Command::new("monitor"));
+            let given = monitor
+                .get_arguments()
+                .find(|arg| matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine));
+            if let Some(arg) = given {
+                let option = arg.get_long().unwrap_or_default();
+                command.error(ErrorKind::ArgumentConflict, format!("--{} is an option of the monitor, not of {}", option, name)).exit();
+            }
+        }
+        cli
+    }
+
+    /// The subcommand to run, the monitor if none was given
+    pub fn split(self) -> (Global, Cmd) {
+        let Cli { global, cmd, monitor } = self;
+        (global, cmd.unwrap_or_else(|| Cmd::Monitor(Box::new(monitor))))
+    }
+}
+
+/// Options shared by all subcommands
+#[derive(Args)]
+pub struct Global {
+    /// Select port, by name or its number in the port list
+    #[arg(short, long, global = true, env = "HUHNITOR_PORT")]
+    pub port: Option<String>,
+
+    /// Baud rate, or auto to detect it after connecting
+    #[arg(short, long, global = true, env = "HUHNITOR_BAUD")]
+    pub baud: Option<serial::Baud>,
+
+    /// Flow control: none, software (XON/XOFF) or hardware (RTS/CTS)
+    #[arg(short, long, global = true, value_parser = serial::parse_flow_control, env = "HUHNITOR_FLOW")]
+    pub flow: Option<FlowControl>,
+
+    /// Don't show the logo and version
+    #[arg(short, long, global = true, value_parser = FalseyValueParser::new(), env = "HUHNITOR_QUIET")]
+    pub quiet: bool,
+
+    /// Disable colored output
+    #[arg(short = 'c', long = "no-color", global = true, value_parser = FalseyValueParser::new(), env = "HUHNITOR_NO_COLOR")]
+    pub no_color: bool,
+
+    /// Color theme: default, solarized, high-contrast or monochrome
+    #[arg(short, long, global = true, env = "HUHNITOR_THEME")]
+    pub theme: Option<String>,
+
+    /// Use another config file
+    #[arg(long, global = true, env = "HUHNITOR_CONFIG")]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+pub enum Cmd {
+    /// Talk to the device in the terminal UI, what runs without a subcommand
+    Monitor(Box<Monitor>),
+    /// List the serial ports with the names of their devices
+    List {
+        /// Only the port names, one per line, for scripts
+        #[arg(long)]
+        names: bool,
+    },
+    /// List the logged sessions with their name, device, firmware and log file
+    Sessions,
+    /// Send commands without the terminal UI and print what the device answers
+    Exec(Exec),
+    /// Send commands to a running Huhnitor, as if typed into it
+    Send(SendArgs),
+    /// Write a firmware image to the board with esptool
+    Flash(Flash),
+    /// Play back a dump written with --dump-raw, at the pace it was recorded
+    Replay(Replay),
+    /// Write a copy of a log with the [[redact]] rules of the config file applied, to publish it
+    Redact(Redact),
+    /// Keep a session running in the background, so closing the terminal doesn't end it
+    Daemon(Box<Daemon>),
+    /// Attach the terminal UI to a session of huhnitor daemon
+    Attach(Box<Attach>),
+    /// Print the completion script for bash, zsh, fish, elvish or powershell
+    Completions {
+        shell: Shell,
+    },
+    /// Give your user access to the serial adapter on Linux with a udev rule
+    SetupPermissions {
+        /// Install the rule with sudo instead of only printing it
+        #[arg(long)]
+        install: bool,
+    },
+}
+
+/// A session without terminal UI, takes the options of the monitor for the port, the log and the servers
+#[derive(Args)]
+pub struct Daemon {
+    /// Name of the session, to attach to it and to run more than one
+    #[arg(long, default_value = "default", env = "HUHNITOR_SESSION")]
+    pub name: String,
+
+    /// Stay in the foreground instead of going to the background, e.g. for a service manager
+    #[arg(long)]
+    pub foreground: bool,
+
+    #[command(flatten)]
+    pub monitor: Monitor,
+}
+
+/// The terminal UI on a session of the daemon, takes the options of the monitor for the UI
+#[derive(Args)]
+pub struct Attach {
+    /// Name of the session
+    #[arg(default_value = "default", env = "HUHNITOR_SESSION")]
+    pub name: String,
+
+    #[command(flatten)]
+    pub monitor: Monitor,
+}
+
+/// Options of the terminal UI
+#[derive(Args)]
+pub struct Monitor {
+    /// Detect the USB serial adapter and open its driver download
+    #[arg(short, long)]
+    pub driver: bool,
+
+    /// Disable automatic port connection
+    #[arg(short = 'a', long = "no-auto", value_parser = FalseyValueParser::new(), env = "HUHNITOR_NO_AUTO")]
+    pub no_auto: bool,
+
+    /// Disable welcome command
+    #[arg(short = 'w', long = "no-welcome", value_parser = FalseyValueParser::new(), env = "HUHNITOR_NO_WELCOME")]
+    pub no_welcome: bool,
+
+    /// Reload the scrollback and command history of the last session, on `--port` if given
+    #[arg(long, value_parser = FalseyValueParser::new(), env = "HUHNITOR_RESTORE")]
+    pub restore: bool,
+
+    /// Don't write anything to the port, show what would have been sent instead
+    #[arg(long, value_parser = FalseyValueParser::new(), env = "HUHNITOR_DRY_RUN")]
+    pub dry_run: bool,
+
+    /// Send at most this many bytes per second
+    #[arg(long, env = "HUHNITOR_TX_BYTES_PER_SEC")]
+    pub tx_bytes_per_sec: Option<u32>,
+
+    /// Send at most this many lines per second
+    #[arg(long, env = "HUHNITOR_TX_LINES_PER_SEC")]
+    pub tx_lines_per_sec: Option<u32>,
+
+    /// Pause this many milliseconds after every sent character
+    #[arg(long, env = "HUHNITOR_TX_CHAR_DELAY")]
+    pub tx_char_delay: Option<u64>,
+
+    /// Echo sent commands: off, plain or highlight
+    #[arg(short, long, env = "HUHNITOR_ECHO")]
+    pub echo: Option<app::Echo>,
+
+    /// Write the session transcript to a file
+    #[arg(short, long, env = "HUHNITOR_LOG")]
+    pub log: Option<PathBuf>,
+
+    /// Name of the logged session, kept with its metadata in <log>.json
+    #[arg(long, env = "HUHNITOR_SESSION_NAME")]
+    pub session_name: Option<String>,
+
+    /// Notes about the logged session, kept with its metadata
+    #[arg(long, env = "HUHNITOR_SESSION_NOTES")]
+    pub session_notes: Option<String>,
+
+    /// Start a new log once it grows past this size, e.g. 10M
+    #[arg(long, value_parser = logfile::parse_size, env = "HUHNITOR_LOG_MAX_SIZE")]
+    pub log_max_size: Option<u64>,
+
+    /// Start a new log every day
+    #[arg(long, value_parser = FalseyValueParser::new(), env = "HUHNITOR_LOG_DAILY")]
+    pub log_daily: bool,
+
+    /// Number of rotated logs to keep
+    #[arg(long, env = "HUHNITOR_LOG_KEEP")]
+    pub log_keep: Option<usize>,
+
+    /// Compress rotated logs with gzip
+    #[arg(long, value_parser = FalseyValueParser::new(), env = "HUHNITOR_LOG_COMPRESS")]
+    pub log_compress: bool,
+
+    /// Don't use the alternate screen, keep the output in the terminal scrollback
+    #[arg(short, long, value_parser = FalseyValueParser::new(), env = "HUHNITOR_INLINE")]
+    pub inline: bool,
+
+    /// Plain line based output without colors or borders, for screen readers
+    #[arg(long, value_parser = FalseyValueParser::new(), env = "HUHNITOR_ACCESSIBLE")]
+    pub accessible: bool,
+
+    /// Start in raw mode, every keystroke goes to the device right away
+    #[arg(long, value_parser = FalseyValueParser::new(), env = "HUHNITOR_RAW")]
+    pub raw: bool,
+
+    /// Show the pressed keys and a bigger input line, for screen recordings of tutorials
+    #[arg(long, value_parser = FalseyValueParser::new(), env = "HUHNITOR_PRESENTER")]
+    pub presenter: bool,
+
+    /// Share the session with TCP clients, e.g. 127.0.0.1:7000
+    #[arg(long, env = "HUHNITOR_SERVE")]
+    pub serve: Option<SocketAddr>,
+
+    /// Let TCP clients of --serve send to the device
+    #[arg(long, value_parser = FalseyValueParser::new(), env = "HUHNITOR_SERVE_INPUT")]
+    pub serve_input: bool,
+
+    /// Let --serve-input and --http take commands from other machines, their clients aren't authenticated
+    #[arg(long, value_parser = FalseyValueParser::new(), env = "HUHNITOR_ALLOW_REMOTE")]
+    pub allow_remote: bool,
+
+    /// Serve an HTTP API to send commands and read output and status, e.g. 127.0.0.1:8080
+    #[arg(long, env = "HUHNITOR_HTTP")]
+    pub http: Option<SocketAddr>,
+
+    /// Tag scan results with the position from gpsd, host:port of a gpsd or the serial port of a GPS
+    #[arg(long, value_parser = gps::parse_source, env = "HUHNITOR_GPS")]
+    pub gps: Option<gps::Source>,
+
+    /// Serve a read-only live view of the output for browsers, e.g. 127.0.0.1:8081
+    #[arg(long, env = "HUHNITOR_WATCH")]
+    pub watch: Option<SocketAddr>,
+
+    /// Mirror the session to a pseudo-terminal linked at this path (Unix only)
+    #[arg(long, env = "HUHNITOR_PTY")]
+    pub pty: Option<PathBuf>,
+
+    /// Run a Rhai script with on_connect, on_line and on_command hooks
+    #[arg(short, long, env = "HUHNITOR_SCRIPT")]
+    pub script: Option<PathBuf>,
+
+    /// Write the exact received bytes to a file
+    #[arg(long, env = "HUHNITOR_DUMP_RAW")]
+    pub dump_raw: Option<PathBuf>,
+
+    /// Frame each chunk of --dump-raw with direction, time and length, and include sent bytes
+    #[arg(long, value_parser = FalseyValueParser::new(), env = "HUHNITOR_DUMP_FRAMED")]
+    pub dump_framed: bool,
+
+    /// Send a keep-alive every given seconds
+    #[arg(long, env = "HUHNITOR_KEEP_ALIVE")]
+    pub keep_alive: Option<u64>,
+
+    /// Mark the link stale after this many seconds without receiving anything and reconnect after twice as long
+    #[arg(long, env = "HUHNITOR_RX_TIMEOUT")]
+    pub rx_timeout: Option<u64>,
+
+    /// Close the port after this many seconds without anything sent or received
+    #[arg(long, env = "HUHNITOR_IDLE_TIMEOUT")]
+    pub idle_timeout: Option<u64>,
+
+    /// Lock the port so no other program can open it while connected (always the case on Windows)
+    #[arg(long, value_parser = FalseyValueParser::new(), env = "HUHNITOR_EXCLUSIVE")]
+    pub exclusive: bool,
+
+    /// Open the port even if another program like minicom holds its lock file
+    #[arg(long, value_parser = FalseyValueParser::new(), env = "HUHNITOR_FORCE")]
+    pub force: bool,
+
+    /// Firmware ELF to decode exception stack traces with
+    #[arg(long, env = "HUHNITOR_ELF")]
+    pub elf: Option<PathBuf>,
+
+    /// Firmware of the device: deauther, marauder or generic, probed after connecting if not given
+    #[arg(long, env = "HUHNITOR_FIRMWARE")]
+    pub firmware: Option<String>,
+}
+
+/// Options of `huhnitor exec`
+#[derive(Args)]
+pub struct Exec {
+    /// Commands sent one after another, e.g. "scan aps" "show ap"
+    #[arg(required = true)]
+    pub commands: Vec<String>,
+
+    /// Milliseconds of silence after which the answer to a command is complete
+    #[arg(long, default_value_t = 1000)]
+    pub timeout: u64,
+
+    /// Milliseconds to wait after opening the port, for boards that restart when it opens
+    #[arg(long, default_value_t = 0)]
+    pub settle: u64,
+
+    /// Lock the port so no other program can open it meanwhile (always the case on Windows)
+    #[arg(long, value_parser = FalseyValueParser::new(), env = "HUHNITOR_EXCLUSIVE")]
+    pub exclusive: bool,
+
+    /// Open the port even if another program like minicom holds its lock file
+    #[arg(long, value_parser = FalseyValueParser::new(), env = "HUHNITOR_FORCE")]
+    pub force: bool,
+}
+
+/// Options of `huhnitor send`
+#[derive(Args)]
+pub struct SendArgs {
+    /// Commands sent one after another, e.g. "scan aps" "show ap"
+    #[arg(required = true)]
+    pub commands: Vec<String>,
+
+    /// Process id, or name of a daemon, of the Huhnitor to send to when more than one runs
+    #[arg(long)]
+    pub to: Option<String>,
+}
+
+/// Options of `huhnitor flash`
+#[derive(Args)]
+pub struct Flash {
+    /// Firmware image, e.g. esp8266_deauther.bin
+    pub image: PathBuf,
+
+    /// Flash address the image is written to
+    #[arg(long, default_value = "0x0")]
+    pub address: String,
+
+    /// Baud rate while flashing, independent of the one of the firmware
+    #[arg(long, default_value_t = 460_800)]
+    pub flash_baud: u32,
+
+    /// Erase the whole flash first, which also clears the saved settings
+    #[arg(long)]
+    pub erase: bool,
+}
+
+/// Options of `huhnitor replay`
+#[derive(Args)]
+pub struct Replay {
+    /// Dump written with --dump-raw, framed dumps are played at their recorded pace
+    pub dump: PathBuf,
+
+    /// Play this many times faster, 0 for no pauses at all
+    #[arg(long, default_value_t = 1.0)]
+    pub speed: f64,
+
+    /// Include what was sent, which only framed dumps record
+    #[arg(long)]
+    pub sent: bool,
+}
+
+/// Options of `huhnitor redact`
+#[derive(Args)]
+pub struct Redact {
+    /// Log to redact
+    pub log: PathBuf,
+
+    /// Write the redacted log to this file instead of printing it
+    #[arg(long, short)]
+    pub output: Option<PathBuf>,
+}
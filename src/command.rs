@@ -0,0 +1,367 @@
+use chrono::NaiveTime;
+use serialport::{DataBits, FlowControl, Parity, StopBits};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::app::Echo;
+use crate::countdown;
+use crate::escape;
+use crate::schedule::When;
+use crate::serial;
+use crate::ssids::{self, SsidCommand};
+use crate::watches::{self, WatchCommand};
+
+/// Lines starting with this prefix are handled by Huhnitor instead of being sent to the device
+pub const PREFIX: char = ':';
+
+/// Usage and description of the local commands, listed by `:help`
+pub const HELP: [(&str, &str); 43] = [
+    ("connect [port]", "Pick a port, or switch to the given one"),
+    ("echo [off|plain|highlight]", "Set how sent commands are shown"),
+    ("wrap [on|off]", "Wrap long lines"),
+    ("numbers [on|off]", "Number the lines of the Messages box"),
+    ("goto <n>", "Scroll to line n of the Messages box"),
+    ("sendhex <bytes>", "Send raw bytes, e.g. 0d 0a"),
+    ("raw", "Send every keystroke right away, for shells of the firmware"),
+    ("presenter [on|off]", "Show the pressed keys and a bigger input line, for screen recordings"),
+    ("break [milliseconds]", "Hold the line in the break condition"),
+    ("databits <5-8>", "Change the data bits of the port"),
+    ("parity <none|odd|even>", "Change the parity of the port"),
+    ("stopbits <1|2>", "Change the stop bits of the port"),
+    ("flow <none|software|hardware>", "Change the flow control of the port"),
+    ("pcap <start <file>|stop>", "Write the packet monitor output to a pcap file"),
+    ("note <text>", "Add a note with the time to the Messages box and the log"),
+    ("mark", "Set a numbered mark, e.g. before starting a scan"),
+    ("since [n]", "Show the time and lines received since a mark, the latest by default"),
+    ("channels [on|off]", "Show the channel activity chart"),
+    ("ssid [add|remove|rename|clear|random]", "Show or change the SSID list"),
+    ("settings", "Edit the settings of the deauther"),
+    ("prefs", "Change the preferences of Huhnitor"),
+    ("watch [<regex>|remove <n>|clear]", "Show the value of a capture group, e.g. heap: (\\d+), in a sidebar"),
+    ("stop", "Stop the running attack"),
+    ("synctime", "Set the clock of the device to the time of this computer"),
+    ("timers", "List the countdowns of timed commands"),
+    ("diff", "Compare the results of the last two scans"),
+    ("db [search]", "Browse the scan results of all sessions"),
+    ("export-scan <file.csv|file.gpx>", "Write the results of the latest scan to a CSV or GPX file"),
+    ("at <hh:mm> <command>", "Send a command at a time of day"),
+    ("every <interval> <command>", "Send a command over and over, e.g. every 10m"),
+    ("jobs", "List the scheduled commands"),
+    ("cancel <id|all>", "Cancel a scheduled command"),
+    ("paste", "Paste the clipboard into the input line"),
+    ("legend", "List the highlight rules"),
+    ("classify [on|off]", "Show which highlight rule matched each line"),
+    ("mute [on|off]", "Silence the bell of the config file"),
+    ("edit", "Write commands in your editor, each line is sent as a command"),
+    ("pipe <command|off>", "Feed received lines to a command and show its output"),
+    ("shell [command]", "Run a command on this computer, or open a shell"),
+    ("broadcast [on|off|skip|join]", "Send typed commands to the other running Huhnitors too, skip ignores theirs"),
+    ("detach", "Leave the session running in the daemon, attach again with huhnitor attach"),
+    ("help", "List the local commands"),
+    ("exit", "Quit Huhnitor, without the prefix"),
+];
+
+/// Whether a local command of this name exists
+pub fn exists(name: &str) -> bool {
+    name.eq_ignore_ascii_case("ssids")
+        || HELP
+            .iter()
+            .filter_map(|(usage, _)| usage.split(' ').next())
+            .any(|command| command != "exit" && command.eq_ignore_ascii_case(name))
+}
+
+/// Lines of `:help`
+pub fn help() -> Vec<String> {
+    HELP.iter()
+        .map(|(usage, description)| match usage.strip_prefix("exit") {
+            Some(_) => format!("> {:<40} {}", usage, description),
+            None => format!("> {}{:<39} {}", PREFIX, usage, description),
+        })
+        .collect()
+}
+
+/// Local commands, typed as `:name [args]`
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Open the port picker, or switch to the given port
+    Connect(Option<String>),
+    /// Set how sent commands are echoed, or cycle through the modes
+    Echo(Option<Echo>),
+    /// Turn line wrapping on or off, or toggle it
+    Wrap(Option<bool>),
+    /// Turn line numbers on or off, or toggle them
+    LineNumbers(Option<bool>),
+    /// Scroll to a line of the Messages box, counted from the start of the session
+    Goto(usize),
+    /// Send raw bytes to the device
+    SendHex(Vec<u8>),
+    /// Turn presenter mode on or off, or toggle it
+    Presenter(Option<bool>),
+    /// Switch to raw mode
+    Raw,
+    /// Assert a break condition on the port for the given number of milliseconds
+    Break(u64),
+    /// Reconfigure the data bits of the open port
+    DataBits(DataBits),
+    /// Reconfigure the parity of the open port
+    Parity(Parity),
+    /// Reconfigure the stop bits of the open port
+    StopBits(StopBits),
+    /// Reconfigure the flow control of the open port
+    Flow(FlowControl),
+    /// Start writing packet monitor output to a pcap file, or stop with `None`
+    Pcap(Option<PathBuf>),
+    /// Mark a moment of the session in the Messages box and the log
+    Note(String),
+    /// Set a numbered mark to measure the time from
+    Mark,
+    /// Show the time and lines since a mark, or since the latest one
+    Since(Option<usize>),
+    /// Show or hide the channel activity chart, or toggle it
+    Channels(Option<bool>),
+    /// Show or change the SSID list of the deauther
+    Ssid(SsidCommand),
+    /// Open the editor for the deauther settings
+    Settings,
+    /// Open the preferences of Huhnitor
+    Prefs,
+    /// List, add or remove watch expressions
+    Watch(WatchCommand),
+    /// Stop the running attack
+    Stop,
+    /// Send the current time to the device
+    SyncTime,
+    /// List the countdowns of timed commands
+    Timers,
+    /// Show what changed between the last two scans
+    Diff,
+    /// Browse the scan database, starting with a search
+    Database(String),
+    /// Write the latest scan results to a CSV or GPX file
+    ExportScan(PathBuf),
+    /// Send a command later or repeatedly
+    Schedule(When, String),
+    /// List the scheduled commands
+    Jobs,
+    /// Cancel a scheduled command, or all of them with `None`
+    Cancel(Option<usize>),
+    /// Put the text of the clipboard into the input line
+    Paste,
+    /// Show the highlight rules with a sample of their style
+    Legend,
+    /// Annotate lines with the highlight rule they matched, or toggle it
+    Classify(Option<bool>),
+    /// Silence the bell, or toggle it
+    Mute(Option<bool>),
+    /// Compose commands in the external editor
+    Edit,
+    /// Feed received lines to a command, or stop with `None`
+    Pipe(Option<String>),
+    /// Run a command in the shell of this computer, or the shell itself with `None`
+    Shell(Option<String>),
+    /// Send typed commands to the devices of the other running Huhnitors too, or toggle it
+    Broadcast(Option<bool>),
+    /// Ignore the broadcasts of other Huhnitors, or take them again
+    SkipBroadcasts(bool),
+    /// Leave the session to the daemon it runs in
+    Detach,
+    /// List the local commands
+    Help,
+}
+
+/// Default length of a break condition in milliseconds
+pub const BREAK_MS: u64 = 250;
+
+/// Longest break condition in milliseconds, longer ones are cut to it
+pub const MAX_BREAK_MS: u64 = 5000;
+
+/// Longest interval of `:every` in hours, longer ones would be better sent `:at` a time of day
+pub const MAX_INTERVAL_H: u64 = 24;
+
+/// Parse an on/off switch, an empty argument means toggle
+fn switch(args: &str) -> Result<Option<bool>, String> {
+    match args.to_lowercase().as_str() {
+        "" => Ok(None),
+        "on" | "true" | "yes" => Ok(Some(true)),
+        "off" | "false" | "no" => Ok(Some(false)),
+        _ => Err(format!("Expected on or off, got '{}'", args)),
+    }
+}
+
+/// Split `<when> <command>` of `:at` and `:every`
+fn schedule(args: &str, usage: &str, when: impl Fn(&str) -> Option<When>) -> Result<Command, String> {
+    match args.split_once(' ') {
+        Some((time, command)) if !command.trim().is_empty() => match when(time) {
+            Some(when) => Ok(Command::Schedule(when, command.trim().to_string())),
+            None => Err(format!("Usage: {}{}", PREFIX, usage)),
+        },
+        _ => Err(format!("Usage: {}{}", PREFIX, usage)),
+    }
+}
+
+/// Parse a local command.
+/// Returns `None` if the line isn't a local command at all.
+pub fn parse(line: &str) -> Option<Result<Command, String>> {
+    let line = line.trim().strip_prefix(PREFIX)?;
+    let (name, args) = match line.split_once(' ') {
+        Some((name, args)) => (name, args.trim()),
+        None => (line, ""),
+    };
+
+    let cmd = match name.to_lowercase().as_str() {
+        "connect" => Ok(Command::Connect(if args.is_empty() { None } else { Some(args.to_string()) })),
+        "echo" if args.is_empty() => Ok(Command::Echo(None)),
+        "echo" => args.parse().map(|echo| Command::Echo(Some(echo))),
+        "wrap" => switch(args).map(Command::Wrap),
+        "numbers" => switch(args).map(Command::LineNumbers),
+        "goto" => args.parse().map(Command::Goto).map_err(|_| format!("Usage: {}goto <n>", PREFIX)),
+        "raw" => Ok(Command::Raw),
+        "presenter" => switch(args).map(Command::Presenter),
+        "sendhex" if args.is_empty() => Err(format!("Usage: {}sendhex 0d 0a ff", PREFIX)),
+        "sendhex" => escape::parse_hex(args).map(Command::SendHex),
+        "break" if args.is_empty() => Ok(Command::Break(BREAK_MS)),
+        "break" => args
+            .parse()
+            .map(|ms: u64| Command::Break(ms.min(MAX_BREAK_MS)))
+            .map_err(|_| format!("Usage: {}break [milliseconds]", PREFIX)),
+        "databits" => serial::parse_data_bits(args).map(Command::DataBits),
+        "parity" => serial::parse_parity(args).map(Command::Parity),
+        "stopbits" => serial::parse_stop_bits(args).map(Command::StopBits),
+        "flow" => serial::parse_flow_control(args).map(Command::Flow),
+        "pcap" => match args.split_once(' ') {
+            Some(("start", file)) => Ok(Command::Pcap(Some(PathBuf::from(file.trim())))),
+            None if args == "stop" => Ok(Command::Pcap(None)),
+            _ => Err(format!("Usage: {0}pcap start <file> or {0}pcap stop", PREFIX)),
+        },
+        "note" if args.is_empty() => Err(format!("Usage: {}note <text>", PREFIX)),
+        "note" => Ok(Command::Note(args.to_string())),
+        "mark" => Ok(Command::Mark),
+        "since" if args.is_empty() => Ok(Command::Since(None)),
+        "since" => args
+            .trim_start_matches('#')
+            .parse()
+            .map(|n| Command::Since(Some(n)))
+            .map_err(|_| format!("Usage: {}since [n]", PREFIX)),
+        "channels" => switch(args).map(Command::Channels),
+        "ssid" | "ssids" => ssids::parse(args).map(Command::Ssid),
+        "settings" => Ok(Command::Settings),
+        "prefs" | "preferences" => Ok(Command::Prefs),
+        "watch" => watches::parse(args).map(Command::Watch),
+        "stop" => Ok(Command::Stop),
+        "synctime" => Ok(Command::SyncTime),
+        "timers" => Ok(Command::Timers),
+        "diff" => Ok(Command::Diff),
+        "db" => Ok(Command::Database(args.to_string())),
+        "export-scan" if args.is_empty() => Err(format!("Usage: {}export-scan <file.csv|file.gpx>", PREFIX)),
+        "export-scan" => Ok(Command::ExportScan(PathBuf::from(args))),
+        "at" => schedule(args, "at 14:30 scan -t 60", |time| {
+            NaiveTime::parse_from_str(time, "%H:%M")
+                .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M:%S"))
+                .ok()
+                .map(When::At)
+        }),
+        "every" => match schedule(args, "every 10m sysinfo", |interval| countdown::parse(interval).map(When::Every)) {
+            Ok(Command::Schedule(When::Every(interval), _)) if interval > Duration::from_secs(MAX_INTERVAL_H * 3600) => {
+                Err(format!("The interval can be at most {}h, use {}at for a time of day", MAX_INTERVAL_H, PREFIX))
+            }
+            cmd => cmd,
+        },
+        "jobs" => Ok(Command::Jobs),
+        "cancel" if args.eq_ignore_ascii_case("all") => Ok(Command::Cancel(None)),
+        "cancel" => args
+            .trim_start_matches('#')
+            .parse()
+            .map(|id| Command::Cancel(Some(id)))
+            .map_err(|_| format!("Usage: {}cancel <id|all>", PREFIX)),
+        "paste" => Ok(Command::Paste),
+        "legend" => Ok(Command::Legend),
+        "classify" => switch(args).map(Command::Classify),
+        "mute" => switch(args).map(Command::Mute),
+        "edit" => Ok(Command::Edit),
+        "pipe" if args.is_empty() => Err(format!("Usage: {0}pipe <command> or {0}pipe off", PREFIX)),
+        "pipe" if args.eq_ignore_ascii_case("off") => Ok(Command::Pipe(None)),
+        "pipe" => Ok(Command::Pipe(Some(args.to_string()))),
+        "shell" => Ok(Command::Shell(if args.is_empty() { None } else { Some(args.to_string()) })),
+        "broadcast" if args.eq_ignore_ascii_case("skip") => Ok(Command::SkipBroadcasts(true)),
+        "broadcast" if args.eq_ignore_ascii_case("join") => Ok(Command::SkipBroadcasts(false)),
+        "broadcast" => switch(args).map(Command::Broadcast),
+        "detach" => Ok(Command::Detach),
+        "help" => Ok(Command::Help),
+        _ => Err(format!("Unknown command '{}{}'", PREFIX, name)),
+    };
+
+    Some(cmd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(line: &str) -> Result<Command, String> {
+        parse(line).expect("a local command")
+    }
+
+    #[test]
+    fn rejects_signs_in_hex_bytes() {
+        assert_eq!(parsed(":sendhex 0d 0x0a ff"), Ok(Command::SendHex(vec![0x0d, 0x0a, 0xff])));
+        assert!(parsed(":sendhex +f").is_err());
+        assert!(parsed(":sendhex -1").is_err());
+        assert!(parsed(":sendhex").is_err());
+    }
+
+    #[test]
+    fn bounds_the_break() {
+        assert_eq!(parsed(":break"), Ok(Command::Break(BREAK_MS)));
+        assert_eq!(parsed(":break 100"), Ok(Command::Break(100)));
+        assert_eq!(parsed(":break 99999999"), Ok(Command::Break(MAX_BREAK_MS)));
+    }
+
+    #[test]
+    fn leaves_device_commands_alone() {
+        assert!(parse("scan aps").is_none());
+        assert!(parse(" show ap ").is_none());
+        assert!(parse("huhn help").is_none());
+    }
+
+    #[test]
+    fn parses_arguments() {
+        assert_eq!(parsed(":connect"), Ok(Command::Connect(None)));
+        assert_eq!(parsed(":connect /dev/ttyUSB0"), Ok(Command::Connect(Some("/dev/ttyUSB0".to_string()))));
+        assert_eq!(parsed(":WRAP on"), Ok(Command::Wrap(Some(true))));
+        assert_eq!(parsed(":numbers"), Ok(Command::LineNumbers(None)));
+        assert_eq!(parsed(":since #2"), Ok(Command::Since(Some(2))));
+        assert_eq!(parsed(":cancel all"), Ok(Command::Cancel(None)));
+        assert_eq!(parsed(":cancel #3"), Ok(Command::Cancel(Some(3))));
+        assert_eq!(parsed(":pipe off"), Ok(Command::Pipe(None)));
+        assert_eq!(parsed(":broadcast skip"), Ok(Command::SkipBroadcasts(true)));
+        assert_eq!(parsed(":settings"), Ok(Command::Settings));
+        assert_eq!(parsed(":preferences"), Ok(Command::Prefs));
+        assert!(parsed(":wrap maybe").is_err());
+        assert!(parsed(":goto").is_err());
+        assert!(parsed(":note").is_err());
+    }
+
+    #[test]
+    fn parses_schedules() {
+        let at = NaiveTime::from_hms_opt(14, 30, 0).unwrap();
+        assert_eq!(parsed(":at 14:30 scan -t 60"), Ok(Command::Schedule(When::At(at), "scan -t 60".to_string())));
+        assert_eq!(parsed(":every 10m sysinfo"), Ok(Command::Schedule(When::Every(Duration::from_secs(600)), "sysinfo".to_string())));
+        assert_eq!(parsed(":every 24h sysinfo"), Ok(Command::Schedule(When::Every(Duration::from_secs(24 * 3600)), "sysinfo".to_string())));
+        assert!(parsed(":at 25:00 scan").is_err());
+        assert!(parsed(":every 10m").is_err());
+        assert!(parsed(":every 25h scan").is_err());
+        assert!(parsed(":every 18446744073709551615 scan").is_err());
+    }
+
+    #[test]
+    fn knows_every_command_of_the_help() {
+        for (usage, _) in HELP.iter().filter(|(usage, _)| !usage.starts_with("exit")) {
+            let name = usage.split(' ').next().unwrap();
+            assert!(exists(name), "{}", name);
+            if let Err(e) = parsed(&format!("{}{}", PREFIX, name)) {
+                assert!(!e.starts_with("Unknown"), "{}", e);
+            }
+        }
+        assert_eq!(parsed(":nope"), Err(format!("Unknown command '{}nope'", PREFIX)));
+    }
+}
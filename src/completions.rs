@@ -0,0 +1,26 @@
+use clap_complete::Shell;
+use regex::{Captures, Regex};
+
+/// Lists the port names for the completion scripts, so they're found when completing and not when installing the script
+const PORTS: &str = "huhnitor list --names 2>/dev/null";
+
+/// Print the completion script of a shell, with the port names completed for `--port` where the shell allows it
+pub fn print(mut command: clap::Command, shell: Shell) {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut command, "huhnitor", &mut script);
+    let script = String::from_utf8_lossy(&script).into_owned();
+
+    // clap only completes file names for values, so --port of every subcommand is changed to call `huhnitor list`.
+    // The patterns match the part before the completion of the value, which is replaced.
+    let (pattern, completion) = match shell {
+        Shell::Bash => (
+            r#"((?:--port|-p)\)\n\s*)COMPREPLY=\(\$\(compgen -f "\$\{cur\}"\)\)"#,
+            format!(r#"COMPREPLY=($(compgen -W "$({})" -- "${{cur}}"))"#, PORTS),
+        ),
+        Shell::Zsh => (r"(\[Select port[^\]]*\]:PORT:)_default", format!("{{compadd -- $({})}}", PORTS)),
+        Shell::Fish => (r"(-s p -l port -d '[^']*' -r)", format!(" -f -a \"({})\"", PORTS)),
+        _ => return print!("{}", script),
+    };
+    let regex = Regex::new(pattern).unwrap();
+    print!("{}", regex.replace_all(&script, |caps: &Captures| format!("{}{}", &caps[1], completion)));
+}
@@ -0,0 +1,194 @@
+use serde::Deserialize;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item};
+
+use crate::app::{CtrlC, Echo};
+use crate::autorun;
+use crate::channel::Overflow;
+use crate::error::{Error, Result};
+use crate::monitor::Idle;
+use crate::mqtt;
+use crate::plugin;
+use crate::redact;
+use crate::serial::Baud;
+use crate::theme::{ColorDepth, Overrides};
+
+/// Settings read from the config file.
+/// Everything is optional, command line arguments take precedence.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// Name of the color theme
+    pub theme: Option<String>,
+    /// Color overrides on top of the theme
+    pub colors: Overrides,
+    /// Override the detected color depth of the terminal
+    pub color_depth: Option<ColorDepth>,
+    /// How sent commands are echoed
+    pub echo: Option<Echo>,
+    /// Wrap long lines in the Messages box
+    pub wrap: Option<bool>,
+    /// Show the time each line arrived
+    pub timestamps: Option<bool>,
+    /// Number the lines of the Messages box
+    pub line_numbers: Option<bool>,
+    /// Number of lines kept in the Messages box, 0 keeps all
+    pub scrollback: Option<usize>,
+    /// Ask before quitting
+    pub confirm_exit: Option<bool>,
+    /// Ask before sending destructive commands
+    pub confirm_commands: Option<bool>,
+    /// Mark lines in the Messages box as sent or received
+    pub direction_markers: Option<bool>,
+    /// Seconds without output after which a separator is shown, 0 to turn it off
+    pub gap_marker: Option<u64>,
+    /// Seconds between saves of the session for `--restore`, off if not set or 0
+    pub autosave: Option<u64>,
+    /// Show the port and connection state in the terminal title
+    pub title: Option<bool>,
+    /// Add the running attack to the terminal title
+    pub title_attack: Option<bool>,
+    /// Language of the user interface, e.g. "de", taken from `LANG` if not set
+    pub language: Option<String>,
+    /// Plain line based output without colors or borders, for screen readers
+    pub accessible: Option<bool>,
+    /// Pastes longer than this many bytes are confirmed first
+    pub paste_limit: Option<usize>,
+    /// Milliseconds between looks for output and keys, higher values use less CPU
+    pub tick_rate: Option<u64>,
+    /// Key that leaves raw mode, e.g. "ctrl-]"
+    pub raw_escape: Option<String>,
+    /// Print a session summary when quitting
+    pub summary: Option<bool>,
+    /// What Ctrl-C does
+    pub ctrl_c: Option<CtrlC>,
+    /// Write a log of every session to a new file in this directory, unless --log is given
+    pub log_dir: Option<PathBuf>,
+    /// Rotate the log once it grows past this size, e.g. "10M"
+    pub log_max_size: Option<String>,
+    /// Rotate the log every day
+    pub log_daily: Option<bool>,
+    /// Number of rotated logs to keep
+    pub log_keep: Option<usize>,
+    /// Compress rotated logs with gzip
+    pub log_compress: Option<bool>,
+    /// Baud rate of the serial port, a number or "auto"
+    pub baud: Option<Baud>,
+    /// Flow control of the serial port: none, software or hardware
+    pub flow_control: Option<String>,
+    /// Publish parsed events to an MQTT broker
+    pub mqtt: Option<mqtt::Settings>,
+    /// Output processors, applied in order
+    pub processor: Vec<plugin::Spec>,
+    /// What to hide in scan exports, the watch page and `huhnitor redact`, applied in order
+    pub redact: Vec<redact::Spec>,
+    /// Rhai script with hooks
+    pub script: Option<PathBuf>,
+    /// Commands sent after connecting, after the welcome command
+    pub on_connect: Vec<autorun::Entry>,
+    /// Milliseconds to wait before each `on-connect` command without its own delay
+    pub on_connect_delay: Option<u64>,
+    /// Send the `on-connect` commands and the changed settings again when the device restarts
+    pub restore_on_reset: Option<bool>,
+    /// Milliseconds between the commands typed on one line, separated by `;`
+    pub chain_delay: Option<u64>,
+    /// Regex of the prompt to wait for before sending the next command of a line
+    pub chain_prompt: Option<String>,
+    /// Command that sets the clock of the device, with chrono placeholders like "time %Y-%m-%d %H:%M:%S"
+    pub synctime_command: Option<String>,
+    /// Set the clock of the device after connecting
+    pub synctime_on_connect: Option<bool>,
+    /// Seconds between keep-alives
+    pub keep_alive: Option<u64>,
+    /// Command sent as keep-alive, an empty line by default
+    pub keep_alive_command: Option<String>,
+    /// Seconds without receiving anything until the link is considered stale
+    pub rx_timeout: Option<u64>,
+    /// Seconds without anything sent or received until the port is closed
+    pub idle_timeout: Option<u64>,
+    /// What happens after the idle timeout: "disconnect" or "exit"
+    pub idle_action: Option<Idle>,
+    /// Connect to a newly plugged in device without asking
+    pub auto_connect: Option<bool>,
+    /// Keep other programs from opening the port while it is open (Unix only)
+    pub exclusive: Option<bool>,
+    /// Let `huhnitor send` and broadcasts send commands to the device, off by default
+    pub control: Option<bool>,
+    /// Send at most this many bytes per second
+    pub tx_bytes_per_sec: Option<u32>,
+    /// Send at most this many lines per second
+    pub tx_lines_per_sec: Option<u32>,
+    /// Milliseconds to pause after every sent character
+    pub tx_char_delay: Option<u64>,
+    /// Lines of device output that may wait for the UI
+    pub output_buffer: Option<usize>,
+    /// What happens to output once the buffer is full: "drop-oldest" or "block"
+    pub output_overflow: Option<Overflow>,
+    /// Firmware profile, probed after connecting if not given
+    pub firmware: Option<String>,
+    /// Save the command history per device
+    pub history: Option<bool>,
+    /// Tag scan results with the position: "gpsd", host:port of a gpsd or the serial port of a GPS
+    pub gps: Option<String>,
+    /// Keep the scan results of all sessions for `:db`, off by default
+    pub record_scans: Option<bool>,
+    /// File of the scan database
+    pub database: Option<PathBuf>,
+    /// Highlight categories and alerts that ring the bell, e.g. "error" or "attack-done"
+    pub bell: Option<Vec<String>>,
+    /// Command playing a sound instead of the terminal bell
+    pub bell_command: Option<String>,
+    /// Show the channel activity chart
+    pub channels: Option<bool>,
+    /// Seconds covered by the channel activity chart
+    pub channel_window: Option<u64>,
+}
+
+impl Config {
+    /// Default location of the config file, e.g. `~/.config/huhnitor/config.toml`
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("huhnitor").join("config.toml"))
+    }
+
+    /// Read the config file, a missing file is the same as an empty one
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path.map(Path::to_path_buf).or_else(Self::default_path) {
+            Some(path) => path,
+            None => return Ok(Self::default()),
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(text) => toml::from_str(&text).map_err(|e| Error::Config(path, e.to_string())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Error::Config(path, e.to_string())),
+        }
+    }
+
+    /// Set top-level values in the config file, keeping its comments and everything else in it
+    pub fn store(path: &Path, values: &[(&str, toml_edit::Value)]) -> Result<()> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut doc: DocumentMut = text.parse().map_err(|e: toml_edit::TomlError| Error::Config(path.to_path_buf(), e.to_string()))?;
+        for (key, value) in values {
+            match doc.get_mut(key).and_then(Item::as_value_mut) {
+                // Replacing only the value keeps a comment at the end of the line
+                Some(old) => {
+                    let decor = old.decor().clone();
+                    *old = value.clone();
+                    *old.decor_mut() = decor;
+                }
+                None => doc[key] = toml_edit::value(value.clone()),
+            }
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, doc.to_string())?;
+        Ok(())
+    }
+}
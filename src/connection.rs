@@ -0,0 +1,285 @@
+use serialport::SerialPort;
+use std::collections::VecDeque;
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc::{self, Receiver, Sender, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_serial::SerialStream;
+use tokio_util::sync::CancellationToken;
+
+use crate::pacing::Pacing;
+use crate::serial::LinkSettings;
+
+/// What happened on the port, reported by the I/O task in this order
+#[derive(Debug)]
+pub enum PortEvent {
+    /// The task took over the port at this path
+    Connected(String),
+    /// A line of output, including the line ending
+    Line(Vec<u8>),
+    /// Output without a line ending yet after a pause, the whole line follows once it ends
+    Partial(Vec<u8>),
+    /// Reading failed, the task stops
+    Error(io::Error),
+    /// Writing failed, the rest of that write was dropped
+    WriteFailed(io::Error),
+    /// The device went away or the connection was closed
+    Disconnected,
+}
+
+/// Work for the I/O task, each with a channel for the result
+enum Request {
+    /// Bytes to write, with a channel for the result if the sender waits for it
+    Write(Vec<u8>, Option<oneshot::Sender<io::Result<()>>>),
+    /// Drop writes that haven't gone out yet
+    Discard,
+    Break(Duration, oneshot::Sender<serialport::Result<()>>),
+    Reconfigure(LinkSettings, oneshot::Sender<serialport::Result<()>>),
+    Reset(oneshot::Sender<serialport::Result<()>>),
+}
+
+/// Part of a write waiting in the queue of the I/O task
+struct Chunk {
+    bytes: Vec<u8>,
+    /// Pause after writing it
+    pause: Duration,
+    /// Whether it ends a write
+    last: bool,
+    /// Where the result of the whole write goes, on the last chunk
+    written: Option<oneshot::Sender<io::Result<()>>>,
+}
+
+/// How long the reset line is held
+const RESET_PULSE: Duration = Duration::from_millis(100);
+
+/// Events waiting for the session. Once that many wait, the port isn't read until the session catches up.
+const EVENT_BUFFER: usize = 64;
+
+/// Pause after which an unfinished line is reported, e.g. a prompt or the echo of a keystroke
+const PARTIAL_DELAY: Duration = Duration::from_millis(50);
+
+/// Handle of the task that owns an open port. Reading and writing happen in the task,
+/// the session only sees events and results.
+pub struct Connection {
+    requests: UnboundedSender<Request>,
+    shutdown: CancellationToken,
+    task: JoinHandle<()>,
+}
+
+fn closed() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "The port is closed")
+}
+
+impl Connection {
+    /// Hand the port over to a new task. Cancelling `shutdown` closes it as well.
+    pub fn spawn(port: SerialStream, path: &str, pacing: Pacing, shutdown: CancellationToken) -> (Self, Receiver<PortEvent>) {
+        let (events_tx, events_rx) = mpsc::channel(EVENT_BUFFER);
+        let (requests, requests_rx) = mpsc::unbounded_channel();
+        let _ = events_tx.try_send(PortEvent::Connected(path.to_string()));
+        let task = tokio::spawn(run(BufReader::new(port), pacing, requests_rx, events_tx, shutdown.clone()));
+        (Self { requests, shutdown, task }, events_rx)
+    }
+
+    /// Queue bytes to be written, paced as configured. Failures are reported as `PortEvent::WriteFailed`.
+    pub fn write(&self, bytes: &[u8]) -> io::Result<()> {
+        self.requests.send(Request::Write(bytes.to_vec(), None)).map_err(|_| closed())
+    }
+
+    /// Queue bytes like `write`, `written` gets the result once the last of them went out.
+    /// It's dropped without one if the write is discarded or the port closes first.
+    pub fn write_confirmed(&self, bytes: &[u8], written: oneshot::Sender<io::Result<()>>) -> io::Result<()> {
+        self.requests.send(Request::Write(bytes.to_vec(), Some(written))).map_err(|_| closed())
+    }
+
+    /// Drop what is still queued, e.g. the rest of a long paste
+    pub fn discard(&self) {
+        let _ = self.requests.send(Request::Discard);
+    }
+
+    /// Hold the line in the break condition for a while
+    pub async fn send_break(&self, duration: Duration) -> serialport::Result<()> {
+        let (reply, result) = oneshot::channel();
+        self.requests.send(Request::Break(duration, reply)).map_err(|_| closed())?;
+        result.await.map_err(|_| closed())?
+    }
+
+    /// Change the line settings of the open port
+    pub async fn reconfigure(&self, link: LinkSettings) -> serialport::Result<()> {
+        let (reply, result) = oneshot::channel();
+        self.requests.send(Request::Reconfigure(link, reply)).map_err(|_| closed())?;
+        result.await.map_err(|_| closed())?
+    }
+
+    /// Reset the board through the DTR and RTS lines
+    pub async fn reset(&self) -> serialport::Result<()> {
+        let (reply, result) = oneshot::channel();
+        self.requests.send(Request::Reset(reply)).map_err(|_| closed())?;
+        result.await.map_err(|_| closed())?
+    }
+
+    /// Flush and close the port, returns once the task is done with it
+    pub async fn close(self) {
+        self.shutdown.cancel();
+        let _ = self.task.await;
+    }
+}
+
+/// Read lines and carry out requests until the port fails or the connection is shut down
+async fn run(
+    mut port: BufReader<SerialStream>,
+    pacing: Pacing,
+    mut requests: UnboundedReceiver<Request>,
+    events: Sender<PortEvent>,
+    shutdown: CancellationToken,
+) {
+    let mut buf = Vec::new();
+    // Length of the unfinished line when it was last reported
+    let mut reported = 0;
+    // Paced writes go out a chunk at a time, reading and other requests carry on in between
+    let mut queue: VecDeque<Chunk> = VecDeque::new();
+    let mut next_write = Instant::now();
+    loop {
+        tokio::select! {
+            // Only this task sends, so there is room for the line once it's read
+            len = port.read_until(b'\n', &mut buf), if events.capacity() > 0 => match len {
+                // EOF
+                Ok(0) => break,
+                Ok(_) => {
+                    reported = 0;
+                    if events.try_send(PortEvent::Line(std::mem::take(&mut buf))).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let _ = events.try_send(PortEvent::Error(e));
+                    break;
+                }
+            },
+
+            Some(request) = requests.recv() => match request {
+                Request::Write(bytes, written) => {
+                    let mut chunks = chunks(&bytes, pacing);
+                    if let Some(last) = chunks.last_mut() {
+                        last.written = written;
+                    }
+                    queue.extend(chunks);
+                }
+                Request::Discard => queue.clear(),
+                Request::Break(duration, reply) => {
+                    let _ = reply.send(send_break(port.get_ref(), duration).await);
+                }
+                Request::Reconfigure(link, reply) => {
+                    let _ = reply.send(link.apply(port.get_mut()));
+                }
+                Request::Reset(reply) => {
+                    let _ = reply.send(reset(port.get_mut()).await);
+                }
+            },
+
+            _ = tokio::time::sleep_until(next_write), if !queue.is_empty() => {
+                let mut chunk = queue.pop_front().expect("queue isn't empty");
+                let result = write(port.get_mut(), &chunk.bytes, !pacing.is_off()).await;
+                next_write = Instant::now() + chunk.pause;
+                match result {
+                    Ok(()) => {
+                        if let Some(written) = chunk.written.take() {
+                            let _ = written.send(Ok(()));
+                        }
+                    }
+                    Err(e) => {
+                        let mut written = chunk.written.take();
+                        if !chunk.last {
+                            while let Some(mut next) = queue.pop_front() {
+                                if next.last {
+                                    written = next.written.take();
+                                    break;
+                                }
+                            }
+                        }
+                        if let Some(written) = written {
+                            let _ = written.send(Err(io::Error::new(e.kind(), e.to_string())));
+                        }
+                        let _ = events.try_send(PortEvent::WriteFailed(e));
+                    }
+                }
+            }
+
+            // Reading a line keeps what it got so far in `buf` when another branch wins
+            _ = tokio::time::sleep(PARTIAL_DELAY), if buf.len() != reported => {
+                reported = buf.len();
+                let _ = events.try_send(PortEvent::Partial(buf.clone()));
+            }
+
+            // The session fell behind, reading resumes once it took an event
+            Ok(_room) = events.reserve(), if events.capacity() == 0 => (),
+
+            _ = shutdown.cancelled() => break,
+        }
+    }
+
+    // Whatever is still buffered goes out before the port is closed
+    let _ = port.get_mut().flush().await;
+    drop(port);
+    // With the buffer full, the session still ends once the buffered events are taken
+    let _ = events.try_send(PortEvent::Disconnected);
+}
+
+/// Split a write into chunks as paced
+fn chunks(bytes: &[u8], pacing: Pacing) -> Vec<Chunk> {
+    let mut chunks: Vec<Chunk> = if pacing.is_off() || bytes.is_empty() {
+        vec![Chunk { bytes: bytes.to_vec(), pause: Duration::ZERO, last: false, written: None }]
+    } else {
+        pacing
+            .chunks(bytes)
+            .into_iter()
+            .map(|(bytes, pause)| Chunk { bytes: bytes.to_vec(), pause, last: false, written: None })
+            .collect()
+    };
+    if let Some(last) = chunks.last_mut() {
+        last.last = true;
+    }
+    chunks
+}
+
+async fn write(port: &mut SerialStream, bytes: &[u8], flush: bool) -> io::Result<()> {
+    port.write_all(bytes).await?;
+    if flush {
+        port.flush().await?;
+    }
+    Ok(())
+}
+
+async fn send_break(port: &SerialStream, duration: Duration) -> serialport::Result<()> {
+    port.set_break()?;
+    tokio::time::sleep(duration).await;
+    port.clear_break()
+}
+
+/// Pull the reset line like esptool and the Arduino IDE do. RTS drives EN,
+/// DTR is kept high so GPIO0 doesn't put the ESP into flash mode.
+async fn reset(port: &mut SerialStream) -> serialport::Result<()> {
+    port.write_data_terminal_ready(false)?;
+    port.write_request_to_send(true)?;
+    tokio::time::sleep(RESET_PULSE).await;
+    port.write_request_to_send(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_the_end_of_a_write() {
+        let pacing = Pacing { lines_per_sec: Some(10), ..Pacing::default() };
+        let chunks = chunks(b"a\nb\n", pacing);
+        let ends: Vec<bool> = chunks.iter().map(|chunk| chunk.last).collect();
+        assert_eq!(ends, [false, true]);
+        assert_eq!(chunks[0].pause, Duration::from_millis(100));
+
+        let empty = super::chunks(b"", pacing);
+        assert!(empty.len() == 1 && empty[0].last);
+    }
+}
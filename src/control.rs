@@ -0,0 +1,435 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::oneshot;
+
+use crate::channel;
+use crate::cli;
+use crate::i18n;
+use crate::output;
+#[cfg(unix)]
+use crate::runtime;
+use crate::status::{self, SharedStatus};
+
+/// How long a command may wait to be written, e.g. behind a paced paste
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What `huhnitor send` asks a running Huhnitor, one JSON object per line
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Request {
+    /// Which Huhnitor this is
+    Info,
+    /// Send a command to the device
+    Send(String),
+    /// Send commands typed into another Huhnitor to the device, unless broadcasts are skipped
+    Broadcast { from: String, commands: Vec<String> },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Reply {
+    Info { pid: u32, name: Option<String>, port: Option<String> },
+    Sent,
+    /// The user skips broadcasts
+    Skipped,
+    Error(String),
+}
+
+/// What became of a broadcast at one of the other Huhnitors
+pub enum Delivery {
+    Sent,
+    Skipped,
+    Failed(String),
+}
+
+/// A command for the device, `written` gets the result once it went out
+pub struct Injected {
+    pub bytes: Vec<u8>,
+    pub written: oneshot::Sender<io::Result<()>>,
+}
+
+/// Everything the connections can see
+#[derive(Clone)]
+struct Shared {
+    name: Option<String>,
+    status: SharedStatus,
+    input_tx: UnboundedSender<Injected>,
+    notify_tx: channel::WeakSender,
+}
+
+/// Where the sockets of the running Huhnitors are, a directory only this user can enter
+#[cfg(unix)]
+fn dir() -> io::Result<PathBuf> {
+    runtime::dir()
+}
+
+/// Named pipes live in their own namespace
+#[cfg(windows)]
+fn dir() -> io::Result<PathBuf> {
+    Ok(PathBuf::from(r"\\.\pipe\"))
+}
+
+/// Socket of the Huhnitor with this process id in `dir`, e.g. `/run/user/1000/huhnitor/huhnitor-4242.ctl`
+fn path(dir: &Path, pid: u32) -> PathBuf {
+    dir.join(format!("huhnitor-{}.ctl", pid))
+}
+
+/// Socket `huhnitor send` talks to, removed when the session ends
+pub struct Server {
+    #[cfg(unix)]
+    path: PathBuf,
+}
+
+impl Server {
+    /// Start listening, commands are sent on `input_tx` while a port is open.
+    /// A daemon is also found by its `name`, every Huhnitor by its process id.
+    pub fn bind(name: Option<String>, status: SharedStatus, input_tx: UnboundedSender<Injected>, notify_tx: &channel::Sender) -> io::Result<Self> {
+        Self::bind_in(&dir()?, name, status, input_tx, notify_tx)
+    }
+
+    /// Listen in `dir` instead of the directory all Huhnitors use
+    fn bind_in(dir: &Path, name: Option<String>, status: SharedStatus, input_tx: UnboundedSender<Injected>, notify_tx: &channel::Sender) -> io::Result<Self> {
+        let path = path(dir, std::process::id());
+        let shared = Shared { name, status, input_tx, notify_tx: notify_tx.downgrade() };
+
+        #[cfg(unix)]
+        {
+            // Left behind by a process that had the same id before
+            let _ = fs::remove_file(&path);
+            // Whoever can connect can send to the device, the directory keeps out other users
+            let listener = tokio::net::UnixListener::bind(&path)?;
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    if runtime::is_own(&stream) {
+                        tokio::spawn(serve(stream, shared.clone()));
+                    }
+                }
+            });
+            Ok(Self { path })
+        }
+
+        #[cfg(windows)]
+        {
+            use tokio::net::windows::named_pipe::ServerOptions;
+
+            let mut server = ServerOptions::new().first_pipe_instance(true).reject_remote_clients(true).create(&path)?;
+            tokio::spawn(async move {
+                // Every client gets the instance it connected to, the next one waits on a new instance
+                while server.connect().await.is_ok() {
+                    let next = match ServerOptions::new().reject_remote_clients(true).create(&path) {
+                        Ok(next) => next,
+                        Err(_) => return,
+                    };
+                    tokio::spawn(serve(std::mem::replace(&mut server, next), shared.clone()));
+                }
+            });
+            Ok(Self {})
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// One line of the socket
+fn encode<T: Serialize>(value: &T) -> String {
+    let mut line = serde_json::to_string(value).unwrap_or_default();
+    line.push('\n');
+    line
+}
+
+/// Answer the requests of one `huhnitor send` until it disconnects
+async fn serve<S: AsyncRead + AsyncWrite + Unpin>(stream: S, shared: Shared) {
+    let mut stream = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match stream.read_line(&mut line).await {
+            Ok(0) | Err(_) => return,
+            Ok(_) => (),
+        }
+        let status = status::get(&shared.status);
+        let reply = match serde_json::from_str(&line) {
+            Ok(Request::Info) => Reply::Info { pid: std::process::id(), name: shared.name.clone(), port: status.port },
+            Ok(Request::Broadcast { .. }) if status.skip_broadcasts => Reply::Skipped,
+            Ok(_) if status.port.is_none() => Reply::Error(i18n::text("control_not_connected").to_string()),
+            Ok(Request::Send(command)) => inject(&shared, "huhnitor send", &[command]).await,
+            Ok(Request::Broadcast { from, commands }) => {
+                let from = i18n::format("broadcast_from", &[&from]);
+                inject(&shared, &from, &commands).await
+            }
+            Err(e) => Reply::Error(i18n::format("invalid_request", &[&e])),
+        };
+        if stream.get_mut().write_all(encode(&reply).as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Send commands to the device one after another, each is shown with where it came from once it's written
+async fn inject(shared: &Shared, from: &str, commands: &[String]) -> Reply {
+    for command in commands {
+        let (written, result) = oneshot::channel();
+        if shared.input_tx.send(Injected { bytes: format!("{}\r\n", command).into_bytes(), written }).is_err() {
+            return Reply::Error(i18n::text("session_gone").to_string());
+        }
+        match tokio::time::timeout(WRITE_TIMEOUT, result).await {
+            Ok(Ok(Ok(()))) => output::notify(&shared.notify_tx, format!("> {}: {}", from, command)),
+            Ok(Ok(Err(e))) => return Reply::Error(e.to_string()),
+            Ok(Err(_)) => return Reply::Error(i18n::text("closed_before_write").to_string()),
+            Err(_) => return Reply::Error(i18n::format("write_timeout", &[&WRITE_TIMEOUT.as_secs()])),
+        }
+    }
+    Reply::Sent
+}
+
+/// Connection to a running Huhnitor
+struct Client {
+    #[cfg(unix)]
+    stream: BufReader<tokio::net::UnixStream>,
+    #[cfg(windows)]
+    stream: BufReader<tokio::net::windows::named_pipe::NamedPipeClient>,
+    pid: u32,
+    /// Name of a daemon
+    name: Option<String>,
+    port: Option<String>,
+}
+
+impl Client {
+    async fn connect(path: &Path) -> io::Result<Self> {
+        #[cfg(unix)]
+        let stream = tokio::net::UnixStream::connect(path).await?;
+        // What it says about itself can't be trusted, `--to` would pick it by a made up name
+        #[cfg(unix)]
+        if !runtime::is_own(&stream) {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "run by another user"));
+        }
+        #[cfg(windows)]
+        let stream = tokio::net::windows::named_pipe::ClientOptions::new().open(path)?;
+
+        let mut client = Self { stream: BufReader::new(stream), pid: 0, name: None, port: None };
+        match client.request(&Request::Info).await? {
+            Reply::Info { pid, name, port } => {
+                client.pid = pid;
+                client.name = name;
+                client.port = port;
+                Ok(client)
+            }
+            _ => Err(io::Error::other("unexpected reply")),
+        }
+    }
+
+    async fn request(&mut self, request: &Request) -> io::Result<Reply> {
+        self.stream.get_mut().write_all(encode(request).as_bytes()).await?;
+        let mut line = String::new();
+        if self.stream.read_line(&mut line).await? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        serde_json::from_str(&line).map_err(io::Error::other)
+    }
+
+    /// e.g. `4242 (roof) on /dev/ttyUSB0`
+    fn describe(&self) -> String {
+        let name = self.name.as_ref().map(|name| format!(" ({})", name)).unwrap_or_default();
+        i18n::format("huhnitor_on", &[&self.pid, &name, &self.port.as_deref().unwrap_or(i18n::text("without_port"))])
+    }
+}
+
+/// The Huhnitors that are running
+async fn running() -> Vec<Client> {
+    match dir() {
+        Ok(dir) => running_in(&dir).await,
+        Err(_) => Vec::new(),
+    }
+}
+
+/// The Huhnitors with a socket in `dir`, the sockets of those that are gone are cleaned up
+async fn running_in(dir: &Path) -> Vec<Client> {
+    let mut clients = Vec::new();
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let is_control = name.strip_prefix("huhnitor-").and_then(|rest| rest.strip_suffix(".ctl")).is_some_and(|pid| pid.parse::<u32>().is_ok());
+        if !is_control {
+            continue;
+        }
+        match Client::connect(&entry.path()).await {
+            Ok(client) => clients.push(client),
+            #[cfg(unix)]
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                let _ = fs::remove_file(entry.path());
+            }
+            Err(_) => (),
+        }
+    }
+    clients.sort_by_key(|client| client.pid);
+    clients
+}
+
+/// Send commands to a running Huhnitor, like typed into it
+pub async fn send(args: &cli::SendArgs) -> Result<(), String> {
+    let mut clients = running().await;
+    if let Some(to) = &args.to {
+        clients.retain(|client| client.pid.to_string() == *to || client.name.as_deref() == Some(to.as_str()));
+    }
+    let mut client = match (clients.len(), &args.to) {
+        (0, Some(to)) => return Err(i18n::format("no_such_huhnitor", &[to])),
+        (0, None) => return Err(i18n::text("no_huhnitor").to_string()),
+        (1, _) => clients.remove(0),
+        _ => {
+            let running: Vec<String> = clients.iter().map(Client::describe).collect();
+            return Err(i18n::format("several_huhnitors", &[&running.join(", ")]));
+        }
+    };
+
+    for command in &args.commands {
+        match client.request(&Request::Send(command.clone())).await {
+            Ok(Reply::Sent) => (),
+            Ok(Reply::Error(e)) => return Err(i18n::format("send_failed", &[command, &e])),
+            Ok(_) => return Err(i18n::format("send_failed", &[command, &i18n::text("unexpected_reply")])),
+            Err(e) => return Err(i18n::format("send_failed", &[command, &e])),
+        }
+    }
+    Ok(())
+}
+
+/// Send commands to the devices of all other running Huhnitors, returns what became of them at each
+pub async fn broadcast(from: String, commands: Vec<String>) -> Vec<(String, Delivery)> {
+    match dir() {
+        Ok(dir) => broadcast_in(&dir, from, commands).await,
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Broadcast to the Huhnitors with a socket in `dir`
+async fn broadcast_in(dir: &Path, from: String, commands: Vec<String>) -> Vec<(String, Delivery)> {
+    let mut deliveries = Vec::new();
+    for mut client in running_in(dir).await.into_iter().filter(|client| client.pid != std::process::id()) {
+        let request = Request::Broadcast { from: from.clone(), commands: commands.clone() };
+        let delivery = match client.request(&request).await {
+            Ok(Reply::Sent) => Delivery::Sent,
+            Ok(Reply::Skipped) => Delivery::Skipped,
+            Ok(Reply::Error(e)) => Delivery::Failed(e),
+            Ok(Reply::Info { .. }) => Delivery::Failed(i18n::text("unexpected_reply").to_string()),
+            Err(e) => Delivery::Failed(e.to_string()),
+        };
+        deliveries.push((client.describe(), delivery));
+    }
+    deliveries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel::Overflow;
+    use crate::protocol::BackendEvent;
+    use tokio::io::DuplexStream;
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    /// A connection to `serve`, and what it passes on to the device and the UI
+    fn connect(port: Option<&str>) -> (BufReader<DuplexStream>, SharedStatus, UnboundedReceiver<Injected>, channel::Sender, channel::Receiver) {
+        let (client, server) = tokio::io::duplex(4096);
+        let status = SharedStatus::default();
+        status::update(&status, |status| status.port = port.map(str::to_string));
+        let (input_tx, input_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (notify_tx, notify_rx) = channel::bounded(16, Overflow::DropOldest);
+        let shared = Shared { name: Some("roof".to_string()), status: status.clone(), input_tx, notify_tx: notify_tx.downgrade() };
+        tokio::spawn(serve(server, shared));
+        (BufReader::new(client), status, input_rx, notify_tx, notify_rx)
+    }
+
+    async fn request(stream: &mut BufReader<DuplexStream>, request: &Request) -> Reply {
+        ask(stream, &encode(request)).await
+    }
+
+    async fn ask(stream: &mut BufReader<DuplexStream>, line: &str) -> Reply {
+        stream.get_mut().write_all(line.as_bytes()).await.unwrap();
+        let mut line = String::new();
+        stream.read_line(&mut line).await.unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[tokio::test]
+    async fn tells_who_it_is() {
+        let (mut stream, ..) = connect(Some("/dev/ttyUSB0"));
+        match request(&mut stream, &Request::Info).await {
+            Reply::Info { pid, name, port } => {
+                assert_eq!(pid, std::process::id());
+                assert_eq!(name.as_deref(), Some("roof"));
+                assert_eq!(port.as_deref(), Some("/dev/ttyUSB0"));
+            }
+            _ => panic!("expected info"),
+        }
+        assert!(matches!(ask(&mut stream, "{\"unknown\":1}\n").await, Reply::Error(_)));
+    }
+
+    #[tokio::test]
+    async fn reports_sent_once_written() {
+        let (mut stream, _status, mut input_rx, _notify_tx, mut notify_rx) = connect(Some("/dev/ttyUSB0"));
+        let device = tokio::spawn(async move {
+            let injected = input_rx.recv().await.unwrap();
+            assert_eq!(injected.bytes, b"scan aps\r\n");
+            let _ = injected.written.send(Ok(()));
+            let injected = input_rx.recv().await.unwrap();
+            let _ = injected.written.send(Err(io::Error::other("unplugged")));
+        });
+
+        assert!(matches!(request(&mut stream, &Request::Send("scan aps".to_string())).await, Reply::Sent));
+        assert_eq!(notify_rx.try_recv().ok(), Some(BackendEvent::Status("> huhnitor send: scan aps".to_string())));
+        match request(&mut stream, &Request::Send("show ap".to_string())).await {
+            Reply::Error(e) => assert_eq!(e, "unplugged"),
+            _ => panic!("a failed write was reported as sent"),
+        }
+        assert!(notify_rx.try_recv().is_err());
+        device.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn refuses_without_a_port() {
+        let (mut stream, _, mut input_rx, ..) = connect(None);
+        assert!(matches!(request(&mut stream, &Request::Send("scan".to_string())).await, Reply::Error(_)));
+        assert!(input_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn skips_broadcasts_when_asked() {
+        let (mut stream, status, mut input_rx, ..) = connect(Some("/dev/ttyUSB0"));
+        status::update(&status, |status| status.skip_broadcasts = true);
+        let broadcast = Request::Broadcast { from: "4242".to_string(), commands: vec!["scan".to_string()] };
+        assert!(matches!(request(&mut stream, &broadcast).await, Reply::Skipped));
+        assert!(input_rx.try_recv().is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn finds_running_huhnitors() {
+        // A directory of its own, the Huhnitors running on this machine are left alone
+        let dir = std::env::temp_dir().join(format!("huhnitor-control-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let stale = path(&dir, 1);
+        drop(std::os::unix::net::UnixListener::bind(&stale).unwrap());
+        fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let (notify_tx, _notify_rx) = channel::bounded(16, Overflow::DropOldest);
+        let (input_tx, _input_rx) = tokio::sync::mpsc::unbounded_channel();
+        let server = Server::bind_in(&dir, Some("bench".to_string()), SharedStatus::default(), input_tx, &notify_tx).unwrap();
+
+        let found: Vec<(u32, Option<String>)> = running_in(&dir).await.into_iter().map(|client| (client.pid, client.name)).collect();
+        assert_eq!(found, [(std::process::id(), Some("bench".to_string()))]);
+        assert!(!stale.exists());
+        // Broadcasts don't go to the Huhnitor sending them
+        assert!(broadcast_in(&dir, "bench".to_string(), vec!["scan".to_string()]).await.is_empty());
+
+        drop(server);
+        assert!(running_in(&dir).await.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
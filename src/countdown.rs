@@ -0,0 +1,142 @@
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, Borders, LineGauge};
+use ratatui::Frame;
+use regex::Regex;
+use std::time::{Duration, Instant};
+
+use crate::i18n;
+use crate::output;
+use crate::theme::Theme;
+
+lazy_static::lazy_static! {
+    // `scan -t 30`, `attack -timeout 5m` or `sniffbeacon -t 500ms`
+    static ref TIMEOUT: Regex = Regex::new(r"(?i)(?:^|\s)-(?:t|time|timeout)\s+(\S+)").unwrap();
+    static ref VALUE: Regex = Regex::new(r"(?i)^(\d+)(ms|s|m|h)?$").unwrap();
+}
+
+/// Parse a duration like `30`, `500ms`, `10m` or `1h`, seconds without a unit
+pub fn parse(text: &str) -> Option<Duration> {
+    let caps = VALUE.captures(text.trim())?;
+    let n: u64 = caps[1].parse().ok()?;
+    let duration = match caps.get(2).map(|unit| unit.as_str().to_lowercase()).as_deref() {
+        Some("ms") => Duration::from_millis(n),
+        Some("m") => Duration::from_secs(n.checked_mul(60)?),
+        Some("h") => Duration::from_secs(n.checked_mul(3600)?),
+        _ => Duration::from_secs(n),
+    };
+    Some(duration).filter(|duration| !duration.is_zero())
+}
+
+/// Duration given to a command, if it has one
+pub fn duration(command: &str) -> Option<Duration> {
+    parse(&TIMEOUT.captures(command)?[1])
+}
+
+/// A command that runs for a known time
+struct Timer {
+    command: String,
+    started: Instant,
+    duration: Duration,
+}
+
+impl Timer {
+    fn remaining(&self) -> Duration {
+        self.duration.saturating_sub(self.started.elapsed())
+    }
+}
+
+/// Countdowns of the timed commands that were sent
+#[derive(Default)]
+pub struct Timers {
+    running: Vec<Timer>,
+}
+
+impl Timers {
+    /// Start a countdown if the command has a duration
+    pub fn start(&mut self, command: &str) {
+        if let Some(duration) = duration(command) {
+            let command = command.trim().to_string();
+            self.running.push(Timer { command, started: Instant::now(), duration });
+        }
+    }
+
+    /// Forget all countdowns, e.g. when the command was stopped
+    pub fn clear(&mut self) {
+        self.running.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.running.len()
+    }
+
+    /// Remove the finished countdowns, returns a message for each
+    pub fn finished(&mut self) -> Vec<String> {
+        let (done, running) = self.running.drain(..).partition(|timer| timer.remaining().is_zero());
+        self.running = running;
+        done.into_iter()
+            .map(|timer: Timer| i18n::format("timer_done", &[&timer.command, &output::duration(timer.duration)]))
+            .collect()
+    }
+
+    /// Countdowns as text, where there is no room for the widget
+    pub fn lines(&self) -> Vec<String> {
+        self.running
+            .iter()
+            .map(|timer| i18n::format("timer_left", &[&timer.command, &output::duration(timer.remaining())]))
+            .collect()
+    }
+
+    /// One gauge per countdown, filling up as time passes
+    pub fn render(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        if self.running.is_empty() {
+            return;
+        }
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.accent))
+            .title(i18n::text("timers"));
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        for (i, timer) in self.running.iter().enumerate().take(inner.height as usize) {
+            let ratio = timer.started.elapsed().as_secs_f64() / timer.duration.as_secs_f64();
+            let gauge = LineGauge::default()
+                .ratio(ratio.clamp(0.0, 1.0))
+                .label(i18n::format("timer_left", &[&timer.command, &output::duration(timer.remaining())]))
+                .style(theme.text)
+                .gauge_style(Style::default().fg(theme.focus).add_modifier(Modifier::BOLD));
+            f.render_widget(gauge, Rect { y: inner.y + i as u16, height: 1, ..inner });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse("30"), Some(Duration::from_secs(30)));
+        assert_eq!(parse("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse("10M"), Some(Duration::from_secs(600)));
+        assert_eq!(parse("1h"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse("0"), None);
+        assert_eq!(parse("-5"), None);
+    }
+
+    #[test]
+    fn rejects_durations_that_overflow() {
+        assert_eq!(parse("99999999999999999999"), None);
+        assert_eq!(parse(&format!("{}m", u64::MAX)), None);
+        assert_eq!(parse(&format!("{}h", u64::MAX / 60)), None);
+    }
+
+    #[test]
+    fn finds_the_duration_of_a_command() {
+        assert_eq!(duration("attack -timeout 5m"), Some(Duration::from_secs(300)));
+        assert_eq!(duration("sniffbeacon -t 500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(duration("scan"), None);
+    }
+}
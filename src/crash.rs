@@ -0,0 +1,303 @@
+use regex::Regex;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// ESP8266 dumps look like
+//   Exception (28):
+//   epc1=0x40201234 epc2=0x00000000 epc3=0x00000000 excvaddr=0x00000000 depc=0x00000000
+//   >>>stack>>>
+//   3ffffdb0:  40201234 3ffee2c8 ...
+//   <<<stack<<<
+// followed by the boot ROM banner `ets Jan  8 2013,rst cause:2, boot mode:(3,6)`.
+// ESP32 panics print `Guru Meditation Error: ...` and `Backtrace: 0x400d1234:0x3ffb1f50 ...`.
+lazy_static::lazy_static! {
+    static ref EXCEPTION: Regex = Regex::new(r"^\s*Exception \((\d+)\):").unwrap();
+    static ref EPC: Regex = Regex::new(r"\bepc1=0x([0-9a-fA-F]{8})").unwrap();
+    static ref GURU: Regex = Regex::new(r"Guru Meditation Error: *(.*)").unwrap();
+    static ref WDT: Regex = Regex::new(r"(?i)\b(soft wdt reset|wdt reset|task watchdog got triggered)\b").unwrap();
+    static ref RESET: Regex = Regex::new(r"\brst cause:? *(\d+)|\brst:0x[0-9a-fA-F]+ \((\w+)\)").unwrap();
+    static ref STACK_START: Regex = Regex::new(r">>>stack>>>").unwrap();
+    static ref STACK_END: Regex = Regex::new(r"<<<stack<<<").unwrap();
+    // Rows of the dump, e.g. `3ffffdb0:  40201234 ...`, and the `ctx: cont` and `sp: ...` lines in front of them
+    static ref STACK_ROW: Regex = Regex::new(r"^\s*(?:[0-9a-fA-F]{8}:|ctx:|sp:|$)").unwrap();
+    static ref BACKTRACE: Regex = Regex::new(r"^\s*Backtrace:").unwrap();
+    static ref ADDRESS: Regex = Regex::new(r"(?:\b|0x)(40[0-9a-fA-F]{6})\b").unwrap();
+}
+
+/// Resets within `BOOT_LOOP_WINDOW` that count as a boot loop
+const BOOT_LOOP_RESETS: usize = 3;
+const BOOT_LOOP_WINDOW: Duration = Duration::from_secs(30);
+
+/// Names of the Xtensa exception causes
+fn exception_cause(code: u32) -> &'static str {
+    match code {
+        0 => "IllegalInstruction",
+        1 => "SyscallCause",
+        2 => "InstructionFetchError",
+        3 => "LoadStoreError",
+        4 => "Level1Interrupt",
+        5 => "Alloca",
+        6 => "IntegerDivideByZero",
+        8 => "Privileged",
+        9 => "LoadStoreAlignment",
+        12 => "InstrPIFDataError",
+        13 => "LoadStorePIFDataError",
+        14 => "InstrPIFAddrError",
+        15 => "LoadStorePIFAddrError",
+        16 => "InstTLBMiss",
+        17 => "InstTLBMultiHit",
+        18 => "InstFetchPrivilege",
+        20 => "InstFetchProhibited",
+        24 => "LoadStoreTLBMiss",
+        25 => "LoadStoreTLBMultiHit",
+        26 => "LoadStorePrivilege",
+        28 => "LoadProhibited",
+        29 => "StoreProhibited",
+        _ => "unknown cause",
+    }
+}
+
+/// Reset reasons of the ESP8266 boot ROM
+fn reset_cause(code: u32) -> &'static str {
+    match code {
+        1 => "power on",
+        2 => "external reset or exception",
+        3 => "software reset",
+        4 => "hardware watchdog",
+        _ => "unknown reason",
+    }
+}
+
+/// Function symbols of a firmware ELF, to turn code addresses into names
+pub struct Symbols {
+    /// Start address, size and name, sorted by address
+    functions: Vec<(u32, u32, String)>,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+impl Symbols {
+    /// Read the symbol table of a 32 bit little endian ELF file, as built for the ESP8266 and ESP32
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let elf = fs::read(path)?;
+        let u16_at = |offset: usize| elf.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+        let u32_at = |offset: usize| elf.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+
+        if !elf.starts_with(b"\x7fELF") || elf.get(4) != Some(&1) || elf.get(5) != Some(&1) {
+            return Err(invalid("not a 32 bit little endian ELF file"));
+        }
+
+        let sections = u32_at(0x20).ok_or_else(|| invalid("truncated header"))? as usize;
+        let entry_size = u16_at(0x2e).ok_or_else(|| invalid("truncated header"))? as usize;
+        let count = u16_at(0x30).ok_or_else(|| invalid("truncated header"))? as usize;
+        let section = |i: usize| {
+            let base = sections + i * entry_size;
+            // Type, offset, size and linked section
+            Some((u32_at(base + 4)?, u32_at(base + 16)? as usize, u32_at(base + 20)? as usize, u32_at(base + 24)? as usize))
+        };
+
+        // SHT_SYMTAB
+        let (_, offset, size, link) = (0..count)
+            .filter_map(section)
+            .find(|(kind, ..)| *kind == 2)
+            .ok_or_else(|| invalid("no symbol table, was the firmware stripped?"))?;
+        let (_, strings, ..) = section(link).ok_or_else(|| invalid("missing string table"))?;
+
+        let name_at = |start: usize| {
+            let bytes = elf.get(start..)?;
+            let end = bytes.iter().position(|b| *b == 0)?;
+            Some(String::from_utf8_lossy(&bytes[..end]).to_string())
+        };
+
+        let mut functions = Vec::new();
+        for symbol in (offset..offset + size).step_by(16) {
+            let (name, value, len, info) = match (u32_at(symbol), u32_at(symbol + 4), u32_at(symbol + 8), elf.get(symbol + 12)) {
+                (Some(name), Some(value), Some(len), Some(info)) => (name, value, len, info),
+                _ => break,
+            };
+            // STT_FUNC
+            if info & 0xf == 2 {
+                if let Some(name) = name_at(strings + name as usize) {
+                    functions.push((value, len.max(1), name));
+                }
+            }
+        }
+        functions.sort();
+
+        Ok(Self { functions })
+    }
+
+    /// Function containing the address, e.g. `loop + 0x12`
+    pub fn lookup(&self, addr: u32) -> Option<String> {
+        let i = self.functions.partition_point(|(start, ..)| *start <= addr).checked_sub(1)?;
+        let (start, len, name) = &self.functions[i];
+        if addr - start >= *len {
+            return None;
+        }
+        Some(format!("{} + 0x{:x}", name, addr - start))
+    }
+}
+
+/// Something worth pointing out about the health of the device
+pub enum Finding {
+    /// The firmware crashed
+    Crash(String),
+    /// The device restarted
+    Reset(String),
+    /// The device keeps restarting
+    BootLoop(usize),
+    /// Stack trace addresses resolved to functions
+    Trace(Vec<String>),
+}
+
+/// Looks for exceptions and resets in the output and decodes stack traces
+pub struct Detector {
+    symbols: Option<Symbols>,
+    /// Inside a `>>>stack>>>` block
+    in_stack: bool,
+    addresses: Vec<u32>,
+    resets: Vec<Instant>,
+}
+
+impl Detector {
+    pub fn new(symbols: Option<Symbols>) -> Self {
+        Self {
+            symbols,
+            in_stack: false,
+            addresses: Vec::new(),
+            resets: Vec::new(),
+        }
+    }
+
+    fn addresses(line: &str) -> impl Iterator<Item = u32> + '_ {
+        ADDRESS.captures_iter(line).filter_map(|caps| u32::from_str_radix(&caps[1], 16).ok())
+    }
+
+    /// Resolve the collected addresses, addresses outside of functions are data on the stack
+    fn trace(&mut self) -> Option<Finding> {
+        let addresses = std::mem::take(&mut self.addresses);
+        let symbols = self.symbols.as_ref()?;
+        let trace: Vec<String> = addresses
+            .into_iter()
+            .filter_map(|addr| symbols.lookup(addr).map(|name| format!("0x{:08x}: {}", addr, name)))
+            .collect();
+        if trace.is_empty() {
+            None
+        } else {
+            Some(Finding::Trace(trace))
+        }
+    }
+
+    pub fn check(&mut self, line: &str) -> Option<Finding> {
+        if self.in_stack {
+            if STACK_END.is_match(line) {
+                self.in_stack = false;
+                return self.trace();
+            }
+            if STACK_ROW.is_match(line) {
+                // Skip the stack pointer in front of each row
+                let values = line.split_once(':').map_or(line, |(_, values)| values);
+                self.addresses.extend(Self::addresses(values));
+                return None;
+            }
+            // The dump was cut off, e.g. by a reset, so this line is regular output again
+            self.in_stack = false;
+            return self.check(line).or_else(|| self.trace());
+        }
+
+        if let Some(caps) = EXCEPTION.captures(line) {
+            let code = caps[1].parse().unwrap_or(u32::MAX);
+            self.addresses.clear();
+            return Some(Finding::Crash(format!("Exception {} ({})", code, exception_cause(code))));
+        }
+
+        if let Some(caps) = EPC.captures(line) {
+            // The faulting instruction goes first in the trace
+            self.addresses.extend(u32::from_str_radix(&caps[1], 16));
+            return None;
+        }
+
+        if STACK_START.is_match(line) {
+            self.in_stack = true;
+            return None;
+        }
+
+        if BACKTRACE.is_match(line) {
+            // Pairs of program counter and stack pointer
+            self.addresses.extend(line.split_whitespace().filter_map(|pair| pair.split(':').next()).flat_map(Self::addresses));
+            return self.trace();
+        }
+
+        if let Some(caps) = GURU.captures(line) {
+            self.addresses.clear();
+            return Some(Finding::Crash(caps[1].trim().to_string()));
+        }
+
+        if let Some(caps) = WDT.captures(line) {
+            return Some(Finding::Crash(caps[1].to_string()));
+        }
+
+        if let Some(caps) = RESET.captures(line) {
+            let reason = match (caps.get(1), caps.get(2)) {
+                (Some(code), _) => reset_cause(code.as_str().parse().unwrap_or(0)).to_string(),
+                (_, Some(reason)) => reason.as_str().to_string(),
+                _ => String::new(),
+            };
+
+            let now = Instant::now();
+            self.resets.retain(|time| now.duration_since(*time) <= BOOT_LOOP_WINDOW);
+            self.resets.push(now);
+            if self.resets.len() >= BOOT_LOOP_RESETS {
+                return Some(Finding::BootLoop(self.resets.len()));
+            }
+            return Some(Finding::Reset(reason));
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crash(finding: Option<Finding>) -> Option<String> {
+        match finding {
+            Some(Finding::Crash(crash)) => Some(crash),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn finds_exceptions() {
+        let mut detector = Detector::new(None);
+        assert_eq!(crash(detector.check("Exception (28):")).as_deref(), Some("Exception 28 (LoadProhibited)"));
+        assert_eq!(crash(detector.check("Guru Meditation Error: Core  1 panic'ed (LoadProhibited)")).as_deref(), Some("Core  1 panic'ed (LoadProhibited)"));
+    }
+
+    #[test]
+    fn leaves_a_stack_dump_that_was_cut_off() {
+        let mut detector = Detector::new(None);
+        for line in [">>>stack>>>", "", "ctx: cont", "sp: 3ffffdf0 end: 3fffffc0 offset: 01a0", "3ffffdf0:  40201234 3ffee2c8"] {
+            assert!(detector.check(line).is_none());
+        }
+        assert!(detector.in_stack);
+
+        // No <<<stack<<<, the device restarted
+        assert!(matches!(detector.check(" ets Jan  8 2013,rst cause:2, boot mode:(3,6)"), Some(Finding::Reset(_))));
+        assert!(!detector.in_stack);
+        assert!(crash(detector.check("Exception (3):")).is_some());
+    }
+
+    #[test]
+    fn counts_boot_loops() {
+        let mut detector = Detector::new(None);
+        assert!(matches!(detector.check("rst cause:4"), Some(Finding::Reset(_))));
+        assert!(matches!(detector.check("rst cause:4"), Some(Finding::Reset(_))));
+        assert!(matches!(detector.check("rst cause:4"), Some(Finding::BootLoop(3))));
+    }
+}
@@ -0,0 +1,467 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+use crate::app::{self, App};
+use crate::channel::{self, Overflow};
+use crate::error;
+use crate::firmware;
+use crate::i18n;
+use crate::logfile::Log;
+use crate::monitor::{self, Frontend, Options};
+use crate::protocol::{BackendEvent, UiEvent};
+use crate::runtime;
+use crate::serial::LinkSettings;
+use crate::stats::Stats;
+use crate::status::{self, SharedStatus, Status};
+
+/// Events kept for the next UI to attach, the oldest go first
+const BACKLOG: usize = 10_000;
+
+/// Lines buffered per attached UI before a slow one starts missing some
+const CLIENT_BUFFER: usize = 1024;
+
+/// How often changes of the status are passed on to the attached UIs
+const STATUS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long `huhnitor daemon` waits for the session to start in the background
+const START_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What the daemon tells an attached UI, one JSON object per line
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Update {
+    Event(BackendEvent),
+    Status(WireStatus),
+}
+
+/// The status of the session as it goes over the socket, the firmware by name
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+struct WireStatus {
+    port: Option<String>,
+    link: String,
+    firmware: Option<String>,
+    resets: u64,
+    pick_port: bool,
+    stale: bool,
+    dry_run: bool,
+    broadcast: bool,
+    skip_broadcasts: bool,
+    failed: Vec<String>,
+}
+
+impl WireStatus {
+    fn of(status: &Status) -> Self {
+        Self {
+            port: status.port.clone(),
+            link: status.link.to_string(),
+            firmware: status.firmware.map(|profile| profile.name().to_string()),
+            resets: status.resets,
+            pick_port: status.pick_port,
+            stale: status.stale,
+            dry_run: status.dry_run,
+            broadcast: status.broadcast,
+            skip_broadcasts: status.skip_broadcasts,
+            failed: status.failed.clone(),
+        }
+    }
+
+    /// Update the status of the UI, what it takes is added instead of replaced
+    fn apply(self, status: &mut Status) {
+        status.port = self.port;
+        status.link = self.link.parse().unwrap_or_else(|_| LinkSettings::default());
+        status.firmware = self.firmware.as_deref().and_then(firmware::by_name);
+        status.resets = self.resets;
+        status.pick_port |= self.pick_port;
+        status.stale = self.stale;
+        status.dry_run = self.dry_run;
+        status.broadcast = self.broadcast;
+        status.skip_broadcasts = self.skip_broadcasts;
+        status.failed.extend(self.failed);
+    }
+}
+
+/// One line of the socket
+fn encode<T: Serialize>(value: &T) -> String {
+    let mut line = serde_json::to_string(value).unwrap_or_default();
+    line.push('\n');
+    line
+}
+
+/// Socket of a named session, e.g. `/run/user/1000/huhnitor/default.sock`
+pub fn socket(name: &str) -> Result<PathBuf, String> {
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return Err(format!("Invalid session name '{}'", name));
+    }
+    let dir = runtime::dir().map_err(|e| format!("Couldn't create the directory for the socket: {}", e))?;
+    Ok(dir.join(format!("{}.sock", name)))
+}
+
+/// Make sure no session of this name runs, the socket of one that died is removed
+fn claim(path: &Path, name: &str) -> Result<(), String> {
+    if !path.exists() {
+        return Ok(());
+    }
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        return Err(format!("The session '{0}' is running already, attach to it with huhnitor attach {0}", name));
+    }
+    fs::remove_file(path).map_err(|e| format!("Couldn't remove the stale socket {}: {}", path.display(), e))
+}
+
+/// Start `huhnitor daemon` again in the background, returns once the session is listening
+pub fn spawn(name: &str) -> Result<PathBuf, String> {
+    let path = socket(name)?;
+    claim(&path, name)?;
+
+    let exe = env::current_exe().map_err(|e| format!("Couldn't find the huhnitor executable: {}", e))?;
+    let mut child = Command::new(exe)
+        .args(env::args_os().skip(1))
+        .arg("--foreground")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // Its own process group, so closing the terminal doesn't hang it up
+        .process_group(0)
+        .spawn()
+        .map_err(|e| format!("Couldn't start the daemon: {}", e))?;
+
+    let started = Instant::now();
+    while started.elapsed() < START_TIMEOUT {
+        if let Ok(Some(exit)) = child.try_wait() {
+            return Err(format!("The daemon stopped right away ({}), run it with --foreground to see why", exit));
+        }
+        if path.exists() {
+            return Ok(path);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    Err(format!("The daemon didn't start listening on {} within {}s", path.display(), START_TIMEOUT.as_secs()))
+}
+
+/// Run the session of `huhnitor daemon` until an attached UI quits it, or it's stopped with SIGTERM
+pub async fn run(name: &str, port: String, options: Options, log: Option<Log>, stats: Arc<Stats>) -> Result<(), String> {
+    let path = socket(name)?;
+    claim(&path, name)?;
+    // Whoever can attach can send to the device, the directory keeps out other users
+    let listener = UnixListener::bind(&path).map_err(|e| format!("Couldn't listen on {}: {}", path.display(), e))?;
+
+    let (frontend, backend) = monitor::connect(&options);
+
+    // Closing the port properly keeps the log and its metadata complete
+    let shutdown = frontend.shutdown.clone();
+    tokio::spawn(async move {
+        let (Ok(mut term), Ok(mut int)) = (signal(SignalKind::terminate()), signal(SignalKind::interrupt())) else {
+            return;
+        };
+        tokio::select! {
+            _ = term.recv() => (),
+            _ = int.recv() => (),
+        }
+        shutdown.cancel();
+    });
+
+    let hub = tokio::spawn(hub(listener, frontend));
+    monitor::serve(Some(port), options, log, stats, backend).await;
+    let _ = hub.await;
+    let _ = fs::remove_file(&path);
+    Ok(())
+}
+
+/// What a newly attached UI gets first
+struct Backlog {
+    lines: VecDeque<String>,
+    clients: broadcast::Sender<String>,
+}
+
+/// Pass the output of the session on to the attached UIs, and keep it for those that attach later
+async fn hub(listener: UnixListener, frontend: Frontend) {
+    let Frontend { input_tx, mut output_rx, status, .. } = frontend;
+    let (clients, _) = broadcast::channel(CLIENT_BUFFER);
+    let backlog = Arc::new(Mutex::new(Backlog { lines: VecDeque::new(), clients }));
+
+    let accepting = backlog.clone();
+    let attached_status = status.clone();
+    let acceptor = tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            if !runtime::is_own(&stream) {
+                continue;
+            }
+            // Subscribed together with the copy of the backlog, so no line is missed or doubled
+            let (lines, output_rx) = {
+                let backlog = accepting.lock().unwrap_or_else(|e| e.into_inner());
+                (backlog.lines.iter().cloned().collect::<Vec<_>>(), backlog.clients.subscribe())
+            };
+            let current = encode(&Update::Status(WireStatus { pick_port: false, failed: Vec::new(), ..WireStatus::of(&status::get(&attached_status)) }));
+            tokio::spawn(serve(stream, lines, current, output_rx, input_tx.clone()));
+        }
+    });
+
+    let mut ticker = tokio::time::interval(STATUS_INTERVAL);
+    let mut sent = WireStatus::default();
+    loop {
+        let line = tokio::select! {
+            event = output_rx.recv() => match event {
+                Some(event) => {
+                    // The unfinished line of the device is only of use while it's current
+                    let keep = !matches!(event, BackendEvent::Partial(_));
+                    let line = encode(&Update::Event(event));
+                    let mut backlog = backlog.lock().unwrap_or_else(|e| e.into_inner());
+                    if keep {
+                        if backlog.lines.len() == BACKLOG {
+                            backlog.lines.pop_front();
+                        }
+                        backlog.lines.push_back(line.clone());
+                    }
+                    // Fails when no UI is attached, which is fine
+                    let _ = backlog.clients.send(line);
+                    continue;
+                }
+                // The session has ended
+                None => break,
+            },
+            _ = ticker.tick() => {
+                if backlog.lock().unwrap_or_else(|e| e.into_inner()).clients.receiver_count() == 0 {
+                    continue;
+                }
+                // A port to pick and failed commands are for the UIs there are now
+                let mut current = WireStatus::default();
+                status::update(&status, |status| {
+                    current = WireStatus::of(status);
+                    status.pick_port = false;
+                    status.failed.clear();
+                });
+                if current == sent {
+                    continue;
+                }
+                sent = WireStatus { pick_port: false, failed: Vec::new(), ..current.clone() };
+                encode(&Update::Status(current))
+            },
+        };
+        let _ = backlog.lock().unwrap_or_else(|e| e.into_inner()).clients.send(line);
+    }
+
+    // Dropping the broadcast sender ends the attached UIs
+    acceptor.abort();
+}
+
+/// Talk to one attached UI until it detaches or the session ends
+async fn serve(
+    stream: UnixStream,
+    backlog: Vec<String>,
+    status: String,
+    mut output_rx: broadcast::Receiver<String>,
+    input_tx: UnboundedSender<UiEvent>,
+) {
+    let (reader, mut writer) = stream.into_split();
+    for line in backlog.iter().chain(Some(&status)) {
+        if writer.write_all(line.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+
+    let mut lines = BufReader::new(reader).lines();
+    loop {
+        tokio::select! {
+            line = output_rx.recv() => match line {
+                Ok(line) => {
+                    if writer.write_all(line.as_bytes()).await.is_err() {
+                        return;
+                    }
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    let warning = format!("WARNING: {}", i18n::format("lines_dropped", &[&missed]));
+                    if writer.write_all(encode(&Update::Event(BackendEvent::Status(warning))).as_bytes()).await.is_err() {
+                        return;
+                    }
+                }
+                Err(RecvError::Closed) => return,
+            },
+
+            line = lines.next_line() => match line {
+                Ok(Some(line)) => {
+                    // Lines that aren't events are ignored, like a closed connection they can't end the session
+                    if let Ok(event) = serde_json::from_str(&line) {
+                        if input_tx.send(event).is_err() {
+                            return;
+                        }
+                    }
+                }
+                Ok(None) | Err(_) => return,
+            },
+        }
+    }
+}
+
+/// How the terminal UI left a session
+pub enum Left {
+    /// With `:detach`, the session goes on
+    Detached,
+    /// The user quit, which ended the session
+    Quit,
+    /// The session ended on its own, e.g. after the idle timeout
+    Ended,
+}
+
+/// Run the terminal UI on a session of `huhnitor daemon` until the user detaches or quits
+pub async fn attach(name: &str, mut app: App) -> Result<Left, String> {
+    let path = socket(name)?;
+    let stream = UnixStream::connect(&path)
+        .await
+        .map_err(|_| format!("There is no session named '{0}', start one with huhnitor daemon --name {0}", name))?;
+    // Keys typed here shouldn't end up at a session of another user
+    if !runtime::is_own(&stream) {
+        return Err(format!("The session '{}' belongs to another user", name));
+    }
+    let (reader, writer) = stream.into_split();
+
+    let (input_tx, input_rx) = tokio::sync::mpsc::unbounded_channel();
+    let (output_tx, output_rx) = channel::bounded(channel::CAPACITY, Overflow::DropOldest);
+    let status = SharedStatus::default();
+    app.set_status(status.clone());
+    app.set_attached(true);
+
+    let receiver = tokio::spawn(receive(reader, output_tx, status));
+    let sender = tokio::spawn(send(writer, input_rx));
+
+    if let Ok(Err(e)) = tokio::spawn(app.run(input_tx, output_rx)).await {
+        app::restore_terminal();
+        error!(e);
+    }
+
+    // The socket stays open until it is clear whether the daemon closed it, which only happens when the session ended
+    let left = match sender.await {
+        Ok((true, _)) => Left::Quit,
+        Ok((false, _)) if receiver.is_finished() => Left::Ended,
+        Ok((false, _)) => Left::Detached,
+        Err(_) => Left::Ended,
+    };
+    receiver.abort();
+    Ok(left)
+}
+
+/// Pass what the daemon sends on to the UI, returns once the daemon closes the socket
+async fn receive(reader: OwnedReadHalf, output_tx: channel::Sender, status: SharedStatus) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        match serde_json::from_str(&line) {
+            Ok(Update::Event(event)) => {
+                if output_tx.send(event).is_err() {
+                    return;
+                }
+            }
+            Ok(Update::Status(wire)) => status::update(&status, |status| wire.apply(status)),
+            Err(_) => (),
+        }
+    }
+}
+
+/// Write the events of the UI to the daemon until the UI is gone.
+/// Returns whether the user quit, and the socket so the daemon doesn't see it close yet.
+async fn send(mut writer: OwnedWriteHalf, mut input_rx: UnboundedReceiver<UiEvent>) -> (bool, OwnedWriteHalf) {
+    while let Some(event) = input_rx.recv().await {
+        let quit = event == UiEvent::Quit;
+        if writer.write_all(encode(&event).as_bytes()).await.is_err() || quit {
+            return (quit, writer);
+        }
+    }
+    (false, writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_sockets_in_the_private_directory() {
+        for name in ["", "a/b", "..\\x"] {
+            assert!(socket(name).is_err(), "{}", name);
+        }
+        let path = socket("roof").unwrap();
+        assert_eq!(path.file_name().and_then(|name| name.to_str()), Some("roof.sock"));
+        assert_eq!(path.parent(), runtime::dir().ok().as_deref());
+    }
+
+    #[test]
+    fn passes_the_status_on() {
+        let firmware = firmware::names()[0];
+        let status = Status {
+            port: Some("/dev/ttyUSB0".to_string()),
+            link: "9600 8N1 flow: none".parse().unwrap(),
+            firmware: firmware::by_name(firmware),
+            resets: 2,
+            pick_port: true,
+            failed: vec!["scan".to_string()],
+            ..Status::default()
+        };
+        let line = encode(&Update::Status(WireStatus::of(&status)));
+
+        let mut attached = Status { failed: vec!["stop".to_string()], ..Status::default() };
+        match serde_json::from_str(&line) {
+            Ok(Update::Status(wire)) => wire.apply(&mut attached),
+            _ => panic!("expected a status"),
+        }
+        assert_eq!(attached.port, status.port);
+        assert_eq!(attached.link.to_string(), status.link.to_string());
+        assert_eq!(attached.firmware.map(|profile| profile.name()), Some(firmware));
+        assert_eq!(attached.resets, 2);
+        assert!(attached.pick_port);
+        assert_eq!(attached.failed, ["stop", "scan"]);
+    }
+
+    async fn next(lines: &mut tokio::io::Lines<BufReader<OwnedReadHalf>>) -> Update {
+        serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn serves_an_attached_ui() {
+        let (ui, daemon) = UnixStream::pair().unwrap();
+        let (clients, output_rx) = broadcast::channel(16);
+        let (input_tx, mut input_rx) = tokio::sync::mpsc::unbounded_channel();
+        let backlog = vec![encode(&Update::Event(BackendEvent::Rx("old".to_string())))];
+        let status = encode(&Update::Status(WireStatus::default()));
+        tokio::spawn(serve(daemon, backlog, status, output_rx, input_tx));
+
+        let (reader, mut writer) = ui.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        assert!(matches!(next(&mut lines).await, Update::Event(BackendEvent::Rx(line)) if line == "old"));
+        assert!(matches!(next(&mut lines).await, Update::Status(_)));
+        clients.send(encode(&Update::Event(BackendEvent::Rx("new".to_string())))).unwrap();
+        assert!(matches!(next(&mut lines).await, Update::Event(BackendEvent::Rx(line)) if line == "new"));
+
+        // Lines that aren't events don't reach the session
+        writer.write_all(b"garbage\n").await.unwrap();
+        writer.write_all(encode(&UiEvent::SendLine("scan\r\n".to_string())).as_bytes()).await.unwrap();
+        assert_eq!(input_rx.recv().await, Some(UiEvent::SendLine("scan\r\n".to_string())));
+    }
+
+    #[tokio::test]
+    async fn shows_what_the_daemon_sends() {
+        let (ui, mut daemon) = UnixStream::pair().unwrap();
+        let (reader, _writer) = ui.into_split();
+        let (output_tx, mut output_rx) = channel::bounded(16, Overflow::DropOldest);
+        let status = SharedStatus::default();
+        let receiver = tokio::spawn(receive(reader, output_tx, status.clone()));
+
+        let wire = WireStatus { port: Some("/dev/ttyUSB0".to_string()), ..WireStatus::default() };
+        daemon.write_all(encode(&Update::Event(BackendEvent::Status("> hi".to_string()))).as_bytes()).await.unwrap();
+        daemon.write_all(b"{broken\n").await.unwrap();
+        daemon.write_all(encode(&Update::Status(wire)).as_bytes()).await.unwrap();
+        drop(daemon);
+        receiver.await.unwrap();
+
+        assert_eq!(output_rx.recv().await, Some(BackendEvent::Status("> hi".to_string())));
+        assert_eq!(status::get(&status).port.as_deref(), Some("/dev/ttyUSB0"));
+    }
+}
@@ -0,0 +1,247 @@
+use chrono::{Local, TimeZone};
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState},
+    Frame,
+};
+use rusqlite::{params, Connection};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::picker;
+use crate::theme::Theme;
+
+/// Matches listed at once, the latest first
+const LIMIT: usize = 200;
+
+/// Database file in the data directory, e.g. `~/.local/share/huhnitor/scans.db`
+pub fn default_path() -> Option<PathBuf> {
+    Some(dirs::data_dir()?.join("huhnitor").join("scans.db"))
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64)
+}
+
+fn time(secs: i64) -> String {
+    Local.timestamp_opt(secs, 0).single().map_or_else(String::new, |time| time.format("%Y-%m-%d %H:%M").to_string())
+}
+
+/// An access point or station as it was last seen
+pub struct Observation {
+    pub mac: String,
+    pub rssi: Option<i32>,
+    pub channel: Option<u8>,
+    /// The row as printed by the device
+    pub line: String,
+    pub first_seen: i64,
+    pub last_seen: i64,
+    /// Number of scans that found it
+    pub count: i64,
+}
+
+/// Scan results of all sessions, one row per MAC address
+pub struct Database {
+    conn: Connection,
+}
+
+impl Database {
+    pub fn open(path: &Path) -> rusqlite::Result<Self> {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let conn = Connection::open(path)?;
+        // Results arrive one at a time, waiting for the disk each time would slow down the UI
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS observations (
+                mac TEXT PRIMARY KEY,
+                rssi INTEGER,
+                channel INTEGER,
+                line TEXT NOT NULL,
+                first_seen INTEGER NOT NULL,
+                last_seen INTEGER NOT NULL,
+                count INTEGER NOT NULL DEFAULT 1
+            )",
+            [],
+        )?;
+        Ok(Self { conn })
+    }
+
+    /// Note a scan result, the first time a MAC is seen is kept
+    pub fn record(&self, mac: &str, rssi: Option<i32>, channel: Option<u8>, line: &str) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT INTO observations (mac, rssi, channel, line, first_seen, last_seen) VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+             ON CONFLICT(mac) DO UPDATE SET
+                rssi = excluded.rssi,
+                channel = excluded.channel,
+                line = excluded.line,
+                last_seen = excluded.last_seen,
+                count = count + 1",
+            params![mac, rssi, channel, line, now()],
+        )?;
+        Ok(())
+    }
+
+    /// Observations whose MAC or row contain the text, all of them for an empty one
+    pub fn search(&self, text: &str) -> rusqlite::Result<Vec<Observation>> {
+        let pattern = format!("%{}%", text.trim().replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+        let mut stmt = self.conn.prepare(
+            "SELECT mac, rssi, channel, line, first_seen, last_seen, count FROM observations
+             WHERE mac LIKE ?1 ESCAPE '\\' OR line LIKE ?1 ESCAPE '\\'
+             ORDER BY last_seen DESC LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![pattern, LIMIT], |row| {
+            Ok(Observation {
+                mac: row.get(0)?,
+                rssi: row.get(1)?,
+                channel: row.get(2)?,
+                line: row.get(3)?,
+                first_seen: row.get(4)?,
+                last_seen: row.get(5)?,
+                count: row.get(6)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Matches as text, where there is no room for the panel
+    pub fn lines(&self, text: &str) -> Vec<String> {
+        match self.search(text) {
+            Ok(found) if found.is_empty() => vec!["> Nothing found".to_string()],
+            Ok(found) => found
+                .iter()
+                .map(|obs| {
+                    format!("> {} seen {}x, {} - {}: {}", obs.mac, obs.count, time(obs.first_seen), time(obs.last_seen), obs.line)
+                })
+                .collect(),
+            Err(e) => vec![format!("ERROR: {}", e)],
+        }
+    }
+}
+
+pub enum Action {
+    /// The search text changed
+    Search,
+    Close,
+    None,
+}
+
+/// Panel to browse and search the database
+pub struct Browser {
+    search: String,
+    found: Result<Vec<Observation>, String>,
+    state: TableState,
+}
+
+impl Browser {
+    pub fn new(db: &Database, search: &str) -> Self {
+        let mut browser = Self { search: search.to_string(), found: Ok(Vec::new()), state: TableState::default() };
+        browser.refresh(db);
+        browser
+    }
+
+    /// Run the search again
+    pub fn refresh(&mut self, db: &Database) {
+        self.found = db.search(&self.search).map_err(|e| e.to_string());
+        let any = self.found.as_ref().is_ok_and(|found| !found.is_empty());
+        self.state.select(if any { Some(0) } else { None });
+    }
+
+    fn len(&self) -> usize {
+        self.found.as_ref().map_or(0, Vec::len)
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Char(c) => {
+                self.search.push(c);
+                return Action::Search;
+            }
+            KeyCode::Backspace => {
+                self.search.pop();
+                return Action::Search;
+            }
+            KeyCode::Up => {
+                if let Some(i) = self.state.selected() {
+                    self.state.select(Some(i.saturating_sub(1)));
+                }
+            }
+            KeyCode::Down => {
+                if let Some(i) = self.state.selected() {
+                    self.state.select(Some((i + 1).min(self.len().saturating_sub(1))));
+                }
+            }
+            KeyCode::Esc => return Action::Close,
+            _ => (),
+        }
+        Action::None
+    }
+
+    pub fn render(&mut self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let area = picker::centered(area, 90, 70);
+        let title = match &self.found {
+            Ok(found) if found.len() == LIMIT => format!("Scan database, latest {} matches", LIMIT),
+            Ok(found) => format!("Scan database, {} matches", found.len()),
+            Err(_) => "Scan database".to_string(),
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.focus))
+            .title(title)
+            .title_bottom("[type] search  [Up/Down] scroll  [Esc] close");
+        let inner = block.inner(area);
+        f.render_widget(Clear, area);
+        f.render_widget(block, area);
+
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(inner);
+        let search = Line::from(vec![
+            Span::styled("Search: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled(format!("{}_", self.search), Style::default().fg(theme.input)),
+        ]);
+        f.render_widget(Paragraph::new(search), rows[0]);
+
+        let found = match &self.found {
+            Ok(found) => found,
+            Err(e) => return f.render_widget(Paragraph::new(format!("ERROR: {}", e)), rows[1]),
+        };
+        let table_rows: Vec<Row> = found
+            .iter()
+            .map(|obs| {
+                Row::new(vec![
+                    Cell::from(obs.mac.clone()),
+                    Cell::from(obs.channel.map(|channel| channel.to_string()).unwrap_or_default()),
+                    Cell::from(obs.rssi.map(|rssi| rssi.to_string()).unwrap_or_default()),
+                    Cell::from(obs.count.to_string()),
+                    Cell::from(time(obs.first_seen)),
+                    Cell::from(time(obs.last_seen)),
+                    Cell::from(obs.line.clone()).style(Style::default().fg(theme.accent)),
+                ])
+            })
+            .collect();
+        let widths = [
+            Constraint::Length(18),
+            Constraint::Length(3),
+            Constraint::Length(5),
+            Constraint::Length(5),
+            Constraint::Length(17),
+            Constraint::Length(17),
+            Constraint::Min(10),
+        ];
+        let table = Table::new(table_rows, widths)
+            .header(
+                Row::new(vec!["MAC", "Ch", "RSSI", "Seen", "First seen", "Last seen", "Line"])
+                    .style(Style::default().add_modifier(Modifier::BOLD)),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(table, rows[1], &mut self.state);
+    }
+}
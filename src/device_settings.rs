@@ -0,0 +1,167 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Constraint, Rect},
+    style::{Modifier, Style},
+    widgets::{Block, Borders, Cell, Row, Table},
+    Frame,
+};
+use regex::Regex;
+
+use crate::i18n;
+use crate::listing::{self, Listing};
+use crate::picker;
+use crate::theme::Theme;
+
+// `get settings` prints one setting per line, e.g. `channel = 1` or `ssid: pwned`
+lazy_static::lazy_static! {
+    static ref SETTING: Regex = Regex::new(r"^\s*([A-Za-z][\w-]*)\s*[=:]\s*(.*?)\s*$").unwrap();
+}
+
+/// What the user did in the settings editor
+pub enum Action {
+    None,
+    Close,
+    /// Send these deauther commands
+    Send(Vec<String>),
+}
+
+struct Setting {
+    key: String,
+    value: String,
+    /// Value the deauther reported, to only send what changed
+    original: String,
+}
+
+impl Setting {
+    fn parse(line: &str) -> Option<Self> {
+        let caps = SETTING.captures(line)?;
+        Some(Self {
+            key: caps[1].to_string(),
+            value: caps[2].to_string(),
+            original: caps[2].to_string(),
+        })
+    }
+}
+
+/// Form for the deauther's `get`/`set` settings
+pub struct SettingsEditor {
+    settings: Listing<Setting>,
+    /// Value being typed for the selected setting
+    editing: Option<String>,
+}
+
+impl SettingsEditor {
+    pub fn new() -> Self {
+        Self {
+            settings: Listing::new(),
+            editing: None,
+        }
+    }
+
+    /// Commands that read the settings, the editor picks up the answer
+    pub fn refresh(&mut self) -> Vec<String> {
+        self.settings.expect(false);
+        self.editing = None;
+        vec!["get settings".to_string()]
+    }
+
+    /// Pick up settings from the output of `get settings`
+    pub fn update(&mut self, line: &str) {
+        self.settings.update(line, Setting::parse);
+    }
+
+    /// `set` commands for every changed setting
+    fn changes(&self) -> Vec<String> {
+        self.settings
+            .items
+            .iter()
+            .filter(|setting| setting.value != setting.original)
+            .map(|setting| {
+                if setting.value.contains(' ') {
+                    format!("set {} \"{}\"", setting.key, setting.value)
+                } else {
+                    format!("set {} {}", setting.key, setting.value)
+                }
+            })
+            .collect()
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if let Some(value) = self.editing.as_mut() {
+            match key.code {
+                KeyCode::Char(c) => value.push(c),
+                KeyCode::Backspace => {
+                    value.pop();
+                }
+                KeyCode::Enter => {
+                    let value = self.editing.take().unwrap_or_default();
+                    if let Some(setting) = self.settings.selected_mut() {
+                        setting.value = value;
+                    }
+                }
+                KeyCode::Esc => self.editing = None,
+                _ => (),
+            }
+            return Action::None;
+        }
+
+        if self.settings.handle_key(key) {
+            return Action::None;
+        }
+        match key.code {
+            KeyCode::Enter => {
+                if let Some(setting) = self.settings.selected() {
+                    self.editing = Some(setting.value.clone());
+                }
+            }
+            KeyCode::Char('s') => {
+                let mut commands = self.changes();
+                if !commands.is_empty() {
+                    commands.extend(self.refresh());
+                }
+                return Action::Send(commands);
+            }
+            KeyCode::Char('r') => return Action::Send(self.refresh()),
+            KeyCode::Esc | KeyCode::Char('q') => return Action::Close,
+            _ => (),
+        }
+        Action::None
+    }
+
+    pub fn render(&mut self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.focus))
+            .title(i18n::text("device_settings"))
+            .title_bottom(if self.editing.is_some() {
+                i18n::text("editing_keys")
+            } else {
+                i18n::text("device_settings_keys")
+            });
+
+        let selected = self.settings.selected_index();
+        let rows: Vec<Row> = self
+            .settings
+            .items
+            .iter()
+            .enumerate()
+            .map(|(i, setting)| {
+                let value = match &self.editing {
+                    Some(value) if Some(i) == selected => format!("{}_", value),
+                    _ => setting.value.clone(),
+                };
+                let style = if setting.value != setting.original {
+                    Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                Row::new(vec![Cell::from(setting.key.clone()), Cell::from(value).style(style)])
+            })
+            .collect();
+
+        let table = Table::new(rows, [Constraint::Length(24), Constraint::Min(8)])
+            .header(listing::header(vec![i18n::text("setting"), i18n::text("value")]))
+            .block(block);
+        self.settings.render(f, picker::centered(area, 80, 70), table);
+    }
+}
@@ -0,0 +1,113 @@
+use serialport::SerialPortType;
+
+use crate::port;
+
+/// USB to serial chips found on ESP8266 and ESP32 boards
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Chip {
+    Cp210x,
+    Ch34x,
+    Ftdi,
+}
+
+pub const CHIPS: [Chip; 3] = [Chip::Cp210x, Chip::Ch34x, Chip::Ftdi];
+
+impl Chip {
+    /// Chip of a USB vendor ID
+    pub fn from_vid(vid: u16) -> Option<Self> {
+        match vid {
+            0x10c4 => Some(Chip::Cp210x),
+            0x1a86 => Some(Chip::Ch34x),
+            0x0403 => Some(Chip::Ftdi),
+            _ => None,
+        }
+    }
+
+    pub fn vid(&self) -> u16 {
+        match self {
+            Chip::Cp210x => 0x10c4,
+            Chip::Ch34x => 0x1a86,
+            Chip::Ftdi => 0x0403,
+        }
+    }
+
+    /// Product IDs of the common models, e.g. CP2102 or FT232R
+    pub fn pids(&self) -> &'static [u16] {
+        match self {
+            Chip::Cp210x => &[0xea60, 0xea70],
+            Chip::Ch34x => &[0x7523, 0x5523],
+            Chip::Ftdi => &[0x6001, 0x6010, 0x6014, 0x6015],
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Chip::Cp210x => "Silicon Labs CP210x",
+            Chip::Ch34x => "WCH CH340/CH341",
+            Chip::Ftdi => "FTDI",
+        }
+    }
+
+    /// Driver download for this OS, `None` if the OS comes with a driver
+    pub fn driver_url(&self) -> Option<&'static str> {
+        match self {
+            _ if cfg!(target_os = "linux") => None,
+            Chip::Cp210x => Some("https://www.silabs.com/developers/usb-to-uart-bridge-vcp-drivers"),
+            Chip::Ch34x if cfg!(target_os = "macos") => Some("https://www.wch-ic.com/downloads/CH34XSER_MAC_ZIP.html"),
+            Chip::Ch34x => Some("https://www.wch-ic.com/downloads/CH341SER_EXE.html"),
+            Chip::Ftdi => Some("https://ftdichip.com/drivers/vcp-drivers/"),
+        }
+    }
+}
+
+/// Vendor and product IDs of attached USB devices, whether or not a driver claimed them
+#[cfg(target_os = "linux")]
+fn usb_devices() -> Vec<(u16, u16)> {
+    let devices = match std::fs::read_dir("/sys/bus/usb/devices") {
+        Ok(devices) => devices,
+        Err(_) => return Vec::new(),
+    };
+    let id = |path: &std::path::Path, name: &str| {
+        let id = std::fs::read_to_string(path.join(name)).ok()?;
+        u16::from_str_radix(id.trim(), 16).ok()
+    };
+    devices
+        .filter_map(|device| {
+            let path = device.ok()?.path();
+            Some((id(&path, "idVendor")?, id(&path, "idProduct")?))
+        })
+        .collect()
+}
+
+/// Without a driver the adapter doesn't show up as a serial port, so only ports with a driver are found
+#[cfg(not(target_os = "linux"))]
+fn usb_devices() -> Vec<(u16, u16)> {
+    Vec::new()
+}
+
+/// Vendor and product IDs of the attached adapters with a known chip
+pub fn adapters() -> Vec<(u16, u16)> {
+    let ports = port::list().into_iter().filter_map(|port| match port.port_type {
+        SerialPortType::UsbPort(usb) => Some((usb.vid, usb.pid)),
+        _ => None,
+    });
+
+    let mut adapters = Vec::new();
+    for (vid, pid) in ports.chain(usb_devices()) {
+        if Chip::from_vid(vid).is_some() && !adapters.contains(&(vid, pid)) {
+            adapters.push((vid, pid));
+        }
+    }
+    adapters
+}
+
+/// Known chips among the attached USB devices
+pub fn detect() -> Vec<Chip> {
+    let mut chips = Vec::new();
+    for chip in adapters().into_iter().filter_map(|(vid, _)| Chip::from_vid(vid)) {
+        if !chips.contains(&chip) {
+            chips.push(chip);
+        }
+    }
+    chips
+}
@@ -0,0 +1,54 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::app;
+
+/// Errors that can end or interrupt a Huhnitor session
+#[derive(Debug)]
+pub enum Error {
+    /// Terminal or file I/O failed
+    Io(io::Error),
+    /// The serial port couldn't be opened or configured
+    Serial(serialport::Error),
+    /// The other end of an internal channel has gone away
+    ChannelClosed(&'static str),
+    /// The config file couldn't be read or parsed
+    Config(PathBuf, String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Serial(e) => write!(f, "Serial port error: {}", e),
+            Error::ChannelClosed(name) => write!(f, "The {} channel was closed", name),
+            Error::Config(path, e) => write!(f, "Invalid config file {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serialport::Error> for Error {
+    fn from(e: serialport::Error) -> Self {
+        Error::Serial(e)
+    }
+}
+
+/// Make sure a panic never leaves the terminal in raw mode or on the alternate screen
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        app::restore_terminal();
+        default_hook(info);
+    }));
+}
@@ -0,0 +1,89 @@
+/// Turn escape sequences typed in the input line into the bytes they stand for.
+/// Supports `\r`, `\n`, `\t`, `\0`, `\e`, `\\` and `\xHH`, anything else is sent as typed.
+pub fn unescape(s: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+
+        match chars.peek() {
+            Some('r') => bytes.push(b'\r'),
+            Some('n') => bytes.push(b'\n'),
+            Some('t') => bytes.push(b'\t'),
+            Some('0') => bytes.push(0),
+            Some('e') => bytes.push(0x1b),
+            Some('\\') => bytes.push(b'\\'),
+            Some('x') => {
+                let hex: String = chars.clone().skip(1).take(2).collect();
+                match hex_byte(&hex) {
+                    Some(byte) if hex.len() == 2 => {
+                        bytes.push(byte);
+                        // Skip the x and the first digit, the second one is skipped below
+                        chars.next();
+                        chars.next();
+                    }
+                    _ => {
+                        bytes.push(b'\\');
+                        continue;
+                    }
+                }
+            }
+            _ => {
+                bytes.push(b'\\');
+                continue;
+            }
+        }
+        chars.next();
+    }
+
+    bytes
+}
+
+/// A byte of hex digits, `from_str_radix` alone would also take a sign like `+f`
+fn hex_byte(digits: &str) -> Option<u8> {
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u8::from_str_radix(digits, 16).ok()
+}
+
+/// Parse space separated hex bytes like `0d 0a ff`
+pub fn parse_hex(s: &str) -> Result<Vec<u8>, String> {
+    s.split_whitespace()
+        .map(|byte| hex_byte(byte.trim_start_matches("0x")).ok_or_else(|| format!("'{}' is not a hex byte", byte)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescapes_known_sequences() {
+        assert_eq!(unescape(r"a\r\n\t\0\e\\"), b"a\r\n\t\0\x1b\\");
+        assert_eq!(unescape(r"\x41\x0d"), b"A\r");
+    }
+
+    #[test]
+    fn keeps_unknown_sequences() {
+        assert_eq!(unescape(r"\q \x4 \x+f \xzz"), br"\q \x4 \x+f \xzz");
+    }
+
+    #[test]
+    fn parses_hex_bytes() {
+        assert_eq!(parse_hex("0d 0a ff 0x41 f"), Ok(vec![0x0d, 0x0a, 0xff, 0x41, 0x0f]));
+        assert_eq!(parse_hex(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn rejects_signs_and_invalid_bytes() {
+        for invalid in ["+f", "-1", "0x", "100", "zz"] {
+            assert!(parse_hex(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use regex::Regex;
+use serde::Serialize;
+
+// Deauther output isn't meant for machines, so this only picks out the lines worth forwarding
+lazy_static::lazy_static! {
+    static ref ERROR: Regex = Regex::new(r"^(ERROR|WARNING): *(.*)").unwrap();
+    static ref MAC: Regex = Regex::new(r"(?i)\b([0-9a-f]{2}(:[0-9a-f]{2}){5})\b").unwrap();
+    static ref RSSI: Regex = Regex::new(r"(?:^|\s)(-\d{2,3})(?:\s|$)").unwrap();
+    static ref CHANNEL: Regex = Regex::new(r"(?:^|\s)(1[0-4]|[1-9])(?:\s|$)").unwrap();
+    // Only the reports of the attack itself, e.g. `Starting deauth attack`, `Deauth attack: 3 targets, 4512 packets sent` or `Attack stopped`
+    static ref ATTACK: Regex = Regex::new(
+        r"(?i)^[>\s]*(?:(?:start\w*|running|stopp\w*) +(?:all +)?(?:(?:deauth|beacon|probe)\w* +)?attacks?|(?:deauth|beacon|probe)\w* +attack|attacks? +(?:start\w*|running|stopped|finished|done))\b"
+    )
+    .unwrap();
+}
+
+/// Something that happened on the device, parsed from its output
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Event {
+    /// A row of scan results, an access point or a station
+    ScanResult {
+        mac: String,
+        rssi: Option<i32>,
+        channel: Option<u8>,
+        line: String,
+    },
+    /// Attack started, stopped or reported progress
+    Attack { status: String },
+    /// The device reported an error or warning
+    Error { message: String },
+}
+
+/// Parse a line of deauther output, most lines aren't events
+pub fn parse(line: &str) -> Option<Event> {
+    let line = line.trim_end();
+
+    if let Some(caps) = ERROR.captures(line) {
+        return Some(Event::Error {
+            message: caps[2].to_string(),
+        });
+    }
+
+    if let Some(caps) = MAC.captures(line) {
+        // The MAC is taken out first so its digits aren't mistaken for a channel,
+        // same for the ID in the first column
+        let rest = MAC.replace_all(line, " ");
+        let columns = rest.trim_start().split_once(char::is_whitespace).map_or("", |(_, columns)| columns);
+        return Some(Event::ScanResult {
+            mac: caps[1].to_lowercase(),
+            rssi: RSSI.captures(columns).and_then(|caps| caps[1].parse().ok()),
+            channel: CHANNEL.captures(columns).and_then(|caps| caps[1].parse().ok()),
+            line: line.trim().to_string(),
+        });
+    }
+
+    if ATTACK.is_match(line) {
+        return Some(Event::Attack {
+            status: line.trim_start_matches('>').trim().to_string(),
+        });
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_attack(line: &str) -> bool {
+        matches!(parse(line), Some(Event::Attack { .. }))
+    }
+
+    #[test]
+    fn finds_attack_reports() {
+        assert!(is_attack("Starting deauth attack"));
+        assert!(is_attack("> Starting attacks"));
+        assert!(is_attack("Deauth attack: 3 targets, 120 pkts/s, 4512 packets sent"));
+        assert!(is_attack("Attack stopped"));
+        assert!(is_attack("Stopped all attacks"));
+    }
+
+    #[test]
+    fn ignores_other_output() {
+        assert!(!is_attack("Scanning for probe requests"));
+        assert!(!is_attack("Added beacon SSID \"attack\""));
+        assert!(!is_attack("attack [-b] [-d] [-da] [-p] [-t <timeout>]"));
+        assert!(!is_attack("Deauthenticating clients is illegal"));
+    }
+
+    #[test]
+    fn parses_scan_results() {
+        let event = parse("  0  aa:bb:cc:dd:ee:ff  -67  6  WPA2  home").unwrap();
+        assert_eq!(
+            event,
+            Event::ScanResult {
+                mac: "aa:bb:cc:dd:ee:ff".to_string(),
+                rssi: Some(-67),
+                channel: Some(6),
+                line: "0  aa:bb:cc:dd:ee:ff  -67  6  WPA2  home".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn parses_errors() {
+        assert_eq!(parse("ERROR: invalid command"), Some(Event::Error { message: "invalid command".to_string() }));
+    }
+}
@@ -0,0 +1,79 @@
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::timeout;
+use tokio_serial::SerialStream;
+
+use crate::cli;
+#[cfg(unix)]
+use crate::i18n;
+#[cfg(unix)]
+use crate::lockfile::{self, Lock};
+use crate::output::Preferences;
+use crate::serial::{self, LinkSettings};
+
+/// Take the lock file of the port like the monitor does, unless another program holds it
+#[cfg(unix)]
+fn lock(out: &Preferences, port: &str, force: bool) -> Result<Option<Lock>, String> {
+    match lockfile::acquire(port) {
+        Ok(lock) => Ok(lock),
+        Err(e) if force => {
+            out.println(&format!("WARNING: {}", i18n::format("locked_anyway", &[&e])));
+            Ok(None)
+        }
+        Err(e) => Err(i18n::format("locked", &[&e])),
+    }
+}
+
+/// Send the commands one after another and print the answer to each,
+/// which is complete once the device is silent for `--timeout`
+pub async fn run(out: &Preferences, port: &str, link: &LinkSettings, args: &cli::Exec, exclusive: bool) -> Result<(), String> {
+    let silence = Duration::from_millis(args.timeout);
+    let settle = Duration::from_millis(args.settle);
+
+    #[cfg(unix)]
+    let _lock = lock(out, port, args.force)?;
+    let settings = link.builder(port);
+    #[cfg(unix)]
+    let settings = settings.exclusive(exclusive);
+    // Windows always opens ports exclusively and has no lock files
+    #[cfg(not(unix))]
+    let _ = exclusive;
+    let mut stream = SerialStream::open(&settings).map_err(|e| serial::open_error(port, link, &e))?;
+
+    // Boot messages of boards that restart when the port opens aren't part of an answer
+    let mut buf = [0; 1024];
+    let _ = timeout(settle, async {
+        while let Ok(n) = stream.read(&mut buf).await {
+            if n == 0 {
+                break;
+            }
+        }
+    })
+    .await;
+
+    let mut pending = Vec::new();
+    for command in &args.commands {
+        stream
+            .write_all(format!("{}\r\n", command).as_bytes())
+            .await
+            .map_err(|e| format!("Couldn't send '{}': {}", command, e))?;
+        loop {
+            let n = match timeout(silence, stream.read(&mut buf)).await {
+                Err(_) => break,
+                Ok(Ok(0)) => return Err(format!("{} was closed", port)),
+                Ok(Ok(n)) => n,
+                Ok(Err(e)) => return Err(format!("Couldn't read from {}: {}", port, e)),
+            };
+            pending.extend_from_slice(&buf[..n]);
+            while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = pending.drain(..=end).collect();
+                out.println(String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n']));
+            }
+        }
+    }
+    // An unfinished line, like a prompt
+    if !pending.is_empty() {
+        out.println(String::from_utf8_lossy(&pending).trim_end());
+    }
+    Ok(())
+}
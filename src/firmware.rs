@@ -0,0 +1,372 @@
+use regex::Regex;
+use std::fmt;
+
+use crate::events::{self, Event};
+
+/// Sent after connecting, its output tells the firmware apart
+pub const PROBE_COMMAND: &str = "help";
+
+/// Lines to look through for a known firmware before giving up
+pub const PROBE_LINES: usize = 200;
+
+/// What to check about a command before it is sent
+#[derive(Debug)]
+pub struct Rule {
+    pub command: &'static str,
+    /// Arguments the command can't do without
+    pub min_args: usize,
+    /// Ask before sending if any of these arguments is given, always if empty, never if `None`
+    pub confirm: Option<&'static [&'static str]>,
+    /// What the command does, shown when asking
+    pub warning: &'static str,
+}
+
+/// Result of checking a command against the profile
+#[derive(Debug, Default)]
+pub struct Check {
+    /// Problems worth a warning, the command is sent anyway
+    pub warnings: Vec<String>,
+    /// Why the command has to be confirmed before it is sent
+    pub confirm: Option<&'static str>,
+}
+
+/// What Huhnitor knows about a firmware: how to recognize it, its commands and how to read its output
+pub trait Profile: Sync + fmt::Debug {
+    /// Name as used by `--firmware`
+    fn name(&self) -> &'static str;
+
+    /// Human readable name
+    fn title(&self) -> &'static str;
+
+    /// Whether the line, from the boot banner or the probe output, comes from this firmware
+    fn identify(&self, line: &str) -> bool;
+
+    /// Commands with a short description, used for completion
+    fn commands(&self) -> &'static [(&'static str, &'static str)];
+
+    /// Checks of commands with required arguments or destructive effects
+    fn rules(&self) -> &'static [Rule] {
+        &[]
+    }
+
+    /// Parse a line of output into an event
+    fn parse_event(&self, line: &str) -> Option<Event> {
+        events::parse(line)
+    }
+
+    /// Command that stops a running attack
+    fn stop_command(&self) -> &'static str {
+        "stop"
+    }
+
+    /// Bring a line into the shape the highlighter expects before it is shown
+    fn display(&self, line: String) -> String {
+        line
+    }
+
+    /// Name of the setting a command changes, for restoring it after a reset
+    fn setting(&self, _command: &str) -> Option<String> {
+        None
+    }
+
+    /// Command that sets the clock of the device, as a chrono format of the current time.
+    /// Neither the deauther nor Marauder have one, custom firmware can be given one in the config file.
+    fn time_command(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref DEAUTHER: Regex = Regex::new(r"(?i)esp8266 deauther|deauther v\d|^\s*chicken\b|^\s*attack \[-b\]").unwrap();
+    static ref MARAUDER: Regex = Regex::new(r"(?i)marauder|^\s*scanap\b|^\s*sniffbeacon\b").unwrap();
+    // Marauder echoes commands as `#scanap`
+    static ref MARAUDER_ECHO: Regex = Regex::new(r"^#(\S)").unwrap();
+    // Scan results look like `RSSI: -65 Ch: 6 BSSID: aa:bb:cc:dd:ee:ff ESSID: MyWifi`, older versions leave out `RSSI:`
+    static ref MARAUDER_AP: Regex = Regex::new(
+        r"(?i)^\s*(?:rssi:\s*)?(-\d{1,3})\s+ch:\s*(\d{1,3})\s+bssid:\s*([0-9a-f]{2}(?::[0-9a-f]{2}){5})"
+    ).unwrap();
+}
+
+/// The classic ESP8266 Deauther
+#[derive(Debug)]
+pub struct Deauther;
+
+impl Profile for Deauther {
+    fn name(&self) -> &'static str {
+        "deauther"
+    }
+
+    fn title(&self) -> &'static str {
+        "ESP8266 Deauther"
+    }
+
+    fn identify(&self, line: &str) -> bool {
+        DEAUTHER.is_match(line)
+    }
+
+    fn commands(&self) -> &'static [(&'static str, &'static str)] {
+        &[
+            ("help", "List all commands"),
+            ("scan", "Scan for access points and stations"),
+            ("show", "Show scan results, SSIDs, names or selections"),
+            ("select", "Select access points, stations or names"),
+            ("deselect", "Deselect access points, stations or names"),
+            ("add", "Add an SSID, name or access point"),
+            ("remove", "Remove SSIDs, names or scan results"),
+            ("attack", "Start deauth, beacon or probe attacks"),
+            ("stop", "Stop scans and attacks"),
+            ("sysinfo", "Show system information"),
+            ("clear", "Clear the serial output"),
+            ("format", "Format the SPIFFS"),
+            ("print", "Print a file"),
+            ("delete", "Delete a file"),
+            ("replace", "Replace a line of a file"),
+            ("copy", "Copy a file"),
+            ("rename", "Rename a file"),
+            ("run", "Run the commands in a file"),
+            ("write", "Append to a file"),
+            ("load", "Load SSIDs, names or settings from a file"),
+            ("save", "Save SSIDs, names or settings to a file"),
+            ("get", "Get a setting"),
+            ("set", "Change a setting"),
+            ("reset", "Reset the settings"),
+            ("enable", "Enable random SSIDs"),
+            ("disable", "Disable random SSIDs"),
+            ("startap", "Start the access point"),
+            ("stopap", "Stop the access point"),
+            ("send", "Send a single deauth, beacon, probe or custom packet"),
+            ("led", "Set the color of the LED"),
+            ("screen", "Turn the display on or off or change its mode"),
+            ("draw", "Draw the packet monitor as text"),
+            ("reboot", "Restart the device"),
+            ("info", "Show information about the project"),
+            ("chicken", "Print a chicken"),
+            ("//", "A comment, ignored by the device"),
+        ]
+    }
+
+    fn rules(&self) -> &'static [Rule] {
+        &[
+            Rule { command: "attack", min_args: 1, confirm: Some(&["-da", "-deauthall"]), warning: "This deauths every network in range" },
+            Rule { command: "select", min_args: 1, confirm: None, warning: "" },
+            Rule { command: "deselect", min_args: 1, confirm: None, warning: "" },
+            Rule { command: "add", min_args: 2, confirm: None, warning: "" },
+            Rule { command: "remove", min_args: 1, confirm: None, warning: "" },
+            Rule { command: "get", min_args: 1, confirm: None, warning: "" },
+            Rule { command: "set", min_args: 2, confirm: None, warning: "" },
+            Rule { command: "print", min_args: 1, confirm: None, warning: "" },
+            Rule { command: "replace", min_args: 3, confirm: None, warning: "" },
+            Rule { command: "copy", min_args: 2, confirm: None, warning: "" },
+            Rule { command: "rename", min_args: 2, confirm: None, warning: "" },
+            Rule { command: "run", min_args: 1, confirm: None, warning: "" },
+            Rule { command: "write", min_args: 2, confirm: None, warning: "" },
+            Rule { command: "send", min_args: 1, confirm: None, warning: "" },
+            Rule { command: "led", min_args: 1, confirm: None, warning: "" },
+            Rule { command: "screen", min_args: 1, confirm: None, warning: "" },
+            Rule { command: "delete", min_args: 1, confirm: Some(&[]), warning: "This deletes the file on the device" },
+            Rule { command: "format", min_args: 0, confirm: Some(&[]), warning: "This erases all files on the device" },
+            Rule { command: "reset", min_args: 0, confirm: Some(&[]), warning: "This resets all settings" },
+        ]
+    }
+
+    // `set <setting> <value>`
+    fn setting(&self, command: &str) -> Option<String> {
+        match command.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [set, name, _, ..] if set.eq_ignore_ascii_case("set") => Some(name.to_lowercase()),
+            _ => None,
+        }
+    }
+}
+
+/// The ESP32 Marauder
+#[derive(Debug)]
+pub struct Marauder;
+
+impl Profile for Marauder {
+    fn name(&self) -> &'static str {
+        "marauder"
+    }
+
+    fn title(&self) -> &'static str {
+        "ESP32 Marauder"
+    }
+
+    fn identify(&self, line: &str) -> bool {
+        MARAUDER.is_match(line)
+    }
+
+    fn commands(&self) -> &'static [(&'static str, &'static str)] {
+        &[
+            ("help", "List all commands"),
+            ("scanap", "Scan for access points"),
+            ("scansta", "Scan for stations"),
+            ("stopscan", "Stop scans, sniffers and attacks"),
+            ("list", "List access points, stations or SSIDs"),
+            ("select", "Select access points, stations or SSIDs"),
+            ("clearlist", "Clear access points, stations or SSIDs"),
+            ("ssid", "Add or generate SSIDs"),
+            ("attack", "Start deauth, beacon, probe or rickroll attacks"),
+            ("sniffbeacon", "Sniff beacon frames"),
+            ("sniffdeauth", "Sniff deauth frames"),
+            ("sniffprobe", "Sniff probe requests"),
+            ("sniffpmkid", "Sniff PMKIDs"),
+            ("sniffraw", "Sniff all frames"),
+            ("sniffpwn", "Sniff for Pwnagotchis"),
+            ("sniffesp", "Sniff for ESP devices"),
+            ("channel", "Show or set the channel"),
+            ("join", "Join a WiFi network"),
+            ("settings", "Show or change settings"),
+            ("ls", "List files on the SD card"),
+            ("led", "Control the LED"),
+            ("gpsdata", "Show GPS data"),
+            ("info", "Show device information"),
+            ("update", "Update the firmware"),
+            ("reboot", "Restart the device"),
+        ]
+    }
+
+    fn rules(&self) -> &'static [Rule] {
+        &[
+            Rule { command: "attack", min_args: 2, confirm: None, warning: "" },
+            Rule { command: "select", min_args: 2, confirm: None, warning: "" },
+            Rule { command: "clearlist", min_args: 1, confirm: None, warning: "" },
+            Rule { command: "ssid", min_args: 2, confirm: None, warning: "" },
+            Rule { command: "join", min_args: 2, confirm: None, warning: "" },
+            Rule { command: "update", min_args: 1, confirm: Some(&[]), warning: "This replaces the firmware" },
+        ]
+    }
+
+    fn stop_command(&self) -> &'static str {
+        "stopscan"
+    }
+
+    fn parse_event(&self, line: &str) -> Option<Event> {
+        match MARAUDER_AP.captures(line) {
+            Some(caps) => Some(Event::ScanResult {
+                mac: caps[3].to_lowercase(),
+                rssi: caps[1].parse().ok(),
+                channel: caps[2].parse().ok(),
+                line: line.trim().to_string(),
+            }),
+            None => events::parse(line),
+        }
+    }
+
+    fn display(&self, line: String) -> String {
+        MARAUDER_ECHO.replace(&line, "# $1").into_owned()
+    }
+
+    // `settings -s <setting> enable|disable` and `channel -s <channel>`
+    fn setting(&self, command: &str) -> Option<String> {
+        match command.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [settings, "-s", name, _, ..] if settings.eq_ignore_ascii_case("settings") => Some(name.to_lowercase()),
+            [channel, "-s", _, ..] if channel.eq_ignore_ascii_case("channel") => Some("channel".to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Any other firmware, output is shown as is and only common events are parsed
+#[derive(Debug)]
+pub struct Generic;
+
+impl Profile for Generic {
+    fn name(&self) -> &'static str {
+        "generic"
+    }
+
+    fn title(&self) -> &'static str {
+        "Generic"
+    }
+
+    fn identify(&self, _line: &str) -> bool {
+        false
+    }
+
+    fn commands(&self) -> &'static [(&'static str, &'static str)] {
+        &[]
+    }
+}
+
+/// Known profiles, tried in order when probing
+static PROFILES: [&dyn Profile; 3] = [&Deauther, &Marauder, &Generic];
+
+/// Names of all profiles, for error messages
+pub fn names() -> Vec<&'static str> {
+    PROFILES.iter().map(|profile| profile.name()).collect()
+}
+
+pub fn by_name(name: &str) -> Option<&'static dyn Profile> {
+    PROFILES.iter().copied().find(|profile| profile.name().eq_ignore_ascii_case(name))
+}
+
+/// Profile of the firmware that printed the line, if it gives it away
+pub fn identify(line: &str) -> Option<&'static dyn Profile> {
+    PROFILES.iter().copied().find(|profile| profile.identify(line))
+}
+
+/// Used until the firmware is known
+pub fn generic() -> &'static dyn Profile {
+    &Generic
+}
+
+/// Commands of the profile starting with the given word
+pub fn complete(profile: &dyn Profile, word: &str) -> Vec<&'static str> {
+    profile
+        .commands()
+        .iter()
+        .map(|(name, _)| *name)
+        .filter(|name| name.starts_with(word))
+        .collect()
+}
+
+/// Check a command typed for the device against the commands and rules of the profile
+pub fn check(profile: &dyn Profile, line: &str) -> Check {
+    let mut check = Check::default();
+    let mut words = line.split_whitespace();
+    let name = match words.next() {
+        Some(name) => name,
+        None => return check,
+    };
+    let args: Vec<&str> = words.collect();
+
+    let commands = profile.commands();
+    if !commands.is_empty() && !commands.iter().any(|(command, _)| command.eq_ignore_ascii_case(name)) {
+        check.warnings.push(format!("Unknown {} command '{}'", profile.title(), name));
+    }
+
+    if let Some(rule) = profile.rules().iter().find(|rule| rule.command.eq_ignore_ascii_case(name)) {
+        if args.len() < rule.min_args {
+            check.warnings.push(format!("'{}' expects at least {} argument(s)", rule.command, rule.min_args));
+        }
+        check.confirm = match rule.confirm {
+            Some([]) => Some(rule.warning),
+            Some(flags) if args.iter().any(|arg| flags.iter().any(|flag| flag.eq_ignore_ascii_case(arg))) => Some(rule.warning),
+            _ => None,
+        };
+    }
+    check
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_only_cover_known_commands() {
+        for profile in PROFILES.iter() {
+            for rule in profile.rules() {
+                assert!(profile.commands().iter().any(|(name, _)| *name == rule.command), "{}", rule.command);
+            }
+        }
+    }
+
+    #[test]
+    fn knows_the_deauther_file_commands() {
+        for line in ["copy a.txt b.txt", "run script.txt", "// note", "led 255 0 0", "send deauth"] {
+            assert!(check(&Deauther, line).warnings.is_empty(), "{}", line);
+        }
+        assert_eq!(check(&Deauther, "copy a.txt").warnings.len(), 1);
+        assert!(check(&Deauther, "format").confirm.is_some());
+    }
+}
@@ -0,0 +1,31 @@
+use std::io::ErrorKind;
+use std::process::Command;
+
+use crate::cli;
+
+/// Names esptool is installed as, by pip and by distributions
+const ESPTOOL: [&str; 2] = ["esptool", "esptool.py"];
+
+/// Write a firmware image with esptool, which knows the boot loaders of all the ESP chips
+pub fn run(port: &str, args: &cli::Flash) -> Result<(), String> {
+    if !args.image.is_file() {
+        return Err(format!("{} is not a file", args.image.display()));
+    }
+    let baud = args.flash_baud.to_string();
+    let mut esptool_args = vec!["--port", port, "--baud", baud.as_str(), "write_flash"];
+    if args.erase {
+        esptool_args.push("--erase-all");
+    }
+    let image = args.image.to_string_lossy();
+    esptool_args.extend([args.address.as_str(), &image]);
+
+    for name in ESPTOOL {
+        match Command::new(name).args(&esptool_args).status() {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => return Err(format!("{} ended with {}", name, status)),
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("Couldn't run {}: {}", name, e)),
+        }
+    }
+    Err("esptool is needed for flashing, install it with pip install esptool".to_string())
+}
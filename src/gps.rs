@@ -0,0 +1,218 @@
+use serde_json::Value;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+
+/// Address of a local gpsd
+pub const GPSD: &str = "127.0.0.1:2947";
+
+/// Baud rate of serial GPS receivers
+const NMEA_BAUD: u32 = 9600;
+
+/// A position this old is treated as lost, e.g. in a tunnel or after unplugging the receiver
+const STALE: Duration = Duration::from_secs(10);
+
+/// Wait before connecting again after the GPS went away
+const RETRY: Duration = Duration::from_secs(5);
+
+/// Where the position comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// A gpsd at this address
+    Gpsd(String),
+    /// A receiver on a serial port that prints NMEA sentences
+    Nmea(PathBuf),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Gpsd(addr) => write!(f, "gpsd at {}", addr),
+            Source::Nmea(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// `gpsd`, `host:port` of a gpsd, or the serial port of a receiver
+pub fn parse_source(text: &str) -> Result<Source, String> {
+    let text = text.trim();
+    let serial = text.starts_with('/') || text.to_uppercase().starts_with("COM");
+    match text {
+        "" => Err("Expected gpsd, host:port or a serial port".to_string()),
+        "gpsd" => Ok(Source::Gpsd(GPSD.to_string())),
+        _ if serial => Ok(Source::Nmea(PathBuf::from(text))),
+        _ if text.contains(':') => Ok(Source::Gpsd(text.to_string())),
+        _ => Err(format!("'{}' is neither gpsd, host:port nor a serial port", text)),
+    }
+}
+
+/// A position in decimal degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fix {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// `ddmm.mmmm` of NMEA in decimal degrees, negative in the south and west
+fn degrees(value: &str, hemisphere: &str) -> Option<f64> {
+    let value: f64 = value.parse().ok()?;
+    let degrees = (value / 100.0).trunc();
+    let degrees = degrees + (value - degrees * 100.0) / 60.0;
+    match hemisphere {
+        "N" | "E" => Some(degrees),
+        "S" | "W" => Some(-degrees),
+        _ => None,
+    }
+}
+
+/// Position of a GGA or RMC sentence from any talker, e.g. `$GPGGA` or `$GNRMC`, if it has a fix
+pub fn parse_nmea(line: &str) -> Option<Fix> {
+    let line = line.trim().split('*').next()?;
+    let fields: Vec<&str> = line.split(',').collect();
+    let kind = fields.first()?.strip_prefix('$')?.get(2..)?;
+    let (lat, lon) = match kind {
+        // Fix quality 0 means none
+        "GGA" if fields.len() > 6 && !matches!(fields[6], "" | "0") => (2, 4),
+        // Status A means valid
+        "RMC" if fields.len() > 6 && fields[2] == "A" => (3, 5),
+        _ => return None,
+    };
+    Some(Fix { lat: degrees(fields[lat], fields[lat + 1])?, lon: degrees(fields[lon], fields[lon + 1])? })
+}
+
+/// Position of a gpsd TPV report, if it has a fix
+pub fn parse_gpsd(line: &str) -> Option<Fix> {
+    let report: Value = serde_json::from_str(line).ok()?;
+    // Mode 2 is a 2D fix, 3 a 3D fix
+    if report["class"] != "TPV" || report["mode"].as_u64().unwrap_or(0) < 2 {
+        return None;
+    }
+    Some(Fix { lat: report["lat"].as_f64()?, lon: report["lon"].as_f64()? })
+}
+
+#[derive(Default)]
+struct State {
+    fix: Option<(Fix, Instant)>,
+    /// Error since the last look, shown once
+    error: Option<String>,
+    /// Last error shown, retries failing the same way stay quiet until a fix came in
+    reported: Option<String>,
+}
+
+impl State {
+    /// Note an error, unless it is the one shown last
+    fn fail(&mut self, error: String) {
+        if self.reported.as_ref() != Some(&error) {
+            self.reported = Some(error.clone());
+            self.error = Some(error);
+        }
+    }
+}
+
+/// Follows the position reported by a GPS in the background
+pub struct Gps {
+    state: Arc<Mutex<State>>,
+    task: JoinHandle<()>,
+}
+
+impl Gps {
+    pub fn start(source: Source) -> Self {
+        let state = Arc::new(Mutex::new(State::default()));
+        let shared = state.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                let result = match &source {
+                    Source::Gpsd(addr) => gpsd(addr, &shared).await,
+                    Source::Nmea(path) => nmea(path, &shared).await,
+                };
+                let error = match result {
+                    Ok(()) => format!("{} closed", source),
+                    Err(e) => format!("{}: {}", source, e),
+                };
+                if let Ok(mut state) = shared.lock() {
+                    state.fail(error);
+                }
+                tokio::time::sleep(RETRY).await;
+            }
+        });
+        Self { state, task }
+    }
+
+    /// The current position, `None` without a recent fix
+    pub fn fix(&self) -> Option<Fix> {
+        let state = self.state.lock().ok()?;
+        state.fix.filter(|(_, at)| at.elapsed() < STALE).map(|(fix, _)| fix)
+    }
+
+    /// What went wrong since the last call
+    pub fn take_error(&self) -> Option<String> {
+        self.state.lock().ok()?.error.take()
+    }
+}
+
+impl Drop for Gps {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Read positions line by line until the source closes
+async fn follow(reader: impl AsyncRead + Unpin, parse: fn(&str) -> Option<Fix>, state: &Mutex<State>) -> io::Result<()> {
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        if let (Some(fix), Ok(mut state)) = (parse(&line), state.lock()) {
+            state.fix = Some((fix, Instant::now()));
+            state.reported = None;
+        }
+    }
+    Ok(())
+}
+
+async fn gpsd(addr: &str, state: &Mutex<State>) -> io::Result<()> {
+    let mut stream = TcpStream::connect(addr).await?;
+    stream.write_all(b"?WATCH={\"enable\":true,\"json\":true}\n").await?;
+    follow(stream, parse_gpsd, state).await
+}
+
+async fn nmea(path: &std::path::Path, state: &Mutex<State>) -> io::Result<()> {
+    let port = tokio_serial::SerialStream::open(&tokio_serial::new(path.to_string_lossy(), NMEA_BAUD))?;
+    follow(port, parse_nmea, state).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nmea_positions() {
+        let fix = parse_nmea("$GNRMC,123519,A,4807.038,N,01131.000,W,022.4,084.4,230394,003.1,W*6A").unwrap();
+        assert!((fix.lat - 48.1173).abs() < 1e-4);
+        assert!((fix.lon + 11.5166).abs() < 1e-4);
+        assert_eq!(parse_nmea("$GPGGA,123519,4807.038,N,01131.000,E,0,08,0.9,545.4,M,46.9,M,,*47"), None);
+        assert_eq!(parse_nmea("$GPRMC,123519,V,4807.038,N,01131.000,E"), None);
+        assert_eq!(parse_nmea("garbage"), None);
+    }
+
+    #[test]
+    fn parses_gpsd_reports() {
+        assert_eq!(parse_gpsd(r#"{"class":"TPV","mode":3,"lat":1.5,"lon":-2.5}"#), Some(Fix { lat: 1.5, lon: -2.5 }));
+        assert_eq!(parse_gpsd(r#"{"class":"TPV","mode":1}"#), None);
+        assert_eq!(parse_gpsd(r#"{"class":"SKY"}"#), None);
+    }
+
+    #[test]
+    fn reports_the_same_error_once() {
+        let mut state = State::default();
+        state.fail("gpsd closed".to_string());
+        assert_eq!(state.error.take().as_deref(), Some("gpsd closed"));
+        state.fail("gpsd closed".to_string());
+        assert_eq!(state.error, None);
+        state.fail("gpsd: refused".to_string());
+        assert_eq!(state.error.as_deref(), Some("gpsd: refused"));
+    }
+}
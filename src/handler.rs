@@ -1,35 +1,117 @@
-use std::fs::File;
-use std::io::prelude::*;
-
-use crate::error;
-
-pub fn handle(command: String) -> String {
-    let words = command.split(' ').collect::<Vec<&str>>();
-    let len = words.len();
-    if let Some(key) = words.get(1) {
-        match key.to_uppercase().trim() {
-            "READ" => {
-                if len > 2 {
-                    let mut out = String::new();
-                    if let Ok(mut file) = File::open(words[2].trim()) {
-                        if file.read_to_string(&mut out).is_err() {
-                            error!(format!("Coudldn't read file: '{}'", words[1]));
-                        }
-                    } else {
-                        error!(format!("Couldn't open file: '{}'", words[2].trim()));
-                    }
-
-                    if !out.ends_with('\n') { out += "\n" }
-                    return out;
-                } else {
-                    println!("Insufficient arguments");
-                    println!("Command format: read [filename]");
-                }
-            }
-
-            _ => error!("Command not found"),
+use std::path::PathBuf;
+
+/// Lines starting with this word are meta commands, carried out by the Huhnitor with the open port
+pub const PREFIX: &str = "huhn";
+
+/// Usage and description of the meta commands, listed by `huhn help`
+pub const HELP: [(&str, &str); 7] = [
+    ("read <file>", "Send the content of a file"),
+    ("reset", "Reset the board with the DTR and RTS lines"),
+    ("reconnect", "Close the port and open it again"),
+    ("log [<file> [name]|off]", "Show where the session is logged, or log to another file under a name"),
+    ("baud [rate]", "Show or change the baud rate of the port"),
+    ("info", "Show the port, firmware and traffic of the session"),
+    ("help", "List the meta commands"),
+];
+
+/// What to do with the session log
+#[derive(Debug, PartialEq)]
+pub enum LogAction {
+    Show,
+    /// Log to a file, with the name of the session for its metadata
+    Start(PathBuf, Option<String>),
+    Stop,
+}
+
+/// Meta commands, typed as `huhn <command> [args]`
+#[derive(Debug, PartialEq)]
+pub enum Meta {
+    /// Send a file to the device
+    Read(PathBuf),
+    /// Pull the reset line of the board like esptool does
+    Reset,
+    Reconnect,
+    Log(LogAction),
+    /// Change the baud rate, or show it with `None`
+    Baud(Option<u32>),
+    Info,
+    Help,
+}
+
+/// Whether a line is a meta command
+pub fn is_meta(line: &str) -> bool {
+    line.split_whitespace().next().is_some_and(|word| word.eq_ignore_ascii_case(PREFIX))
+}
+
+/// Parse a meta command.
+/// Returns `None` if the line isn't a meta command at all.
+pub fn parse(line: &str) -> Option<Result<Meta, String>> {
+    if !is_meta(line) {
+        return None;
+    }
+    let mut words = line.split_whitespace().skip(1);
+    let name = words.next().unwrap_or("help").to_lowercase();
+    let args: Vec<&str> = words.collect();
+
+    let meta = match (name.as_str(), args.as_slice()) {
+        ("read", [file]) => Ok(Meta::Read(PathBuf::from(file))),
+        ("read", _) => Err(format!("Usage: {} read <file>", PREFIX)),
+        ("reset", []) => Ok(Meta::Reset),
+        ("reconnect", []) => Ok(Meta::Reconnect),
+        ("log", []) => Ok(Meta::Log(LogAction::Show)),
+        ("log", [off]) if off.eq_ignore_ascii_case("off") => Ok(Meta::Log(LogAction::Stop)),
+        ("log", [file, name @ ..]) => {
+            let name = Some(name.join(" ")).filter(|name| !name.is_empty());
+            Ok(Meta::Log(LogAction::Start(PathBuf::from(file), name)))
         }
+        ("baud", []) => Ok(Meta::Baud(None)),
+        ("baud", [rate]) => rate
+            .parse()
+            .map(|rate| Meta::Baud(Some(rate)))
+            .map_err(|_| format!("Usage: {} baud [rate]", PREFIX)),
+        ("info", []) => Ok(Meta::Info),
+        ("help", []) => Ok(Meta::Help),
+        _ => Err(format!("Unknown meta command '{} {}', '{} help' lists them", PREFIX, name, PREFIX)),
+    };
+    Some(meta)
+}
+
+/// Lines of `huhn help`
+pub fn help() -> Vec<String> {
+    HELP.iter()
+        .map(|(usage, description)| format!("> {} {:<36} {}", PREFIX, usage, description))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaves_device_commands_alone() {
+        assert!(parse("scan aps").is_none());
+        assert!(parse("huhnitor").is_none());
+        assert!(parse("show huhn").is_none());
+    }
+
+    #[test]
+    fn parses_meta_commands() {
+        assert_eq!(parse("huhn"), Some(Ok(Meta::Help)));
+        assert_eq!(parse("HUHN Reset"), Some(Ok(Meta::Reset)));
+        assert_eq!(parse("huhn read cmds.txt"), Some(Ok(Meta::Read(PathBuf::from("cmds.txt")))));
+        assert_eq!(parse("huhn baud 9600"), Some(Ok(Meta::Baud(Some(9600)))));
+        assert_eq!(parse("huhn log off"), Some(Ok(Meta::Log(LogAction::Stop))));
+        assert_eq!(
+            parse("huhn log survey.log roof top"),
+            Some(Ok(Meta::Log(LogAction::Start(PathBuf::from("survey.log"), Some("roof top".to_string())))))
+        );
     }
 
-    String::new()
+    #[test]
+    fn rejects_wrong_arguments() {
+        assert!(matches!(parse("huhn read"), Some(Err(_))));
+        assert!(matches!(parse("huhn baud fast"), Some(Err(_))));
+        assert!(matches!(parse("huhn reset now"), Some(Err(_))));
+        assert!(matches!(parse("huhn fly"), Some(Err(_))));
+    }
 }
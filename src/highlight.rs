@@ -0,0 +1,93 @@
+use regex::RegexSet;
+
+/// Number of highlight categories, one per rule
+pub const COUNT: usize = 9;
+
+/// Category of error and warning lines
+pub const ERROR: usize = 5;
+
+/// A highlight rule, listed in the legend
+pub struct Rule {
+    pub name: &'static str,
+    pub pattern: &'static str,
+    /// Line the rule matches
+    pub sample: &'static str,
+}
+
+/// The first matching rule picks the category of a line
+// Rust Regex can be tested here: https://rustexp.lpil.uk/
+pub const RULES: [Rule; COUNT] = [
+    Rule { name: "chicken", pattern: r"^(\x60|\.|:|/|-|\+|o|s|h|d|y| ){50,}", sample: "ASCII Chicken" },
+    Rule { name: "comment", pattern: r"^# ", sample: "# command" },
+    Rule { name: "divider", pattern: r"(?m)^\s*(-|=|#)+\s*$", sample: "================" },
+    Rule { name: "headline", pattern: r"^\[ =+ ?.* ?=+ \]", sample: "[ ===== Headline ====== ]" },
+    Rule { name: "info", pattern: r"^> \w+", sample: "> Finished job" },
+    Rule { name: "error", pattern: r"^(ERROR)|(WARNING): ", sample: "ERROR: something went wrong :(" },
+    Rule { name: "value", pattern: r"^.*: +.*", sample: "-arg: value" },
+    Rule { name: "bracket", pattern: r"^\[.*\]", sample: "[default=something]" },
+    Rule { name: "usage", pattern: r"(?m)^\S+( \[?-\S*( <\S*>)?\]?)*\s*$", sample: "command [-arg <value>] [-flag]" },
+];
+
+// Statically compile regex to avoid repetetive compiling
+lazy_static::lazy_static! {
+    static ref REGSET: RegexSet = RegexSet::new(RULES.iter().map(|rule| rule.pattern)).unwrap();
+}
+
+/// Index of the first highlight rule matching the line, if any
+pub fn classify(s: &str) -> Option<usize> {
+    REGSET.matches(s).into_iter().next()
+}
+
+/// Parts of a command typed into the input line
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Token {
+    /// Command the firmware knows
+    Command,
+    /// Command the firmware doesn't know
+    Unknown,
+    /// `-flag`
+    Flag,
+    /// `<value>` or `[value]` left over from a usage line
+    Placeholder,
+    /// Anything else, including whitespace
+    Text,
+}
+
+/// Split the input line into highlighted parts as it is typed. `known` tells whether a command exists,
+/// `None` if the commands of the firmware aren't known. Quoted arguments stay in one part.
+pub fn tokenize(line: &str, known: impl Fn(&str) -> Option<bool>) -> Vec<(&str, Token)> {
+    let mut tokens = Vec::new();
+    let mut rest = line;
+    let mut first = true;
+    while !rest.is_empty() {
+        let space = rest.len() - rest.trim_start().len();
+        if space > 0 {
+            tokens.push((&rest[..space], Token::Text));
+            rest = &rest[space..];
+            continue;
+        }
+
+        let end = match rest.strip_prefix('"') {
+            Some(quoted) => quoted.find('"').map_or(rest.len(), |i| i + 2),
+            None => rest.find(char::is_whitespace).unwrap_or(rest.len()),
+        };
+        let (word, tail) = rest.split_at(end);
+        let token = if first {
+            match known(word) {
+                Some(true) => Token::Command,
+                Some(false) => Token::Unknown,
+                None => Token::Text,
+            }
+        } else if word.strip_prefix('-').is_some_and(|flag| flag.starts_with(char::is_alphabetic)) {
+            Token::Flag
+        } else if (word.starts_with('<') && word.ends_with('>')) || (word.starts_with('[') && word.ends_with(']')) {
+            Token::Placeholder
+        } else {
+            Token::Text
+        };
+        tokens.push((word, token));
+        first = false;
+        rest = tail;
+    }
+    tokens
+}
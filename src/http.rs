@@ -0,0 +1,166 @@
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::channel;
+use crate::i18n;
+use crate::output;
+use crate::stats::Stats;
+use crate::status::{self, SharedStatus};
+
+/// Lines of output kept for `GET /output`
+const RECENT_LINES: usize = 1000;
+
+/// Everything the request handlers can see
+#[derive(Clone)]
+struct Shared {
+    recent: Arc<Mutex<VecDeque<String>>>,
+    status: SharedStatus,
+    stats: Arc<Stats>,
+    input_tx: UnboundedSender<Vec<u8>>,
+}
+
+/// Embedded HTTP server to control the session remotely
+pub struct Server {
+    recent: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl Server {
+    /// Start serving, commands posted to the server are sent on `input_tx`
+    pub async fn bind(
+        addr: SocketAddr,
+        status: SharedStatus,
+        stats: Arc<Stats>,
+        input_tx: UnboundedSender<Vec<u8>>,
+        notify_tx: &channel::Sender,
+    ) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let _ = notify_tx.notify(format!("> {}", i18n::format("http_serving", &[&listener.local_addr()?])));
+
+        let recent = Arc::new(Mutex::new(VecDeque::with_capacity(RECENT_LINES)));
+        let shared = Shared {
+            recent: recent.clone(),
+            status,
+            stats,
+            input_tx,
+        };
+
+        let app = Router::new()
+            .route("/status", get(get_status))
+            .route("/output", get(get_output))
+            .route("/command", post(post_command))
+            .route("/metrics", get(get_metrics))
+            .with_state(shared);
+
+        let notify_tx = notify_tx.downgrade();
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                output::notify(&notify_tx, format!("ERROR: {}", i18n::format("http_stopped", &[&e])));
+            }
+        });
+
+        Ok(Self { recent })
+    }
+
+    /// Remember a line of output for `GET /output`
+    pub fn push(&self, line: &str) {
+        if let Ok(mut recent) = self.recent.lock() {
+            if recent.len() == RECENT_LINES {
+                recent.pop_front();
+            }
+            recent.push_back(line.trim_end().to_string());
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct StatusResponse {
+    connected: bool,
+    port: Option<String>,
+    settings: String,
+    firmware: Option<&'static str>,
+    dry_run: bool,
+    rx_bytes: u64,
+    tx_bytes: u64,
+    errors: u64,
+    uptime_secs: u64,
+}
+
+async fn get_status(State(shared): State<Shared>) -> Json<StatusResponse> {
+    let status = status::get(&shared.status);
+    Json(StatusResponse {
+        connected: status.port.is_some(),
+        port: status.port,
+        settings: status.link.to_string(),
+        firmware: status.firmware.map(|profile| profile.name()),
+        dry_run: status.dry_run,
+        rx_bytes: shared.stats.rx_bytes(),
+        tx_bytes: shared.stats.tx_bytes(),
+        errors: shared.stats.errors(),
+        uptime_secs: shared.stats.elapsed().as_secs(),
+    })
+}
+
+#[derive(Deserialize)]
+struct OutputQuery {
+    /// Only the last n lines
+    lines: Option<usize>,
+}
+
+async fn get_output(State(shared): State<Shared>, Query(query): Query<OutputQuery>) -> Json<Vec<String>> {
+    let recent = match shared.recent.lock() {
+        Ok(recent) => recent,
+        Err(_) => return Json(Vec::new()),
+    };
+    let skip = recent.len().saturating_sub(query.lines.unwrap_or(RECENT_LINES));
+    Json(recent.iter().skip(skip).cloned().collect())
+}
+
+#[derive(Deserialize)]
+struct CommandRequest {
+    command: String,
+}
+
+async fn post_command(State(shared): State<Shared>, Json(request): Json<CommandRequest>) -> StatusCode {
+    if status::get(&shared.status).port.is_none() {
+        return StatusCode::SERVICE_UNAVAILABLE;
+    }
+
+    match shared.input_tx.send(format!("{}\r\n", request.command).into_bytes()) {
+        Ok(()) => StatusCode::ACCEPTED,
+        Err(_) => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+/// Prometheus text format
+async fn get_metrics(State(shared): State<Shared>) -> String {
+    let stats = &shared.stats;
+    let events = stats.events();
+    let connected = status::get(&shared.status).port.is_some();
+    let metrics: [(&str, &str, &str, f64); 7] = [
+        ("huhnitor_rx_bytes_total", "counter", "Bytes received from the device", stats.rx_bytes() as f64),
+        ("huhnitor_tx_bytes_total", "counter", "Bytes sent to the device", stats.tx_bytes() as f64),
+        ("huhnitor_errors_total", "counter", "Errors reported by the device or the Huhnitor", stats.errors() as f64),
+        ("huhnitor_connects_total", "counter", "Successful connections, reconnects included", stats.connects() as f64),
+        ("huhnitor_events_total", "counter", "Parsed scan results, attack updates and errors", events as f64),
+        ("huhnitor_events_per_second", "gauge", "Parsed events per second within the last minute", stats.events_per_second()),
+        ("huhnitor_connected", "gauge", "Whether a port is open", if connected { 1.0 } else { 0.0 }),
+    ];
+
+    let mut text = String::new();
+    for (name, kind, help, value) in metrics.iter() {
+        let _ = writeln!(text, "# HELP {} {}", name, help);
+        let _ = writeln!(text, "# TYPE {} {}", name, kind);
+        let _ = writeln!(text, "{} {}", name, value);
+    }
+    text
+}
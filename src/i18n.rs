@@ -0,0 +1,428 @@
+use std::env;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Language of the user interface
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(u8)]
+pub enum Locale {
+    En,
+    De,
+}
+
+impl Locale {
+    /// Language of the environment, English if it isn't translated
+    pub fn detect() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(Locale::En)
+    }
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    /// Accepts a language code or a locale like `de_DE.UTF-8`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let language = s.split(['_', '-', '.', '@']).next().unwrap_or_default();
+        match language.to_lowercase().as_str() {
+            "en" | "c" | "posix" => Ok(Locale::En),
+            "de" => Ok(Locale::De),
+            _ => Err(format!("Unknown language '{}', expected en or de", s)),
+        }
+    }
+}
+
+static LOCALE: AtomicU8 = AtomicU8::new(Locale::En as u8);
+
+/// Switch the language, before the UI is built
+pub fn set(locale: Locale) {
+    LOCALE.store(locale as u8, Ordering::Relaxed);
+}
+
+pub fn current() -> Locale {
+    match LOCALE.load(Ordering::Relaxed) {
+        x if x == Locale::De as u8 => Locale::De,
+        _ => Locale::En,
+    }
+}
+
+/// Key, English and German text. `{}` is replaced by the arguments in order.
+/// Prefixes like "> " and "ERROR: " select the highlighting and stay outside the catalog.
+const CATALOG: &[(&str, &str, &str)] = &[
+    // Prompts
+    ("quit_prompt", "Quit Huhnitor? [y/N]", "Huhnitor beenden? [j/N]"),
+    ("send_prompt", "{}. Send '{}'? [y/N]", "{}. '{}' senden? [j/N]"),
+    ("paste_prompt", "Paste {} into the input line? [y/N]", "{} in die Eingabezeile einfügen? [j/N]"),
+    // Ports and drivers
+    ("no_port", "No serial port found", "Kein serieller Port gefunden"),
+    (
+        "no_port_hint",
+        "Make sure the USB connection works and necessary drivers are installed:",
+        "Stelle sicher, dass die USB-Verbindung funktioniert und die nötigen Treiber installiert sind:",
+    ),
+    ("connected", "Connected to {} \\o/", "Verbunden mit {} \\o/"),
+    ("pick_port", "Type {}connect [ID or name] to pick a port", "Tippe {}connect [ID oder Name], um einen Port zu wählen"),
+    (
+        "no_adapter",
+        "No known USB serial adapter found, make sure the cable can transmit data",
+        "Kein bekannter USB-Seriell-Adapter gefunden, stelle sicher, dass das Kabel Daten übertragen kann",
+    ),
+    ("drivers", "Drivers for the common chips:", "Treiber für die gängigen Chips:"),
+    ("driver_builtin", "{}: included in {}", "{}: in {} enthalten"),
+    ("more_help", "More help: {}", "Mehr Hilfe: {}"),
+    ("adapter_builtin", "Found a {} adapter, its driver is included in {}", "{}-Adapter gefunden, der Treiber ist in {} enthalten"),
+    ("adapter_opening", "Found a {} adapter, opening \"{}\"...", "{}-Adapter gefunden, öffne \"{}\"..."),
+    ("no_browser", "Couldn't open a browser, download the driver from the URL above", "Konnte keinen Browser öffnen, lade den Treiber über die URL oben herunter"),
+    // Banner and summary
+    ("version", "Huhnitor Version {}", "Huhnitor Version {}"),
+    ("summary", "Session Summary", "Zusammenfassung der Sitzung"),
+    ("duration", "Duration: {}", "Dauer: {}"),
+    ("received", "Received: {}", "Empfangen: {}"),
+    ("sent", "Sent: {}", "Gesendet: {}"),
+    ("errors", "Errors: {}", "Fehler: {}"),
+    ("log_file", "Log file: {}", "Logdatei: {}"),
+    ("goodbye", "Thanks for using Huhnitor", "Danke, dass du Huhnitor benutzt"),
+    // Session
+    ("save_failed", "Couldn't save the session, autosave stopped: {}", "Konnte die Sitzung nicht speichern, automatisches Speichern beendet: {}"),
+    ("scan_diff", "Scan diff, {} results before, {} now", "Scan-Vergleich, vorher {} Ergebnisse, jetzt {}"),
+    ("scan_diff_waiting", "Scan diff, run at least two scans", "Scan-Vergleich, starte mindestens zwei Scans"),
+    ("scan_diff_keys", "[+] new  [-] gone  [~] RSSI  [Esc] close", "[+] neu  [-] weg  [~] RSSI  [Esc] schließen"),
+    ("two_scans", "Run at least two scans to compare them", "Starte mindestens zwei Scans, um sie zu vergleichen"),
+    ("nothing_changed", "Nothing changed", "Nichts hat sich geändert"),
+    ("line", "Line", "Zeile"),
+    ("exported_scan", "Wrote {} scan results to {}", "{} Scan-Ergebnisse in {} geschrieben"),
+    ("export_failed", "Couldn't write {}: {}", "Konnte {} nicht schreiben: {}"),
+    ("export_refused", "Not exporting, the redaction rules are invalid: {}", "Kein Export, die Schwärzungsregeln sind ungültig: {}"),
+    ("gps_failed", "GPS lost, {}", "GPS verloren, {}"),
+    ("raw_on", "Raw mode, keys go to the device right away, {} leaves", "Raw-Modus, Tasten gehen direkt ans Gerät, {} beendet ihn"),
+    ("raw_off", "Left raw mode", "Raw-Modus beendet"),
+    ("raw_plain", "Raw mode needs the terminal UI", "Der Raw-Modus braucht die Terminal-Oberfläche"),
+    ("not_attached", "Not attached to a daemon, start the session with huhnitor daemon to detach from it", "Nicht mit einem Daemon verbunden, starte die Sitzung mit huhnitor daemon, um dich von ihr zu trennen"),
+    ("presenter_plain", "Presenter mode needs the full terminal UI, not --inline or --accessible", "Der Präsentationsmodus braucht die volle Terminal-Oberfläche, nicht --inline oder --accessible"),
+    ("pipe_started", "Piping received lines into {}", "Empfangene Zeilen gehen an {}"),
+    ("pipe_stopped", "Stopped piping into {}", "Keine Zeilen mehr an {}"),
+    ("pipe_failed", "Couldn't start {}: {}", "Konnte {} nicht starten: {}"),
+    ("no_pipe", "Nothing is piped", "Es läuft keine Pipe"),
+    ("editor_failed", "Couldn't edit the commands: {}", "Konnte die Befehle nicht bearbeiten: {}"),
+    ("bell_muted", "Bell muted", "Glocke stumm"),
+    ("bell_unmuted", "Bell on", "Glocke an"),
+    ("shell_failed", "Couldn't run the shell: {}", "Konnte die Shell nicht starten: {}"),
+    ("shell_exit", "The shell command ended with {}", "Der Shell-Befehl endete mit {}"),
+    ("database_failed", "Couldn't open the scan database {}: {}", "Konnte die Scan-Datenbank {} nicht öffnen: {}"),
+    ("no_database", "The scan database is off, turn it on with record-scans = true in the config file", "Die Scan-Datenbank ist aus, record-scans = true in der Konfigurationsdatei schaltet sie ein"),
+    ("restore_failed", "Couldn't restore the last session: {}", "Konnte die letzte Sitzung nicht wiederherstellen: {}"),
+    ("restored", "Restored {} lines of the session{} from {} ago", "{} Zeilen der Sitzung{} von vor {} wiederhergestellt"),
+    ("restored_port", " on {}", " an {}"),
+    ("settings_unchanged", "No preference changed, nothing to save", "Keine Einstellung geändert, nichts zu speichern"),
+    ("settings_saved", "Saved the settings to {}", "Einstellungen in {} gespeichert"),
+    ("settings_failed", "Couldn't save the settings to {}: {}", "Konnte die Einstellungen nicht in {} speichern: {}"),
+    ("no_config_dir", "There is no config directory to save the settings to", "Es gibt kein Konfigurationsverzeichnis für die Einstellungen"),
+    // Messages box
+    ("help_hint", "Type {}help to list the commands", "Tippe {}help, um die Befehle aufzulisten"),
+    ("gap", "{} without output", "{} ohne Ausgabe"),
+    ("line_wrap", "Line wrap: {}", "Zeilenumbruch: {}"),
+    ("line_numbers", "Line numbers: {}", "Zeilennummern: {}"),
+    ("presenter", "Presenter mode: {}", "Präsentationsmodus: {}"),
+    ("no_line", "There is no line {} in the Messages box, it has lines {} to {}", "Es gibt keine Zeile {} im Nachrichtenfeld, es enthält die Zeilen {} bis {}"),
+    ("goto_inline", "The lines are in the scrollback of the terminal, scroll there instead", "Die Zeilen sind im Verlauf des Terminals, scrolle dort"),
+    ("echo", "Echo: {}", "Echo: {}"),
+    ("not_sent", "Not sent: {}", "Nicht gesendet: {}"),
+    ("not_sent_mark", " (not sent)", " (nicht gesendet)"),
+    ("stop_failed", "Couldn't stop!", "Konnte nicht stoppen!"),
+    ("on", "on", "an"),
+    ("off", "off", "aus"),
+    // Boxes and status bar
+    ("messages", "Messages", "Nachrichten"),
+    ("input", "Input", "Eingabe"),
+    ("raw", "Raw, {} leaves", "Raw, {} beendet"),
+    ("new_line", " {} new line \u{2193} [End] ", " {} neue Zeile \u{2193} [Ende] "),
+    ("new_lines", " {} new lines \u{2193} [End] ", " {} neue Zeilen \u{2193} [Ende] "),
+    ("not_connected", "Not connected", "Nicht verbunden"),
+    ("dry_run", "dry run", "Probelauf"),
+    ("stale", "stale", "veraltet"),
+    ("broadcast", "broadcast", "Broadcast"),
+    ("skip_broadcasts", "no broadcasts", "keine Broadcasts"),
+    ("muted", "muted", "stumm"),
+    ("reset", "{} reset", "{} Reset"),
+    ("gps", "GPS", "GPS"),
+    ("pipe", "Pipe: {}", "Pipe: {}"),
+    ("pipe_ended", "Pipe: {}, ended with {}", "Pipe: {}, beendet mit {}"),
+    ("no_fix", "no GPS fix", "kein GPS-Fix"),
+    // Countdowns
+    ("timers", "Timers", "Timer"),
+    ("timer_left", "{}: {} left", "{}: noch {}"),
+    ("timer_done", "Finished: {} after {}", "Fertig: {} nach {}"),
+    ("no_timers", "No timed command running", "Kein zeitgesteuerter Befehl läuft"),
+    ("resets", "{} resets", "{} Resets"),
+    // Popups
+    ("attack", "Attack", "Angriff"),
+    ("attack_stop", " [Ctrl-{}] stop ", " [Strg-{}] stoppen "),
+    ("ssids_keys", "[a] add  [e] edit  [d] remove  [r] random  [R] refresh  [Esc] close", "[a] hinzufügen  [e] bearbeiten  [d] entfernen  [r] zufällig  [R] neu laden  [Esc] schließen"),
+    ("device_settings", "Deauther settings", "Deauther-Einstellungen"),
+    ("device_settings_keys", "[Enter] edit  [s] save  [r] reload  [Esc] close", "[Enter] bearbeiten  [s] speichern  [r] neu laden  [Esc] schließen"),
+    ("editing_keys", "[Enter] done  [Esc] cancel", "[Enter] fertig  [Esc] abbrechen"),
+    ("setting", "Setting", "Einstellung"),
+    ("value", "Value", "Wert"),
+    ("select_port", "Select port", "Port wählen"),
+    ("select_port_auto", "Select port (plug in your deauther to connect automatically)", "Port wählen (schließe deinen Deauther an, um automatisch zu verbinden)"),
+    ("picker_keys", "[Up/Down] move  [Enter] connect  [r] refresh  [Esc] close", "[Hoch/Runter] bewegen  [Enter] verbinden  [r] neu laden  [Esc] schließen"),
+    ("legend", "Highlight rules", "Hervorhebungsregeln"),
+    ("legend_keys_show", "[d] show matches  [Esc] close", "[d] Treffer zeigen  [Esc] schließen"),
+    ("legend_keys_hide", "[d] hide matches  [Esc] close", "[d] Treffer ausblenden  [Esc] schließen"),
+    ("rule", "Rule", "Regel"),
+    ("sample", "Sample", "Beispiel"),
+    ("pattern", "Pattern", "Muster"),
+    ("anything_else", "Anything else", "Alles andere"),
+    ("preferences", "Preferences", "Einstellungen"),
+    ("preferences_keys", "[Enter] change  [w] write to config  [d] deauther settings  [Esc] close", "[Enter] ändern  [w] in die Konfiguration schreiben  [d] Deauther-Einstellungen  [Esc] schließen"),
+    ("pref_timestamps", "Timestamps", "Zeitstempel"),
+    ("pref_line_numbers", "Line numbers", "Zeilennummern"),
+    ("pref_wrap", "Wrap long lines", "Lange Zeilen umbrechen"),
+    ("pref_theme", "Theme", "Farbschema"),
+    ("pref_echo", "Echo", "Echo"),
+    ("pref_scrollback", "Scrollback", "Verlauf"),
+    ("pref_lines", "{} lines", "{} Zeilen"),
+    ("unlimited", "unlimited", "unbegrenzt"),
+    ("fill_in", "Fill in {} ({}/{})", "{} ausfüllen ({}/{})"),
+    ("fill_in_keys", "[Enter] next  [Esc] cancel", "[Enter] weiter  [Esc] abbrechen"),
+    ("fill_in_pick_keys", "[Up/Down] pick  [Enter] next  [Esc] cancel", "[Hoch/Runter] wählen  [Enter] weiter  [Esc] abbrechen"),
+    ("enter_for", "Enter for {}", "Enter für {}"),
+    // Connection
+    ("port_settings", "Port settings: {}", "Port-Einstellungen: {}"),
+    ("port_settings_failed", "Couldn't change port settings: {}", "Konnte die Port-Einstellungen nicht ändern: {}"),
+    ("detecting_baud", "Detecting baud rate, resetting the board helps...", "Erkenne die Baudrate, ein Reset des Boards hilft..."),
+    ("no_baud", "Couldn't detect the baud rate, using {}", "Konnte die Baudrate nicht erkennen, nutze {}"),
+    ("detected_baud", "Detected baud rate: {}", "Erkannte Baudrate: {}"),
+    ("baud_failed", "Baud rate detection failed: {}", "Erkennung der Baudrate fehlgeschlagen: {}"),
+    ("baud_rate", "Baud rate: {}", "Baudrate: {}"),
+    ("invalid_port", "'{}' is not a valid port", "'{}' ist kein gültiger Port"),
+    ("use_connect", "Not connected, use {}connect to pick a port", "Nicht verbunden, wähle mit {}connect einen Port"),
+    ("press_enter", "Press enter to open {} again", "Drücke Enter, um {} wieder zu öffnen"),
+    ("port_closed", "{} is closed, press enter to open it again", "{} ist geschlossen, drücke Enter, um ihn wieder zu öffnen"),
+    ("idle_closing", "Nothing sent or received for {}, closing {}", "Seit {} nichts gesendet oder empfangen, schließe {}"),
+    ("reconnecting", "Reconnecting to {}", "Verbinde erneut mit {}"),
+    ("link_dead", "Nothing received for {}, the link seems dead", "Seit {} nichts empfangen, die Verbindung scheint tot"),
+    ("link_silent", "Nothing received for {}", "Seit {} nichts empfangen"),
+    ("keep_alive_failed", "Couldn't send keep-alive: {}", "Konnte das Keep-alive nicht senden: {}"),
+    ("locked", "{}. Close it or pass --force to open the port anyway", "{}. Schließe es oder öffne den Port trotzdem mit --force"),
+    ("locked_anyway", "{}, opening it anyway", "{}, öffne ihn trotzdem"),
+    ("firmware", "Firmware: {}", "Firmware: {}"),
+    ("probing", "probing...", "wird erkannt..."),
+    ("probe_failed", "Couldn't probe the firmware!", "Konnte die Firmware nicht erkennen!"),
+    ("welcome_failed", "Couldn't send welcome command!", "Konnte den Begrüßungsbefehl nicht senden!"),
+    // Sending
+    ("sending", "Sending {}", "Sende {}"),
+    ("send_failed", "Couldn't send '{}': {}", "Konnte '{}' nicht senden: {}"),
+    ("write_failed", "Couldn't send: {}", "Konnte nicht senden: {}"),
+    ("bytes_failed", "Couldn't send bytes: {}", "Konnte die Bytes nicht senden: {}"),
+    ("keystroke_failed", "Couldn't send keystroke: {}", "Konnte den Tastendruck nicht senden: {}"),
+    ("remote_failed", "Couldn't send remote input: {}", "Konnte die entfernte Eingabe nicht senden: {}"),
+    ("script_send_failed", "Couldn't send script command: {}", "Konnte den Skriptbefehl nicht senden: {}"),
+    ("stop_error", "Couldn't stop: {}", "Konnte nicht stoppen: {}"),
+    ("read_failed", "Couldn't read {}: {}", "Konnte {} nicht lesen: {}"),
+    ("break", "break ({} ms)", "Break ({} ms)"),
+    ("sent_break", "Sent break ({} ms)", "Break gesendet ({} ms)"),
+    ("break_failed", "Couldn't send break: {}", "Konnte den Break nicht senden: {}"),
+    ("reset_board", "Reset the board", "Board zurückgesetzt"),
+    ("reset_failed", "Couldn't reset the board: {}", "Konnte das Board nicht zurücksetzen: {}"),
+    ("no_time_command", "{} has no command to set the time, set synctime-command in the config file", "{} hat keinen Befehl zum Stellen der Uhr, setze synctime-command in der Konfigurationsdatei"),
+    ("invalid_time_command", "Invalid synctime-command '{}'", "Ungültiger synctime-command '{}'"),
+    ("setting_time", "Setting the time: {}", "Stelle die Uhr: {}"),
+    ("paste_needs_tui", "{}paste needs the TUI, paste with your terminal instead", "{}paste braucht die TUI, füge stattdessen mit deinem Terminal ein"),
+    ("clipboard_failed", "Couldn't open the clipboard: {}", "Konnte die Zwischenablage nicht öffnen: {}"),
+    ("clipboard_no_text", "The clipboard holds no text, e.g. an image", "Die Zwischenablage enthält keinen Text, z.B. ein Bild"),
+    ("clipboard_unreadable", "Couldn't read the clipboard: {}", "Konnte die Zwischenablage nicht lesen: {}"),
+    // Device
+    ("crashed", "Device crashed: {}", "Gerät abgestürzt: {}"),
+    ("device_reset", "Device reset: {}", "Gerät neu gestartet: {}"),
+    ("boot_loop", "Boot loop: {} resets in a row", "Boot-Schleife: {} Resets hintereinander"),
+    ("restoring", "Restoring {} commands after the reset", "Stelle {} Befehle nach dem Reset wieder her"),
+    // Logs and captures
+    ("logging", "Logging to {}", "Protokolliere nach {}"),
+    ("logging_session", "Logging session '{}' to {}", "Protokolliere die Sitzung '{}' nach {}"),
+    ("logging_stopped", "Stopped logging to {}", "Protokollieren nach {} beendet"),
+    ("not_logging", "Not logging, start with '{} log <file>'", "Es wird nicht protokolliert, starte mit '{} log <Datei>'"),
+    ("log_open_failed", "Couldn't open log file {}: {}", "Konnte die Logdatei {} nicht öffnen: {}"),
+    ("log_failed", "Couldn't write log, logging stopped: {}", "Konnte das Log nicht schreiben, Protokollieren beendet: {}"),
+    ("session_meta_failed", "Couldn't write the metadata of the log, it isn't updated anymore: {}", "Konnte die Metadaten des Logs nicht schreiben, sie werden nicht mehr aktualisiert: {}"),
+    ("session_meta_start_failed", "Couldn't write the metadata of {}: {}", "Konnte die Metadaten von {} nicht schreiben: {}"),
+    ("dump_failed", "Couldn't write raw dump, dump stopped: {}", "Konnte den Rohdaten-Dump nicht schreiben, Dump beendet: {}"),
+    ("pcap_started", "Capturing packet monitor output to {}", "Zeichne die Ausgabe des Paketmonitors in {} auf"),
+    ("pcap_written", "Wrote {} frames to {}", "{} Frames in {} geschrieben"),
+    ("pcap_refused", "Not capturing, the redaction rules are invalid: {}", "Keine Aufzeichnung, die Schwärzungsregeln sind ungültig: {}"),
+    ("pcap_failed", "Couldn't write capture, capture stopped: {}", "Konnte die Aufzeichnung nicht schreiben, Aufzeichnung beendet: {}"),
+    ("create_failed", "Couldn't create {}: {}", "Konnte {} nicht anlegen: {}"),
+    // Marks
+    ("no_marks", "No marks yet, set one with {}mark", "Noch keine Markierungen, setze eine mit {}mark"),
+    ("no_such_mark", "There is no mark #{}, the marks go from #1 to #{}", "Es gibt keine Markierung #{}, die Markierungen gehen von #1 bis #{}"),
+    ("since_mark", "{} and {} lines since mark #{}", "{} und {} Zeilen seit Markierung #{}"),
+    ("since_mark_line", "{} and {} line since mark #{}", "{} und {} Zeile seit Markierung #{}"),
+    // Session info
+    ("info_port", "Port: {} ({})", "Port: {} ({})"),
+    ("info_traffic", "Traffic: {} received, {} sent, {} errors in {}", "Verkehr: {} empfangen, {} gesendet, {} Fehler in {}"),
+    ("info_log", "Log: {}", "Log: {}"),
+    ("info_log_session", "{}, session '{}'", "{}, Sitzung '{}'"),
+    // Schedules
+    ("scheduled", "Scheduled #{} {}: {}", "Geplant #{} {}: {}"),
+    ("fired", "Scheduled #{}: {}", "Geplant #{}: {}"),
+    ("cancelled", "Cancelled #{}", "#{} abgebrochen"),
+    ("cancelled_all", "Cancelled {} scheduled commands", "{} geplante Befehle abgebrochen"),
+    ("no_job", "No scheduled command #{}", "Kein geplanter Befehl #{}"),
+    ("skipped_job", "Skipped #{} while disconnected: {}", "#{} ohne Verbindung übersprungen: {}"),
+    ("no_jobs", "No scheduled commands", "Keine geplanten Befehle"),
+    ("job", "#{} {} (next in {}): {}", "#{} {} (nächstes Mal in {}): {}"),
+    // Servers
+    ("serve_failed", "Couldn't serve on {}: {}", "Konnte auf {} nicht bereitstellen: {}"),
+    ("serving", "Serving the session on {}", "Stelle die Sitzung auf {} bereit"),
+    ("bridge_connected", "Bridge client {} connected", "Bridge-Client {} verbunden"),
+    ("bridge_disconnected", "Bridge client {} disconnected", "Bridge-Client {} getrennt"),
+    ("remote_refused", "Not starting {} on {}, anyone on the network could send to the device. Use a loopback address like 127.0.0.1, or --allow-remote", "Starte {} auf {} nicht, jeder im Netzwerk könnte ans Gerät senden. Nimm eine Loopback-Adresse wie 127.0.0.1 oder --allow-remote"),
+    ("remote_open", "{} on {} lets anyone who can reach it send to the device", "{} auf {} lässt jeden, der es erreicht, ans Gerät senden"),
+    ("http_failed", "Couldn't serve the HTTP API on {}: {}", "Konnte die HTTP-API auf {} nicht bereitstellen: {}"),
+    ("http_serving", "HTTP API on http://{}", "HTTP-API auf http://{}"),
+    ("http_stopped", "HTTP API stopped: {}", "HTTP-API beendet: {}"),
+    ("watch_failed", "Couldn't serve the watch page on {}: {}", "Konnte die Live-Ansicht auf {} nicht bereitstellen: {}"),
+    ("watching", "Watch the session at http://{}", "Sieh dir die Sitzung auf http://{} an"),
+    ("watch_stopped", "Watch page stopped: {}", "Live-Ansicht beendet: {}"),
+    ("lines_skipped", "... {} lines skipped ...", "... {} Zeilen übersprungen ..."),
+    ("watch_refused", "Not serving the watch page on {}, the redaction rules are invalid: {}", "Keine Live-Ansicht auf {}, die Schwärzungsregeln sind ungültig: {}"),
+    ("control_failed", "Couldn't open the socket for huhnitor send: {}", "Konnte den Socket für huhnitor send nicht öffnen: {}"),
+    ("pty_started", "Mirroring the session to {}", "Spiegele die Sitzung nach {}"),
+    ("pty_failed", "Couldn't create pty {}: {}", "Konnte das Pty {} nicht anlegen: {}"),
+    ("pty_unsupported", "Can't create pty {}, only supported on Unix", "Kann das Pty {} nicht anlegen, nur unter Unix unterstützt"),
+    // Broadcasts
+    ("broadcast_on", "Broadcast on, typed commands also go to the other running Huhnitors", "Broadcast an, eingegebene Befehle gehen auch an die anderen laufenden Huhnitors"),
+    ("broadcast_off", "Broadcast off", "Broadcast aus"),
+    ("broadcasts_skipped", "Broadcasts of other Huhnitors are ignored", "Broadcasts anderer Huhnitors werden ignoriert"),
+    ("broadcasts_joined", "Broadcasts of other Huhnitors are sent to the device again", "Broadcasts anderer Huhnitors gehen wieder ans Gerät"),
+    ("broadcast_sent", "Broadcast to {}", "Broadcast an {}"),
+    ("broadcast_unheard", "No other Huhnitor took the broadcast", "Kein anderer Huhnitor hat den Broadcast angenommen"),
+    ("broadcast_skipped_by", ", skipped by {}", ", übersprungen von {}"),
+    ("broadcast_failed", "Couldn't broadcast to {}: {}", "Konnte keinen Broadcast an {} senden: {}"),
+    ("broadcast_from", "Broadcast from {}", "Broadcast von {}"),
+    // Remote control
+    ("control_not_connected", "Not connected to a device", "Nicht mit einem Gerät verbunden"),
+    ("invalid_request", "Invalid request: {}", "Ungültige Anfrage: {}"),
+    ("session_gone", "The session has ended", "Die Sitzung ist beendet"),
+    ("closed_before_write", "The port closed before the command was written", "Der Port wurde geschlossen, bevor der Befehl geschrieben war"),
+    ("write_timeout", "The command wasn't written within {}s", "Der Befehl wurde nicht innerhalb von {}s geschrieben"),
+    ("unexpected_reply", "unexpected reply", "unerwartete Antwort"),
+    ("no_huhnitor", "No Huhnitor is running", "Es läuft kein Huhnitor"),
+    ("no_such_huhnitor", "No running Huhnitor has the process id or name {}", "Kein laufender Huhnitor hat die Prozess-ID oder den Namen {}"),
+    ("several_huhnitors", "More than one Huhnitor is running, pick one with --to <pid|name>: {}", "Mehr als ein Huhnitor läuft, wähle einen mit --to <pid|name>: {}"),
+    ("huhnitor_on", "{}{} on {}", "{}{} an {}"),
+    ("without_port", "no port", "kein Port"),
+    // Daemon
+    ("daemon_started", "The session '{}' runs in the background, attach to it with huhnitor attach {}", "Die Sitzung '{}' läuft im Hintergrund, verbinde dich mit huhnitor attach {}"),
+    ("detached", "Detached from the session '{}', attach again with huhnitor attach {}", "Von der Sitzung '{}' getrennt, verbinde dich wieder mit huhnitor attach {}"),
+    ("session_ended", "The session '{}' has ended", "Die Sitzung '{}' ist beendet"),
+    ("daemon_unsupported", "huhnitor daemon and attach are only supported on Unix", "huhnitor daemon und attach werden nur unter Unix unterstützt"),
+    ("lines_dropped", "{} lines dropped, the output came in faster than it could be shown", "{} Zeilen verworfen, die Ausgabe kam schneller, als sie angezeigt werden konnte"),
+    // Terminal title
+    ("title_stale", "Huhnitor - {} (stale)", "Huhnitor - {} (veraltet)"),
+    ("title_not_connected", "Huhnitor - not connected", "Huhnitor - nicht verbunden"),
+    ("title_attack", " - {} attack", " - {}-Angriff"),
+    // Setup
+    ("setup_title", "Welcome to the Huhnitor! Setup {} of {}", "Willkommen beim Huhnitor! Einrichtung {} von {}"),
+    ("setup_baud", "How fast does your device talk? Pick 115200 if you aren't sure.", "Wie schnell spricht dein Gerät? Nimm 115200, wenn du unsicher bist."),
+    ("setup_theme", "Which colors do you like?", "Welche Farben magst du?"),
+    ("setup_log_dir", "Where should the Huhnitor keep a log of every session, e.g. ~/Documents/huhnitor-logs? Leave it empty to not keep logs.", "Wo soll der Huhnitor ein Log jeder Sitzung ablegen, z.B. ~/Dokumente/huhnitor-logs? Lass es leer, um keine Logs zu behalten."),
+    ("setup_auto_connect", "Connect to a deauther as soon as it's plugged in?", "Mit einem Deauther verbinden, sobald er angeschlossen wird?"),
+    ("setup_save", "That's it! This is saved as your config file:", "Das war's! Das wird als deine Konfigurationsdatei gespeichert:"),
+    ("setup_keys", "[Up/Down] choose  [Enter] next  [Esc] skip the setup", "[Hoch/Runter] wählen  [Enter] weiter  [Esc] Einrichtung überspringen"),
+    ("setup_input_keys", "[Enter] next  [Esc] skip the setup", "[Enter] weiter  [Esc] Einrichtung überspringen"),
+    ("setup_save_keys", "[Enter] save  [r] start over  [Esc] skip the setup", "[Enter] speichern  [r] von vorn  [Esc] Einrichtung überspringen"),
+    ("baud_deauther", "the deauther and most ESP boards", "der Deauther und die meisten ESP-Boards"),
+    ("baud_auto", "detect it after connecting", "nach dem Verbinden erkennen"),
+    ("baud_gps", "GPS receivers and older modules", "GPS-Empfänger und ältere Module"),
+    ("baud_boot", "boot messages of the ESP8266", "Bootmeldungen des ESP8266"),
+    ("yes", "Yes", "Ja"),
+    ("no", "No", "Nein"),
+    ("setup_auto_connect_yes", "plugging in a device connects to it", "ein angeschlossenes Gerät wird verbunden"),
+    ("setup_auto_connect_no", "pick the port from the list yourself", "den Port selbst aus der Liste wählen"),
+    ("setup_header", "# Written by the setup on the first start of the Huhnitor.\n# All options are described in the README: {}\n", "# Geschrieben von der Einrichtung beim ersten Start des Huhnitors.\n# Alle Optionen sind im README beschrieben: {}\n"),
+    ("setup_skipped", "The setup was skipped, delete this file to run it again.", "Die Einrichtung wurde übersprungen, lösche diese Datei, um sie erneut zu starten."),
+    ("setup_baud_comment", "Baud rate of the serial port, a number or \"auto\"", "Baudrate des seriellen Ports, eine Zahl oder \"auto\""),
+    ("setup_theme_comment", "Color theme: {}", "Farbschema: {}"),
+    ("setup_log_dir_comment", "Keep a log of every session in this directory, ~ is your home directory", "Ein Log jeder Sitzung in diesem Verzeichnis ablegen, ~ ist dein Home-Verzeichnis"),
+    ("setup_auto_connect_comment", "Connect to a newly plugged in device without asking", "Ohne Nachfrage mit einem neu angeschlossenen Gerät verbinden"),
+];
+
+/// Text of a key in the current language, falls back to English and then to the key itself in release builds
+pub fn text(key: &'static str) -> &'static str {
+    match CATALOG.iter().find(|(k, _, _)| *k == key) {
+        Some((_, en, de)) => match current() {
+            Locale::De if !de.is_empty() => de,
+            _ => en,
+        },
+        None => {
+            // A typo in a key shouldn't make it into a release unnoticed
+            if cfg!(debug_assertions) {
+                panic!("No text for the key '{}'", key);
+            }
+            key
+        }
+    }
+}
+
+/// Text of a key with each `{}` replaced by the next argument
+pub fn format(key: &'static str, args: &[&dyn Display]) -> String {
+    let mut parts = text(key).split("{}");
+    let mut text = parts.next().unwrap_or_default().to_string();
+    for (i, part) in parts.enumerate() {
+        if let Some(arg) = args.get(i) {
+            text.push_str(&arg.to_string());
+        }
+        text.push_str(part);
+    }
+    text
+}
+
+/// Whether the answer to a [y/N] prompt means yes, "j" counts as well in German
+pub fn is_yes(answer: &str) -> bool {
+    let answer = answer.trim();
+    answer.eq_ignore_ascii_case("y") || (current() == Locale::De && answer.eq_ignore_ascii_case("j"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+    use std::collections::HashSet;
+    use std::fs;
+
+    #[test]
+    fn translates_every_key() {
+        let mut keys = HashSet::new();
+        for (key, en, de) in CATALOG {
+            assert!(keys.insert(key), "'{}' is in the catalog twice", key);
+            assert!(!en.is_empty() && !de.is_empty(), "'{}' isn't translated", key);
+            assert_eq!(en.matches("{}").count(), de.matches("{}").count(), "'{}' has different arguments", key);
+        }
+    }
+
+    #[test]
+    fn knows_every_key_in_the_code() {
+        let call = Regex::new(r#"i18n::(?:text|format)\(\s*"(\w+)""#).unwrap();
+        for entry in fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/src")).unwrap() {
+            let path = entry.unwrap().path();
+            let code = match fs::read_to_string(&path) {
+                Ok(code) => code,
+                Err(_) => continue,
+            };
+            for caps in call.captures_iter(&code) {
+                assert!(CATALOG.iter().any(|(key, _, _)| *key == &caps[1]), "{} uses the unknown key '{}'", path.display(), &caps[1]);
+            }
+        }
+    }
+
+    #[test]
+    fn fills_in_arguments() {
+        assert_eq!(format("timer_left", &[&"scan", &"5s"]), "scan: 5s left");
+    }
+}
@@ -1,27 +1,16 @@
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
-use rustyline::{Cmd, KeyCode, KeyEvent, Modifiers};
+use tokio::sync::mpsc::UnboundedReceiver;
 
-use crate::error;
+use crate::protocol::UiEvent;
 
-pub fn receiver(sender: UnboundedSender<String>) {
-    let mut rl = rustyline::DefaultEditor::new().expect("Unable to start command history");
-    rl.bind_sequence(KeyEvent(KeyCode::Up, Modifiers::empty()), Cmd::LineUpOrPreviousHistory(1));
-    rl.bind_sequence(KeyEvent(KeyCode::Down, Modifiers::empty()), Cmd::LineDownOrNextHistory(1));
-
-    match rl.readline(">> ") {
-        Ok(line) => {
-            rl.add_history_entry(&line).expect("Unable to add history entry");
-            if sender.send(format!("{}\r\n", line.clone())).is_err() {
-                error!("Couldn't report input to main thread!");
-            }
-        }
-        Err(rustyline::error::ReadlineError::Interrupted) => {
-            sender.send("stop\n".to_string()).expect("Couldn't stop!");
+/// Wait for the next line entered in the TUI.
+/// Returns `None` when the user quits or the UI has closed.
+pub async fn read_line(receiver: &mut UnboundedReceiver<UiEvent>) -> Option<String> {
+    loop {
+        match receiver.recv().await? {
+            UiEvent::SendLine(line) => return Some(line.trim().to_string()),
+            // Nothing to stop or type into without a device
+            UiEvent::Interrupt | UiEvent::SendBytes(_) => (),
+            UiEvent::Quit => return None,
         }
-        Err(e) => error!(e)
     }
 }
-
-pub async fn read_line(receiver: &mut UnboundedReceiver<String>) -> Option<String> {
-    Some(receiver.recv().await?.trim().to_string())
-}
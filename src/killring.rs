@@ -0,0 +1,71 @@
+use std::mem;
+
+/// Killed texts kept for yanking
+const LIMIT: usize = 16;
+
+/// What the previous key did, kills and yanks chain like in readline
+#[derive(Clone, Copy, Default)]
+enum Last {
+    #[default]
+    Other,
+    Kill,
+    /// Yanked text starts at this position of the line and is this ring entry
+    Yank { start: usize, index: usize },
+}
+
+/// Text deleted with the kill commands, to be yanked back with ctrl + y
+#[derive(Default)]
+pub struct KillRing {
+    /// Oldest first
+    ring: Vec<String>,
+    /// What the previous key did
+    last: Last,
+    /// What the current key does
+    current: Last,
+}
+
+impl KillRing {
+    /// Call for every key, only the key right before can be continued
+    pub fn next_key(&mut self) {
+        self.last = mem::take(&mut self.current);
+    }
+
+    /// Save killed text, consecutive kills are joined into one entry
+    pub fn kill(&mut self, text: String, backward: bool) {
+        match self.ring.last_mut() {
+            Some(entry) if matches!(self.last, Last::Kill) => {
+                if backward {
+                    entry.insert_str(0, &text);
+                } else {
+                    entry.push_str(&text);
+                }
+            }
+            _ => {
+                self.ring.push(text);
+                if self.ring.len() > LIMIT {
+                    self.ring.remove(0);
+                }
+            }
+        }
+        self.current = Last::Kill;
+    }
+
+    /// Latest killed text, to be inserted at `start`
+    pub fn yank(&mut self, start: usize) -> Option<String> {
+        let index = self.ring.len().checked_sub(1)?;
+        self.current = Last::Yank { start, index };
+        Some(self.ring[index].clone())
+    }
+
+    /// Replace the text yanked right before with the next older entry,
+    /// returns where the yanked text starts and the replacement
+    pub fn yank_pop(&mut self) -> Option<(usize, String)> {
+        let (start, index) = match self.last {
+            Last::Yank { start, index } => (start, index),
+            _ => return None,
+        };
+        let index = index.checked_sub(1).unwrap_or(self.ring.len() - 1);
+        self.current = Last::Yank { start, index };
+        Some((start, self.ring[index].clone()))
+    }
+}
@@ -0,0 +1,85 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Constraint, Rect},
+    style::{Modifier, Style},
+    text::Span,
+    widgets::{Block, Borders, Cell, Clear, Row, Table},
+    Frame,
+};
+
+use crate::highlight::RULES;
+use crate::i18n;
+use crate::picker;
+use crate::theme::Theme;
+
+pub enum Action {
+    /// Turn the annotation of the Messages box on or off
+    ToggleDebug,
+    Close,
+    None,
+}
+
+pub fn handle_key(key: KeyEvent) -> Action {
+    match key.code {
+        KeyCode::Char('d') => Action::ToggleDebug,
+        KeyCode::Esc | KeyCode::Char('q') => Action::Close,
+        _ => Action::None,
+    }
+}
+
+/// Note added to a line in debug mode, naming the rule that picked its style
+pub fn annotation(category: Option<usize>) -> String {
+    match category {
+        Some(i) => format!("  [{} {}]", i, RULES[i].name),
+        None => "  [-]".to_string(),
+    }
+}
+
+/// The legend as text, where there is no room for the popup
+pub fn lines() -> Vec<String> {
+    RULES
+        .iter()
+        .enumerate()
+        .map(|(i, rule)| format!("> {} {:<10} {:<32} {}", i, rule.name, rule.sample, rule.pattern))
+        .collect()
+}
+
+/// Popup listing the highlight rules in the order they are tried, each with a sample in its style
+pub fn render(f: &mut Frame, area: Rect, theme: &Theme, debug: bool) {
+    let area = picker::centered(area, 80, 60);
+
+    let keys = i18n::text(if debug { "legend_keys_hide" } else { "legend_keys_show" });
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.focus))
+        .title(i18n::text("legend"))
+        .title_bottom(keys);
+
+    let pattern = Style::default().fg(theme.border);
+    let mut rows: Vec<Row> = RULES
+        .iter()
+        .enumerate()
+        .map(|(i, rule)| {
+            Row::new(vec![
+                Cell::from(i.to_string()),
+                Cell::from(rule.name),
+                Cell::from(Span::styled(rule.sample, theme.categories[i])),
+                Cell::from(rule.pattern).style(pattern),
+            ])
+        })
+        .collect();
+    rows.push(Row::new(vec![
+        Cell::from("-"),
+        Cell::from("text"),
+        Cell::from(Span::styled(i18n::text("anything_else"), theme.text)),
+        Cell::from(""),
+    ]));
+
+    let widths = [Constraint::Length(3), Constraint::Length(10), Constraint::Length(32), Constraint::Min(10)];
+    let table = Table::new(rows, widths)
+        .header(Row::new(vec!["#", i18n::text("rule"), i18n::text("sample"), i18n::text("pattern")]).style(Style::default().add_modifier(Modifier::BOLD)))
+        .block(block);
+
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+}
@@ -0,0 +1,143 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    widgets::{Clear, Row, Table, TableState},
+    Frame,
+};
+use std::time::{Duration, Instant};
+
+/// How long to wait for the first item of a list, an empty list prints none
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A list the deauther prints line by line, shown in a popup table
+pub struct Listing<T> {
+    pub items: Vec<T>,
+    state: TableState,
+    /// Items seen since the list was asked for, replacing `items` once the list is complete
+    pending: Vec<T>,
+    /// Until when the first item is waited for, other output looks too similar to parse it all the time
+    expecting: Option<Instant>,
+}
+
+impl<T> Listing<T> {
+    pub fn new() -> Self {
+        Self {
+            items: Vec::new(),
+            state: TableState::default(),
+            pending: Vec::new(),
+            expecting: None,
+        }
+    }
+
+    /// Wait for the list to be printed, `clear` forgets the current items so an emptied list shows up empty
+    pub fn expect(&mut self, clear: bool) {
+        if clear {
+            self.items.clear();
+            self.state.select(None);
+        }
+        self.pending.clear();
+        self.expecting = Some(Instant::now() + TIMEOUT);
+    }
+
+    /// Pick up an item from a line of output, the first line that isn't one ends the list
+    pub fn update(&mut self, line: &str, parse: impl FnOnce(&str) -> Option<T>) {
+        let deadline = match self.expecting {
+            Some(deadline) => deadline,
+            None => return,
+        };
+
+        match parse(line) {
+            Some(item) => self.pending.push(item),
+            None if !self.pending.is_empty() => {
+                self.items = std::mem::take(&mut self.pending);
+                self.expecting = None;
+                let selected = self.state.selected().unwrap_or(0);
+                self.state.select(Some(selected.min(self.items.len() - 1)));
+            }
+            // Headlines come before the list, but without any item it's empty
+            None if Instant::now() >= deadline => self.expecting = None,
+            None => (),
+        }
+    }
+
+    pub fn selected(&self) -> Option<&T> {
+        self.state.selected().and_then(|i| self.items.get(i))
+    }
+
+    pub fn selected_mut(&mut self) -> Option<&mut T> {
+        let i = self.state.selected()?;
+        self.items.get_mut(i)
+    }
+
+    /// Move the selection with the arrow keys, returns whether the key was one of them
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let selected = match (key.code, self.state.selected()) {
+            (KeyCode::Up, Some(i)) => i.saturating_sub(1),
+            (KeyCode::Down, Some(i)) => (i + 1).min(self.items.len().saturating_sub(1)),
+            (KeyCode::Up | KeyCode::Down, None) => return true,
+            _ => return false,
+        };
+        self.state.select(Some(selected));
+        true
+    }
+
+    pub fn selected_index(&self) -> Option<usize> {
+        self.state.selected()
+    }
+
+    /// Draw the table of the items as a popup over `area`, the selected item highlighted
+    pub fn render(&mut self, f: &mut Frame, area: Rect, table: Table) {
+        let table = table.highlight_style(Style::default().add_modifier(Modifier::REVERSED)).highlight_symbol("> ");
+        f.render_widget(Clear, area);
+        f.render_stateful_widget(table, area, &mut self.state);
+    }
+}
+
+/// Header row of a popup table
+pub fn header<'a>(titles: Vec<&'a str>) -> Row<'a> {
+    Row::new(titles).style(Style::default().add_modifier(Modifier::BOLD))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(line: &str) -> Option<u32> {
+        line.trim().parse().ok()
+    }
+
+    #[test]
+    fn collects_until_the_first_other_line() {
+        let mut listing = Listing::new();
+        listing.expect(true);
+        for line in ["Numbers:", "1", "2", "done", "3"] {
+            listing.update(line, number);
+        }
+        assert_eq!(listing.items, [1, 2]);
+        assert_eq!(listing.selected(), Some(&1));
+    }
+
+    #[test]
+    fn ignores_output_while_not_expecting() {
+        let mut listing = Listing::new();
+        listing.update("1", number);
+        listing.update("", number);
+        assert!(listing.items.is_empty());
+    }
+
+    #[test]
+    fn gives_up_on_an_empty_list() {
+        let mut listing: Listing<u32> = Listing::new();
+        listing.expect(true);
+        listing.update("Numbers:", number);
+        assert!(listing.expecting.is_some());
+
+        listing.expecting = Some(Instant::now());
+        listing.update("Nothing here", number);
+        assert!(listing.expecting.is_none());
+        listing.update("1", number);
+        listing.update("", number);
+        assert!(listing.items.is_empty());
+    }
+}
@@ -0,0 +1,116 @@
+use nix::errno::Errno;
+use nix::sys::signal::kill;
+use nix::unistd::Pid;
+use std::convert::TryInto;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Where minicom, picocom and other UUCP style programs put their lock files, the first that exists is used
+const DIRS: [&str; 3] = ["/var/lock", "/run/lock", "/var/spool/lock"];
+
+/// A `LCK..ttyUSB0` lock file telling other serial programs the port is in use, removed when dropped
+pub struct Lock {
+    path: PathBuf,
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Lock file of a port, named after the device the path points to, e.g. `/var/lock/LCK..ttyUSB0`
+fn path(port: &str) -> Option<PathBuf> {
+    let dir = DIRS.iter().map(Path::new).find(|dir| dir.is_dir())?;
+    // Links like /dev/serial/by-id/... have to end up at the same lock as the device itself
+    let device = fs::canonicalize(port).unwrap_or_else(|_| PathBuf::from(port));
+    Some(dir.join(format!("LCK..{}", device.file_name()?.to_str()?)))
+}
+
+/// The running process holding a lock file, with its pid and name
+fn owner(path: &Path) -> Option<(i32, String)> {
+    let content = fs::read(path).ok()?;
+    // The pid is written as text by today's programs and as a 4 byte integer by old ones
+    let text = String::from_utf8_lossy(&content);
+    let mut fields = text.split_whitespace();
+    let pid = match fields.next().and_then(|pid| pid.parse().ok()) {
+        Some(pid) => pid,
+        None => i32::from_ne_bytes(content.as_slice().try_into().ok()?),
+    };
+    if pid <= 0 || pid as u32 == std::process::id() {
+        return None;
+    }
+    match kill(Pid::from_raw(pid), None) {
+        // Processes of other users can't be signalled, but they are still running
+        Ok(()) | Err(Errno::EPERM) => (),
+        Err(_) => return None,
+    }
+    let name = fs::read_to_string(format!("/proc/{}/comm", pid))
+        .ok()
+        .map(|name| name.trim().to_string())
+        .or_else(|| fields.next().map(str::to_string))
+        .unwrap_or_else(|| "another program".to_string());
+    Some((pid, name))
+}
+
+/// Take the lock file of a port, after removing a stale one.
+/// Returns `None` if there is no lock directory or it isn't writable, and why not if another program holds the port.
+pub fn acquire(port: &str) -> Result<Option<Lock>, String> {
+    let path = match path(port) {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    for _ in 0..2 {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                let user = std::env::var("USER").unwrap_or_default();
+                let _ = writeln!(file, "{:>10} huhnitor {}", std::process::id(), user);
+                return Ok(Some(Lock { path }));
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => match owner(&path) {
+                Some((pid, name)) => {
+                    return Err(format!("{} is locked by {} (pid {}), see {}", port, name, pid, path.display()));
+                }
+                // Left behind by a program that crashed
+                None => {
+                    if fs::remove_file(&path).is_err() {
+                        return Ok(None);
+                    }
+                }
+            },
+            // Most systems only let the uucp or lock group write there
+            Err(_) => return Ok(None),
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner_of(content: &[u8]) -> Option<(i32, String)> {
+        let path = std::env::temp_dir().join(format!("huhnitor-test-lock-{}", std::process::id()));
+        fs::write(&path, content).unwrap();
+        let owner = owner(&path);
+        let _ = fs::remove_file(&path);
+        owner
+    }
+
+    #[test]
+    fn finds_the_running_owner() {
+        let parent = std::os::unix::process::parent_id() as i32;
+        assert_eq!(owner_of(format!("{:>10} minicom\n", parent).as_bytes()).map(|(pid, _)| pid), Some(parent));
+        // Written by old programs as a 4 byte integer
+        assert_eq!(owner_of(&parent.to_ne_bytes()).map(|(pid, _)| pid), Some(parent));
+    }
+
+    #[test]
+    fn ignores_stale_and_broken_locks() {
+        assert_eq!(owner_of(format!("{}\n", std::process::id()).as_bytes()), None);
+        assert_eq!(owner_of(b"0\n"), None);
+        assert_eq!(owner_of(b"-1\n"), None);
+        assert_eq!(owner_of(b"garbage"), None);
+    }
+}
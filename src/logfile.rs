@@ -0,0 +1,196 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::sessions;
+
+/// Rotated logs kept by default
+pub const KEEP: usize = 5;
+
+/// When to start a new log file
+#[derive(Clone, Copy, Debug)]
+pub struct Rotation {
+    /// Rotate once the log grows past this many bytes
+    pub max_size: Option<u64>,
+    /// Rotate when the day changes (UTC)
+    pub daily: bool,
+    /// Number of rotated logs to keep, as `<log>.1` (newest) to `<log>.<keep>`
+    pub keep: usize,
+    /// Compress rotated logs with gzip
+    pub compress: bool,
+}
+
+/// A new log in `dir` named after the current time, e.g. `huhnitor-2024-05-01-134500.log`, creating the directory
+pub fn in_dir(dir: &Path) -> io::Result<PathBuf> {
+    let dir = expand_home(dir);
+    fs::create_dir_all(&dir)?;
+    Ok(dir.join(format!("huhnitor-{}.log", chrono::Local::now().format("%Y-%m-%d-%H%M%S"))))
+}
+
+/// `dir` with a leading `~` replaced by the home directory, the config file isn't read by a shell
+fn expand_home(dir: &Path) -> PathBuf {
+    match (dir.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => dir.to_path_buf(),
+    }
+}
+
+/// Parse a size like `500K`, `10M` or `1G`, plain numbers are bytes
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (number, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let factor = match unit.trim().to_uppercase().trim_end_matches("IB").trim_end_matches('B') {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => return Err(format!("Invalid size '{}', expected e.g. 500K, 10M or 1G", s)),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(factor))
+        .ok_or_else(|| format!("Invalid size '{}', expected e.g. 500K, 10M or 1G", s))
+}
+
+/// Compress `from` into the gzip file `to` and remove it
+fn gzip(from: &Path, to: &Path) -> io::Result<()> {
+    let mut encoder = GzEncoder::new(File::create(to)?, Compression::default());
+    io::copy(&mut File::open(from)?, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    fs::remove_file(from)
+}
+
+/// Days since the epoch, the day changes at midnight UTC
+fn day(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() / 86400)
+}
+
+/// Transcript of the session, one line per received or sent line
+pub struct Log {
+    path: PathBuf,
+    file: LineWriter<File>,
+    rotation: Rotation,
+    /// Size of the current file
+    size: u64,
+    /// Day the current file was started
+    day: u64,
+}
+
+impl Log {
+    /// Open the log file, appending to it if it already exists
+    pub fn open(path: &Path, rotation: Rotation) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let metadata = file.metadata()?;
+        let mut log = Self {
+            path: path.to_path_buf(),
+            file: LineWriter::new(file),
+            rotation,
+            size: metadata.len(),
+            day: metadata.modified().map_or_else(|_| day(SystemTime::now()), day),
+        };
+
+        // A log left over from an earlier day starts out rotated
+        if log.size > 0 && rotation.daily && log.day != day(SystemTime::now()) {
+            log.rotate()?;
+        }
+        Ok(log)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Name of the n-th rotated log
+    fn rotated(&self, n: usize) -> PathBuf {
+        let suffix = if self.rotation.compress { ".gz" } else { "" };
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}{}", n, suffix));
+        PathBuf::from(name)
+    }
+
+    /// Move the current log to `<log>.1`, shifting older ones and dropping the oldest.
+    /// The metadata of the logged sessions moves along, it isn't worth failing the log for.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        let ignore_missing = |result: io::Result<()>| match result {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+
+        if self.rotation.keep == 0 {
+            ignore_missing(fs::remove_file(&self.path))?;
+            let _ = sessions::removed(&self.path);
+        } else {
+            ignore_missing(fs::remove_file(self.rotated(self.rotation.keep)))?;
+            let _ = sessions::removed(&self.rotated(self.rotation.keep));
+            for n in (1..self.rotation.keep).rev() {
+                ignore_missing(fs::rename(self.rotated(n), self.rotated(n + 1)))?;
+                let _ = sessions::moved(&self.rotated(n), &self.rotated(n + 1));
+            }
+
+            if self.rotation.compress {
+                gzip(&self.path, &self.rotated(1))?;
+            } else {
+                fs::rename(&self.path, self.rotated(1))?;
+            }
+            let _ = sessions::moved(&self.path, &self.rotated(1));
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.file = LineWriter::new(file);
+        self.size = 0;
+        self.day = day(SystemTime::now());
+        Ok(())
+    }
+
+    fn needs_rotation(&self, len: u64) -> bool {
+        let too_big = self.rotation.max_size.is_some_and(|max| self.size > 0 && self.size + len > max);
+        let new_day = self.rotation.daily && self.day != day(SystemTime::now());
+        too_big || new_day
+    }
+
+    /// Write a line, returns true if the log was rotated first
+    pub fn write_line(&mut self, line: &str) -> io::Result<bool> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let rotate = self.needs_rotation(line.len() as u64 + 1);
+        if rotate {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += line.len() as u64 + 1;
+        Ok(rotate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("500"), Ok(500));
+        assert_eq!(parse_size("500K"), Ok(500 << 10));
+        assert_eq!(parse_size("10 MiB"), Ok(10 << 20));
+        assert_eq!(parse_size("1gb"), Ok(1 << 30));
+    }
+
+    #[test]
+    fn expands_the_home_directory() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_home(Path::new("~/Documents/huhnitor-logs")), home.join("Documents/huhnitor-logs"));
+        assert_eq!(expand_home(Path::new("/var/log/~")), Path::new("/var/log/~"));
+        assert_eq!(expand_home(Path::new("~other/logs")), Path::new("~other/logs"));
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        for invalid in ["", "K", "10T", "-1M", "1.5G", "99999999999G", "18446744073709551616"] {
+            assert!(parse_size(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
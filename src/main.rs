@@ -1,146 +1,486 @@
 use crate::app::App;
-use handler::handle;
+use crate::bell::Bell;
+use crate::cli::{Cli, Cmd, Global};
+use crate::config::Config;
+use crate::logfile::Log;
+use crate::stats::Stats;
+use crate::theme::Theme;
+use clap::CommandFactory;
 use std::env;
+use std::sync::Arc;
 use std::time::Duration;
-use serialport::{DataBits, FlowControl, Parity, StopBits};
-use structopt::StructOpt;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 
+mod activity;
 mod app;
+mod attack;
+mod autorun;
+mod bell;
+mod bridge;
+mod chain;
+mod channel;
+mod cli;
+mod command;
+mod completions;
+mod config;
+mod connection;
+mod control;
+mod countdown;
+mod crash;
+#[cfg(unix)]
+mod daemon;
+mod database;
+mod device_settings;
+mod driver;
+mod error;
+mod escape;
+mod events;
+mod exec;
+mod firmware;
+mod flash;
+mod gps;
 #[macro_use]
 mod handler;
+mod highlight;
+mod http;
+mod i18n;
 mod input;
+mod killring;
+mod legend;
+mod listing;
+#[cfg(unix)]
+mod lockfile;
+mod logfile;
+mod marks;
+mod monitor;
+mod mqtt;
 mod output;
+mod pacing;
+mod paste;
+mod pcap;
+mod permissions;
+mod picker;
+mod pipe;
+mod plugin;
 mod port;
+mod preferences;
+mod presenter;
+mod protocol;
+#[cfg(unix)]
+mod pty;
+mod raw;
+mod rawdump;
+mod redact;
+mod replay;
+#[cfg(unix)]
+mod runtime;
+mod scans;
+mod script;
+mod schedule;
+mod serial;
+mod session;
+mod sessions;
+mod shell;
+mod sniffer;
+mod ssids;
+mod stats;
+mod status;
+mod theme;
+mod template;
+mod title;
+mod watch;
+mod watches;
+mod undo;
+mod wizard;
 
-async fn monitor(
-    cmd_port: Option<String>,
-    auto: bool,
-    no_welcome: bool,
-    out: &output::Preferences,
-    app: App,
-) {
-    let (input_tx, mut input_rx) = tokio::sync::mpsc::unbounded_channel();
-    let (output_tx, output_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
-    let input_clone = input_tx.clone();
-
-    std::thread::spawn(|| input::receiver(input_clone));
-
-    let tty_path = if cmd_port.is_some() {
-        cmd_port
-    } else if auto {
-        port::auto(&mut input_rx, out).await
-    } else {
-        port::manual(&mut input_rx, out).await
+/// Whether to use colors at all, not with `--no-color`, a non-empty `NO_COLOR` or in the accessible mode
+fn colored(args: &Global, accessible: bool) -> bool {
+    let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    !(args.no_color || no_color || accessible)
+}
+
+/// Pick the color theme from the arguments and config, monochrome without colors
+fn theme(args: &Global, config: &Config, colored: bool) -> Theme {
+    if !colored {
+        return Theme::monochrome();
+    }
+
+    let theme = match args.theme.as_ref().or(config.theme.as_ref()) {
+        Some(name) => Theme::by_name(name).unwrap_or_else(|| {
+            error!(format!("Unknown theme '{}', available themes: {}", name, theme::NAMES.join(", ")));
+            Theme::default()
+        }),
+        None => Theme::default(),
     };
 
-    if let Some(inner_tty_path) = tty_path {
-        let settings = tokio_serial::new(&inner_tty_path, 115200)
-            .data_bits(DataBits::Eight)
-            .flow_control(FlowControl::None)
-            .parity(Parity::None)
-            .stop_bits(StopBits::One)
-            .timeout(Duration::from_secs(10));
-        #[allow(unused_mut)] // Ignore warning from windows compilers
-        if let Ok(mut port) = tokio_serial::SerialStream::open(&settings) {
-            #[cfg(unix)]
-            port.set_exclusive(false)
-                .expect("Unable to set serial port exclusive to false");
-
-            let mut port = BufReader::new(port);
-
-            out.connected(&inner_tty_path);
-
-            if !no_welcome && port.write("welcome\r\n".as_bytes()).await.is_err() {
-                out.print("Couldn't send welcome command!");
-            }
+    let theme = theme.with_overrides(&config.colors).unwrap_or_else(|e| {
+        error!(e);
+        theme
+    });
 
-            tokio::spawn(async move { app.run(input_tx, output_rx, Duration::from_millis(15)).await });
-
-            let mut buf = Vec::new();
-            loop {
-                tokio::select! {
-                    len = port.read_until(b'\n', &mut buf) => match len {
-                        Ok(0) => { // EOF
-                            break;
-                        },
-                        Ok(_) => {
-                            let input = String::from_utf8_lossy(&buf).to_string();
-                            output_tx.send(input).unwrap();
-                            buf = Vec::new();
-                        },
-                        Err(e) => {
-                            error!(e);
-                            break;
-                        }
-                    },
-
-                    Some(text) = input_rx.recv() => {
-                        if text.trim().to_uppercase() == "EXIT" {
-                            break;
-                        } else if text.trim().to_uppercase() == "CLEAR" {
-                            output::clear();
-                        } else if text.to_uppercase().starts_with("HUHN") {
-                            if port.write(handle(text).as_bytes()).await.is_err() {
-                                error!("Command failed");
-                            }
-                        } else if port.write(text.as_bytes()).await.is_err() {
-                            error!("Couldn't send message");
-                        }
-                    }
-                }
-            }
-        } else {
-            // Port creation handler
-            error!("Couldn't create port object!");
+    theme.fit(config.color_depth.unwrap_or_else(theme::ColorDepth::detect))
+}
+
+/// Pick the language from the config, or the environment
+fn locale(config: &Config) -> i18n::Locale {
+    match config.language.as_deref().map(str::parse) {
+        Some(Ok(locale)) => locale,
+        Some(Err(e)) => {
+            error!(e);
+            i18n::Locale::detect()
         }
-    } else {
-        // Path handler
-        out.hint();
+        None => i18n::Locale::detect(),
     }
 }
 
-#[derive(StructOpt)]
-#[structopt(name = "Huhnitor", about = env!("CARGO_PKG_DESCRIPTION"))]
-struct Opt {
-    /// Open driver page
-    #[structopt(short, long)]
-    driver: bool,
+/// Pick the log rotation from the arguments and config
+fn rotation(args: &cli::Monitor, config: &Config) -> logfile::Rotation {
+    let max_size = args.log_max_size.map(Ok).or_else(|| config.log_max_size.as_deref().map(logfile::parse_size));
+    logfile::Rotation {
+        max_size: max_size.and_then(|size| size.map_err(|e| error!(e)).ok()),
+        daily: args.log_daily || config.log_daily.unwrap_or(false),
+        keep: args.log_keep.or(config.log_keep).unwrap_or(logfile::KEEP),
+        compress: args.log_compress || config.log_compress.unwrap_or(false),
+    }
+}
 
-    /// Disable automatic port connection
-    #[structopt(short = "a", long = "no-auto")]
-    auto: bool,
+/// Pick the serial line settings from the arguments and config
+fn link(args: &Global, config: &Config) -> serial::LinkSettings {
+    let mut link = serial::LinkSettings::default();
 
-    /// Disable colored output
-    #[structopt(short = "c", long = "no-color")]
-    color: bool,
+    if let Some(serial::Baud::Fixed(rate)) = args.baud.or(config.baud) {
+        link.baud_rate = rate;
+    }
 
-    /// Select port
-    #[structopt(short, long)]
-    port: Option<String>,
+    let flow = args.flow.map(Ok).or_else(|| config.flow_control.as_deref().map(serial::parse_flow_control));
+    match flow {
+        Some(Ok(flow)) => link.flow_control = flow,
+        Some(Err(e)) => error!(e),
+        None => (),
+    }
 
-    /// Disable welcome command
-    #[structopt(short = "w", long = "no-welcome")]
-    no_welcome: bool,
+    link
 }
 
-#[tokio::main]
-async fn main() {
-    let args = Opt::from_args();
+/// Print the serial ports with the names of their devices, or only the names for scripts
+fn list_ports(names: bool) {
+    for port in port::list() {
+        match port::friendly_name(&port) {
+            Some(friendly) if !names => println!("{:<16} {}", port.port_name, friendly),
+            _ => println!("{}", port.port_name),
+        }
+    }
+}
 
-    let out = output::Preferences {
-        color_enabled: !args.color,
+/// Print the sessions that were logged, the oldest first
+fn list_sessions() {
+    let sessions = sessions::list();
+    if sessions.is_empty() {
+        return println!("No sessions were logged yet, start one with --log or 'huhn log <file> [name]'");
+    }
+    println!("{:<16}  {:>8}  {:<20}  {:<24}  {:<16}  LOG", "STARTED", "DURATION", "NAME", "DEVICE", "FIRMWARE");
+    for line in sessions::table(&sessions) {
+        println!("{}", line);
+    }
+}
+
+/// The terminal UI with the preferences of the arguments and config
+fn app(global: &Global, args: &cli::Monitor, config: &Config, redactor: Result<redact::Redactor, String>, out: &output::Preferences, accessible: bool) -> App {
+    let settings = app::Settings {
+        echo: args.echo.or(config.echo).unwrap_or(app::Echo::Plain),
+        wrap: config.wrap.unwrap_or(false),
+        timestamps: config.timestamps.unwrap_or(false),
+        line_numbers: config.line_numbers.unwrap_or(false),
+        scrollback: config.scrollback.filter(|lines| *lines > 0),
+        theme: match global.theme.as_deref().or(config.theme.as_deref()) {
+            _ if !out.color_enabled => "monochrome",
+            Some(name) => theme::NAMES.iter().copied().find(|known| known.eq_ignore_ascii_case(name)).unwrap_or("default"),
+            None => "default",
+        },
+        color_depth: config.color_depth.unwrap_or_else(theme::ColorDepth::detect),
+        confirm_exit: config.confirm_exit.unwrap_or(false),
+        ctrl_c: config.ctrl_c.unwrap_or(app::CtrlC::Spam),
+        channels: config.channels.unwrap_or(false),
+        channel_window: Duration::from_secs(config.channel_window.unwrap_or(10).max(1)),
+        history: config.history.unwrap_or(true),
+        confirm_commands: config.confirm_commands.unwrap_or(true),
+        direction_markers: config.direction_markers.unwrap_or(false),
+        gap: config.gap_marker.filter(|secs| *secs > 0).map(Duration::from_secs),
+        autosave: config.autosave.filter(|secs| *secs > 0).map(Duration::from_secs),
+        title: config.title.unwrap_or(true),
+        title_attack: config.title_attack.unwrap_or(false),
+        accessible,
+        paste_limit: config.paste_limit.unwrap_or(paste::LIMIT),
+        tick_rate: config.tick_rate.filter(|ms| *ms > 0).map_or(app::TICK_RATE, Duration::from_millis),
+        raw_escape: config
+            .raw_escape
+            .as_deref()
+            .and_then(|key| raw::Escape::parse(key).map_err(|e| error!(e)).ok())
+            .unwrap_or_else(|| raw::Escape::parse(raw::ESCAPE).unwrap()),
     };
+    let mut app = App::new(settings, out.theme);
+    app.set_config_path(global.config.clone());
+    app.set_redactor(redactor);
+    app.set_inline(args.inline || accessible);
+    if args.raw {
+        app.set_raw(true);
+    }
+    if args.presenter {
+        app.set_presenter(true);
+    }
+    // Printed before the alternate screen it would only flash up, so the TUI shows it
+    if !global.quiet {
+        app.greet(if accessible { output::plain_banner() } else { output::banner() });
+    }
+    if args.restore {
+        app.restore(global.port.as_deref());
+    }
+    let gps = args.gps.clone().or_else(|| {
+        config.gps.as_ref().and_then(|source| gps::parse_source(source).map_err(|e| error!(format!("Invalid gps: {}", e))).ok())
+    });
+    if let Some(source) = gps {
+        app.start_gps(source);
+    }
+    if let Some(names) = &config.bell {
+        match Bell::new(names, config.bell_command.clone()) {
+            Ok(bell) => app.set_bell(bell),
+            Err(e) => error!(e),
+        }
+    }
+    if config.record_scans.unwrap_or(false) {
+        app.open_database(config.database.clone().or_else(database::default_path));
+    }
+    app
+}
 
-    out.logo();
-    out.version();
+/// The connection preferences and log of the serial side
+fn backend(global: &Global, args: &cli::Monitor, config: &Config, redactor: Result<redact::Redactor, String>) -> (monitor::Options, Option<Log>) {
+    let rotation = rotation(args, config);
+    let on_connect_delay = config.on_connect_delay.map_or(autorun::DELAY, Duration::from_millis);
+    let log_file = match (&args.log, &config.log_dir) {
+        (Some(path), _) => Ok(Some(path.clone())),
+        (None, Some(dir)) => logfile::in_dir(dir).map(Some),
+        (None, None) => Ok(None),
+    };
+    let log = match log_file.and_then(|path| path.map(|path| Log::open(&path, rotation)).transpose()) {
+        Ok(log) => log,
+        Err(e) => {
+            error!(format!("Couldn't open log file: {}", e));
+            None
+        }
+    };
 
-    if args.driver {
-        out.driver();
+    let framed = args.dump_framed;
+    let options = monitor::Options {
+        no_welcome: args.no_welcome,
+        link: link(global, config),
+        auto_baud: global.baud.or(config.baud) == Some(serial::Baud::Auto),
+        serve: args.serve,
+        serve_input: args.serve_input,
+        allow_remote: args.allow_remote,
+        pty: args.pty.clone(),
+        mqtt: config.mqtt.clone(),
+        http: args.http,
+        watch: args.watch,
+        redactor,
+        control: config.control.unwrap_or(false),
+        control_name: None,
+        pipeline: plugin::Pipeline::from_specs(&config.processor).unwrap_or_else(|e| {
+            error!(e);
+            plugin::Pipeline::default()
+        }),
+        firmware: args.firmware.as_ref().or(config.firmware.as_ref()).and_then(|name| {
+            let profile = firmware::by_name(name);
+            if profile.is_none() {
+                error!(format!("Unknown firmware '{}', available: {}", name, firmware::names().join(", ")));
+            }
+            profile
+        }),
+        symbols: args.elf.as_ref().and_then(|path| {
+            crash::Symbols::load(path)
+                .map_err(|e| error!(format!("Couldn't read symbols from {}: {}", path.display(), e)))
+                .ok()
+        }),
+        dump: args.dump_raw.as_ref().and_then(|path| {
+            rawdump::Dump::create(path, framed)
+                .map_err(|e| error!(format!("Couldn't create {}: {}", path.display(), e)))
+                .ok()
+        }),
+        keep_alive: args.keep_alive.or(config.keep_alive).filter(|secs| *secs > 0).map(Duration::from_secs),
+        keep_alive_command: config.keep_alive_command.clone().unwrap_or_default(),
+        rx_timeout: args.rx_timeout.or(config.rx_timeout).filter(|secs| *secs > 0).map(Duration::from_secs),
+        idle_timeout: args.idle_timeout.or(config.idle_timeout).filter(|secs| *secs > 0).map(Duration::from_secs),
+        idle_action: config.idle_action.unwrap_or_default(),
+        exclusive: args.exclusive || config.exclusive.unwrap_or(false),
+        force: args.force,
+        dry_run: args.dry_run,
+        pacing: pacing::Pacing {
+            bytes_per_sec: args.tx_bytes_per_sec.or(config.tx_bytes_per_sec),
+            lines_per_sec: args.tx_lines_per_sec.or(config.tx_lines_per_sec),
+            char_delay: args.tx_char_delay.or(config.tx_char_delay).filter(|ms| *ms > 0).map(Duration::from_millis),
+        },
+        on_connect: autorun::steps(&config.on_connect, on_connect_delay),
+        on_connect_delay,
+        chain_delay: config.chain_delay.map_or(chain::DELAY, Duration::from_millis),
+        chain_prompt: config.chain_prompt.as_ref().and_then(|prompt| {
+            regex::Regex::new(prompt)
+                .map_err(|e| error!(format!("Invalid chain-prompt: {}", e)))
+                .ok()
+        }),
+        restore_on_reset: config.restore_on_reset.unwrap_or(false),
+        time_command: config.synctime_command.clone(),
+        sync_time: config.synctime_on_connect.unwrap_or(false),
+        rotation,
+        session_name: args.session_name.clone(),
+        session_notes: args.session_notes.clone(),
+        output_buffer: config.output_buffer.unwrap_or(channel::CAPACITY),
+        overflow: config.output_overflow.unwrap_or_default(),
+        script: args.script.as_ref().or(config.script.as_ref()).and_then(|path| {
+            script::Script::load(path).map_err(|e| error!(e)).ok()
+        }),
+    };
+    (options, log)
+}
+
+/// Run the terminal UI until the user quits, then show the summary
+async fn monitor(global: Global, args: Box<cli::Monitor>, config: Config, out: output::Preferences, accessible: bool) {
+    let redactor = redact::Redactor::from_specs(&config.redact);
+    let mut app = app(&global, &args, &config, redactor.clone(), &out, accessible);
+    if global.port.is_none() {
+        app.open_port_picker(!args.no_auto && config.auto_connect.unwrap_or(true));
+    }
+    let (options, log) = backend(&global, &args, &config, redactor);
+    let log_path = log.as_ref().map(|log| log.path().to_path_buf());
+
+    let stats = Arc::new(Stats::new());
+    monitor::run(global.port, options, log, stats.clone(), app).await;
+
+    if config.summary.unwrap_or(false) {
+        out.summary(&stats, log_path.as_deref());
     } else {
-        let app = App::new();
-        monitor(args.port, !args.auto, args.no_welcome, &out, app).await;
+        out.goodbye();
     }
+}
+
+/// Run a session without terminal UI until an attached UI quits it, then show the summary
+#[cfg(unix)]
+async fn daemon(global: Global, args: Box<cli::Daemon>, config: Config, out: output::Preferences) -> Result<(), String> {
+    let port = port::choose(global.port.as_deref())?;
+    let (mut options, log) = backend(&global, &args.monitor, &config, redact::Redactor::from_specs(&config.redact));
+    options.control_name = Some(args.name.clone());
+    let log_path = log.as_ref().map(|log| log.path().to_path_buf());
+
+    let stats = Arc::new(Stats::new());
+    daemon::run(&args.name, port, options, log, stats.clone()).await?;
+
+    if config.summary.unwrap_or(false) {
+        out.summary(&stats, log_path.as_deref());
+    } else {
+        out.goodbye();
+    }
+    Ok(())
+}
+
+/// Run the terminal UI on a session of the daemon until the user detaches or quits
+#[cfg(unix)]
+async fn attach(global: Global, args: Box<cli::Attach>, config: Config, out: output::Preferences, accessible: bool) -> Result<(), String> {
+    let app = app(&global, &args.monitor, &config, redact::Redactor::from_specs(&config.redact), &out, accessible);
+    match daemon::attach(&args.name, app).await? {
+        daemon::Left::Detached => println!("{}", i18n::format("detached", &[&args.name, &args.name])),
+        daemon::Left::Quit => out.goodbye(),
+        daemon::Left::Ended => {
+            println!("{}", i18n::format("session_ended", &[&args.name]));
+            out.goodbye();
+        }
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    let (global, cmd) = Cli::parse_checked().split();
 
-    out.goodbye();
+    // Printed for the shell, without banner or goodbye
+    match &cmd {
+        Cmd::Completions { shell } => return completions::print(Cli::command(), *shell),
+        Cmd::List { names } => return list_ports(*names),
+        Cmd::Sessions => return list_sessions(),
+        _ => (),
+    }
+
+    error::install_panic_hook();
+
+    // On the first start there is no config file yet, so the basic settings are asked for
+    let first_start = matches!(&cmd, Cmd::Monitor(args) if !args.driver && !args.accessible);
+    if let Some(path) = Config::default_path().filter(|path| first_start && wizard::wanted(global.config.as_deref(), path)) {
+        if let Err(e) = wizard::run(&path, theme(&global, &Config::default(), colored(&global, false))) {
+            error!(format!("Couldn't write the config file {}: {}", path.display(), e));
+        }
+    }
+
+    let config = Config::load(global.config.as_deref()).unwrap_or_else(|e| {
+        error!(e);
+        Config::default()
+    });
+    i18n::set(locale(&config));
+
+    let accessible = match &cmd {
+        Cmd::Monitor(args) => args.accessible,
+        Cmd::Attach(args) => args.monitor.accessible,
+        _ => false,
+    } || config.accessible.unwrap_or(false);
+    let color_enabled = colored(&global, accessible);
+    let out = output::Preferences {
+        color_enabled,
+        theme: theme(&global, &config, color_enabled),
+    };
+
+    // Commands for scripts print only what they're asked for, and fail with an exit code
+    let result = match cmd {
+        Cmd::Monitor(args) if args.driver => {
+            if !global.quiet {
+                out.banner();
+            }
+            out.driver();
+            out.goodbye();
+            Ok(())
+        }
+        Cmd::Monitor(args) => {
+            monitor(global, args, config, out, accessible).await;
+            Ok(())
+        }
+        Cmd::SetupPermissions { install } => {
+            if !global.quiet {
+                out.banner();
+            }
+            permissions::setup(&out, install);
+            out.goodbye();
+            Ok(())
+        }
+        Cmd::Exec(args) => match port::choose(global.port.as_deref()) {
+            Ok(port) => {
+                let exclusive = args.exclusive || config.exclusive.unwrap_or(false);
+                exec::run(&out, &port, &link(&global, &config), &args, exclusive).await
+            }
+            Err(e) => Err(e),
+        },
+        Cmd::Flash(args) => port::choose(global.port.as_deref()).and_then(|port| flash::run(&port, &args)),
+        Cmd::Send(args) => control::send(&args).await,
+        Cmd::Replay(args) => replay::run(&args).await,
+        Cmd::Redact(args) => redact::Redactor::from_specs(&config.redact).and_then(|redactor| redact::run(&args, &redactor)),
+        #[cfg(unix)]
+        Cmd::Daemon(args) if args.foreground => daemon(global, args, config, out).await,
+        #[cfg(unix)]
+        Cmd::Daemon(args) => port::choose(global.port.as_deref()).and_then(|_| daemon::spawn(&args.name)).map(|_| {
+            println!("{}", i18n::format("daemon_started", &[&args.name, &args.name]));
+        }),
+        #[cfg(unix)]
+        Cmd::Attach(args) => attach(global, args, config, out, accessible).await,
+        #[cfg(not(unix))]
+        Cmd::Daemon(_) | Cmd::Attach(_) => Err(i18n::text("daemon_unsupported").to_string()),
+        Cmd::Completions { .. } | Cmd::List { .. } | Cmd::Sessions => Ok(()),
+    };
+    if let Err(e) = result {
+        error!(e);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,70 @@
+use chrono::Local;
+use std::time::Instant;
+
+use crate::command;
+use crate::i18n;
+use crate::output;
+
+/// A moment of the session set with `:mark`
+struct Mark {
+    at: Instant,
+    /// Lines received before the mark
+    lines: u64,
+}
+
+/// Numbered marks to time how long scans or attacks take, counted from 1
+#[derive(Default)]
+pub struct Marks {
+    marks: Vec<Mark>,
+    /// Lines received in the whole session
+    lines: u64,
+}
+
+impl Marks {
+    /// A line was received
+    pub fn line(&mut self) {
+        self.lines += 1;
+    }
+
+    /// Set a new mark, returns the marker line for the Messages box and the log
+    pub fn mark(&mut self) -> String {
+        self.marks.push(Mark { at: Instant::now(), lines: self.lines });
+        format!("MARK #{}: {}", self.marks.len(), Local::now().format("%Y-%m-%d %H:%M:%S"))
+    }
+
+    /// Time and lines since mark `n`, or since the latest one
+    pub fn since(&self, n: Option<usize>) -> Result<String, String> {
+        if self.marks.is_empty() {
+            return Err(i18n::format("no_marks", &[&command::PREFIX]));
+        }
+        let n = n.unwrap_or(self.marks.len());
+        let mark = n
+            .checked_sub(1)
+            .and_then(|i| self.marks.get(i))
+            .ok_or_else(|| i18n::format("no_such_mark", &[&n, &self.marks.len()]))?;
+        let lines = self.lines - mark.lines;
+        let key = if lines == 1 { "since_mark_line" } else { "since_mark" };
+        Ok(format!("> {}", i18n::format(key, &[&output::duration(mark.at.elapsed()), &lines, &n])))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_lines_since_a_mark() {
+        let mut marks = Marks::default();
+        assert!(marks.since(None).is_err());
+        marks.line();
+        assert!(marks.mark().starts_with("MARK #1: "));
+        marks.line();
+        marks.line();
+        marks.mark();
+        marks.line();
+        assert!(marks.since(Some(1)).unwrap().contains(" 3 "));
+        assert!(marks.since(None).unwrap().contains(" 1 "));
+        assert!(marks.since(Some(0)).is_err());
+        assert!(marks.since(Some(3)).is_err());
+    }
+}
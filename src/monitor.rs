@@ -0,0 +1,1392 @@
+use serde::Deserialize;
+use serialport::SerialPort;
+use chrono::Local;
+use regex::Regex;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio_util::sync::CancellationToken;
+
+use crate::app::{self, App};
+use crate::autorun::{self, Step};
+use crate::bridge::Bridge;
+use crate::chain;
+use crate::channel::{self, Overflow};
+use crate::command::{self, Command};
+use crate::connection::{Connection, PortEvent};
+use crate::control::{self, Delivery};
+use crate::crash::{self, Detector, Finding};
+use crate::error;
+use crate::error::Error;
+use crate::escape;
+use crate::firmware::{self, Profile};
+use crate::handler::{self, LogAction, Meta};
+use crate::http;
+use crate::i18n;
+use crate::input;
+#[cfg(unix)]
+use crate::lockfile::{self, Lock};
+use crate::logfile::{self, Log};
+use crate::marks::Marks;
+use crate::mqtt::{self, Publisher};
+use crate::output;
+use crate::pacing::Pacing;
+use crate::pcap;
+use crate::plugin::Pipeline;
+use crate::rawdump::Dump;
+use crate::redact::Redactor;
+use crate::schedule::{Fired, Scheduler};
+use crate::port;
+use crate::protocol::{BackendEvent, UiEvent};
+use crate::script::{Action, Script, Verdict};
+#[cfg(unix)]
+use crate::pty::Pty;
+use crate::serial::{self, LinkSettings};
+use crate::sessions::LoggedSession;
+use crate::sniffer;
+use crate::stats::Stats;
+use crate::status::{self, SharedStatus};
+use crate::watch::Watch;
+
+/// What happens once nothing was sent or received for the idle timeout
+#[derive(Clone, Copy, PartialEq, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Idle {
+    /// Close the port and open it again once the user presses enter
+    #[default]
+    Disconnect,
+    /// Close the port and quit
+    Exit,
+}
+
+/// Connection preferences from the command line and config
+pub struct Options {
+    /// Don't send the welcome command after connecting
+    pub no_welcome: bool,
+    /// Line settings used to open the port
+    pub link: LinkSettings,
+    /// Keep other programs from opening the port while it is open (Unix only, Windows always does)
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub exclusive: bool,
+    /// Open the port even if another program holds its lock file
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub force: bool,
+    /// Detect the baud rate after connecting
+    pub auto_baud: bool,
+    /// Share the session with TCP clients on this address
+    pub serve: Option<SocketAddr>,
+    /// Let TCP clients send to the device
+    pub serve_input: bool,
+    /// Take input and commands on addresses other than loopback
+    pub allow_remote: bool,
+    /// Mirror the session to a pseudo-terminal linked at this path
+    pub pty: Option<PathBuf>,
+    /// Publish parsed events to an MQTT broker
+    pub mqtt: Option<mqtt::Settings>,
+    /// Serve the HTTP API on this address
+    pub http: Option<SocketAddr>,
+    /// Serve the read-only web view on this address
+    pub watch: Option<SocketAddr>,
+    /// Let `huhnitor send` send commands to the device
+    pub control: bool,
+    /// Name `huhnitor send --to` finds a daemon by, besides its process id
+    pub control_name: Option<String>,
+    /// What to hide on the watch page, the error if the rules don't parse
+    pub redactor: Result<Redactor, String>,
+    /// Output processors applied to received lines before they are shown
+    pub pipeline: Pipeline,
+    /// Firmware profile to use instead of probing the device
+    pub firmware: Option<&'static dyn Profile>,
+    /// User script with hooks
+    pub script: Option<Script>,
+    /// Symbols of the firmware to decode stack traces with
+    pub symbols: Option<crash::Symbols>,
+    /// Write the exact bytes exchanged with the device here
+    pub dump: Option<Dump>,
+    /// Send `keep_alive_command` this often
+    pub keep_alive: Option<Duration>,
+    /// No-op sent to keep the link busy, without line ending
+    pub keep_alive_command: String,
+    /// Consider the link stale after this long without receiving anything, and dead after twice as long
+    pub rx_timeout: Option<Duration>,
+    /// Close the port after this long without anything sent or received, so it isn't held forever
+    pub idle_timeout: Option<Duration>,
+    pub idle_action: Idle,
+    /// Show what would be sent instead of writing to the port
+    pub dry_run: bool,
+    /// Limits on how fast data is sent
+    pub pacing: Pacing,
+    /// Commands sent after connecting, each after its delay
+    pub on_connect: Vec<Step>,
+    /// Pause before each restored setting
+    pub on_connect_delay: Duration,
+    /// Pause between the commands typed on one line
+    pub chain_delay: Duration,
+    /// Wait for a line matching this before sending the next command of a line
+    pub chain_prompt: Option<Regex>,
+    /// Send `on_connect` and the settings changed during the session again after the device restarted
+    pub restore_on_reset: bool,
+    /// Command setting the clock of the device, instead of the one of the firmware profile
+    pub time_command: Option<String>,
+    /// Set the clock of the device once the firmware is known after connecting
+    pub sync_time: bool,
+    /// Rotation of log files started with `huhn log`
+    pub rotation: logfile::Rotation,
+    /// Name of the session, kept with the metadata of the log
+    pub session_name: Option<String>,
+    /// Notes about the session for the metadata of the log
+    pub session_notes: Option<String>,
+    /// Lines of output that may wait for the UI
+    pub output_buffer: usize,
+    /// What happens to output that doesn't fit into the buffer
+    pub overflow: Overflow,
+}
+
+/// How long to listen at each rate while detecting the baud rate
+const AUTO_BAUD_LISTEN: Duration = Duration::from_millis(750);
+
+/// How often the link is checked when a keep-alive or RX timeout is set
+const LINK_CHECK: Duration = Duration::from_secs(1);
+
+/// Time for a dead USB adapter to go away before reconnecting
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// The serial side of a session, talks to the device and the UI
+struct Monitor {
+    input_rx: UnboundedReceiver<UiEvent>,
+    output_tx: channel::Sender,
+    options: Options,
+    log: Option<Log>,
+    /// Metadata of the log, written next to it
+    logged_session: Option<LoggedSession>,
+    stats: Arc<Stats>,
+    status: SharedStatus,
+    bridge: Option<Bridge>,
+    #[cfg(unix)]
+    pty: Option<Pty>,
+    /// Lock file of the open port, for minicom and the like
+    #[cfg(unix)]
+    lock: Option<Lock>,
+    publisher: Option<Publisher>,
+    http: Option<http::Server>,
+    watch: Option<Watch>,
+    control: Option<control::Server>,
+    /// Commands typed while broadcasting, passed on to the other Huhnitors one line after another
+    broadcaster: Option<UnboundedSender<Vec<String>>>,
+    /// Packet monitor capture started with `:pcap start`
+    pcap: Option<pcap::Writer>,
+    /// Moments set with `:mark`, to time scans and attacks
+    marks: Marks,
+    /// Input from TCP clients and the pty, written to the device as is
+    remote_tx: UnboundedSender<Vec<u8>>,
+    remote_rx: UnboundedReceiver<Vec<u8>>,
+    /// Commands from `huhnitor send` and broadcasts, which wait until they are written
+    control_tx: UnboundedSender<control::Injected>,
+    control_rx: UnboundedReceiver<control::Injected>,
+    /// Commands, completions and parsers of the connected firmware
+    profile: &'static dyn Profile,
+    /// Lines left to look through while probing the firmware
+    probing: usize,
+    /// The clock of the device is set once probing is done
+    sync_pending: bool,
+    /// Exceptions and resets of the device
+    crash: Detector,
+    /// Cancelled when the session has to end, e.g. because the UI failed
+    shutdown: CancellationToken,
+    /// `on-connect` commands and restored settings still to send
+    autorun: autorun::Queue,
+    /// Settings changed by commands sent to the device
+    applied: autorun::Applied,
+    /// Commands typed on one line, still to send
+    chain: autorun::Queue,
+    /// Commands sent at a time or in intervals
+    scheduler: Scheduler,
+    fired_rx: UnboundedReceiver<Fired>,
+}
+
+impl Monitor {
+    /// Show an error in the Messages box, falling back to stderr if the UI is gone
+    /// Whether a server taking input may listen on this address. Anyone reaching it can send to the device,
+    /// so only loopback is allowed without `--allow-remote`, and even then it comes with a warning.
+    fn remote_allowed(&self, addr: SocketAddr, option: &str) -> bool {
+        if addr.ip().is_loopback() {
+            return true;
+        }
+        if !self.options.allow_remote {
+            self.report(i18n::format("remote_refused", &[&option, &addr]));
+            return false;
+        }
+        let _ = self.output_tx.notify(format!("WARNING: {}", i18n::format("remote_open", &[&option, &addr])));
+        true
+    }
+
+    fn report<E: std::fmt::Display>(&self, e: E) {
+        self.stats.add_error();
+        if self.output_tx.notify(format!("ERROR: {}", e)).is_err() {
+            error!(e);
+        }
+    }
+
+    /// Keep the metadata of the log up to date, it isn't worth ending the log for
+    fn update_logged_session(&mut self, update: impl FnOnce(&mut LoggedSession) -> io::Result<()>) {
+        if let Some(Err(e)) = self.logged_session.as_mut().map(update) {
+            self.logged_session = None;
+            self.report(i18n::format("session_meta_failed", &[&e]));
+        }
+    }
+
+    /// Start the metadata of a log that was just opened
+    fn start_logged_session(&mut self, log: &Path, name: Option<String>) {
+        // The previous session ends first, with its stop time
+        self.logged_session = None;
+        match LoggedSession::start(log, name, self.options.session_notes.clone()) {
+            Ok(session) => self.logged_session = Some(session),
+            Err(e) => self.report(i18n::format("session_meta_start_failed", &[&log.display(), &e])),
+        }
+    }
+
+    /// Mark a moment of the session, e.g. "moved to the 2nd floor" during a survey
+    fn note(&mut self, text: &str) {
+        let note = format!("{} {}", Local::now().format("%Y-%m-%d %H:%M:%S"), text);
+        self.log(&format!("NOTE: {}", note));
+        self.update_logged_session(|session| session.note(note.clone()));
+        let _ = self.output_tx.notify(format!("NOTE: {}", note));
+    }
+
+    /// Log a line of the session transcript
+    fn log(&mut self, line: &str) {
+        match self.log.as_mut().map(|log| log.write_line(line)) {
+            // The earlier part of the session went with the rotated log, the new file gets its own metadata
+            Some(Ok(true)) => self.update_logged_session(LoggedSession::rotated),
+            Some(Ok(false)) | None => (),
+            Some(Err(e)) => {
+                // Don't fail on every line once the disk is full
+                self.log = None;
+                self.report(i18n::format("log_failed", &[&e]));
+            }
+        }
+    }
+
+    /// Add bytes to the raw dump
+    fn dump(&mut self, bytes: &[u8], received: bool) {
+        let result = match self.options.dump.as_mut() {
+            Some(dump) if received => dump.received(bytes),
+            Some(dump) => dump.sent(bytes),
+            None => return,
+        };
+        if let Err(e) = result {
+            self.options.dump = None;
+            self.report(i18n::format("dump_failed", &[&e]));
+        }
+    }
+
+    /// Queue bytes for the device, they go out in the background as paced
+    fn write(&mut self, conn: &Connection, bytes: &[u8]) -> std::io::Result<()> {
+        if self.options.dry_run {
+            let text = String::from_utf8_lossy(bytes);
+            let _ = self.output_tx.notify(format!("> {}", i18n::format("not_sent", &[&text.trim_end_matches(['\r', '\n'])])));
+            return Ok(());
+        }
+        conn.write(bytes)?;
+        self.queued(bytes);
+        Ok(())
+    }
+
+    /// Count, dump and log bytes that were queued for the device
+    fn queued(&mut self, bytes: &[u8]) {
+        self.stats.add_tx(bytes.len());
+        self.dump(bytes, false);
+        self.log(&String::from_utf8_lossy(bytes));
+    }
+
+    /// Queue a command of `huhnitor send` or a broadcast, it learns whether it was written once it went out
+    fn inject(&mut self, conn: &Connection, injected: control::Injected) {
+        let control::Injected { bytes, written } = injected;
+        let command = String::from_utf8_lossy(&bytes).into_owned();
+        if self.options.dry_run {
+            let _ = self.output_tx.notify(format!("> {}", i18n::format("not_sent", &[&command.trim_end()])));
+            let _ = written.send(Err(std::io::Error::other("Dry run, nothing is sent to the device")));
+            return;
+        }
+        if let Err(e) = conn.write_confirmed(&bytes, written) {
+            return self.report(i18n::format("remote_failed", &[&e]));
+        }
+        self.queued(&bytes);
+        self.sent(&command);
+    }
+
+    /// Tell the UI about a command that went to the device, e.g. so it sees scans it didn't start
+    fn sent(&self, command: &str) {
+        if !self.options.dry_run {
+            let _ = self.output_tx.send(BackendEvent::Sent(command.trim_end().to_string()));
+        }
+    }
+
+    /// Hold the line in the break condition for a while, resets or interrupts some boards
+    async fn send_break(&self, conn: &Connection, ms: u64) -> serialport::Result<()> {
+        if self.options.dry_run {
+            let _ = self.output_tx.notify(format!("> {}", i18n::format("not_sent", &[&i18n::format("break", &[&ms])])));
+            return Ok(());
+        }
+        conn.send_break(Duration::from_millis(ms)).await?;
+        let _ = self.output_tx.notify(format!("> {}", i18n::format("sent_break", &[&ms])));
+        Ok(())
+    }
+
+    /// Change the line settings of the open port, keeping the old ones if that fails
+    async fn reconfigure<F: FnOnce(&mut LinkSettings)>(&mut self, conn: &Connection, change: F) {
+        let mut link = self.options.link;
+        change(&mut link);
+
+        match conn.reconfigure(link).await {
+            Ok(()) => {
+                self.options.link = link;
+                status::update(&self.status, |status| status.link = link);
+                let _ = self.output_tx.notify(format!("> {}", i18n::format("port_settings", &[&link])));
+            }
+            Err(e) => {
+                // Some drivers apply settings partially
+                let _ = conn.reconfigure(self.options.link).await;
+                self.report(i18n::format("port_settings_failed", &[&e]));
+            }
+        }
+    }
+
+    /// Listen at every common baud rate and switch to the one with the most readable output
+    async fn detect_baud(&mut self, port: &mut tokio_serial::SerialStream) -> serialport::Result<()> {
+        let _ = self.output_tx.notify(format!("> {}", i18n::text("detecting_baud")));
+
+        let mut best = (0.0, self.options.link.baud_rate);
+        for &rate in serial::AUTO_BAUD_RATES.iter() {
+            port.set_baud_rate(rate)?;
+            port.clear(serialport::ClearBuffer::Input)?;
+
+            let mut received = Vec::new();
+            let mut chunk = [0; 256];
+            let deadline = tokio::time::Instant::now() + AUTO_BAUD_LISTEN;
+            while let Ok(Ok(len)) = tokio::time::timeout_at(deadline, port.read(&mut chunk)).await {
+                if len == 0 {
+                    break;
+                }
+                received.extend_from_slice(&chunk[..len]);
+            }
+
+            let score = serial::score(&received);
+            if score > best.0 {
+                best = (score, rate);
+            }
+        }
+
+        // Garbage at every rate, better stay with the configured one
+        if best.0 < 0.5 {
+            port.set_baud_rate(self.options.link.baud_rate)?;
+            let _ = self.output_tx.notify(format!("> {}", i18n::format("no_baud", &[&self.options.link.baud_rate])));
+            return Ok(());
+        }
+
+        port.set_baud_rate(best.1)?;
+        self.options.link.baud_rate = best.1;
+        let _ = self.output_tx.notify(format!("> {}", i18n::format("detected_baud", &[&best.1])));
+        Ok(())
+    }
+
+    /// Start or stop a capture
+    fn set_pcap(&mut self, path: Option<PathBuf>) {
+        if let Some(pcap) = self.pcap.take() {
+            let _ = self.output_tx.notify(format!("> {}", i18n::format("pcap_written", &[&pcap.frames(), &pcap.path().display()])));
+        }
+
+        // Without valid redaction rules nothing is captured, the file may be shared
+        let path = match (path, &self.options.redactor) {
+            (Some(_), Err(e)) => return self.report(i18n::format("pcap_refused", &[e])),
+            (path, _) => path,
+        };
+        let redactor = self.options.redactor.clone().unwrap_or_default();
+        match path.map(|path| pcap::Writer::create(&path, redactor).map_err(|e| (path, e))) {
+            Some(Ok(pcap)) => {
+                let _ = self.output_tx.notify(format!("> {}", i18n::format("pcap_started", &[&pcap.path().display()])));
+                self.pcap = Some(pcap);
+            }
+            Some(Err((path, e))) => self.report(i18n::format("create_failed", &[&path.display(), &e])),
+            None => (),
+        }
+    }
+
+    /// Add a line of packet monitor output to the capture
+    fn capture(&mut self, line: &str) {
+        let frame = match (&self.pcap, sniffer::parse(line)) {
+            (Some(_), Some(frame)) => frame,
+            _ => return,
+        };
+        if let Some(Err(e)) = self.pcap.as_mut().map(|pcap| pcap.write(&frame)) {
+            self.pcap = None;
+            self.report(i18n::format("pcap_failed", &[&e]));
+        }
+    }
+
+    /// Point out crashes and resets of the device
+    fn report_finding(&mut self, finding: Finding) {
+        match finding {
+            Finding::Crash(what) => self.report(i18n::format("crashed", &[&what])),
+            Finding::Reset(reason) => {
+                status::update(&self.status, |status| status.resets += 1);
+                let _ = self.output_tx.notify(format!("WARNING: {}", i18n::format("device_reset", &[&reason])));
+                if self.options.restore_on_reset {
+                    self.restore();
+                }
+            }
+            Finding::BootLoop(resets) => {
+                status::update(&self.status, |status| status.resets += 1);
+                self.report(i18n::format("boot_loop", &[&resets]));
+                // Sending the same commands again would likely crash it again
+                self.autorun = autorun::Queue::default();
+            }
+            Finding::Trace(trace) => {
+                for line in trace {
+                    let _ = self.output_tx.notify(format!("> {}", line));
+                }
+            }
+        }
+    }
+
+    /// Queue the `on-connect` commands and the changed settings after the device restarted
+    fn restore(&mut self) {
+        let mut steps = self.options.on_connect.clone();
+        steps.extend(self.applied.steps(self.options.on_connect_delay));
+        if !steps.is_empty() {
+            let _ = self.output_tx.notify(format!("> {}", i18n::format("restoring", &[&steps.len()])));
+            self.autorun.start(&steps);
+        }
+    }
+
+    /// Carry out what the script asked for during its last hook
+    async fn script_actions(&mut self, conn: &Connection) {
+        let actions = match self.options.script.as_mut() {
+            Some(script) => script.actions(),
+            None => return,
+        };
+
+        for action in actions {
+            match action {
+                Action::Print(text) => {
+                    let _ = self.output_tx.notify(text);
+                }
+                Action::Send(command) => {
+                    match self.write(conn, format!("{}\r\n", command).as_bytes()) {
+                        Ok(()) => self.sent(&command),
+                        Err(e) => self.report(i18n::format("script_send_failed", &[&e])),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Send a command of the `on-connect` list or a restored setting
+    async fn autorun(&mut self, conn: &Connection, command: String) {
+        let _ = self.output_tx.notify(format!("> {}", i18n::format("sending", &[&command])));
+        match self.write(conn, format!("{}\r\n", command).as_bytes()) {
+            Ok(()) => self.sent(&command),
+            Err(e) => self.report(i18n::format("send_failed", &[&command, &e])),
+        }
+    }
+
+    /// Send a scheduled command that is due
+    async fn scheduled(&mut self, conn: &Connection, fired: Fired) {
+        // It may have been cancelled while its command was on the way
+        if !self.scheduler.fired(fired.id) {
+            return;
+        }
+        let _ = self.output_tx.notify(format!("> {}", i18n::format("fired", &[&fired.id, &fired.command])));
+        match self.write(conn, format!("{}\r\n", fired.command).as_bytes()) {
+            Ok(()) => self.sent(&fired.command),
+            Err(e) => self.report(i18n::format("send_failed", &[&fired.command, &e])),
+        }
+    }
+
+    /// Wait until the user picks a port, returns `None` if they quit instead
+    async fn wait_for_port(&mut self) -> Option<String> {
+        loop {
+            let line = tokio::select! {
+                line = input::read_line(&mut self.input_rx) => line?,
+                _ = self.shutdown.cancelled() => return None,
+            };
+            match command::parse(&line) {
+                Some(Ok(Command::Connect(Some(port)))) => match port::resolve(&port, &port::list()) {
+                    Some(path) => return Some(path),
+                    None => self.report(i18n::format("invalid_port", &[&port])),
+                },
+                _ => self.report(i18n::format("use_connect", &[&command::PREFIX])),
+            }
+        }
+    }
+
+    /// Keep the port closed after the idle timeout until the user wants it back.
+    /// Returns the port to open, `None` if they quit instead.
+    async fn wait_after_idle(&mut self, tty_path: &str) -> Option<String> {
+        status::update(&self.status, |status| {
+            status.port = None;
+            status.firmware = None;
+            status.stale = false;
+        });
+        let _ = self.output_tx.notify(format!("> {}", i18n::format("press_enter", &[&tty_path])));
+        loop {
+            let line = tokio::select! {
+                line = input::read_line(&mut self.input_rx) => line?,
+                _ = self.shutdown.cancelled() => return None,
+            };
+            if line.is_empty() {
+                return Some(tty_path.to_string());
+            }
+            match command::parse(&line) {
+                Some(Ok(Command::Connect(Some(port)))) => match port::resolve(&port, &port::list()) {
+                    Some(path) => return Some(path),
+                    None => self.report(i18n::format("invalid_port", &[&port])),
+                },
+                _ => self.report(i18n::format("port_closed", &[&tty_path])),
+            }
+        }
+    }
+
+    /// Send the keep-alive when it is due and check for silence on the line.
+    /// Returns true if the link is dead.
+    async fn watch_link(&mut self, conn: &Connection, last_rx: Instant, last_ping: &mut Instant) -> bool {
+        if let Some(interval) = self.options.keep_alive {
+            if last_ping.elapsed() >= interval {
+                *last_ping = Instant::now();
+                let command = format!("{}\r\n", self.options.keep_alive_command);
+                if let Err(e) = self.write(conn, command.as_bytes()) {
+                    self.report(i18n::format("keep_alive_failed", &[&e]));
+                }
+            }
+        }
+
+        let timeout = match self.options.rx_timeout {
+            Some(timeout) => timeout,
+            None => return false,
+        };
+        let silence = last_rx.elapsed();
+        if silence >= timeout * 2 {
+            self.report(i18n::format("link_dead", &[&output::duration(silence)]));
+            return true;
+        }
+        if silence >= timeout && !status::get(&self.status).stale {
+            status::update(&self.status, |status| status.stale = true);
+            let _ = self.output_tx.notify(format!("WARNING: {}", i18n::format("link_silent", &[&output::duration(silence)])));
+        }
+        false
+    }
+
+    /// Switch to the profile of the connected firmware
+    fn set_profile(&mut self, profile: &'static dyn Profile) {
+        self.profile = profile;
+        self.probing = 0;
+        status::update(&self.status, |status| status.firmware = Some(profile));
+        let _ = self.output_tx.notify(format!("> {}", i18n::format("firmware", &[&profile.title()])));
+        self.update_logged_session(|session| session.firmware(profile.name()));
+    }
+
+    /// Look for a known firmware in a line of output while probing
+    fn probe(&mut self, line: &str) {
+        if self.probing == 0 {
+            return;
+        }
+
+        match firmware::identify(line) {
+            Some(profile) => self.set_profile(profile),
+            None if self.probing == 1 => self.set_profile(firmware::generic()),
+            None => self.probing -= 1,
+        }
+    }
+
+    /// Take the lock file of the port, unless another program holds it.
+    /// Returns false if the port must not be opened.
+    #[cfg(unix)]
+    fn lock(&mut self, tty_path: &str) -> bool {
+        // Our own lock of the last connection would look stale anyway, but this way it's gone for sure
+        self.lock = None;
+        match lockfile::acquire(tty_path) {
+            Ok(lock) => {
+                self.lock = lock;
+                true
+            }
+            Err(e) if self.options.force => {
+                let _ = self.output_tx.notify(format!("WARNING: {}", i18n::format("locked_anyway", &[&e])));
+                true
+            }
+            Err(e) => {
+                self.report(i18n::format("locked", &[&e]));
+                false
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn lock(&mut self, _tty_path: &str) -> bool {
+        true
+    }
+
+    /// Remove the lock file once the port is closed
+    fn unlock(&mut self) {
+        #[cfg(unix)]
+        {
+            self.lock = None;
+        }
+    }
+
+    /// Open the port, falls back to the port picker if that fails
+    async fn open(&mut self, tty_path: &str) -> Result<tokio_serial::SerialStream, Option<String>> {
+        if !self.lock(tty_path) {
+            status::update(&self.status, |status| status.pick_port = true);
+            return Err(self.wait_for_port().await);
+        }
+
+        let settings = self.options.link.builder(tty_path);
+        #[cfg(unix)]
+        let settings = settings.exclusive(self.options.exclusive);
+
+        #[allow(unused_mut)] // Ignore warning from windows compilers
+        let mut port = match tokio_serial::SerialStream::open(&settings) {
+            Ok(port) => port,
+            Err(e) => {
+                self.unlock();
+                self.report(serial::open_error(tty_path, &self.options.link, &e));
+                status::update(&self.status, |status| status.pick_port = true);
+                return Err(self.wait_for_port().await);
+            }
+        };
+
+        if self.options.auto_baud {
+            if let Err(e) = self.detect_baud(&mut port).await {
+                self.report(i18n::format("baud_failed", &[&e]));
+                let _ = port.set_baud_rate(self.options.link.baud_rate);
+            }
+        }
+
+        Ok(port)
+    }
+
+    /// Announce the connection and greet the device
+    async fn connected(&mut self, conn: &Connection, tty_path: &str) {
+        self.stats.add_connect();
+        self.applied.connected(tty_path);
+
+        let link = self.options.link;
+        status::update(&self.status, |status| {
+            status.port = Some(tty_path.to_string());
+            status.link = link;
+        });
+
+        for line in output::connected(tty_path) {
+            let _ = self.output_tx.notify(line);
+        }
+        let device = port::friendly_name_of(tty_path);
+        self.update_logged_session(|session| session.connected(tty_path, device));
+
+        match self.options.firmware {
+            Some(profile) => self.set_profile(profile),
+            None => {
+                self.profile = firmware::generic();
+                self.probing = firmware::PROBE_LINES;
+                status::update(&self.status, |status| status.firmware = None);
+                if self.write(conn, format!("{}\r\n", firmware::PROBE_COMMAND).as_bytes()).is_err() {
+                    self.report(i18n::text("probe_failed"));
+                }
+            }
+        }
+
+        if !self.options.no_welcome && self.write(conn, b"welcome\r\n").is_err() {
+            self.report(i18n::text("welcome_failed"));
+        }
+
+        if let Some(Err(e)) = self.options.script.as_mut().map(|script| script.on_connect(tty_path)) {
+            self.report(e);
+        }
+        self.script_actions(conn).await;
+        self.autorun.start(&self.options.on_connect);
+        self.sync_pending = self.options.sync_time;
+        self.sync_time_when_known(conn).await;
+    }
+
+    /// Set the clock of the device with the command of the config file or the firmware profile
+    async fn sync_time(&mut self, conn: &Connection) {
+        let format = match self.options.time_command.as_deref().or(self.profile.time_command()) {
+            Some(format) => format.to_string(),
+            None => {
+                return self.report(i18n::format("no_time_command", &[&self.profile.title()]))
+            }
+        };
+        let mut command = String::new();
+        if write!(command, "{}", Local::now().format(&format)).is_err() {
+            return self.report(i18n::format("invalid_time_command", &[&format]));
+        }
+        let _ = self.output_tx.notify(format!("> {}", i18n::format("setting_time", &[&command])));
+        self.send_command(conn, format!("{}\r\n", command)).await;
+    }
+
+    /// Set the clock after connecting, once the probe found out which firmware it is
+    async fn sync_time_when_known(&mut self, conn: &Connection) {
+        if self.sync_pending && self.probing == 0 {
+            self.sync_pending = false;
+            self.sync_time(conn).await;
+        }
+    }
+
+    /// Handle a line received from the device, returns false once the UI is gone
+    async fn received(&mut self, conn: &Connection, buf: Vec<u8>) -> bool {
+        if status::get(&self.status).stale {
+            status::update(&self.status, |status| status.stale = false);
+        }
+        self.stats.add_rx(buf.len());
+        self.dump(&buf, true);
+        let input = String::from_utf8_lossy(&buf).to_string();
+        if input.starts_with("ERROR") {
+            self.stats.add_error();
+        }
+        self.log(&input);
+        self.marks.line();
+        if let Some(bridge) = &self.bridge {
+            bridge.send(&buf);
+        }
+        #[cfg(unix)]
+        if let Some(pty) = &self.pty {
+            pty.send(&buf);
+        }
+        if self.options.chain_prompt.as_ref().is_some_and(|prompt| prompt.is_match(input.trim_end())) {
+            self.chain.prompt();
+        }
+        self.capture(&input);
+        self.update_logged_session(|session| session.line(&input));
+        self.probe(&input);
+        self.sync_time_when_known(conn).await;
+        let finding = self.crash.check(&input);
+        if let Some(event) = self.profile.parse_event(&input) {
+            self.stats.add_event();
+            if let Some(publisher) = &self.publisher {
+                publisher.publish(event);
+            }
+        }
+        if let Some(Err(e)) = self.options.script.as_mut().map(|script| script.on_line(&input)) {
+            self.report(e);
+        }
+        self.script_actions(conn).await;
+        // Log, mirrors and events get the raw line, processors only change what is shown
+        let input = self.profile.display(input);
+        for line in self.options.pipeline.run(input) {
+            if let Some(http) = &self.http {
+                http.push(&line);
+            }
+            if let Some(watch) = &self.watch {
+                watch.push(&line);
+            }
+            if self.output_tx.send_output(line).await.is_err() {
+                return false;
+            }
+        }
+        if let Some(finding) = finding {
+            self.report_finding(finding);
+        }
+        true
+    }
+
+    /// Pull the reset line of the board
+    async fn reset(&self, conn: &Connection) -> serialport::Result<()> {
+        if self.options.dry_run {
+            let _ = self.output_tx.notify(format!("> {}", i18n::format("not_sent", &[&"reset"])));
+            return Ok(());
+        }
+        conn.reset().await?;
+        let _ = self.output_tx.notify(format!("> {}", i18n::text("reset_board")));
+        Ok(())
+    }
+
+    /// Show, start or stop the session log
+    fn set_log(&mut self, action: LogAction) {
+        let text = match (action, self.log.take()) {
+            (LogAction::Show, Some(log)) => {
+                let text = format!("> {}", i18n::format("logging", &[&log.path().display()]));
+                self.log = Some(log);
+                text
+            }
+            (LogAction::Stop, Some(log)) => {
+                self.logged_session = None;
+                format!("> {}", i18n::format("logging_stopped", &[&log.path().display()]))
+            }
+            (LogAction::Show, None) | (LogAction::Stop, None) => {
+                format!("> {}", i18n::format("not_logging", &[&handler::PREFIX]))
+            }
+            (LogAction::Start(path, name), _) => match Log::open(&path, self.options.rotation) {
+                Ok(log) => {
+                    self.log = Some(log);
+                    let text = match &name {
+                        Some(name) => format!("> {}", i18n::format("logging_session", &[&name, &path.display()])),
+                        None => format!("> {}", i18n::format("logging", &[&path.display()])),
+                    };
+                    self.start_logged_session(&path, name);
+                    text
+                }
+                Err(e) => return self.report(i18n::format("log_open_failed", &[&path.display(), &e])),
+            },
+        };
+        let _ = self.output_tx.notify(text);
+    }
+
+    /// Summary of the session for `huhn info`
+    fn info(&self, tty_path: &str) -> Vec<String> {
+        let firmware = if self.probing > 0 { i18n::text("probing") } else { self.profile.title() };
+        let log = match (&self.log, self.logged_session.as_ref().and_then(LoggedSession::name)) {
+            (Some(log), Some(name)) => i18n::format("info_log_session", &[&log.path().display(), &name]),
+            (Some(log), None) => log.path().display().to_string(),
+            (None, _) => i18n::text("off").to_string(),
+        };
+        vec![
+            format!("> {}", i18n::format("info_port", &[&tty_path, &self.options.link])),
+            format!("> {}", i18n::format("firmware", &[&firmware])),
+            format!(
+                "> {}",
+                i18n::format(
+                    "info_traffic",
+                    &[
+                        &output::bytes(self.stats.rx_bytes()),
+                        &output::bytes(self.stats.tx_bytes()),
+                        &self.stats.errors(),
+                        &output::duration(self.stats.elapsed()),
+                    ]
+                )
+            ),
+            format!("> {}", i18n::format("info_log", &[&log])),
+        ]
+    }
+
+    /// Carry out a meta command, returns `Some` to leave the session like `typed`
+    async fn meta(&mut self, conn: &Connection, tty_path: &str, meta: Meta) -> Option<Option<String>> {
+        match meta {
+            Meta::Read(path) => match fs::read_to_string(&path) {
+                Ok(mut text) => {
+                    if !text.ends_with('\n') {
+                        text.push('\n');
+                    }
+                    if let Err(e) = self.write(conn, text.as_bytes()) {
+                        self.report(i18n::format("send_failed", &[&path.display(), &e]));
+                    }
+                }
+                Err(e) => self.report(i18n::format("read_failed", &[&path.display(), &e])),
+            },
+            Meta::Reset => {
+                if let Err(e) = self.reset(conn).await {
+                    self.report(i18n::format("reset_failed", &[&e]));
+                }
+            }
+            Meta::Reconnect => {
+                let _ = self.output_tx.notify(format!("> {}", i18n::format("reconnecting", &[&tty_path])));
+                return Some(Some(tty_path.to_string()));
+            }
+            Meta::Log(action) => self.set_log(action),
+            Meta::Baud(None) => {
+                let _ = self.output_tx.notify(format!("> {}", i18n::format("baud_rate", &[&self.options.link.baud_rate])));
+            }
+            Meta::Baud(Some(rate)) => self.reconfigure(conn, |link| link.baud_rate = rate).await,
+            Meta::Info => {
+                for line in self.info(tty_path) {
+                    let _ = self.output_tx.notify(line);
+                }
+            }
+            Meta::Help => {
+                for line in handler::help() {
+                    let _ = self.output_tx.notify(line);
+                }
+            }
+        }
+        None
+    }
+
+    /// Handle a line typed into the UI.
+    /// Returns `Some` to leave the session, with the port to switch to if any.
+    async fn typed(&mut self, conn: &Connection, tty_path: &str, text: String) -> Option<Option<String>> {
+        match handler::parse(&text) {
+            Some(Ok(meta)) => return self.meta(conn, tty_path, meta).await,
+            Some(Err(e)) => {
+                self.report(e);
+                return None;
+            }
+            None => (),
+        }
+        match command::parse(&text) {
+            Some(Ok(Command::Connect(Some(next)))) => match port::resolve(&next, &port::list()) {
+                Some(next) => return Some(Some(next)),
+                None => self.report(i18n::format("invalid_port", &[&next])),
+            },
+            Some(Ok(Command::SendHex(bytes))) => {
+                if let Err(e) = self.write(conn, &bytes) {
+                    self.report(i18n::format("bytes_failed", &[&e]));
+                }
+            }
+            Some(Ok(Command::Break(ms))) => {
+                if let Err(e) = self.send_break(conn, ms).await {
+                    self.report(i18n::format("break_failed", &[&e]));
+                }
+            }
+            Some(Ok(Command::DataBits(bits))) => self.reconfigure(conn, |link| link.data_bits = bits).await,
+            Some(Ok(Command::Parity(parity))) => self.reconfigure(conn, |link| link.parity = parity).await,
+            Some(Ok(Command::StopBits(bits))) => self.reconfigure(conn, |link| link.stop_bits = bits).await,
+            Some(Ok(Command::Flow(flow))) => self.reconfigure(conn, |link| link.flow_control = flow).await,
+            Some(Ok(Command::Pcap(path))) => self.set_pcap(path),
+            Some(Ok(Command::Note(text))) => self.note(&text),
+            Some(Ok(Command::Mark)) => {
+                let mark = self.marks.mark();
+                self.log(&mark);
+                let _ = self.output_tx.notify(mark);
+            }
+            Some(Ok(Command::Since(n))) => match self.marks.since(n) {
+                Ok(text) => {
+                    let _ = self.output_tx.notify(text);
+                }
+                Err(e) => self.report(e),
+            },
+            Some(Ok(Command::Broadcast(on))) => {
+                let on = on.unwrap_or(!status::get(&self.status).broadcast);
+                status::update(&self.status, |status| status.broadcast = on);
+                let text = i18n::text(if on { "broadcast_on" } else { "broadcast_off" });
+                let _ = self.output_tx.notify(format!("> {}", text));
+            }
+            Some(Ok(Command::SkipBroadcasts(skip))) => {
+                status::update(&self.status, |status| status.skip_broadcasts = skip);
+                let text = i18n::text(if skip { "broadcasts_skipped" } else { "broadcasts_joined" });
+                let _ = self.output_tx.notify(format!("> {}", text));
+            }
+            Some(Ok(Command::SyncTime)) => self.sync_time(conn).await,
+            Some(Ok(Command::Schedule(when, command))) => {
+                let id = self.scheduler.add(when, command.clone());
+                let _ = self.output_tx.notify(format!("> {}", i18n::format("scheduled", &[&id, &when, &command])));
+            }
+            Some(Ok(Command::Jobs)) => {
+                for line in self.scheduler.lines() {
+                    let _ = self.output_tx.notify(line);
+                }
+            }
+            Some(Ok(Command::Cancel(Some(id)))) => {
+                if self.scheduler.cancel(id) {
+                    let _ = self.output_tx.notify(format!("> {}", i18n::format("cancelled", &[&id])));
+                } else {
+                    self.report(i18n::format("no_job", &[&id]));
+                }
+            }
+            Some(Ok(Command::Cancel(None))) => {
+                let count = self.scheduler.cancel_all();
+                let _ = self.output_tx.notify(format!("> {}", i18n::format("cancelled_all", &[&count])));
+            }
+            Some(_) => (),
+            None => {
+                let commands = chain::split(&text);
+                if status::get(&self.status).broadcast {
+                    let line = if commands.len() > 1 { commands.clone() } else { vec![text.trim_end().replace("\\;", ";")] };
+                    self.broadcast(line);
+                }
+                if commands.len() > 1 {
+                    // Sent one after another by the session, like the on-connect commands
+                    let delay = self.options.chain_delay;
+                    let steps = commands
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, command)| Step { command, delay: if i == 0 { Duration::ZERO } else { delay } })
+                        .collect();
+                    self.chain.extend(steps);
+                } else {
+                    self.send_command(conn, text.replace("\\;", ";")).await;
+                }
+            }
+        }
+        None
+    }
+
+    /// Send the commands of a typed line to the devices of the other running Huhnitors
+    fn broadcast(&mut self, commands: Vec<String>) {
+        if self.broadcaster.is_none() {
+            let (broadcast_tx, mut broadcast_rx) = tokio::sync::mpsc::unbounded_channel::<Vec<String>>();
+            let from = self.options.control_name.clone().unwrap_or_else(|| std::process::id().to_string());
+            let notify_tx = self.output_tx.downgrade();
+            tokio::spawn(async move {
+                while let Some(commands) = broadcast_rx.recv().await {
+                    let (mut sent, mut skipped) = (Vec::new(), Vec::new());
+                    for (session, delivery) in control::broadcast(from.clone(), commands).await {
+                        match delivery {
+                            Delivery::Sent => sent.push(session),
+                            Delivery::Skipped => skipped.push(session),
+                            Delivery::Failed(e) => output::notify(&notify_tx, format!("ERROR: {}", i18n::format("broadcast_failed", &[&session, &e]))),
+                        }
+                    }
+                    let mut text = if sent.is_empty() {
+                        i18n::text("broadcast_unheard").to_string()
+                    } else {
+                        i18n::format("broadcast_sent", &[&sent.join(", ")])
+                    };
+                    if !skipped.is_empty() {
+                        text.push_str(&i18n::format("broadcast_skipped_by", &[&skipped.join(", ")]));
+                    }
+                    output::notify(&notify_tx, format!("> {}", text));
+                }
+            });
+            self.broadcaster = Some(broadcast_tx);
+        }
+        if let Some(broadcaster) = &self.broadcaster {
+            let _ = broadcaster.send(commands);
+        }
+    }
+
+    /// Send a command to the device, after the script had its say
+    async fn send_command(&mut self, conn: &Connection, text: String) {
+        let typed = text.trim_end().to_string();
+        let text = match self.options.script.as_mut().map(|script| script.on_command(text.trim_end())) {
+            None => text,
+            Some(Ok(Verdict::Send(command))) => format!("{}\r\n", command),
+            Some(Ok(Verdict::Drop)) => String::new(),
+            Some(Err(e)) => {
+                self.report(e);
+                text
+            }
+        };
+        if !text.is_empty() {
+            match self.write(conn, &escape::unescape(&text)) {
+                Ok(()) => {
+                    self.sent(&text);
+                    if let Some(setting) = self.profile.setting(text.trim_end()) {
+                        self.applied.record(setting, text.trim_end());
+                    }
+                }
+                Err(e) => {
+                    self.report(i18n::format("send_failed", &[&typed, &e]));
+                    status::update(&self.status, |status| status.failed.push(typed));
+                }
+            }
+        }
+        self.script_actions(conn).await;
+    }
+
+    /// Talk to the device until the user quits (`None`) or switches to another port
+    async fn session(&mut self, tty_path: &str) -> Option<String> {
+        let port = match self.open(tty_path).await {
+            Ok(port) => port,
+            Err(next) => return next,
+        };
+        let (conn, mut events) = Connection::spawn(port, tty_path, self.options.pacing, self.shutdown.child_token());
+
+        // Jobs that came due while disconnected are skipped rather than sent all at once
+        while let Ok(fired) = self.fired_rx.try_recv() {
+            if !self.scheduler.fired(fired.id) {
+                continue;
+            }
+            let _ = self.output_tx.notify(format!("WARNING: {}", i18n::format("skipped_job", &[&fired.id, &fired.command])));
+        }
+
+        let watch = self.options.keep_alive.is_some() || self.options.rx_timeout.is_some() || self.options.idle_timeout.is_some();
+        let mut ticker = tokio::time::interval(LINK_CHECK);
+        let mut last_rx = Instant::now();
+        let mut last_ping = Instant::now();
+        // Keep-alives don't count, commands of the user, scripts and schedules do
+        let mut last_active = Instant::now();
+
+        let next = loop {
+            tokio::select! {
+                // With `Overflow::Block` the port isn't read while the UI is behind, everything else goes on
+                event = events.recv(), if self.output_tx.has_room() => match event {
+                    Some(PortEvent::Connected(path)) => self.connected(&conn, &path).await,
+                    Some(PortEvent::Line(buf)) => {
+                        last_rx = Instant::now();
+                        last_active = last_rx;
+                        if !self.received(&conn, buf).await {
+                            // UI is gone, nothing left to show the output on
+                            break None;
+                        }
+                    }
+                    Some(PortEvent::Partial(buf)) => {
+                        last_rx = Instant::now();
+                        last_active = last_rx;
+                        let line = String::from_utf8_lossy(&buf).trim_end_matches(['\r', '\n']).to_string();
+                        let _ = self.output_tx.send(BackendEvent::Partial(line));
+                    }
+                    Some(PortEvent::Error(e)) => {
+                        self.report(Error::from(e));
+                        break None;
+                    }
+                    Some(PortEvent::WriteFailed(e)) => self.report(i18n::format("write_failed", &[&e])),
+                    Some(PortEvent::Disconnected) | None => break None,
+                },
+
+                _ = self.output_tx.room(), if !self.output_tx.has_room() => (),
+
+                _ = ticker.tick(), if watch => {
+                    if let Some(timeout) = self.options.idle_timeout.filter(|timeout| last_active.elapsed() >= *timeout) {
+                        let _ = self.output_tx.notify(format!("> {}", i18n::format("idle_closing", &[&output::duration(timeout), &tty_path])));
+                        conn.close().await;
+                        self.unlock();
+                        return match self.options.idle_action {
+                            Idle::Disconnect => self.wait_after_idle(tty_path).await,
+                            Idle::Exit => None,
+                        };
+                    }
+                    if self.watch_link(&conn, last_rx, &mut last_ping).await {
+                        let _ = self.output_tx.notify(format!("> {}", i18n::format("reconnecting", &[&tty_path])));
+                        conn.close().await;
+                        self.unlock();
+                        tokio::time::sleep(RECONNECT_DELAY).await;
+                        return Some(tty_path.to_string());
+                    }
+                }
+
+                _ = tokio::time::sleep_until(self.autorun.due()), if !self.autorun.is_empty() => {
+                    if let Some(command) = self.autorun.pop() {
+                        self.autorun(&conn, command).await;
+                    }
+                }
+
+                // The scheduler keeps a sender itself, so this never ends
+                Some(fired) = self.fired_rx.recv() => {
+                    last_active = Instant::now();
+                    self.scheduled(&conn, fired).await;
+                }
+
+                _ = tokio::time::sleep_until(self.chain.due()), if !self.chain.is_empty() => {
+                    if let Some(command) = self.chain.pop() {
+                        self.send_command(&conn, format!("{}\r\n", command)).await;
+                    }
+                }
+
+                // The monitor keeps a sender itself, so this never ends
+                Some(bytes) = self.remote_rx.recv() => {
+                    last_active = Instant::now();
+                    match self.write(&conn, &bytes) {
+                        // Keystrokes of a mirror come a few at a time, only whole lines are commands
+                        Ok(()) if bytes.ends_with(b"\n") || bytes.ends_with(b"\r") => {
+                            for line in String::from_utf8_lossy(&bytes).lines().filter(|line| !line.trim().is_empty()) {
+                                self.sent(line);
+                            }
+                        }
+                        Ok(()) => (),
+                        Err(e) => self.report(i18n::format("remote_failed", &[&e])),
+                    }
+                }
+
+                // The monitor keeps a sender itself, so this never ends
+                Some(injected) = self.control_rx.recv() => {
+                    last_active = Instant::now();
+                    self.inject(&conn, injected);
+                }
+
+                event = self.input_rx.recv() => {
+                    last_active = Instant::now();
+                    match event {
+                        None | Some(UiEvent::Quit) => break None,
+                        Some(UiEvent::Interrupt) => {
+                            self.chain.clear();
+                            conn.discard();
+                            let stop = format!("{}\r\n", self.profile.stop_command());
+                            if let Err(e) = self.write(&conn, stop.as_bytes()) {
+                                self.report(i18n::format("stop_error", &[&e]));
+                            }
+                        }
+                        Some(UiEvent::SendLine(text)) => {
+                            if let Some(next) = self.typed(&conn, tty_path, text).await {
+                                break next;
+                            }
+                        }
+                        Some(UiEvent::SendBytes(bytes)) => {
+                            if let Err(e) = self.write(&conn, &bytes) {
+                                self.report(i18n::format("keystroke_failed", &[&e]));
+                            }
+                        }
+                    }
+                }
+
+                _ = self.shutdown.cancelled() => break None,
+            }
+        };
+
+        conn.close().await;
+        self.unlock();
+        next
+    }
+}
+
+/// What the UI holds of a session: its events go to the serial side, which answers on `output_rx`
+pub struct Frontend {
+    pub input_tx: UnboundedSender<UiEvent>,
+    pub output_rx: channel::Receiver,
+    pub status: SharedStatus,
+    /// Ends the session, e.g. because the UI failed
+    pub shutdown: CancellationToken,
+}
+
+/// What the serial side holds of a session
+pub struct Backend {
+    input_rx: UnboundedReceiver<UiEvent>,
+    output_tx: channel::Sender,
+    status: SharedStatus,
+    shutdown: CancellationToken,
+}
+
+/// Channels and status between the UI and the serial side of a session
+pub fn connect(options: &Options) -> (Frontend, Backend) {
+    let (input_tx, input_rx) = tokio::sync::mpsc::unbounded_channel();
+    let (output_tx, output_rx) = channel::bounded(options.output_buffer, options.overflow);
+
+    let status = SharedStatus::default();
+    status::update(&status, |status| {
+        status.link = options.link;
+        status.dry_run = options.dry_run;
+    });
+
+    let shutdown = CancellationToken::new();
+    let frontend = Frontend { input_tx, output_rx, status: status.clone(), shutdown: shutdown.clone() };
+    (frontend, Backend { input_rx, output_tx, status, shutdown })
+}
+
+/// Run the TUI and the serial connection until the user quits
+pub async fn run(cmd_port: Option<String>, options: Options, log: Option<Log>, stats: Arc<Stats>, mut app: App) {
+    let (Frontend { input_tx, output_rx, status, shutdown }, backend) = connect(&options);
+    app.set_status(status);
+
+    // The TUI owns the terminal for the whole session, port selection included
+    let ui = tokio::spawn(async move {
+        if let Err(e) = app.run(input_tx, output_rx).await {
+            app::restore_terminal();
+            error!(e);
+            // Make sure the monitor loop ends together with the UI, closing the port properly
+            shutdown.cancel();
+        }
+    });
+
+    serve(cmd_port, options, log, stats, backend).await;
+    let _ = ui.await;
+}
+
+/// Talk to the device until the UI quits or the session is shut down.
+/// Returning closes the output channel, which ends the UI.
+pub async fn serve(cmd_port: Option<String>, mut options: Options, log: Option<Log>, stats: Arc<Stats>, backend: Backend) {
+    let Backend { input_rx, output_tx, status, shutdown } = backend;
+    let (remote_tx, remote_rx) = tokio::sync::mpsc::unbounded_channel();
+    let (control_tx, control_rx) = tokio::sync::mpsc::unbounded_channel();
+
+    let crash = Detector::new(options.symbols.take());
+    let (scheduler, fired_rx) = Scheduler::new();
+    let chain = autorun::Queue::waiting_for_prompt(options.chain_prompt.is_some());
+    let mut monitor = Monitor {
+        input_rx,
+        output_tx,
+        options,
+        log,
+        logged_session: None,
+        stats,
+        status,
+        bridge: None,
+        #[cfg(unix)]
+        pty: None,
+        #[cfg(unix)]
+        lock: None,
+        publisher: None,
+        http: None,
+        watch: None,
+        control: None,
+        broadcaster: None,
+        pcap: None,
+        marks: Marks::default(),
+        remote_tx,
+        remote_rx,
+        control_tx,
+        control_rx,
+        profile: firmware::generic(),
+        probing: 0,
+        sync_pending: false,
+        crash,
+        shutdown,
+        autorun: autorun::Queue::default(),
+        applied: autorun::Applied::default(),
+        chain,
+        scheduler,
+        fired_rx,
+    };
+    // A log of the command line or config file gets its metadata like one started with `huhn log`
+    if let Some(path) = monitor.log.as_ref().map(|log| log.path().to_path_buf()) {
+        let name = monitor.options.session_name.take();
+        monitor.start_logged_session(&path, name);
+    }
+
+    if let Some(addr) = monitor.options.serve {
+        let input_tx = if monitor.options.serve_input { Some(monitor.remote_tx.clone()) } else { None };
+        if input_tx.is_none() || monitor.remote_allowed(addr, "--serve-input") {
+            match Bridge::bind(addr, input_tx, &monitor.output_tx).await {
+                Ok(bridge) => monitor.bridge = Some(bridge),
+                Err(e) => monitor.report(i18n::format("serve_failed", &[&addr, &e])),
+            }
+        }
+    }
+
+    if let Some(path) = monitor.options.pty.clone() {
+        #[cfg(unix)]
+        match Pty::open(&path, monitor.remote_tx.clone()) {
+            Ok(pty) => {
+                let _ = monitor.output_tx.notify(format!("> {}", i18n::format("pty_started", &[&pty.link().display()])));
+                monitor.pty = Some(pty);
+            }
+            Err(e) => monitor.report(i18n::format("pty_failed", &[&path.display(), &e])),
+        }
+        #[cfg(not(unix))]
+        monitor.report(i18n::format("pty_unsupported", &[&path.display()]));
+    }
+
+    if let Some(settings) = monitor.options.mqtt.clone() {
+        monitor.publisher = Some(Publisher::start(settings, &monitor.output_tx));
+    }
+
+    if let Some(addr) = monitor.options.http.filter(|addr| monitor.remote_allowed(*addr, "--http")) {
+        let server = http::Server::bind(
+            addr,
+            monitor.status.clone(),
+            monitor.stats.clone(),
+            monitor.remote_tx.clone(),
+            &monitor.output_tx,
+        )
+        .await;
+        match server {
+            Ok(server) => monitor.http = Some(server),
+            Err(e) => monitor.report(i18n::format("http_failed", &[&addr, &e])),
+        }
+    }
+
+    if let Some(addr) = monitor.options.watch {
+        // Rules that don't parse would let everything through, so the page isn't served at all
+        match monitor.options.redactor.clone() {
+            Ok(redactor) => match Watch::bind(addr, &monitor.output_tx, redactor).await {
+                Ok(watch) => monitor.watch = Some(watch),
+                Err(e) => monitor.report(i18n::format("watch_failed", &[&addr, &e])),
+            },
+            Err(e) => monitor.report(i18n::format("watch_refused", &[&addr, &e])),
+        }
+    }
+
+    if monitor.options.control {
+        let name = monitor.options.control_name.clone();
+        match control::Server::bind(name, monitor.status.clone(), monitor.control_tx.clone(), &monitor.output_tx) {
+            Ok(server) => monitor.control = Some(server),
+            Err(e) => monitor.report(i18n::format("control_failed", &[&e])),
+        }
+    }
+
+    let mut tty_path = match cmd_port {
+        Some(port) => Some(port),
+        None => monitor.wait_for_port().await,
+    };
+
+    while let Some(path) = tty_path {
+        tty_path = monitor.session(&path).await;
+        status::update(&monitor.status, |status| {
+            status.port = None;
+            status.firmware = None;
+            status.stale = false;
+        });
+    }
+}
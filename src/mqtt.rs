@@ -0,0 +1,103 @@
+use rumqttc::{AsyncClient, MqttOptions, QoS};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use crate::channel;
+use crate::events::Event;
+use crate::output;
+
+/// `[mqtt]` section of the config file
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Settings {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    /// Topic for scanned access points and stations
+    pub scan_topic: String,
+    /// Topic for attack status changes
+    pub attack_topic: String,
+    /// Topic for errors and warnings of the device
+    pub error_topic: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "huhnitor".to_string(),
+            scan_topic: "huhnitor/scan".to_string(),
+            attack_topic: "huhnitor/attack".to_string(),
+            error_topic: "huhnitor/error".to_string(),
+        }
+    }
+}
+
+impl Settings {
+    fn topic(&self, event: &Event) -> &str {
+        match event {
+            Event::ScanResult { .. } => &self.scan_topic,
+            Event::Attack { .. } => &self.attack_topic,
+            Event::Error { .. } => &self.error_topic,
+        }
+    }
+}
+
+/// Publishes parsed events of the session as JSON
+pub struct Publisher {
+    events_tx: UnboundedSender<Event>,
+}
+
+impl Publisher {
+    /// Connect in the background, connection problems are announced on `notify_tx`
+    pub fn start(settings: Settings, notify_tx: &channel::Sender) -> Self {
+        let mut options = MqttOptions::new(settings.client_id.clone(), settings.host.clone(), settings.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        let (client, mut eventloop) = AsyncClient::new(options, 64);
+
+        let notify_tx = notify_tx.downgrade();
+        let broker = format!("{}:{}", settings.host, settings.port);
+        tokio::spawn(async move {
+            // Only announce changes, not every reconnect attempt
+            let mut connected = None;
+            loop {
+                match eventloop.poll().await {
+                    Ok(_) => {
+                        if connected != Some(true) {
+                            output::notify(&notify_tx, format!("> Publishing events to MQTT broker {}", broker));
+                        }
+                        connected = Some(true);
+                    }
+                    Err(e) => {
+                        if connected != Some(false) {
+                            output::notify(&notify_tx, format!("ERROR: Can't reach MQTT broker {}: {}", broker, e));
+                        }
+                        connected = Some(false);
+                        // rumqttc reconnects on the next poll
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        });
+
+        let (events_tx, mut events_rx) = mpsc::unbounded_channel::<Event>();
+        tokio::spawn(async move {
+            while let Some(event) = events_rx.recv().await {
+                let payload = match serde_json::to_vec(&event) {
+                    Ok(payload) => payload,
+                    Err(_) => continue,
+                };
+                // Events are dropped while the broker is unreachable
+                let _ = client.try_publish(settings.topic(&event), QoS::AtMostOnce, false, payload);
+            }
+        });
+
+        Self { events_tx }
+    }
+
+    pub fn publish(&self, event: Event) {
+        let _ = self.events_tx.send(event);
+    }
+}
@@ -1,7 +1,15 @@
-use regex::RegexSet;
+use ratatui::style::{Color as TuiColor, Modifier, Style};
 use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
+use crate::channel::WeakSender;
+use crate::driver;
+use crate::i18n;
+use crate::stats::Stats;
+use crate::theme::Theme;
+
 #[macro_export]
 macro_rules! error {
     ($expression:expr) => {
@@ -9,75 +17,127 @@ macro_rules! error {
     };
 }
 
-// Statically compile regex to avoid repetetive compiling
-// Rust Regex can be tested here: https://rustexp.lpil.uk/
-lazy_static::lazy_static! {
-    static ref REGSET: RegexSet = RegexSet::new([
-        r"^(\x60|\.|:|/|-|\+|o|s|h|d|y| ){50,}",      // ASCII Chicken
-        r"^# ",                                       // # command
-        r"(?m)^\s*(-|=|#)+\s*$",                      // ================
-        r"^\[ =+ ?.* ?=+ \]",                         // [ ===== Headline ====== ]
-        r"^> \w+",                                    // > Finished job
-        r"^(ERROR)|(WARNING): ",                      // ERROR: something went wrong :(
-        r"^.*: +.*",                                  // -arg: value
-        r"^\[.*\]",                                   // [default=something]
-        r"(?m)^\S+( \[?-\S*( <\S*>)?\]?)*\s*$",       // command [-arg <value>] [-flag]
-    ]).unwrap();
-
-    static ref COLORSET: Vec<(Color, bool)> = vec![
-        (Color::White, false),  // # command
-        (Color::White, true),   // # command
-        (Color::Blue, false),   // ================
-        (Color::Yellow, true),  // [ ===== Headline ====== ]
-        (Color::Cyan, false),   // > Finished job
-        (Color::Red, false),    // ERROR: something went wrong :(
-        (Color::Green, false),  // -arg value
-        (Color::Green, true),   // [default=something]
-        (Color::Yellow, false), // command [-arg <value>] [-flag]
-    ];
+/// Translate a TUI style into a terminal color spec
+fn color_spec(style: Style) -> ColorSpec {
+    fn color(color: Option<TuiColor>) -> (Option<Color>, bool) {
+        match color {
+            Some(TuiColor::Black) => (Some(Color::Black), false),
+            Some(TuiColor::Red) => (Some(Color::Red), false),
+            Some(TuiColor::Green) => (Some(Color::Green), false),
+            Some(TuiColor::Yellow) => (Some(Color::Yellow), false),
+            Some(TuiColor::Blue) => (Some(Color::Blue), false),
+            Some(TuiColor::Magenta) => (Some(Color::Magenta), false),
+            Some(TuiColor::Cyan) => (Some(Color::Cyan), false),
+            Some(TuiColor::Gray) => (Some(Color::White), false),
+            Some(TuiColor::DarkGray) => (Some(Color::Black), true),
+            Some(TuiColor::LightRed) => (Some(Color::Red), true),
+            Some(TuiColor::LightGreen) => (Some(Color::Green), true),
+            Some(TuiColor::LightYellow) => (Some(Color::Yellow), true),
+            Some(TuiColor::LightBlue) => (Some(Color::Blue), true),
+            Some(TuiColor::LightMagenta) => (Some(Color::Magenta), true),
+            Some(TuiColor::LightCyan) => (Some(Color::Cyan), true),
+            Some(TuiColor::White) => (Some(Color::White), true),
+            Some(TuiColor::Rgb(r, g, b)) => (Some(Color::Rgb(r, g, b)), false),
+            Some(TuiColor::Indexed(i)) => (Some(Color::Ansi256(i)), false),
+            Some(TuiColor::Reset) | None => (None, false),
+        }
+    }
+
+    let (fg, intense) = color(style.fg);
+    let mut spec = ColorSpec::new();
+    spec.set_fg(fg)
+        .set_intense(intense)
+        .set_bold(style.add_modifier.contains(Modifier::BOLD))
+        .set_italic(style.add_modifier.contains(Modifier::ITALIC))
+        .set_underline(style.add_modifier.contains(Modifier::UNDERLINED))
+        .set_dimmed(style.add_modifier.contains(Modifier::DIM));
+    spec
 }
 
-fn parse(s: &str) {
-    let matches: Vec<_> = REGSET.matches(s).into_iter().collect();
+fn print_color(input: &str, style: Style) -> io::Result<()> {
+    let mut stdout = StandardStream::stdout(ColorChoice::Always);
 
-    let (color, bold) = if !matches.is_empty() {
-        COLORSET[matches[0]]
-    } else {
-        (Color::White, false)
-    };
+    stdout.set_color(&color_spec(style))?;
 
-    fn print_color(input: &str, color: Color, bold: bool) -> io::Result<()> {
-        let mut stdout = StandardStream::stdout(ColorChoice::Always);
+    write!(&mut stdout, "{}", input)?;
+    stdout.reset()
+}
 
-        stdout.set_color(
-            ColorSpec::new()
-                .set_fg(Some(color))
-                //.set_bg(Some(Color::Black))
-                .set_bold(bold),
-        )?;
+/// Format a duration like "1h 02m 03s"
+pub fn duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {:02}s", m, s),
+        (h, m, s) => format!("{}h {:02}m {:02}s", h, m, s),
+    }
+}
 
-        write!(&mut stdout, "{}", input)?;
-        stdout.reset()
+/// Format a byte count like "1.5 KiB"
+pub fn bytes(n: u64) -> String {
+    match n {
+        0..=1023 => format!("{} B", n),
+        1024..=1048575 => format!("{:.1} KiB", n as f64 / 1024.0),
+        _ => format!("{:.1} MiB", n as f64 / 1048576.0),
     }
+}
+
+pub fn divider() -> String {
+    format!("[ {:=^76} ]", '=')
+}
+
+pub fn hint() -> Vec<String> {
+    vec![
+        format!("> {}", i18n::text("no_port")),
+        i18n::text("no_port_hint").to_string(),
+        "https://github.com/SpacehuhnTech/Huhnitor#drivers".to_string(),
+    ]
+}
 
-    if let Err(e) = print_color(s, color, bold) {
-        error!(e);
+/// Show a line in the Messages box from a background task, unless the UI is already gone
+pub fn notify(output_tx: &WeakSender, text: String) {
+    if let Some(output_tx) = output_tx.upgrade() {
+        let _ = output_tx.notify(text);
     }
 }
 
-pub fn clear() {
-    print!("{esc}[2J{esc}[1;1H", esc = 27 as char);
-    println!(" ");
+/// The chicken and the version headline
+pub fn banner() -> Vec<String> {
+    let c_bytes = include_bytes!("visual/chicken.txt");
+    let mut lines: Vec<String> = String::from_utf8_lossy(c_bytes).lines().map(str::to_string).collect();
+    lines.push(version());
+    lines
+}
+
+/// Banner without the drawing, for screen readers
+pub fn plain_banner() -> Vec<String> {
+    vec![i18n::format("version", &[&env!("CARGO_PKG_VERSION")])]
+}
+
+fn version() -> String {
+    let version = format!(" {} ", i18n::format("version", &[&env!("CARGO_PKG_VERSION")]));
+    format!("[ {:=^76} ]", version)
+}
+
+pub fn connected(port: &str) -> Vec<String> {
+    let port = match crate::port::friendly_name_of(port) {
+        Some(name) => format!("{} ({})", port, name),
+        None => port.to_string(),
+    };
+    vec![i18n::format("connected", &[&port]), divider()]
 }
 
 pub struct Preferences {
     pub color_enabled: bool,
+    pub theme: Theme,
 }
 
 impl Preferences {
     pub fn print(&self, s: &str) {
         if self.color_enabled {
-            parse(s);
+            if let Err(e) = print_color(s, self.theme.style(s)) {
+                error!(e);
+            }
         } else {
             print!("{}", s);
         }
@@ -88,60 +148,62 @@ impl Preferences {
         println!();
     }
 
-    pub fn logo(&self) {
-        let c_bytes = include_bytes!("visual/chicken.txt");
-        let logo_str = String::from_utf8_lossy(c_bytes).to_string();
-        println!("{}", logo_str);
-    }
-
-    pub fn version(&self) {
-        let version = format!(" Huhnitor Version {} ", env!("CARGO_PKG_VERSION"));
-        let headline = format!("[ {:=^76} ]", version);
-        self.println(&headline);
-    }
-
-    pub fn divider(&self) {
-        let divider = format!("[ {:=^76} ]", '=');
-        self.println(&divider);
+    /// Print the banner outside of the TUI
+    pub fn banner(&self) {
+        for line in banner() {
+            self.println(&line);
+        }
     }
 
-    pub fn ports(&self, ports: &[serialport::SerialPortInfo]) {
-        if ports.is_empty() {
-            self.hint();
-        } else {
-            self.println("Available serial ports:");
-            for (id, port) in ports.iter().enumerate() {
-                let port = format!("[{}] {}", id, port.port_name);
-                self.println(&port);
+    /// Open the driver download of the attached adapter, or list all of them if none is found
+    pub fn driver(&self) {
+        let chips = driver::detect();
+        if chips.is_empty() {
+            self.println(&format!("> {}", i18n::text("no_adapter")));
+            self.println(i18n::text("drivers"));
+            for chip in driver::CHIPS.iter() {
+                match chip.driver_url() {
+                    Some(url) => self.println(&format!("{}: {}", chip.name(), url)),
+                    None => self.println(&i18n::format("driver_builtin", &[&chip.name(), &std::env::consts::OS])),
+                }
             }
+            self.println(&i18n::format("more_help", &[&"https://github.com/spacehuhntech/huhnitor#drivers"]));
+            return;
         }
-    }
 
-    pub fn hint(&self) {
-        self.println("> No serial port found");
-        self.println("Make sure the USB connection works and necessary drivers are installed:");
-        self.println("https://github.com/SpacehuhnTech/Huhnitor#drivers");
-    }
-
-    pub fn connected(&self, port: &str) {
-        let msg = format!("Connected to {} \\o/", port);
-
-        self.println(&msg);
-        self.divider();
+        for chip in chips {
+            let url = match chip.driver_url() {
+                Some(url) => url,
+                None => {
+                    self.println(&format!("> {}", i18n::format("adapter_builtin", &[&chip.name(), &std::env::consts::OS])));
+                    continue;
+                }
+            };
+
+            self.print(&format!("> {}", i18n::format("adapter_opening", &[&chip.name(), &url])));
+            if webbrowser::open(url).is_err() {
+                self.println(i18n::text("no_browser"));
+            } else {
+                self.println("OK")
+            }
+        }
     }
 
-    pub fn driver(&self) {
-        self.print("Opening \"https://github.com/spacehuhntech/huhnitor#drivers\"...");
-
-        if webbrowser::open("https://github.com/spacehuhntech/huhnitor#drivers").is_err() {
-            self.println("Couldn't open URL :(");
-        } else {
-            self.println("OK")
+    pub fn summary(&self, stats: &Stats, log: Option<&Path>) {
+        let headline = format!("[ {:=^76} ]", format!(" {} ", i18n::text("summary")));
+        self.println(&headline);
+        self.println(&i18n::format("duration", &[&duration(stats.elapsed())]));
+        self.println(&i18n::format("received", &[&bytes(stats.rx_bytes())]));
+        self.println(&i18n::format("sent", &[&bytes(stats.tx_bytes())]));
+        self.println(&i18n::format("errors", &[&stats.errors()]));
+        if let Some(log) = log {
+            self.println(&i18n::format("log_file", &[&log.display()]));
         }
+        self.goodbye();
     }
 
     pub fn goodbye(&self) {
-        let bye = format!("[ {:=^76} ]", " Thanks for using Huhnitor ");
+        let bye = format!("[ {:=^76} ]", format!(" {} ", i18n::text("goodbye")));
         self.println(&bye);
     }
 }
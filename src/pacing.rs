@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+/// Pauses per second when only a byte rate is given, larger chunks keep the number of writes down
+const CHUNKS_PER_SEC: u32 = 100;
+
+/// Limits on how fast data is sent, some firmware drops input when a paste arrives faster than its UART buffer drains
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Pacing {
+    /// Send at most this many bytes per second
+    pub bytes_per_sec: Option<u32>,
+    /// Pause after each line so at most this many lines are sent per second
+    pub lines_per_sec: Option<u32>,
+    /// Pause after every character
+    pub char_delay: Option<Duration>,
+}
+
+impl Pacing {
+    pub fn is_off(&self) -> bool {
+        self.bytes_per_sec.is_none() && self.lines_per_sec.is_none() && self.char_delay.is_none()
+    }
+
+    /// Split data into chunks, each with the pause to make after writing it
+    pub fn chunks<'b>(&self, bytes: &'b [u8]) -> Vec<(&'b [u8], Duration)> {
+        let per_byte = |bps: u32| Duration::from_secs_f64(1.0 / bps as f64);
+        let (size, pause) = match (self.char_delay, self.bytes_per_sec.filter(|bps| *bps > 0)) {
+            (Some(delay), bps) => (1, bps.map_or(delay, |bps| delay.max(per_byte(bps)))),
+            (None, Some(bps)) => {
+                let size = (bps / CHUNKS_PER_SEC).max(1);
+                (size as usize, per_byte(bps) * size)
+            }
+            (None, None) => (bytes.len().max(1), Duration::ZERO),
+        };
+        let line_pause = self
+            .lines_per_sec
+            .filter(|lps| *lps > 0)
+            .map_or(Duration::ZERO, |lps| Duration::from_secs_f64(1.0 / lps as f64));
+
+        let mut chunks = Vec::new();
+        for line in bytes.split_inclusive(|b| *b == b'\n') {
+            chunks.extend(line.chunks(size).map(|chunk| (chunk, pause)));
+            if let Some(last) = chunks.last_mut().filter(|_| line.ends_with(b"\n")) {
+                last.1 = last.1.max(line_pause);
+            }
+        }
+        chunks
+    }
+}
@@ -0,0 +1,39 @@
+use regex::Regex;
+
+use crate::chain;
+use crate::i18n;
+
+/// Pastes longer than this many bytes are confirmed first, unless the config sets another limit
+pub const LIMIT: usize = 1024;
+
+lazy_static::lazy_static! {
+    // Color codes, cursor movement and terminal titles, e.g. from text copied out of another terminal
+    static ref ESCAPE: Regex = Regex::new(r"\x1b(?:\[[0-?]*[ -/]*[@-~]|\][^\x07\x1b]*(?:\x07|\x1b\\)|.)").unwrap();
+}
+
+/// Text on the system clipboard
+pub fn clipboard() -> Result<String, String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| i18n::format("clipboard_failed", &[&e]))?;
+    clipboard.get_text().map_err(|e| match e {
+        arboard::Error::ContentNotAvailable => i18n::text("clipboard_no_text").to_string(),
+        e => i18n::format("clipboard_unreadable", &[&e]),
+    })
+}
+
+/// Make pasted text fit into the input line. Escape sequences and control characters are dropped,
+/// tabs become spaces and lines are joined into commands separated by `;`.
+pub fn sanitize(text: &str) -> String {
+    let text = ESCAPE.replace_all(text, "");
+    text.lines()
+        .map(|line| {
+            line.chars()
+                .map(|c| if c == '\t' { ' ' } else { c })
+                .filter(|c| !c.is_control())
+                .collect::<String>()
+                .replace(chain::SEPARATOR, "\\;")
+        })
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(&format!("{} ", chain::SEPARATOR))
+}
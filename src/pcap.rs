@@ -0,0 +1,276 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::redact::Redactor;
+use crate::sniffer::{self, Frame, Kind, BROADCAST};
+
+/// LINKTYPE_IEEE802_11_RADIOTAP
+const LINKTYPE: u32 = 127;
+
+/// Radiotap fields present: channel and antenna signal
+const RADIOTAP_CHANNEL: u32 = 1 << 3;
+const RADIOTAP_SIGNAL: u32 = 1 << 5;
+
+/// Writes reconstructed frames in the classic pcap format Wireshark reads
+pub struct Writer {
+    path: PathBuf,
+    file: BufWriter<File>,
+    frames: u64,
+    /// Captures are shared like exports, so the redaction rules apply to their addresses and SSIDs
+    redactor: Redactor,
+}
+
+impl Writer {
+    pub fn create(path: &Path, redactor: Redactor) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+
+        file.write_all(&0xa1b2_c3d4u32.to_le_bytes())?; // Magic, microsecond timestamps
+        file.write_all(&2u16.to_le_bytes())?; // Version 2.4
+        file.write_all(&4u16.to_le_bytes())?;
+        file.write_all(&0i32.to_le_bytes())?; // UTC
+        file.write_all(&0u32.to_le_bytes())?; // Timestamp accuracy
+        file.write_all(&65535u32.to_le_bytes())?; // Snapshot length
+        file.write_all(&LINKTYPE.to_le_bytes())?;
+        file.flush()?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            frames: 0,
+            redactor,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Append a frame, timestamped now since the summaries don't carry a time
+    pub fn write(&mut self, frame: &Frame) -> io::Result<()> {
+        let frame = redacted(frame, &self.redactor);
+        let mut packet = radiotap(&frame);
+        packet.extend(ieee80211(&frame));
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        self.file.write_all(&(now.as_secs() as u32).to_le_bytes())?;
+        self.file.write_all(&now.subsec_micros().to_le_bytes())?;
+        self.file.write_all(&(packet.len() as u32).to_le_bytes())?;
+        self.file.write_all(&(packet.len() as u32).to_le_bytes())?;
+        self.file.write_all(&packet)?;
+        // Keep the file readable while capturing
+        self.file.flush()?;
+
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+/// The frame with the MACs and SSID as the redaction rules would print them, masked bytes become 0
+fn redacted(frame: &Frame, redactor: &Redactor) -> Frame {
+    if redactor.is_empty() {
+        return frame.clone();
+    }
+    let mac = |mac: [u8; 6]| {
+        if mac == BROADCAST {
+            return mac;
+        }
+        let text = mac.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(":");
+        sniffer::mac(&redactor.line(&text))
+    };
+    Frame {
+        source: mac(frame.source),
+        destination: mac(frame.destination),
+        bssid: mac(frame.bssid),
+        ssid: frame.ssid.as_deref().map(|ssid| redactor.line(ssid)),
+        ..frame.clone()
+    }
+}
+
+/// Radiotap header with channel and signal strength, if known
+fn radiotap(frame: &Frame) -> Vec<u8> {
+    let mut present = 0;
+    let mut fields = Vec::new();
+
+    if let Some(channel) = frame.channel {
+        present |= RADIOTAP_CHANNEL;
+        let mhz: u16 = if channel == 14 { 2484 } else { 2407 + 5 * channel as u16 };
+        fields.extend_from_slice(&mhz.to_le_bytes());
+        fields.extend_from_slice(&0x0080u16.to_le_bytes()); // 2 GHz
+    }
+    if let Some(rssi) = frame.rssi {
+        present |= RADIOTAP_SIGNAL;
+        fields.push(rssi as u8);
+    }
+
+    let mut header = vec![0, 0]; // Version, padding
+    header.extend_from_slice(&(8 + fields.len() as u16).to_le_bytes());
+    header.extend_from_slice(&present.to_le_bytes());
+    header.extend(fields);
+    header
+}
+
+/// Management frame without FCS
+fn ieee80211(frame: &Frame) -> Vec<u8> {
+    let subtype = match frame.kind {
+        Kind::ProbeRequest => 0x40,
+        Kind::Beacon => 0x80,
+        Kind::Disassoc => 0xa0,
+        Kind::Deauth => 0xc0,
+    };
+
+    let destination = match frame.kind {
+        Kind::Beacon | Kind::ProbeRequest => BROADCAST,
+        _ => frame.destination,
+    };
+
+    let mut packet = vec![subtype, 0x00, 0x00, 0x00]; // Frame control, duration
+    packet.extend_from_slice(&destination);
+    packet.extend_from_slice(&frame.source);
+    packet.extend_from_slice(&frame.bssid);
+    packet.extend_from_slice(&[0x00, 0x00]); // Sequence control
+
+    match frame.kind {
+        Kind::Deauth | Kind::Disassoc => packet.extend_from_slice(&frame.reason.to_le_bytes()),
+        Kind::Beacon => {
+            packet.extend_from_slice(&[0; 8]); // Timestamp
+            packet.extend_from_slice(&100u16.to_le_bytes()); // Beacon interval
+            packet.extend_from_slice(&0x0001u16.to_le_bytes()); // Capabilities: ESS
+            ssid_element(&mut packet, frame.ssid.as_deref().unwrap_or_default());
+        }
+        Kind::ProbeRequest => ssid_element(&mut packet, frame.ssid.as_deref().unwrap_or_default()),
+    }
+
+    packet
+}
+
+fn ssid_element(packet: &mut Vec<u8>, ssid: &str) {
+    let ssid = &ssid.as_bytes()[..ssid.len().min(32)];
+    packet.push(0);
+    packet.push(ssid.len() as u8);
+    packet.extend_from_slice(ssid);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redact::Spec;
+
+    fn deauth() -> Frame {
+        Frame {
+            kind: Kind::Deauth,
+            source: [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff],
+            destination: [0x11, 0x22, 0x33, 0x44, 0x55, 0x66],
+            bssid: [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff],
+            channel: Some(6),
+            rssi: Some(-56),
+            reason: 7,
+            ssid: None,
+        }
+    }
+
+    #[test]
+    fn writes_the_headers() {
+        let path = std::env::temp_dir().join(format!("huhnitor-pcap-test-{}.pcap", std::process::id()));
+        let mut writer = Writer::create(&path, Redactor::default()).unwrap();
+        writer.write(&deauth()).unwrap();
+        assert_eq!(writer.frames(), 1);
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            bytes[..24],
+            [
+                0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0, 0, 127, 0, 0, 0
+            ]
+        );
+
+        // Record header: timestamp, then captured and original length of the 13 + 26 byte packet
+        let record = &bytes[24..40];
+        assert_eq!(record[8..12], 39u32.to_le_bytes());
+        assert_eq!(record[12..16], 39u32.to_le_bytes());
+        assert_eq!(bytes.len(), 40 + 39);
+        assert_eq!(bytes[40..53], radiotap(&deauth())[..]);
+    }
+
+    #[test]
+    fn builds_radiotap_headers() {
+        // Channel 6 is 2437 MHz, signal -56 dBm
+        assert_eq!(
+            radiotap(&deauth()),
+            [0, 0, 13, 0, 0x28, 0, 0, 0, 0x85, 0x09, 0x80, 0x00, 0xc8]
+        );
+
+        let unknown = Frame {
+            channel: None,
+            rssi: None,
+            ..deauth()
+        };
+        assert_eq!(radiotap(&unknown), [0, 0, 8, 0, 0, 0, 0, 0]);
+
+        let japan = Frame {
+            channel: Some(14),
+            rssi: None,
+            ..deauth()
+        };
+        assert_eq!(radiotap(&japan)[8..10], 2484u16.to_le_bytes());
+    }
+
+    #[test]
+    fn builds_management_frames() {
+        assert_eq!(
+            ieee80211(&deauth()),
+            [
+                0xc0, 0, 0, 0, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff, 0xaa, 0xbb, 0xcc,
+                0xdd, 0xee, 0xff, 0, 0, 7, 0
+            ]
+        );
+
+        let beacon = Frame {
+            kind: Kind::Beacon,
+            ssid: Some("Wifi".to_string()),
+            ..deauth()
+        };
+        let packet = ieee80211(&beacon);
+        assert_eq!(packet[0], 0x80);
+        assert_eq!(packet[4..10], BROADCAST);
+        assert_eq!(packet[24..], [0, 0, 0, 0, 0, 0, 0, 0, 100, 0, 1, 0, 0, 4, b'W', b'i', b'f', b'i']);
+
+        let probe = Frame {
+            kind: Kind::ProbeRequest,
+            ssid: Some("x".repeat(40)),
+            ..deauth()
+        };
+        let packet = ieee80211(&probe);
+        assert_eq!(packet[0], 0x40);
+        assert_eq!(packet[24..26], [0, 32]);
+        assert_eq!(packet.len(), 24 + 2 + 32);
+    }
+
+    #[test]
+    fn redacts_addresses_and_ssids() {
+        let specs = [
+            Spec::Mac { bytes: 3 },
+            Spec::Ssid { names: vec!["Home".to_string()], with: "<ssid>".to_string() },
+        ];
+        let redactor = Redactor::from_specs(&specs).unwrap();
+        let beacon = Frame {
+            kind: Kind::Beacon,
+            destination: BROADCAST,
+            ssid: Some("Home".to_string()),
+            ..deauth()
+        };
+
+        let frame = redacted(&beacon, &redactor);
+        assert_eq!(frame.source, [0xaa, 0xbb, 0xcc, 0, 0, 0]);
+        assert_eq!(frame.bssid, [0xaa, 0xbb, 0xcc, 0, 0, 0]);
+        assert_eq!(frame.destination, BROADCAST);
+        assert_eq!(frame.ssid.as_deref(), Some("<ssid>"));
+        assert_eq!(redacted(&beacon, &Redactor::default()), beacon);
+    }
+}
@@ -0,0 +1,138 @@
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::driver::{self, Chip, CHIPS};
+use crate::output::Preferences;
+
+/// Where the rule is installed
+const RULE_PATH: &str = "/etc/udev/rules.d/99-huhnitor.rules";
+
+/// udev rule giving the logged in user and the serial group access to the adapters
+fn rule(adapters: &[(u16, u16)]) -> String {
+    let mut rule = String::from("# Serial adapters of ESP8266 and ESP32 boards, generated by huhnitor setup-permissions\n");
+    for (vid, pid) in adapters {
+        let name = Chip::from_vid(*vid).map_or("Unknown", |chip| chip.name());
+        rule.push_str(&format!(
+            "# {}\nSUBSYSTEM==\"tty\", ATTRS{{idVendor}}==\"{:04x}\", ATTRS{{idProduct}}==\"{:04x}\", MODE=\"0660\", TAG+=\"uaccess\"\n",
+            name, vid, pid
+        ));
+    }
+    rule
+}
+
+/// Group owning serial ports on this distribution, `dialout` on most, `uucp` on Arch
+fn serial_group() -> Option<&'static str> {
+    let groups = fs::read_to_string("/etc/group").ok()?;
+    ["dialout", "uucp"]
+        .iter()
+        .copied()
+        .find(|group| groups.lines().any(|line| line.starts_with(&format!("{}:", group))))
+}
+
+/// Groups of the current user
+fn user_groups() -> Vec<String> {
+    Command::new("id")
+        .arg("-Gn")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Run a command with sudo, printing it first
+fn sudo(out: &Preferences, args: &[&str]) -> bool {
+    sudo_with_input(out, args, None)
+}
+
+/// Run a command with sudo, `input` is written to its standard input and what it prints is dropped
+fn sudo_with_input(out: &Preferences, args: &[&str], input: Option<&str>) -> bool {
+    out.println(&format!("$ sudo {}", args.join(" ")));
+    let mut command = Command::new("sudo");
+    command.args(args);
+    if input.is_some() {
+        command.stdin(Stdio::piped()).stdout(Stdio::null());
+    }
+    let status = command.spawn().and_then(|mut child| {
+        if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+            stdin.write_all(input.as_bytes())?;
+        }
+        child.wait()
+    });
+    match status {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            out.println(&format!("ERROR: Command failed with {}", status));
+            false
+        }
+        Err(e) => {
+            out.println(&format!("ERROR: Couldn't run sudo: {}", e));
+            false
+        }
+    }
+}
+
+/// Explain how to get access to the serial port and optionally install a udev rule
+pub fn setup(out: &Preferences, install: bool) {
+    if !cfg!(target_os = "linux") {
+        out.println("> Serial port permissions only need to be set up on Linux");
+        return;
+    }
+
+    let mut adapters = driver::adapters();
+    if adapters.is_empty() {
+        out.println("> No known USB serial adapter found, generating a rule for all common chips");
+        adapters = CHIPS.iter().flat_map(|chip| chip.pids().iter().map(move |pid| (chip.vid(), *pid))).collect();
+    } else {
+        for (vid, pid) in adapters.iter() {
+            let name = Chip::from_vid(*vid).map_or("Unknown", |chip| chip.name());
+            out.println(&format!("> Found a {} adapter ({:04x}:{:04x})", name, vid, pid));
+        }
+    }
+
+    if let Some(group) = serial_group() {
+        if user_groups().iter().any(|g| g == group) {
+            out.println(&format!("> You are in the {} group, which usually has access to serial ports already", group));
+        } else {
+            out.println(&format!("> Alternatively to the rule, add yourself to the {} group and log in again:", group));
+            out.println(&format!("$ sudo usermod -aG {} $USER", group));
+        }
+    }
+
+    let rule = rule(&adapters);
+    if !install {
+        out.println(&format!("> Rule for {}, install it with --install:", RULE_PATH));
+        for line in rule.lines() {
+            out.println(line);
+        }
+        return;
+    }
+
+    // Piped straight to root, a file in the shared temp directory could be swapped by another user
+    let installed = sudo_with_input(out, &["tee", RULE_PATH], Some(&rule))
+        && sudo(out, &["chmod", "0644", RULE_PATH])
+        && sudo(out, &["udevadm", "control", "--reload-rules"])
+        && sudo(out, &["udevadm", "trigger", "--subsystem-match=tty"]);
+
+    if installed {
+        out.println("> Rule installed, replug the adapter to apply it");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_a_rule_per_adapter() {
+        let rule = rule(&[(0x10c4, 0xea60), (0x1a86, 0x7523)]);
+        let rules: Vec<&str> = rule.lines().filter(|line| !line.starts_with('#')).collect();
+        assert_eq!(
+            rules,
+            [
+                r#"SUBSYSTEM=="tty", ATTRS{idVendor}=="10c4", ATTRS{idProduct}=="ea60", MODE="0660", TAG+="uaccess""#,
+                r#"SUBSYSTEM=="tty", ATTRS{idVendor}=="1a86", ATTRS{idProduct}=="7523", MODE="0660", TAG+="uaccess""#,
+            ]
+        );
+        assert!(!rule.contains("RUN"));
+    }
+}
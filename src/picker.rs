@@ -0,0 +1,181 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
+    Frame,
+};
+use serialport::SerialPortInfo;
+use std::time::{Duration, Instant};
+
+use crate::i18n;
+use crate::output;
+use crate::port;
+use crate::theme::Theme;
+
+/// How often the port list is refreshed while waiting for a device
+const POLL_RATE: Duration = Duration::from_millis(500);
+
+/// What the user did in the port picker
+pub enum Action {
+    /// Nothing that concerns the rest of the app
+    None,
+    /// Close the picker without choosing a port
+    Close,
+    /// Connect to the given port
+    Select(String),
+}
+
+/// Popup listing the available serial ports
+pub struct PortPicker {
+    /// Ports shown in the list
+    ports: Vec<SerialPortInfo>,
+    /// Selected list entry
+    state: ListState,
+    /// Connect to newly plugged in devices automatically
+    auto: bool,
+    /// Last time the port list was polled
+    last_poll: Instant,
+    /// Colors
+    theme: Theme,
+}
+
+impl PortPicker {
+    pub fn new(auto: bool, theme: Theme) -> Self {
+        let mut picker = Self {
+            ports: Vec::new(),
+            state: ListState::default(),
+            auto,
+            last_poll: Instant::now(),
+            theme,
+        };
+        picker.refresh();
+        picker
+    }
+
+    fn refresh(&mut self) {
+        self.ports = port::list();
+        self.last_poll = Instant::now();
+
+        let selected = self.state.selected().unwrap_or(0);
+        self.state.select(if self.ports.is_empty() {
+            None
+        } else {
+            Some(selected.min(self.ports.len() - 1))
+        });
+    }
+
+    /// Poll for newly plugged in devices, returns the port to connect to in auto mode
+    pub fn tick(&mut self) -> Option<String> {
+        if !self.auto || self.last_poll.elapsed() < POLL_RATE {
+            return None;
+        }
+
+        let old = std::mem::take(&mut self.ports);
+        self.refresh();
+        port::detect_new(&old, &self.ports)
+    }
+
+    /// The port list as plain text, for when there is no room for the popup
+    pub fn lines(&self) -> Vec<String> {
+        if self.ports.is_empty() {
+            return output::hint();
+        }
+
+        let mut lines = vec!["Available serial ports:".to_string()];
+        for (id, port) in self.ports.iter().enumerate() {
+            let name = port::friendly_name(port).map_or(String::new(), |name| format!("{} ", name));
+            lines.push(format!("[{}] {} {}{}", id, port.port_name, name, port::describe(port)));
+        }
+        lines
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Up => {
+                if let Some(i) = self.state.selected() {
+                    self.state.select(Some(i.saturating_sub(1)));
+                }
+            }
+            KeyCode::Down => {
+                if let Some(i) = self.state.selected() {
+                    self.state.select(Some((i + 1).min(self.ports.len().saturating_sub(1))));
+                }
+            }
+            KeyCode::Char('r') => self.refresh(),
+            KeyCode::Enter => {
+                if let Some(port) = self.state.selected().and_then(|i| self.ports.get(i)) {
+                    return Action::Select(port.port_name.clone());
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') => return Action::Close,
+            _ => (),
+        }
+        Action::None
+    }
+
+    pub fn render(&mut self, f: &mut Frame, area: Rect) {
+        let area = centered(area, 80, 60);
+
+        let title = if self.auto {
+            i18n::text("select_port_auto")
+        } else {
+            i18n::text("select_port")
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(self.theme.focus))
+            .title(title)
+            .title_bottom(i18n::text("picker_keys"));
+
+        f.render_widget(Clear, area);
+
+        if self.ports.is_empty() {
+            let items: Vec<ListItem> = output::hint().into_iter().map(ListItem::new).collect();
+            f.render_widget(List::new(items).block(block), area);
+            return;
+        }
+
+        let items: Vec<ListItem> = self
+            .ports
+            .iter()
+            .enumerate()
+            .map(|(id, port)| {
+                let mut spans = vec![Span::raw(format!("[{}] {} ", id, port.port_name))];
+                if let Some(name) = port::friendly_name(port) {
+                    spans.push(Span::styled(format!("{} ", name), Style::default().add_modifier(Modifier::BOLD)));
+                }
+                spans.push(Span::styled(port::describe(port), Style::default().fg(self.theme.accent)));
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        f.render_stateful_widget(list, area, &mut self.state);
+    }
+}
+
+/// Rectangle of the given percentage size in the middle of `area`
+pub fn centered(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
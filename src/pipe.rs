@@ -0,0 +1,158 @@
+use ratatui::layout::Rect;
+use ratatui::style::Style;
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::Frame;
+use std::collections::VecDeque;
+use std::io;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::process::{ChildStdin, Command};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+
+use crate::i18n;
+use crate::shell;
+use crate::theme::Theme;
+
+/// Lines of output kept for the pane
+const KEEP: usize = 500;
+
+enum Output {
+    Line(String),
+    /// The command ended, with its exit status or why it couldn't be waited for
+    End(String),
+}
+
+/// An external command fed with the received lines, e.g. `grep --line-buffered`, `tee` or a parser
+pub struct Pipe {
+    command: String,
+    /// Closed when the pipe stops, so the command sees the end of its input
+    input: UnboundedSender<String>,
+    output: UnboundedReceiver<Output>,
+    lines: VecDeque<String>,
+    /// Lines the command printed so far, to notice new ones
+    count: usize,
+    /// How the command ended, once it did
+    ended: Option<String>,
+    task: JoinHandle<()>,
+}
+
+impl Pipe {
+    /// Run the command in the shell, its output and errors both go to the pane
+    pub fn start(command: &str) -> io::Result<Self> {
+        let (shell, flag) = shell::shell();
+        let mut child = Command::new(shell)
+            .arg(flag)
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let (input, input_rx) = mpsc::unbounded_channel();
+        let (output_tx, output) = mpsc::unbounded_channel();
+        let stdin = child.stdin.take();
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        if let Some(stdin) = stdin {
+            // Not waited for, the command may end without reading all of its input, e.g. head
+            tokio::spawn(write(stdin, input_rx));
+        }
+        let task = tokio::spawn(async move {
+            tokio::join!(follow(stdout, &output_tx), follow(stderr, &output_tx));
+            let end = match child.wait().await {
+                Ok(status) => status.to_string(),
+                Err(e) => e.to_string(),
+            };
+            let _ = output_tx.send(Output::End(end));
+        });
+
+        Ok(Self {
+            command: command.to_string(),
+            input,
+            output,
+            lines: VecDeque::new(),
+            count: 0,
+            ended: None,
+            task,
+        })
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Give a received line to the command
+    pub fn push(&self, line: &str) {
+        // Gone once the command stopped reading
+        let _ = self.input.send(line.trim_end_matches(['\r', '\n']).to_string());
+    }
+
+    /// Take in what the command printed since the last call.
+    /// Returns the new lines and, once, how the command ended.
+    pub fn poll(&mut self) -> (Vec<String>, Option<String>) {
+        let mut new = Vec::new();
+        let mut end = None;
+        while let Ok(output) = self.output.try_recv() {
+            match output {
+                Output::Line(line) => new.push(line),
+                Output::End(status) => end = Some(status),
+            }
+        }
+        for line in &new {
+            if self.lines.len() == KEEP {
+                self.lines.pop_front();
+            }
+            self.lines.push_back(line.clone());
+        }
+        self.count += new.len();
+        if end.is_some() {
+            self.ended = end.clone();
+        }
+        (new, end)
+    }
+
+    /// Lines the command printed so far
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Pane below the Messages box with the latest output of the command
+    pub fn render(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let title = match &self.ended {
+            Some(status) => i18n::format("pipe_ended", &[&self.command, status]),
+            None => i18n::format("pipe", &[&self.command]),
+        };
+        let rows = area.height.saturating_sub(2) as usize;
+        let lines: Vec<String> = self.lines.iter().skip(self.lines.len().saturating_sub(rows)).cloned().collect();
+        let pane = Paragraph::new(lines.join("\n"))
+            .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(theme.border)).title(title));
+        f.render_widget(pane, area);
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        // Kills the command along with the task
+        self.task.abort();
+    }
+}
+
+async fn write(mut stdin: ChildStdin, mut lines: UnboundedReceiver<String>) {
+    while let Some(line) = lines.recv().await {
+        if stdin.write_all(format!("{}\n", line).as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn follow(reader: Option<impl AsyncRead + Unpin>, output: &UnboundedSender<Output>) {
+    let mut lines = match reader {
+        Some(reader) => BufReader::new(reader).lines(),
+        None => return,
+    };
+    while let Ok(Some(line)) = lines.next_line().await {
+        let _ = output.send(Output::Line(line));
+    }
+}
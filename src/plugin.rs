@@ -0,0 +1,141 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// A stage that processes every line received from the device before it is shown
+pub trait Processor: Send {
+    /// Turn a line into any number of lines, an empty result drops the line
+    fn process(&mut self, line: String) -> Vec<String>;
+}
+
+/// `[[processor]]` entries of the config file, applied in order
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Spec {
+    /// Prefix lines with the time since the session started
+    Timestamp,
+    /// Replace every match of a regex, `$1` etc. refer to capture groups
+    Replace { pattern: String, with: String },
+    /// Drop lines matching a regex
+    Drop { pattern: String },
+    /// Append the vendor to MAC addresses, from a file like the IEEE `oui.txt`
+    Oui { file: PathBuf },
+}
+
+/// The processors of a session, in order
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<Box<dyn Processor>>,
+}
+
+impl Pipeline {
+    /// Build the processors listed in the config
+    pub fn from_specs(specs: &[Spec]) -> Result<Self, String> {
+        let mut pipeline = Self::default();
+        for spec in specs {
+            pipeline.add(build(spec)?);
+        }
+        Ok(pipeline)
+    }
+
+    pub fn add(&mut self, stage: Box<dyn Processor>) {
+        self.stages.push(stage);
+    }
+
+    /// Run a line through every stage
+    pub fn run(&mut self, line: String) -> Vec<String> {
+        let mut lines = vec![line];
+        for stage in self.stages.iter_mut() {
+            lines = lines.into_iter().flat_map(|line| stage.process(line)).collect();
+        }
+        lines
+    }
+}
+
+fn build(spec: &Spec) -> Result<Box<dyn Processor>, String> {
+    let regex = |pattern: &str| Regex::new(pattern).map_err(|e| format!("Invalid processor pattern '{}': {}", pattern, e));
+
+    Ok(match spec {
+        Spec::Timestamp => Box::new(Timestamp { started: Instant::now() }),
+        Spec::Replace { pattern, with } => Box::new(Replace {
+            regex: regex(pattern)?,
+            with: with.clone(),
+        }),
+        Spec::Drop { pattern } => Box::new(Filter { regex: regex(pattern)? }),
+        Spec::Oui { file } => Box::new(Oui::load(file)?),
+    })
+}
+
+struct Timestamp {
+    started: Instant,
+}
+
+impl Processor for Timestamp {
+    fn process(&mut self, line: String) -> Vec<String> {
+        let elapsed = self.started.elapsed();
+        vec![format!("[{:>5}.{:03}] {}", elapsed.as_secs(), elapsed.subsec_millis(), line)]
+    }
+}
+
+struct Replace {
+    regex: Regex,
+    with: String,
+}
+
+impl Processor for Replace {
+    fn process(&mut self, line: String) -> Vec<String> {
+        vec![self.regex.replace_all(&line, self.with.as_str()).into_owned()]
+    }
+}
+
+struct Filter {
+    regex: Regex,
+}
+
+impl Processor for Filter {
+    fn process(&mut self, line: String) -> Vec<String> {
+        if self.regex.is_match(&line) {
+            Vec::new()
+        } else {
+            vec![line]
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref MAC: Regex = Regex::new(r"(?i)\b([0-9a-f]{2})[:-]([0-9a-f]{2})[:-]([0-9a-f]{2})(?:[:-][0-9a-f]{2}){3}\b").unwrap();
+    static ref OUI_LINE: Regex = Regex::new(r"(?i)^\s*([0-9a-f]{2})[:-]?([0-9a-f]{2})[:-]?([0-9a-f]{2})\s+(?:\((?:hex|base 16)\)\s+)?(.+?)\s*$").unwrap();
+}
+
+struct Oui {
+    /// Vendor by the first three bytes, e.g. "AABBCC"
+    vendors: HashMap<String, String>,
+}
+
+impl Oui {
+    fn load(file: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(file).map_err(|e| format!("Couldn't read OUI file {}: {}", file.display(), e))?;
+        let vendors = text
+            .lines()
+            .filter_map(|line| OUI_LINE.captures(line))
+            .map(|caps| (format!("{}{}{}", &caps[1], &caps[2], &caps[3]).to_uppercase(), caps[4].to_string()))
+            .collect();
+        Ok(Self { vendors })
+    }
+}
+
+impl Processor for Oui {
+    fn process(&mut self, line: String) -> Vec<String> {
+        let line = MAC.replace_all(&line, |caps: &regex::Captures| {
+            let prefix = format!("{}{}{}", &caps[1], &caps[2], &caps[3]).to_uppercase();
+            match self.vendors.get(&prefix) {
+                Some(vendor) => format!("{} ({})", &caps[0], vendor),
+                None => caps[0].to_string(),
+            }
+        });
+        vec![line.into_owned()]
+    }
+}
@@ -1,66 +1,95 @@
-use serialport::{available_ports, SerialPortInfo};
-use tokio::sync::mpsc::UnboundedReceiver;
+use serialport::{available_ports, SerialPortInfo, SerialPortType};
 
-use crate::input;
-use crate::output;
+use crate::driver::Chip;
 
-async fn detect_port(ports: &mut Vec<SerialPortInfo>) -> Option<String> {
-    loop {
-        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+/// All serial ports currently known to the OS
+pub fn list() -> Vec<SerialPortInfo> {
+    available_ports().unwrap_or_default()
+}
 
-        if let Ok(new_ports) = available_ports() {
-            for path in &new_ports {
-                if !ports.contains(path) {
-                    return Some(path.port_name.clone());
-                }
-            }
+/// Find a port that is in `new` but wasn't in `old`, e.g. a freshly plugged in deauther
+pub fn detect_new(old: &[SerialPortInfo], new: &[SerialPortInfo]) -> Option<String> {
+    new.iter()
+        .find(|port| !old.contains(port))
+        .map(|port| port.port_name.clone())
+}
 
-            *ports = new_ports;
+/// Name people recognize the device by, like "Silicon Labs CP210x USB to UART Bridge".
+/// Windows reports the friendly name as the product, with the COM port appended.
+/// Without a product name, the chip is named after the USB vendor.
+pub fn friendly_name(port: &SerialPortInfo) -> Option<String> {
+    match &port.port_type {
+        SerialPortType::UsbPort(usb) => {
+            let suffix = format!(" ({})", port.port_name);
+            let product = usb.product.as_deref().map(|product| product.trim_end_matches(suffix.as_str()).to_string());
+            product.or_else(|| Chip::from_vid(usb.vid).map(|chip| chip.name().to_string()))
         }
+        _ => None,
     }
 }
 
-fn manual_port(port: String, ports: &mut Vec<SerialPortInfo>) -> Option<String> {
-    if port.to_lowercase().contains("dev/") || port.to_lowercase().contains("com") {
-        Some(port)
-    } else {
-        let index = port.parse().ok()?;
+/// Friendly name of a port that is currently attached
+pub fn friendly_name_of(port_name: &str) -> Option<String> {
+    list().iter().find(|port| port.port_name == port_name).and_then(friendly_name)
+}
 
-        if index < ports.len() {
-            Some(ports.remove(index).port_name)
-        } else {
-            None
+/// Human readable metadata of a port besides its friendly name, like "USB 10c4:ea60 Silicon Labs"
+pub fn describe(port: &SerialPortInfo) -> String {
+    match &port.port_type {
+        SerialPortType::UsbPort(usb) => {
+            let mut desc = format!("USB {:04x}:{:04x}", usb.vid, usb.pid);
+            for field in [&usb.manufacturer, &usb.serial_number].iter().filter_map(|f| f.as_ref()) {
+                desc.push(' ');
+                desc.push_str(field);
+            }
+            desc
         }
+        SerialPortType::PciPort => "PCI".to_string(),
+        SerialPortType::BluetoothPort => "Bluetooth".to_string(),
+        SerialPortType::Unknown => String::new(),
     }
 }
 
-pub async fn manual(
-    receiver: &mut UnboundedReceiver<String>,
-    out: &output::Preferences,
-) -> Option<String> {
-    let mut ports = available_ports().ok()?;
-
-    out.ports(&ports);
-
-    let port = input::read_line(receiver).await?;
-
-    manual_port(port, &mut ports)
+/// Name that stays the same for a device on any port, e.g. `10c4-ea60-0001` for a USB device with a serial number.
+/// Other devices are known by their port.
+pub fn device_key(port_name: &str) -> String {
+    let usb = list().into_iter().find(|port| port.port_name == port_name).and_then(|port| match port.port_type {
+        SerialPortType::UsbPort(usb) => Some(usb),
+        _ => None,
+    });
+    let key = match usb {
+        Some(usb) => match usb.serial_number {
+            Some(serial) => format!("{:04x}-{:04x}-{}", usb.vid, usb.pid, serial),
+            None => format!("{:04x}-{:04x}", usb.vid, usb.pid),
+        },
+        None => port_name.to_string(),
+    };
+    // Safe as a file name
+    key.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' }).collect()
 }
 
-pub async fn auto(
-    receiver: &mut UnboundedReceiver<String>,
-    out: &output::Preferences,
-) -> Option<String> {
-    let mut ports = available_ports().ok()?;
-
-    out.ports(&ports);
-    out.println("> Plug your deauther in, or type the port ID or name");
-
-    tokio::select! {
-        port = detect_port(&mut ports) => port,
+/// The port given on the command line, or the only one attached
+pub fn choose(port: Option<&str>) -> Result<String, String> {
+    let ports = list();
+    match port {
+        Some(port) => resolve(port, &ports).ok_or_else(|| format!("'{}' is not a valid port", port)),
+        None => match ports.as_slice() {
+            [] => Err("No serial port found".to_string()),
+            [port] => Ok(port.port_name.clone()),
+            ports => {
+                let names: Vec<&str> = ports.iter().map(|port| port.port_name.as_str()).collect();
+                Err(format!("Pick one of the ports with --port: {}", names.join(", ")))
+            }
+        },
+    }
+}
 
-        Some(port) = input::read_line(receiver) => {
-            manual_port(port, &mut ports)
-        }
+/// Resolve user input (a port ID from the list or a port name) to a port name
+pub fn resolve(port: &str, ports: &[SerialPortInfo]) -> Option<String> {
+    if port.to_lowercase().contains("dev/") || port.to_lowercase().contains("com") {
+        Some(port.to_string())
+    } else {
+        let index: usize = port.parse().ok()?;
+        ports.get(index).map(|port| port.port_name.clone())
     }
 }
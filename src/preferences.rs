@@ -0,0 +1,196 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Constraint, Rect},
+    style::{Modifier, Style},
+    widgets::{Block, Borders, Cell, Clear, Row, Table, TableState},
+    Frame,
+};
+
+use crate::app::Settings;
+use crate::i18n;
+use crate::picker;
+use crate::theme::{self, Theme};
+
+/// Scrollback sizes to step through, `None` keeps every line
+const SCROLLBACK: [Option<usize>; 4] = [Some(1_000), Some(10_000), Some(100_000), None];
+
+/// What the user did in the preferences panel
+pub enum Action {
+    None,
+    Close,
+    /// Write the preferences to the config file
+    Save,
+    /// Open the editor for the settings of the deauther instead
+    DeviceSettings,
+}
+
+#[derive(Clone, Copy)]
+enum Pref {
+    Timestamps,
+    LineNumbers,
+    Wrap,
+    Theme,
+    Echo,
+    Scrollback,
+}
+
+impl Pref {
+    const ALL: [Pref; 6] = [Pref::Timestamps, Pref::LineNumbers, Pref::Wrap, Pref::Theme, Pref::Echo, Pref::Scrollback];
+
+    fn name(self) -> &'static str {
+        i18n::text(match self {
+            Pref::Timestamps => "pref_timestamps",
+            Pref::LineNumbers => "pref_line_numbers",
+            Pref::Wrap => "pref_wrap",
+            Pref::Theme => "pref_theme",
+            Pref::Echo => "pref_echo",
+            Pref::Scrollback => "pref_scrollback",
+        })
+    }
+
+    fn value(self, settings: &Settings) -> String {
+        let switch = |on: bool| i18n::text(if on { "on" } else { "off" }).to_string();
+        match self {
+            Pref::Timestamps => switch(settings.timestamps),
+            Pref::LineNumbers => switch(settings.line_numbers),
+            Pref::Wrap => switch(settings.wrap),
+            Pref::Theme => settings.theme.to_string(),
+            Pref::Echo => settings.echo.to_string(),
+            Pref::Scrollback => match settings.scrollback {
+                Some(lines) => i18n::format("pref_lines", &[&lines]),
+                None => i18n::text("unlimited").to_string(),
+            },
+        }
+    }
+
+    /// Key and value in the config file
+    fn config(self, settings: &Settings) -> (&'static str, toml_edit::Value) {
+        match self {
+            Pref::Timestamps => ("timestamps", settings.timestamps.into()),
+            Pref::LineNumbers => ("line-numbers", settings.line_numbers.into()),
+            Pref::Wrap => ("wrap", settings.wrap.into()),
+            Pref::Theme => ("theme", settings.theme.into()),
+            Pref::Echo => ("echo", settings.echo.to_string().into()),
+            Pref::Scrollback => ("scrollback", (settings.scrollback.unwrap_or(0) as i64).into()),
+        }
+    }
+
+    /// Go to the next value, or the previous one
+    fn step(self, settings: &mut Settings, forward: bool) {
+        match self {
+            Pref::Timestamps => settings.timestamps = !settings.timestamps,
+            Pref::LineNumbers => settings.line_numbers = !settings.line_numbers,
+            Pref::Wrap => settings.wrap = !settings.wrap,
+            Pref::Theme => {
+                let i = theme::NAMES.iter().position(|name| *name == settings.theme).unwrap_or(0);
+                settings.theme = theme::NAMES[cycle(i, theme::NAMES.len(), forward)];
+            }
+            // Three modes, so two steps forward are one back
+            Pref::Echo if forward => settings.echo = settings.echo.next(),
+            Pref::Echo => settings.echo = settings.echo.next().next(),
+            Pref::Scrollback => {
+                let i = match SCROLLBACK.iter().position(|size| *size == settings.scrollback) {
+                    Some(i) => cycle(i, SCROLLBACK.len(), forward),
+                    // A size of the config file that isn't a step goes to the next bigger or smaller one
+                    None => {
+                        let bigger = SCROLLBACK.iter().position(|size| size.is_none() || *size > settings.scrollback).unwrap_or(0);
+                        if forward { bigger } else { cycle(bigger, SCROLLBACK.len(), false) }
+                    }
+                };
+                settings.scrollback = SCROLLBACK[i];
+            }
+        }
+    }
+}
+
+fn cycle(i: usize, len: usize, forward: bool) -> usize {
+    if forward {
+        (i + 1) % len
+    } else {
+        (i + len - 1) % len
+    }
+}
+
+
+/// The preferences as lines, where there's no room for the panel
+pub fn lines(settings: &Settings) -> Vec<String> {
+    Pref::ALL.iter().map(|pref| format!("> {}: {}", pref.name(), pref.value(settings))).collect()
+}
+
+/// Popup to change the preferences of Huhnitor while it runs
+pub struct Preferences {
+    state: TableState,
+    /// Values when the panel was opened or last saved, changed ones stand out
+    saved: Vec<String>,
+}
+
+impl Preferences {
+    pub fn new(settings: &Settings) -> Self {
+        let mut panel = Self { state: TableState::default().with_selected(Some(0)), saved: Vec::new() };
+        panel.saved(settings);
+        panel
+    }
+
+    /// The preferences were written to the config file
+    pub fn saved(&mut self, settings: &Settings) {
+        self.saved = Pref::ALL.iter().map(|pref| pref.value(settings)).collect();
+    }
+
+    /// Values of the config file for the preferences changed since opening or saving, so a theme
+    /// forced by `--theme` or `NO_COLOR` isn't written along with them
+    pub fn changes(&self, settings: &Settings) -> Vec<(&'static str, toml_edit::Value)> {
+        Pref::ALL
+            .iter()
+            .zip(&self.saved)
+            .filter(|(pref, saved)| pref.value(settings) != **saved)
+            .map(|(pref, _)| pref.config(settings))
+            .collect()
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent, settings: &mut Settings) -> Action {
+        let selected = self.state.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Up => self.state.select(Some(selected.saturating_sub(1))),
+            KeyCode::Down => self.state.select(Some((selected + 1).min(Pref::ALL.len() - 1))),
+            KeyCode::Enter | KeyCode::Char(' ') | KeyCode::Right => Pref::ALL[selected].step(settings, true),
+            KeyCode::Left => Pref::ALL[selected].step(settings, false),
+            KeyCode::Char('w') => return Action::Save,
+            KeyCode::Char('d') => return Action::DeviceSettings,
+            KeyCode::Esc | KeyCode::Char('q') => return Action::Close,
+            _ => (),
+        }
+        Action::None
+    }
+
+    pub fn render(&mut self, f: &mut Frame, area: Rect, theme: &Theme, settings: &Settings) {
+        let area = picker::centered(area, 60, 40);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.focus))
+            .title(i18n::text("preferences"))
+            .title_bottom(i18n::text("preferences_keys"));
+
+        let rows: Vec<Row> = Pref::ALL
+            .iter()
+            .zip(&self.saved)
+            .map(|(pref, saved)| {
+                let value = pref.value(settings);
+                let style = if value != *saved {
+                    Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                Row::new(vec![Cell::from(pref.name()), Cell::from(value).style(style)])
+            })
+            .collect();
+
+        let table = Table::new(rows, [Constraint::Length(24), Constraint::Min(8)])
+            .block(block)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+
+        f.render_widget(Clear, area);
+        f.render_stateful_widget(table, area, &mut self.state);
+    }
+}
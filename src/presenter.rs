@@ -0,0 +1,133 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::theme::Theme;
+
+/// Keys stay in the overlay this long after they were pressed
+const SHOWN_FOR: Duration = Duration::from_secs(3);
+
+/// Keys shown at once, the oldest go first
+const MAX_KEYS: usize = 6;
+
+/// Typed text in the overlay is cut to its end beyond this many characters
+const MAX_TEXT: usize = 24;
+
+/// A pressed key, or characters typed one after another
+struct Pressed {
+    label: String,
+    typed: bool,
+    at: Instant,
+}
+
+/// Overlay of the keys being pressed, for screen recordings of tutorials
+#[derive(Default)]
+pub struct Presenter {
+    keys: VecDeque<Pressed>,
+}
+
+impl Presenter {
+    pub fn key(&mut self, key: KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        let now = Instant::now();
+        let plain = key.modifiers.difference(KeyModifiers::SHIFT).is_empty();
+        match key.code {
+            // Typing goes into one entry, so words stay readable
+            KeyCode::Char(c) if plain => match self.keys.back_mut() {
+                Some(last) if last.typed => {
+                    last.label.push(c);
+                    last.at = now;
+                }
+                _ => self.push(Pressed { label: c.to_string(), typed: true, at: now }),
+            },
+            _ => self.push(Pressed { label: name(key), typed: false, at: now }),
+        }
+    }
+
+    fn push(&mut self, pressed: Pressed) {
+        if self.keys.len() == MAX_KEYS {
+            self.keys.pop_front();
+        }
+        self.keys.push_back(pressed);
+    }
+
+    /// Forget the keys that were shown long enough, returns whether any went
+    pub fn expire(&mut self) -> bool {
+        let before = self.keys.len();
+        self.keys.retain(|pressed| pressed.at.elapsed() < SHOWN_FOR);
+        self.keys.len() != before
+    }
+
+    /// Draw the overlay in the bottom right corner of `area`
+    pub fn render(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        if self.keys.is_empty() {
+            return;
+        }
+        let key_style = Style::default().fg(theme.focus).add_modifier(Modifier::BOLD | Modifier::REVERSED);
+        let mut spans = Vec::new();
+        for pressed in &self.keys {
+            if !spans.is_empty() {
+                spans.push(Span::raw(" "));
+            }
+            let label = match pressed.label.chars().count() {
+                n if pressed.typed && n > MAX_TEXT => format!("…{}", pressed.label.chars().skip(n - MAX_TEXT).collect::<String>()),
+                _ => pressed.label.clone(),
+            };
+            spans.push(Span::styled(format!(" {} ", label), key_style));
+        }
+        let line = Line::from(spans);
+
+        let width = (line.width() as u16 + 2).min(area.width);
+        let height = 3.min(area.height);
+        let overlay = Rect::new(area.right().saturating_sub(width + 1), area.bottom().saturating_sub(height + 1), width, height);
+        let block = Block::default().borders(Borders::ALL).border_style(Style::default().fg(theme.accent));
+        f.render_widget(Clear, overlay);
+        f.render_widget(Paragraph::new(line).block(block), overlay);
+    }
+}
+
+/// Label of a key, e.g. `Ctrl+C` or `Enter`
+fn name(key: KeyEvent) -> String {
+    let code = match key.code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_uppercase().to_string(),
+        KeyCode::Enter => "Enter".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::BackTab => "Shift+Tab".to_string(),
+        KeyCode::Backspace => "Backspace".to_string(),
+        KeyCode::Delete => "Del".to_string(),
+        KeyCode::Esc => "Esc".to_string(),
+        KeyCode::Up => "↑".to_string(),
+        KeyCode::Down => "↓".to_string(),
+        KeyCode::Left => "←".to_string(),
+        KeyCode::Right => "→".to_string(),
+        KeyCode::Home => "Home".to_string(),
+        KeyCode::End => "End".to_string(),
+        KeyCode::PageUp => "PgUp".to_string(),
+        KeyCode::PageDown => "PgDn".to_string(),
+        KeyCode::Insert => "Ins".to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        other => format!("{:?}", other),
+    };
+    let mut label = String::new();
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        label.push_str("Ctrl+");
+    }
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        label.push_str("Alt+");
+    }
+    if key.modifiers.contains(KeyModifiers::SHIFT) && !matches!(key.code, KeyCode::Char(_) | KeyCode::BackTab) {
+        label.push_str("Shift+");
+    }
+    label.push_str(&code);
+    label
+}
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+/// What the UI asks the serial side to do
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub enum UiEvent {
+    /// A typed line or local command, including the line ending
+    SendLine(String),
+    /// Keystrokes of raw mode, sent as they are
+    SendBytes(Vec<u8>),
+    /// Stop what the device is doing, e.g. with ctrl + c
+    Interrupt,
+    /// End the session
+    Quit,
+}
+
+/// What the serial side tells the UI
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub enum BackendEvent {
+    /// A line received from the device, after the output processors
+    Rx(String),
+    /// The start of a line the device hasn't finished, e.g. the prompt of its shell
+    Partial(String),
+    /// A message of the Huhnitor itself, e.g. `> Firmware: ...` or `ERROR: ...`
+    Status(String),
+    /// A command written to the device, typed or sent by a schedule, chain, script or `huhnitor send`
+    Sent(String),
+}
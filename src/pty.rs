@@ -0,0 +1,86 @@
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use nix::pty::openpty;
+use nix::sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg};
+use nix::unistd::ttyname;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::unix::AsyncFd;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Pseudo-terminal mirroring the serial session, for programs that expect a tty
+pub struct Pty {
+    master: Arc<AsyncFd<File>>,
+    /// Kept open so the master doesn't fail while no program is attached
+    _slave: File,
+    /// Symlink pointing at the slave side
+    link: PathBuf,
+}
+
+impl Pty {
+    /// Create the pty and a symlink to it at `link`, bytes written to it are sent on `input_tx`
+    pub fn open(link: &Path, input_tx: UnboundedSender<Vec<u8>>) -> io::Result<Self> {
+        let pty = openpty(None, None)?;
+        // Safety: openpty just created both descriptors and nothing else owns them
+        let (master, slave) = unsafe { (File::from_raw_fd(pty.master), File::from_raw_fd(pty.slave)) };
+
+        // Pass bytes through untouched instead of echoing and translating line endings
+        let mut termios = tcgetattr(slave.as_raw_fd())?;
+        cfmakeraw(&mut termios);
+        tcsetattr(slave.as_raw_fd(), SetArg::TCSANOW, &termios)?;
+
+        fcntl(master.as_raw_fd(), FcntlArg::F_SETFL(OFlag::O_NONBLOCK))?;
+
+        // Replace a stale link from an earlier run, but never a real file
+        if fs::symlink_metadata(link).map(|meta| meta.file_type().is_symlink()).unwrap_or(false) {
+            fs::remove_file(link)?;
+        }
+        std::os::unix::fs::symlink(ttyname(slave.as_raw_fd())?, link)?;
+
+        let master = Arc::new(AsyncFd::new(master)?);
+
+        let reader = master.clone();
+        tokio::spawn(async move {
+            let mut buf = [0; 1024];
+            loop {
+                let mut guard = match reader.readable().await {
+                    Ok(guard) => guard,
+                    Err(_) => return,
+                };
+                match guard.try_io(|fd| fd.get_ref().read(&mut buf)) {
+                    Ok(Ok(0)) | Ok(Err(_)) => return,
+                    Ok(Ok(len)) => {
+                        if input_tx.send(buf[..len].to_vec()).is_err() {
+                            return;
+                        }
+                    }
+                    Err(_would_block) => continue,
+                }
+            }
+        });
+
+        Ok(Self {
+            master,
+            _slave: slave,
+            link: link.to_path_buf(),
+        })
+    }
+
+    /// Path of the symlink
+    pub fn link(&self) -> &Path {
+        &self.link
+    }
+
+    /// Mirror serial output to the pty, dropped when nobody reads it and the buffer is full
+    pub fn send(&self, bytes: &[u8]) {
+        let _ = self.master.get_ref().write(bytes);
+    }
+}
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.link);
+    }
+}
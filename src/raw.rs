@@ -0,0 +1,102 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use regex::Regex;
+use std::fmt;
+
+lazy_static::lazy_static! {
+    // Cursor movement and colors of shells that redraw their prompt
+    static ref CSI: Regex = Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]").unwrap();
+}
+
+/// Key that leaves raw mode unless the config sets another, like in telnet
+pub const ESCAPE: &str = "ctrl-]";
+
+/// The key combination that leaves raw mode, every other key goes to the device
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Escape {
+    code: KeyCode,
+    ctrl: bool,
+}
+
+impl Escape {
+    /// `ctrl-]`, `ctrl-x`, `f12` or a single character
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let lower = text.trim().to_lowercase();
+        let (ctrl, key) = match lower.strip_prefix("ctrl-").or_else(|| lower.strip_prefix("ctrl+")) {
+            Some(key) => (true, key),
+            None => (false, lower.as_str()),
+        };
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                _ => return Err(format!("Invalid raw-escape '{}', e.g. ctrl-] or f12", text)),
+            },
+        };
+        Ok(Self { code, ctrl })
+    }
+
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        // Terminals report ctrl-] as ctrl-5 as well
+        let code = match key.code {
+            KeyCode::Char('5') if self.code == KeyCode::Char(']') && key.modifiers.contains(KeyModifiers::CONTROL) => KeyCode::Char(']'),
+            code => code,
+        };
+        code == self.code && key.modifiers.contains(KeyModifiers::CONTROL) == self.ctrl
+    }
+}
+
+impl fmt::Display for Escape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "ctrl-")?;
+        }
+        match self.code {
+            KeyCode::F(n) => write!(f, "f{}", n),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// An unfinished line as a terminal would show it: backspaces delete, a carriage return starts over
+/// and other control characters and escape sequences are dropped
+pub fn visible(line: &str) -> String {
+    let mut text = String::new();
+    for c in CSI.replace_all(line, "").chars() {
+        match c {
+            '\x08' | '\x7f' => {
+                text.pop();
+            }
+            '\r' => text.clear(),
+            c if c.is_control() => (),
+            c => text.push(c),
+        }
+    }
+    text
+}
+
+/// What a key sends to the device, the way a terminal would send it
+pub fn bytes(key: KeyEvent) -> Option<Vec<u8>> {
+    let bytes = match key.code {
+        // Ctrl with a letter or one of @[\]^_ gives the control characters 0 to 31
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => match c.to_ascii_uppercase() {
+            c @ '@'..='_' => vec![c as u8 - b'@'],
+            _ => return None,
+        },
+        KeyCode::Char(c) => c.to_string().into_bytes(),
+        KeyCode::Enter => b"\r".to_vec(),
+        KeyCode::Tab => b"\t".to_vec(),
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Esc => vec![0x1b],
+        KeyCode::Up => b"\x1b[A".to_vec(),
+        KeyCode::Down => b"\x1b[B".to_vec(),
+        KeyCode::Right => b"\x1b[C".to_vec(),
+        KeyCode::Left => b"\x1b[D".to_vec(),
+        KeyCode::Home => b"\x1b[H".to_vec(),
+        KeyCode::End => b"\x1b[F".to_vec(),
+        KeyCode::Delete => b"\x1b[3~".to_vec(),
+        _ => return None,
+    };
+    Some(bytes)
+}
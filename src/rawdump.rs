@@ -0,0 +1,107 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Exact bytes exchanged with the device, for when the transcript hides too much
+pub struct Dump {
+    file: File,
+    /// Frame each chunk with its direction, time and length, and include sent bytes
+    framed: bool,
+    started: Instant,
+}
+
+impl Dump {
+    /// Create the dump file, replacing an existing one
+    pub fn create(path: &Path, framed: bool) -> io::Result<Self> {
+        Ok(Self {
+            file: File::create(path)?,
+            framed,
+            started: Instant::now(),
+        })
+    }
+
+    /// Framed chunks look like `RX 1.234567 12\n` followed by the 12 bytes and a newline,
+    /// so the bytes can be recovered exactly whatever they contain
+    fn frame(&mut self, direction: &str, bytes: &[u8]) -> io::Result<()> {
+        let time = self.started.elapsed();
+        writeln!(self.file, "{} {}.{:06} {}", direction, time.as_secs(), time.subsec_micros(), bytes.len())?;
+        self.file.write_all(bytes)?;
+        self.file.write_all(b"\n")
+    }
+
+    pub fn received(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if self.framed {
+            self.frame("RX", bytes)
+        } else {
+            self.file.write_all(bytes)
+        }
+    }
+
+    /// Sent bytes are only part of framed dumps, plain dumps hold what the device sent
+    pub fn sent(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if self.framed {
+            self.frame("TX", bytes)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Bytes of a dump read back, as they were sent or received
+pub struct Chunk {
+    pub sent: bool,
+    /// Since the dump was started
+    pub time: Duration,
+    pub bytes: Vec<u8>,
+}
+
+/// Read a dump, a plain one is a single chunk received at the start
+pub fn read(path: &Path) -> io::Result<Vec<Chunk>> {
+    let data = fs::read(path)?;
+    Ok(framed(&data).unwrap_or_else(|| vec![Chunk { sent: false, time: Duration::ZERO, bytes: data }]))
+}
+
+/// The chunks of a framed dump, `None` if it isn't one
+fn framed(mut data: &[u8]) -> Option<Vec<Chunk>> {
+    let mut chunks = Vec::new();
+    while !data.is_empty() {
+        let end = data.iter().position(|b| *b == b'\n')?;
+        let header = std::str::from_utf8(&data[..end]).ok()?;
+        let mut fields = header.split(' ');
+        let sent = match fields.next()? {
+            "RX" => false,
+            "TX" => true,
+            _ => return None,
+        };
+        let time = Duration::try_from_secs_f64(fields.next()?.parse().ok()?).ok()?;
+        let len: usize = fields.next()?.parse().ok()?;
+        let bytes = data.get(end + 1..end + 1 + len)?;
+        if fields.next().is_some() || data.get(end + 1 + len) != Some(&b'\n') {
+            return None;
+        }
+        chunks.push(Chunk { sent, time, bytes: bytes.to_vec() });
+        data = &data[end + len + 2..];
+    }
+    Some(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_framed_chunks() {
+        let chunks = framed(b"RX 0.5 3\nab\n\nTX 1.000001 2\nhi\n").unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!((chunks[0].sent, chunks[0].time, chunks[0].bytes.as_slice()), (false, Duration::from_millis(500), &b"ab\n"[..]));
+        assert_eq!((chunks[1].sent, chunks[1].bytes.as_slice()), (true, &b"hi"[..]));
+    }
+
+    #[test]
+    fn rejects_broken_frames() {
+        for dump in [&b"RX -1 2\nhi\n"[..], b"RX NaN 2\nhi\n", b"RX inf 2\nhi\n", b"RX 1 5\nhi\n", b"XX 1 2\nhi\n", b"plain output\n"] {
+            assert!(framed(dump).is_none(), "{}", String::from_utf8_lossy(dump));
+        }
+    }
+}
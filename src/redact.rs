@@ -0,0 +1,168 @@
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use std::fs;
+use std::io::{self, Write};
+
+use crate::cli;
+
+lazy_static::lazy_static! {
+    // `aa:bb:cc:dd:ee:ff` or `AA-BB-CC-DD-EE-FF`, with the same separator throughout
+    static ref MAC: Regex = Regex::new(r"(?i)\b[0-9a-f]{2}(?:(?::[0-9a-f]{2}){5}|(?:-[0-9a-f]{2}){5})\b").unwrap();
+}
+
+/// `[[redact]]` entries of the config file, applied in order to what is shared: scan exports, the watch page and `huhnitor redact`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Spec {
+    /// Mask the last bytes of MAC addresses, the first ones still tell the vendor
+    Mac {
+        #[serde(default = "default_mac_bytes")]
+        bytes: usize,
+    },
+    /// Hide these SSIDs wherever they appear
+    Ssid {
+        names: Vec<String>,
+        #[serde(default = "default_ssid_with")]
+        with: String,
+    },
+    /// Replace every match of a regex, `$1` etc. refer to capture groups
+    Replace {
+        pattern: String,
+        #[serde(default = "default_with")]
+        with: String,
+    },
+}
+
+fn default_mac_bytes() -> usize {
+    3
+}
+
+fn default_ssid_with() -> String {
+    "<ssid>".to_string()
+}
+
+fn default_with() -> String {
+    "<redacted>".to_string()
+}
+
+#[derive(Clone)]
+enum Rule {
+    /// Number of bytes to mask at the end
+    Mac(usize),
+    Replace(Regex, String),
+}
+
+/// The redaction rules, nothing is changed without any
+#[derive(Clone, Default)]
+pub struct Redactor {
+    rules: Vec<Rule>,
+}
+
+impl Redactor {
+    /// Build the rules listed in the config
+    pub fn from_specs(specs: &[Spec]) -> Result<Self, String> {
+        let regex = |pattern: &str| Regex::new(pattern).map_err(|e| format!("Invalid redact pattern '{}': {}", pattern, e));
+
+        let mut rules = Vec::new();
+        for spec in specs {
+            match spec {
+                Spec::Mac { bytes: bytes @ 1..=6 } => rules.push(Rule::Mac(*bytes)),
+                Spec::Mac { bytes } => return Err(format!("Invalid number of MAC bytes to redact {}, expected 1 to 6", bytes)),
+                Spec::Ssid { names, .. } if names.is_empty() => (),
+                Spec::Ssid { names, with } => {
+                    let names: Vec<String> = names.iter().map(|name| regex::escape(name)).collect();
+                    rules.push(Rule::Replace(regex(&names.join("|"))?, with.replace('$', "$$")));
+                }
+                Spec::Replace { pattern, with } => rules.push(Rule::Replace(regex(pattern)?, with.clone())),
+            }
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// A line with everything the rules match hidden
+    pub fn line(&self, line: &str) -> String {
+        let mut line = line.to_string();
+        for rule in &self.rules {
+            line = match rule {
+                Rule::Mac(bytes) => MAC.replace_all(&line, |caps: &Captures| mask(&caps[0], *bytes)).into_owned(),
+                Rule::Replace(regex, with) => regex.replace_all(&line, with.as_str()).into_owned(),
+            };
+        }
+        line
+    }
+}
+
+/// `aa:bb:cc:xx:xx:xx` for the last 3 bytes
+fn mask(mac: &str, bytes: usize) -> String {
+    let separator = &mac[2..3];
+    mac.split(separator)
+        .enumerate()
+        .map(|(i, byte)| if i >= 6 - bytes { "xx" } else { byte })
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// Write a redacted copy of a log, to publish it
+pub fn run(args: &cli::Redact, redactor: &Redactor) -> Result<(), String> {
+    if redactor.is_empty() {
+        return Err("There are no [[redact]] rules in the config file, see https://github.com/SpacehuhnTech/Huhnitor#redaction".to_string());
+    }
+    let bytes = fs::read(&args.log).map_err(|e| format!("Couldn't read {}: {}", args.log.display(), e))?;
+    let mut text = String::new();
+    for line in String::from_utf8_lossy(&bytes).lines() {
+        text.push_str(&redactor.line(line));
+        text.push('\n');
+    }
+    match &args.output {
+        Some(path) => fs::write(path, text).map_err(|e| format!("Couldn't write {}: {}", path.display(), e)),
+        None => io::stdout().write_all(text.as_bytes()).map_err(|e| e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor(specs: &str) -> Result<Redactor, String> {
+        #[derive(Deserialize)]
+        struct Config {
+            redact: Vec<Spec>,
+        }
+        let config: Config = toml::from_str(specs).map_err(|e| e.to_string())?;
+        Redactor::from_specs(&config.redact)
+    }
+
+    #[test]
+    fn masks_the_last_bytes_of_macs() {
+        let redactor = redactor("[[redact]]\ntype = \"mac\"").unwrap();
+        assert_eq!(redactor.line("AP aa:bb:cc:dd:ee:ff -56"), "AP aa:bb:cc:xx:xx:xx -56");
+        assert_eq!(redactor.line("AA-BB-CC-DD-EE-FF"), "AA-BB-CC-xx-xx-xx");
+    }
+
+    #[test]
+    fn leaves_mixed_separators_alone() {
+        let redactor = redactor("[[redact]]\ntype = \"mac\"\nbytes = 6").unwrap();
+        assert_eq!(redactor.line("aa:bb-cc:dd:ee:ff"), "aa:bb-cc:dd:ee:ff");
+        assert_eq!(redactor.line("aa:bb:cc:dd:ee:ff"), "xx:xx:xx:xx:xx:xx");
+    }
+
+    #[test]
+    fn hides_ssids_and_patterns() {
+        let redactor = redactor(
+            "[[redact]]\ntype = \"ssid\"\nnames = [\"Home (5G)\"]\n\n[[redact]]\ntype = \"replace\"\npattern = \"ch (\\\\d+)\"\nwith = \"ch #$1\"",
+        )
+        .unwrap();
+        assert_eq!(redactor.line("\"Home (5G)\" ch 6"), "\"<ssid>\" ch #6");
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        assert!(redactor("[[redact]]\ntype = \"mac\"\nbytes = 7").is_err());
+        assert!(redactor("[[redact]]\ntype = \"replace\"\npattern = \"(\"").is_err());
+        assert!(redactor("redact = []").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,28 @@
+use std::io::{self, Write};
+use std::time::Duration;
+use tokio::time::{self, Instant};
+
+use crate::cli;
+use crate::rawdump;
+
+/// Write the bytes of a dump to stdout like the device sent them, control sequences included
+pub async fn run(args: &cli::Replay) -> Result<(), String> {
+    if args.speed < 0.0 || args.speed.is_nan() {
+        return Err(format!("Invalid speed {}, expected 0 or more", args.speed));
+    }
+    let chunks = rawdump::read(&args.dump).map_err(|e| format!("Couldn't read {}: {}", args.dump.display(), e))?;
+    let started = Instant::now();
+    let mut stdout = io::stdout();
+    for chunk in chunks.iter().filter(|chunk| args.sent || !chunk.sent) {
+        if args.speed > 0.0 {
+            // A tiny speed can push the time further out than a clock can hold
+            let due = Duration::try_from_secs_f64(chunk.time.as_secs_f64() / args.speed)
+                .ok()
+                .and_then(|wait| started.checked_add(wait))
+                .ok_or_else(|| format!("Speed {} is too slow for this dump", args.speed))?;
+            time::sleep_until(due).await;
+        }
+        stdout.write_all(&chunk.bytes).and_then(|_| stdout.flush()).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use nix::unistd::getuid;
+use std::env;
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use tokio::net::UnixStream;
+
+/// Directory of the sockets of this user's Huhnitors, e.g. `/run/user/1000/huhnitor`.
+/// Only the user can enter it, so nobody else can put a socket there or connect to one.
+pub fn dir() -> io::Result<PathBuf> {
+    let dir = match dirs::runtime_dir() {
+        Some(runtime) => runtime.join("huhnitor"),
+        // Shared by all users, so the name can be taken by someone else first
+        None => env::temp_dir().join(format!("huhnitor-{}", getuid())),
+    };
+    match DirBuilder::new().mode(0o700).create(&dir) {
+        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+        _ => (),
+    }
+    if !is_private(&dir) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} doesn't belong to you alone, remove it or set XDG_RUNTIME_DIR", dir.display()),
+        ));
+    }
+    Ok(dir)
+}
+
+/// Whether a directory is owned by this user and closed to everyone else, a symlink isn't followed
+fn is_private(dir: &Path) -> bool {
+    fs::symlink_metadata(dir).is_ok_and(|meta| meta.is_dir() && meta.uid() == getuid().as_raw() && meta.mode() & 0o077 == 0)
+}
+
+/// Whether the process at the other end of a socket runs as this user
+pub fn is_own(stream: &UnixStream) -> bool {
+    stream.peer_cred().is_ok_and(|cred| cred.uid() == getuid().as_raw())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn creates_a_private_directory() {
+        let first = dir().unwrap();
+        assert!(is_private(&first));
+        assert_eq!(dir().unwrap(), first);
+    }
+
+    #[test]
+    fn rejects_directories_others_can_enter() {
+        let dir = env::temp_dir().join(format!("huhnitor-test-{}", std::process::id()));
+        DirBuilder::new().mode(0o700).create(&dir).unwrap();
+        assert!(is_private(&dir));
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(!is_private(&dir));
+
+        let link = dir.with_extension("link");
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700)).unwrap();
+        std::os::unix::fs::symlink(&dir, &link).unwrap();
+        assert!(!is_private(&link));
+        let _ = fs::remove_file(&link);
+        let _ = fs::remove_dir(&dir);
+    }
+
+    #[tokio::test]
+    async fn trusts_peers_of_this_user() {
+        let (a, _b) = UnixStream::pair().unwrap();
+        assert!(is_own(&a));
+    }
+}
@@ -0,0 +1,338 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Constraint, Rect},
+    style::{Modifier, Style},
+    widgets::{Block, Borders, Cell, Clear, Row, Table, TableState},
+    Frame,
+};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::events::Event;
+use crate::gps::Fix;
+use crate::i18n;
+use crate::redact::Redactor;
+use crate::picker;
+use crate::theme::Theme;
+
+/// Commands starting a scan, on the deauther and on Marauder
+const SCAN_COMMANDS: [&str; 4] = ["scan", "scanap", "scansta", "scanall"];
+
+/// Smaller changes of the signal strength are noise
+const RSSI_CHANGE: i32 = 5;
+
+/// An access point or station found by a scan
+#[derive(Clone, Debug)]
+struct Found {
+    rssi: Option<i32>,
+    channel: Option<u8>,
+    /// The row as printed by the device, it has the SSID and vendor in whatever format
+    line: String,
+    /// Where it was found, with a GPS
+    fix: Option<Fix>,
+}
+
+/// Results of a scan by MAC address
+type Scan = BTreeMap<String, Found>;
+
+/// Quote a CSV field if it needs to be, doubling the quotes in it. Fields a spreadsheet would take
+/// for a formula, like an SSID `=HYPERLINK(...)`, are quoted and start with `'` to stay text.
+fn csv_field(text: &str) -> String {
+    if text.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("\"'{}\"", text.replace('"', "\"\""))
+    } else if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Escape text for XML, dropping control characters it doesn't allow even escaped
+fn xml(text: &str) -> String {
+    text.chars()
+        .filter(|c| (!c.is_control() || matches!(c, '\t' | '\n' | '\r')) && !matches!(c, '\u{fffe}' | '\u{ffff}'))
+        .collect::<String>()
+        .replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+pub enum Action {
+    Close,
+    None,
+}
+
+enum Change {
+    New,
+    Gone,
+    /// Signal strength before and after
+    Rssi(i32, i32),
+}
+
+/// Results of the last two scans, to see what changed between them
+#[derive(Default)]
+pub struct Scans {
+    previous: Option<Scan>,
+    latest: Option<Scan>,
+    /// Results go into the latest scan until another command is sent
+    recording: bool,
+    state: TableState,
+}
+
+impl Scans {
+    /// Follow the commands sent, a scan command starts a new scan
+    pub fn sent(&mut self, command: &str) {
+        let word = command.split_whitespace().next().unwrap_or_default();
+        self.recording = SCAN_COMMANDS.iter().any(|scan| scan.eq_ignore_ascii_case(word));
+        if !self.recording {
+            return;
+        }
+        // A scan that found nothing was most likely cut short, so it doesn't count
+        if self.latest.as_ref().is_some_and(|scan| !scan.is_empty()) {
+            self.previous = self.latest.take();
+        }
+        self.latest = Some(Scan::new());
+        self.state.select(Some(0));
+    }
+
+    /// Take in a result of the running scan, tagged with the position if there is one.
+    /// Returns true the first time the scan finds its MAC.
+    pub fn update(&mut self, event: &Event, fix: Option<Fix>) -> bool {
+        match (self.recording, &mut self.latest, event) {
+            (true, Some(scan), Event::ScanResult { mac, rssi, channel, line }) => {
+                let found = Found { rssi: *rssi, channel: *channel, line: line.clone(), fix };
+                scan.insert(mac.clone(), found).is_none()
+            }
+            _ => false,
+        }
+    }
+
+    /// Changes from the previous to the latest scan, `None` until there are two
+    fn changes(&self) -> Option<Vec<(&str, &Found, Change)>> {
+        let (previous, latest) = (self.previous.as_ref()?, self.latest.as_ref()?);
+        let mut changes = Vec::new();
+        for (mac, found) in latest {
+            match previous.get(mac) {
+                None => changes.push((mac.as_str(), found, Change::New)),
+                Some(before) => {
+                    if let (Some(was), Some(now)) = (before.rssi, found.rssi) {
+                        if (now - was).abs() >= RSSI_CHANGE {
+                            changes.push((mac.as_str(), found, Change::Rssi(was, now)));
+                        }
+                    }
+                }
+            }
+        }
+        for (mac, found) in previous {
+            if !latest.contains_key(mac) {
+                changes.push((mac.as_str(), found, Change::Gone));
+            }
+        }
+        changes.sort_by_key(|(_, _, change)| match change {
+            Change::New => 0,
+            Change::Gone => 1,
+            Change::Rssi(..) => 2,
+        });
+        Some(changes)
+    }
+
+    /// Write the results of the latest scan to a file, GPX for `.gpx` files and CSV otherwise.
+    /// Returns how many were written, GPX only has the ones with a position.
+    pub fn export(&self, path: &Path, redactor: &Redactor) -> io::Result<usize> {
+        let scan = self
+            .latest
+            .as_ref()
+            .filter(|scan| !scan.is_empty())
+            .or(self.previous.as_ref())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No scan results yet"))?;
+        let gpx = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gpx"));
+        let (text, count) = if gpx { Self::gpx(scan, redactor) } else { Self::csv(scan, redactor) };
+        fs::write(path, text)?;
+        Ok(count)
+    }
+
+    fn csv(scan: &Scan, redactor: &Redactor) -> (String, usize) {
+        let mut csv = String::from("mac,channel,rssi,line,lat,lon\r\n");
+        for (mac, found) in scan {
+            let channel = found.channel.map(|channel| channel.to_string()).unwrap_or_default();
+            let rssi = found.rssi.map(|rssi| rssi.to_string()).unwrap_or_default();
+            let (lat, lon) = found.fix.map_or((String::new(), String::new()), |fix| (fix.lat.to_string(), fix.lon.to_string()));
+            let line = csv_field(&redactor.line(&found.line));
+            csv.push_str(&format!("{},{},{},{},{},{}\r\n", csv_field(&redactor.line(mac)), channel, rssi, line, lat, lon));
+        }
+        (csv, scan.len())
+    }
+
+    /// A waypoint for every result with a position, named after the MAC
+    fn gpx(scan: &Scan, redactor: &Redactor) -> (String, usize) {
+        let mut gpx = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        gpx.push_str("<gpx version=\"1.1\" creator=\"Huhnitor\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n");
+        let mut count = 0;
+        for (mac, found) in scan {
+            if let Some(fix) = found.fix {
+                gpx.push_str(&format!(
+                    "  <wpt lat=\"{}\" lon=\"{}\"><name>{}</name><desc>{}</desc></wpt>\n",
+                    fix.lat,
+                    fix.lon,
+                    xml(&redactor.line(mac)),
+                    xml(&redactor.line(&found.line))
+                ));
+                count += 1;
+            }
+        }
+        gpx.push_str("</gpx>\n");
+        (gpx, count)
+    }
+
+    /// Title with the counts, also heading the text version
+    fn summary(&self) -> String {
+        let count = |scan: &Option<Scan>| scan.as_ref().map_or(0, |scan| scan.len());
+        i18n::format("scan_diff", &[&count(&self.previous), &count(&self.latest)])
+    }
+
+    /// The diff as text, where there is no room for the popup
+    pub fn lines(&self) -> Vec<String> {
+        let changes = match self.changes() {
+            Some(changes) => changes,
+            None => return vec![format!("> {}", i18n::text("two_scans"))],
+        };
+        let mut lines = vec![format!("> {}", self.summary())];
+        if changes.is_empty() {
+            lines.push(format!("> {}", i18n::text("nothing_changed")));
+        }
+        for (mac, found, change) in changes {
+            lines.push(match change {
+                // The row has the MAC already
+                Change::New => format!("> + {}", found.line),
+                Change::Gone => format!("> - {}", found.line),
+                Change::Rssi(was, now) => format!("> ~ {} RSSI {} -> {}", mac, was, now),
+            });
+        }
+        lines
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        let count = self.changes().map_or(0, |changes| changes.len());
+        match key.code {
+            KeyCode::Up => self.state.select(Some(self.state.selected().unwrap_or(0).saturating_sub(1))),
+            KeyCode::Down => self.state.select(Some((self.state.selected().unwrap_or(0) + 1).min(count.saturating_sub(1)))),
+            KeyCode::Esc | KeyCode::Char('q') => return Action::Close,
+            _ => (),
+        }
+        Action::None
+    }
+
+    /// Popup with the new, vanished and weaker or stronger access points and stations
+    pub fn render(&mut self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let area = picker::centered(area, 80, 60);
+        let changes = self.changes();
+        let title = match changes {
+            Some(_) => self.summary(),
+            None => i18n::text("scan_diff_waiting").to_string(),
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.focus))
+            .title(title)
+            .title_bottom(i18n::text("scan_diff_keys"));
+
+        let rows: Vec<Row> = changes
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(mac, found, change)| {
+                let (marker, rssi, style) = match change {
+                    Change::New => ("+", found.rssi.map(|rssi| rssi.to_string()), Style::default().fg(theme.accent)),
+                    Change::Gone => ("-", found.rssi.map(|rssi| rssi.to_string()), Style::default().fg(theme.border)),
+                    Change::Rssi(was, now) => ("~", Some(format!("{} -> {}", was, now)), Style::default()),
+                };
+                Row::new(vec![
+                    Cell::from(marker),
+                    Cell::from(mac.to_string()),
+                    Cell::from(found.channel.map(|channel| channel.to_string()).unwrap_or_default()),
+                    Cell::from(rssi.unwrap_or_default()),
+                    Cell::from(found.line.clone()),
+                ])
+                .style(style)
+            })
+            .collect();
+
+        let widths = [
+            Constraint::Length(2),
+            Constraint::Length(18),
+            Constraint::Length(3),
+            Constraint::Length(10),
+            Constraint::Min(10),
+        ];
+        let table = Table::new(rows, widths)
+            .header(Row::new(vec!["", "MAC", "Ch", "RSSI", i18n::text("line")]).style(Style::default().add_modifier(Modifier::BOLD)))
+            .block(block)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+        f.render_widget(Clear, area);
+        f.render_stateful_widget(table, area, &mut self.state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(mac: &str, rssi: i32) -> Event {
+        Event::ScanResult { mac: mac.to_string(), rssi: Some(rssi), channel: Some(1), line: mac.to_string() }
+    }
+
+    #[test]
+    fn escapes_xml() {
+        assert_eq!(xml("<a & \"b\">"), "&lt;a &amp; &quot;b&quot;&gt;");
+        assert_eq!(xml("evil\u{1b}[31m\u{0}ssid\t"), "evil[31mssid\t");
+    }
+
+    #[test]
+    fn compares_the_last_two_scans() {
+        let mut scans = Scans::default();
+        scans.sent("scan");
+        assert!(scans.update(&result("aa", -50), None));
+        assert!(scans.update(&result("bb", -60), None));
+        assert!(!scans.update(&result("aa", -50), None));
+        scans.sent("SCAN");
+        scans.update(&result("aa", -70), None);
+        scans.update(&result("cc", -40), None);
+        scans.sent("show ap");
+        assert!(!scans.update(&result("dd", -40), None));
+
+        let changes: Vec<(&str, char)> = scans
+            .changes()
+            .unwrap()
+            .iter()
+            .map(|(mac, _, change)| {
+                let kind = match change {
+                    Change::New => '+',
+                    Change::Gone => '-',
+                    Change::Rssi(..) => '~',
+                };
+                (*mac, kind)
+            })
+            .collect();
+        assert_eq!(changes, [("cc", '+'), ("bb", '-'), ("aa", '~')]);
+    }
+
+    #[test]
+    fn keeps_formulas_out_of_csv() {
+        assert_eq!(csv_field("MyWifi"), "MyWifi");
+        assert_eq!(csv_field("My, \"Wifi\""), "\"My, \"\"Wifi\"\"\"");
+        assert_eq!(csv_field("-56"), "\"'-56\"");
+        assert_eq!(csv_field("@SUM(A1)"), "\"'@SUM(A1)\"");
+
+        let mut scans = Scans::default();
+        scans.sent("scan");
+        let ssid = r#"=HYPERLINK("http://evil.example","Free Wifi")"#;
+        let line = format!("aa:bb:cc:dd:ee:ff {}", ssid);
+        scans.update(&Event::ScanResult { mac: ssid.to_string(), rssi: Some(-56), channel: Some(6), line }, None);
+        let (csv, count) = Scans::csv(scans.latest.as_ref().unwrap(), &Redactor::default());
+        assert_eq!(count, 1);
+        assert_eq!(
+            csv.lines().nth(1),
+            Some(r#""'=HYPERLINK(""http://evil.example"",""Free Wifi"")",6,-56,"aa:bb:cc:dd:ee:ff =HYPERLINK(""http://evil.example"",""Free Wifi"")",,"#)
+        );
+    }
+}
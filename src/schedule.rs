@@ -0,0 +1,193 @@
+use chrono::{Local, NaiveTime};
+use std::fmt;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant};
+
+use crate::i18n;
+use crate::output;
+
+/// When a job sends its command
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum When {
+    /// Once, the next time the clock shows this time
+    At(NaiveTime),
+    /// Over and over, first after one interval
+    Every(Duration),
+}
+
+impl fmt::Display for When {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            When::At(time) => write!(f, "at {}", time.format("%H:%M:%S")),
+            When::Every(interval) => write!(f, "every {}", output::duration(*interval)),
+        }
+    }
+}
+
+/// Time until the clock shows `time` next, today or tomorrow
+fn until(time: NaiveTime) -> Duration {
+    wait(time, Local::now().time())
+}
+
+/// Time from `now` until `time`. A time that is now or has passed today is meant for tomorrow,
+/// `:at 12:30` typed at 12:30:20 doesn't fire right away.
+fn wait(time: NaiveTime, now: NaiveTime) -> Duration {
+    const DAY_MS: i64 = 24 * 3600 * 1000;
+    let mut wait = (time - now).num_milliseconds();
+    if wait <= 0 {
+        wait += DAY_MS;
+    }
+    Duration::from_millis(wait as u64)
+}
+
+/// A job that is due
+#[derive(Debug)]
+pub struct Fired {
+    pub id: usize,
+    pub command: String,
+}
+
+struct Job {
+    id: usize,
+    when: When,
+    command: String,
+    /// Next time the job fires, for the list
+    next: Instant,
+    task: JoinHandle<()>,
+}
+
+/// Commands sent at a time of day or in intervals. Every job is a task that waits for its time,
+/// the session sends the commands when they arrive.
+pub struct Scheduler {
+    jobs: Vec<Job>,
+    next_id: usize,
+    fired: UnboundedSender<Fired>,
+}
+
+impl Scheduler {
+    pub fn new() -> (Self, UnboundedReceiver<Fired>) {
+        let (fired, fired_rx) = mpsc::unbounded_channel();
+        (Self { jobs: Vec::new(), next_id: 1, fired }, fired_rx)
+    }
+
+    /// Start a job, returns its ID
+    pub fn add(&mut self, when: When, command: String) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let fired = self.fired.clone();
+        let line = command.clone();
+        let (next, task) = match when {
+            When::At(time) => {
+                let next = Instant::now() + until(time);
+                let task = tokio::spawn(async move {
+                    time::sleep_until(next).await;
+                    let _ = fired.send(Fired { id, command: line });
+                });
+                (next, task)
+            }
+            When::Every(interval) => {
+                let next = Instant::now() + interval;
+                let task = tokio::spawn(async move {
+                    let mut ticker = time::interval_at(next, interval);
+                    loop {
+                        ticker.tick().await;
+                        if fired.send(Fired { id, command: line.clone() }).is_err() {
+                            break;
+                        }
+                    }
+                });
+                (next, task)
+            }
+        };
+        self.jobs.push(Job { id, when, command, next, task });
+        id
+    }
+
+    /// Note that a job fired, one-time jobs are done then. Returns false if the job was cancelled
+    /// after it fired, its command must not be sent anymore.
+    pub fn fired(&mut self, id: usize) -> bool {
+        let job = match self.jobs.iter_mut().find(|job| job.id == id) {
+            Some(job) => job,
+            None => return false,
+        };
+        match job.when {
+            When::At(_) => self.jobs.retain(|job| job.id != id),
+            When::Every(interval) => job.next += interval,
+        }
+        true
+    }
+
+    /// Stop a job, returns false if there is none with this ID
+    pub fn cancel(&mut self, id: usize) -> bool {
+        match self.jobs.iter().position(|job| job.id == id) {
+            Some(index) => {
+                self.jobs.remove(index).task.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stop all jobs, returns how many there were
+    pub fn cancel_all(&mut self) -> usize {
+        let count = self.jobs.len();
+        for job in self.jobs.drain(..) {
+            job.task.abort();
+        }
+        count
+    }
+
+    /// Pending jobs for `:jobs`
+    pub fn lines(&self) -> Vec<String> {
+        if self.jobs.is_empty() {
+            return vec![format!("> {}", i18n::text("no_jobs"))];
+        }
+        self.jobs
+            .iter()
+            .map(|job| {
+                let due = output::duration(job.next.saturating_duration_since(Instant::now()));
+                format!("> {}", i18n::format("job", &[&job.id, &job.when, &due, &job.command]))
+            })
+            .collect()
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.cancel_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(text: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(text, "%H:%M:%S%.f").unwrap()
+    }
+
+    #[test]
+    fn waits_for_the_next_occurrence() {
+        assert_eq!(wait(time("12:30:00"), time("12:00:00")), Duration::from_secs(30 * 60));
+        assert_eq!(wait(time("12:30:00"), time("12:30:20")), Duration::from_secs(24 * 3600 - 20));
+        assert_eq!(wait(time("12:30:00"), time("12:30:00")), Duration::from_secs(24 * 3600));
+        assert_eq!(wait(time("00:00:00"), time("23:59:59.5")), Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn forgets_cancelled_jobs() {
+        let (mut scheduler, _fired) = Scheduler::new();
+        let once = scheduler.add(When::At(time("12:00:00")), "scan".to_string());
+        let repeated = scheduler.add(When::Every(Duration::from_secs(60)), "sysinfo".to_string());
+
+        assert!(scheduler.cancel(once));
+        assert!(!scheduler.fired(once));
+        assert!(scheduler.fired(repeated));
+        assert!(scheduler.fired(repeated));
+        assert_eq!(scheduler.cancel_all(), 1);
+        assert!(!scheduler.fired(repeated));
+    }
+}
@@ -0,0 +1,100 @@
+use rhai::{Dynamic, Engine, Scope, AST};
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Something a script asked for, carried out by the monitor after the hook returns
+pub enum Action {
+    /// Send a command to the device
+    Send(String),
+    /// Show a line in the Messages box
+    Print(String),
+}
+
+/// What to do with a command the user entered, decided by `on_command`
+pub enum Verdict {
+    Send(String),
+    Drop,
+}
+
+/// A user script with `on_connect`, `on_line` and `on_command` hooks
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    actions: Rc<RefCell<Vec<Action>>>,
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let actions = Rc::new(RefCell::new(Vec::new()));
+
+        let mut engine = Engine::new();
+        let queue = actions.clone();
+        engine.register_fn("send", move |command: &str| {
+            queue.borrow_mut().push(Action::Send(command.to_string()));
+        });
+        let queue = actions.clone();
+        engine.on_print(move |text| queue.borrow_mut().push(Action::Print(text.to_string())));
+
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|e| format!("Couldn't load script {}: {}", path.display(), e))?;
+
+        let mut script = Self {
+            engine,
+            ast,
+            scope: Scope::new(),
+            actions,
+        };
+
+        // Run top level statements once, e.g. to set up variables
+        script
+            .engine
+            .run_ast_with_scope(&mut script.scope, &script.ast)
+            .map_err(|e| format!("Script {} failed: {}", path.display(), e))?;
+
+        Ok(script)
+    }
+
+    /// Call a hook if the script defines it
+    fn call(&mut self, hook: &str, arg: &str) -> Result<Option<Dynamic>, String> {
+        if !self.ast.iter_functions().any(|f| f.name == hook && f.params.len() == 1) {
+            return Ok(None);
+        }
+
+        self.engine
+            .call_fn::<Dynamic>(&mut self.scope, &self.ast, hook, (arg.to_string(),))
+            .map(Some)
+            .map_err(|e| format!("Script hook {} failed: {}", hook, e))
+    }
+
+    pub fn on_connect(&mut self, port: &str) -> Result<(), String> {
+        self.call("on_connect", port).map(|_| ())
+    }
+
+    pub fn on_line(&mut self, line: &str) -> Result<(), String> {
+        self.call("on_line", line.trim_end()).map(|_| ())
+    }
+
+    /// `on_command` can return a different command to send instead, or `false` to not send it at all
+    pub fn on_command(&mut self, command: &str) -> Result<Verdict, String> {
+        let result = match self.call("on_command", command)? {
+            Some(result) => result,
+            None => return Ok(Verdict::Send(command.to_string())),
+        };
+
+        if result.as_bool() == Ok(false) {
+            Ok(Verdict::Drop)
+        } else if result.is_string() {
+            Ok(Verdict::Send(result.into_string().unwrap_or_default()))
+        } else {
+            Ok(Verdict::Send(command.to_string()))
+        }
+    }
+
+    /// Take what the script asked for since the last call
+    pub fn actions(&mut self) -> Vec<Action> {
+        self.actions.borrow_mut().drain(..).collect()
+    }
+}
@@ -0,0 +1,292 @@
+use serde::{Deserialize, Deserializer};
+use serialport::{DataBits, FlowControl, Parity, SerialPort, StopBits};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Rates tried by `--baud auto`, 74880 is what the ESP8266 boot ROM uses
+pub const AUTO_BAUD_RATES: [u32; 9] = [115200, 74880, 9600, 19200, 38400, 57600, 230400, 460800, 921600];
+
+/// Output seen when a board boots or a deauther answers
+const BANNERS: [&str; 7] = ["rst cause", "boot mode", "ets ", "load 0x", "csum", "ESP", "Deauther"];
+
+/// Baud rate setting, either fixed or detected after connecting
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Baud {
+    Fixed(u32),
+    Auto,
+}
+
+impl FromStr for Baud {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(Baud::Auto);
+        }
+        match s.parse() {
+            Ok(rate) if rate > 0 => Ok(Baud::Fixed(rate)),
+            _ => Err(format!("Invalid baud rate '{}', expected a number or auto", s)),
+        }
+    }
+}
+
+/// Accepts `baud = 115200` as well as `baud = "auto"`
+impl<'de> Deserialize<'de> for Baud {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Rate(u32),
+            Text(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Rate(rate) => Baud::from_str(&rate.to_string()),
+            Raw::Text(text) => Baud::from_str(&text),
+        }
+        .map_err(serde::de::Error::custom)
+    }
+}
+
+/// How likely it is that the bytes were read at the right baud rate.
+/// Wrong rates produce mostly non-printable garbage, known boot banners add a bonus.
+pub fn score(bytes: &[u8]) -> f64 {
+    if bytes.is_empty() {
+        return 0.0;
+    }
+
+    let printable = bytes
+        .iter()
+        .filter(|b| b.is_ascii_graphic() || matches!(b, b' ' | b'\r' | b'\n' | b'\t'))
+        .count();
+    let ratio = printable as f64 / bytes.len() as f64;
+
+    // A handful of bytes that happen to be printable shouldn't win
+    let amount = (bytes.len() as f64 / 32.0).min(1.0);
+
+    let text = String::from_utf8_lossy(bytes);
+    let banners = BANNERS.iter().filter(|banner| text.contains(*banner)).count();
+
+    ratio * amount + banners as f64 * 0.5
+}
+
+/// Line settings of the serial port
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinkSettings {
+    pub baud_rate: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub flow_control: FlowControl,
+}
+
+impl Default for LinkSettings {
+    fn default() -> Self {
+        Self {
+            baud_rate: 115200,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+        }
+    }
+}
+
+impl LinkSettings {
+    /// Port builder with these settings
+    pub fn builder(&self, path: &str) -> serialport::SerialPortBuilder {
+        tokio_serial::new(path, self.baud_rate)
+            .data_bits(self.data_bits)
+            .flow_control(self.flow_control)
+            .parity(self.parity)
+            .stop_bits(self.stop_bits)
+            .timeout(Duration::from_secs(10))
+    }
+
+    /// Reconfigure an open port without closing it
+    pub fn apply<P: SerialPort>(&self, port: &mut P) -> serialport::Result<()> {
+        port.set_baud_rate(self.baud_rate)?;
+        port.set_data_bits(self.data_bits)?;
+        port.set_parity(self.parity)?;
+        port.set_stop_bits(self.stop_bits)?;
+        port.set_flow_control(self.flow_control)
+    }
+}
+
+/// Short form like "115200 8N1 flow: none"
+impl fmt::Display for LinkSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let data_bits = match self.data_bits {
+            DataBits::Five => 5,
+            DataBits::Six => 6,
+            DataBits::Seven => 7,
+            DataBits::Eight => 8,
+        };
+        let parity = match self.parity {
+            Parity::None => 'N',
+            Parity::Odd => 'O',
+            Parity::Even => 'E',
+        };
+        let stop_bits = match self.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        let flow = match self.flow_control {
+            FlowControl::None => "none",
+            FlowControl::Software => "xon/xoff",
+            FlowControl::Hardware => "rts/cts",
+        };
+        write!(f, "{} {}{}{} flow: {}", self.baud_rate, data_bits, parity, stop_bits, flow)
+    }
+}
+
+/// Parse what `Display` writes, e.g. `115200 8N1 flow: none`
+impl FromStr for LinkSettings {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid line settings '{}', expected e.g. 115200 8N1 flow: none", s);
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let (baud_rate, frame, flow) = match words.as_slice() {
+            [baud_rate, frame, "flow:", flow] if frame.len() == 3 && frame.is_ascii() => (baud_rate, frame, flow),
+            _ => return Err(invalid()),
+        };
+        Ok(Self {
+            baud_rate: baud_rate.parse().map_err(|_| invalid())?,
+            data_bits: parse_data_bits(&frame[..1])?,
+            parity: parse_parity(&frame[1..2])?,
+            stop_bits: parse_stop_bits(&frame[2..])?,
+            flow_control: parse_flow_control(flow)?,
+        })
+    }
+}
+
+pub fn parse_data_bits(s: &str) -> Result<DataBits, String> {
+    match s {
+        "5" => Ok(DataBits::Five),
+        "6" => Ok(DataBits::Six),
+        "7" => Ok(DataBits::Seven),
+        "8" => Ok(DataBits::Eight),
+        _ => Err(format!("Invalid data bits '{}', expected 5, 6, 7 or 8", s)),
+    }
+}
+
+pub fn parse_parity(s: &str) -> Result<Parity, String> {
+    match s.to_lowercase().as_str() {
+        "none" | "n" => Ok(Parity::None),
+        "odd" | "o" => Ok(Parity::Odd),
+        "even" | "e" => Ok(Parity::Even),
+        _ => Err(format!("Invalid parity '{}', expected none, odd or even", s)),
+    }
+}
+
+pub fn parse_stop_bits(s: &str) -> Result<StopBits, String> {
+    match s {
+        "1" => Ok(StopBits::One),
+        "2" => Ok(StopBits::Two),
+        _ => Err(format!("Invalid stop bits '{}', expected 1 or 2", s)),
+    }
+}
+
+pub fn parse_flow_control(s: &str) -> Result<FlowControl, String> {
+    match s.to_lowercase().as_str() {
+        "none" => Ok(FlowControl::None),
+        "software" | "xon/xoff" | "xonxoff" => Ok(FlowControl::Software),
+        "hardware" | "rts/cts" | "rtscts" => Ok(FlowControl::Hardware),
+        _ => Err(format!("Invalid flow control '{}', expected none, software or hardware", s)),
+    }
+}
+
+/// Other processes that have the port open, with their pid and name.
+/// Only found on Linux, and only the processes of the same user unless running as root.
+#[cfg(target_os = "linux")]
+pub fn users(port: &str) -> Vec<(u32, String)> {
+    use std::fs;
+
+    let (target, procs) = match (fs::canonicalize(port), fs::read_dir("/proc")) {
+        (Ok(target), Ok(procs)) => (target, procs),
+        _ => return Vec::new(),
+    };
+    let own = std::process::id();
+    let mut users = Vec::new();
+    for entry in procs.flatten() {
+        let pid = match entry.file_name().to_str().and_then(|name| name.parse().ok()) {
+            Some(pid) if pid != own => pid,
+            _ => continue,
+        };
+        // Unreadable for processes of other users
+        let fds = match fs::read_dir(entry.path().join("fd")) {
+            Ok(fds) => fds,
+            Err(_) => continue,
+        };
+        if fds.flatten().any(|fd| fs::read_link(fd.path()).is_ok_and(|link| link == target)) {
+            let name = fs::read_to_string(entry.path().join("comm")).unwrap_or_default();
+            users.push((pid, name.trim().to_string()));
+        }
+    }
+    users
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn users(_port: &str) -> Vec<(u32, String)> {
+    Vec::new()
+}
+
+/// Explain why a port couldn't be opened and what to do about it
+pub fn open_error(port: &str, link: &LinkSettings, e: &serialport::Error) -> String {
+    use serialport::ErrorKind;
+    use std::io::ErrorKind as Io;
+
+    match e.kind() {
+        ErrorKind::Io(Io::PermissionDenied) => format!(
+            "Permission denied for {}. Run `huhnitor setup-permissions` to get access to serial ports",
+            port
+        ),
+        // Windows reports a port that is in use as access denied, which ends up here as well
+        ErrorKind::NoDevice => match users(port).as_slice() {
+            [] => format!(
+                "{} is busy or gone ({}). Close other programs using it, like the Arduino IDE or another serial monitor, or replug the device",
+                port, e.description
+            ),
+            users => {
+                let users: Vec<String> = users.iter().map(|(pid, name)| format!("{} (pid {})", name, pid)).collect();
+                format!("{} is busy, it is open in {}. Close those programs or replug the device", port, users.join(", "))
+            }
+        },
+        ErrorKind::Io(Io::NotFound) => format!("{} doesn't exist. Check the name or pick a port from the list", port),
+        ErrorKind::InvalidInput => format!(
+            "{} doesn't support {} ({}). Try another baud rate with --baud",
+            port, link, e.description
+        ),
+        _ => format!("Couldn't open {}: {}", port, e.description),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_line_settings() {
+        let link: LinkSettings = "9600 7E2 flow: hardware".parse().unwrap();
+        assert_eq!(link.baud_rate, 9600);
+        assert_eq!(link.data_bits, DataBits::Seven);
+        assert_eq!(link.parity, Parity::Even);
+        assert_eq!(link.stop_bits, StopBits::Two);
+        assert_eq!(link.flow_control, FlowControl::Hardware);
+    }
+
+    #[test]
+    fn reads_what_it_shows() {
+        let link = LinkSettings::default();
+        assert_eq!(link.to_string().parse::<LinkSettings>().map(|parsed| parsed.to_string()), Ok(link.to_string()));
+    }
+
+    #[test]
+    fn rejects_broken_line_settings() {
+        for text in ["", "115200", "115200 8N1", "fast 8N1 flow: none", "115200 9N1 flow: none", "115200 8X1 flow: none", "115200 8N3 flow: none", "115200 8N1 flow: magic", "115200 8ä flow: none"] {
+            assert!(text.parse::<LinkSettings>().is_err(), "{}", text);
+        }
+    }
+}
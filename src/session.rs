@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::app::Origin;
+
+/// A line of the Messages box
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SavedLine {
+    pub text: String,
+    /// Where the line came from, device output can look like a message of the Huhnitor
+    #[serde(default)]
+    pub origin: Option<Origin>,
+    /// Typed by the user, written by older versions instead of `origin`
+    #[serde(default, skip_serializing)]
+    pub sent: bool,
+}
+
+/// Scrollback and command history of a session, saved so they survive a crash
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Snapshot {
+    /// Seconds since the epoch
+    pub saved: u64,
+    /// Port that was connected
+    pub port: Option<String>,
+    pub lines: Vec<SavedLine>,
+    pub history: Vec<String>,
+}
+
+impl Snapshot {
+    /// Time since the snapshot was saved
+    pub fn age(&self) -> Duration {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        now.saturating_sub(Duration::from_secs(self.saved))
+    }
+}
+
+/// Directory of the session files, e.g. `~/.local/share/huhnitor/sessions`
+fn dir() -> io::Result<PathBuf> {
+    dirs::data_dir()
+        .map(|dir| dir.join("huhnitor").join("sessions"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No data directory"))
+}
+
+/// Name of the session file of a port, e.g. `dev-ttyUSB0.json`. Sessions without a port are
+/// kept per process, so several Huhnitors don't overwrite each other.
+fn file_name(port: Option<&str>) -> String {
+    match port {
+        Some(port) => {
+            let name: String = port.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '-' }).collect();
+            format!("{}.json", name.trim_matches('-'))
+        }
+        None => format!("pid-{}.json", std::process::id()),
+    }
+}
+
+/// Save the snapshot, replacing the file only once it is written completely
+pub fn save(snapshot: &mut Snapshot) -> io::Result<()> {
+    let dir = dir()?;
+    fs::create_dir_all(&dir)?;
+
+    snapshot.saved = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let json = serde_json::to_vec(snapshot).map_err(io::Error::other)?;
+    let path = dir.join(file_name(snapshot.port.as_deref()));
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, json)?;
+    fs::rename(tmp, path)
+}
+
+/// Snapshot of the last session on the port, or of the last session at all without one
+pub fn load(port: Option<&str>) -> io::Result<Snapshot> {
+    let dir = dir()?;
+    let path = match port {
+        Some(port) => dir.join(file_name(Some(port))),
+        None => fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .max_by_key(|entry| entry.metadata().and_then(|meta| meta.modified()).ok())
+            .map(|entry| entry.path())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No saved session"))?,
+    };
+    let json = fs::read(&path)?;
+    serde_json::from_slice(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_files_after_the_port() {
+        assert_eq!(file_name(Some("/dev/ttyUSB0")), "dev-ttyUSB0.json");
+        assert_eq!(file_name(Some("COM3")), "COM3.json");
+        assert_ne!(file_name(None), file_name(Some("")));
+    }
+
+    #[test]
+    fn keeps_the_origin_of_lines() {
+        let line = SavedLine { text: "ERROR: no such file".to_string(), origin: Some(Origin::Received), sent: false };
+        let json = serde_json::to_string(&line).unwrap();
+        assert_eq!(json, r#"{"text":"ERROR: no such file","origin":"received"}"#);
+
+        let old: SavedLine = serde_json::from_str(r#"{"text":"scan","sent":true}"#).unwrap();
+        assert!(old.sent && old.origin.is_none());
+    }
+}
@@ -0,0 +1,308 @@
+use chrono::{DateTime, Local};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::output;
+
+lazy_static::lazy_static! {
+    // `ESP8266 Deauther v3.0.0` or `Version 2.6.1`
+    static ref VERSION: Regex = Regex::new(r"(?i)\bv(?:ersion)?\s*(\d+\.\d+(?:\.\d+)*)").unwrap();
+}
+
+/// Lines after connecting that are looked through for the firmware version, the banner comes first
+const VERSION_LINES: usize = 50;
+
+/// Sessions kept in the index, the oldest are forgotten
+const INDEX_SIZE: usize = 1000;
+
+/// What is known about a logged session, written next to its log as `<log>.json`
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct SessionMeta {
+    pub name: Option<String>,
+    pub log: PathBuf,
+    pub port: Option<String>,
+    /// Name of the USB adapter, e.g. `CP2102 USB to UART Bridge Controller`
+    pub device: Option<String>,
+    pub firmware: Option<String>,
+    pub version: Option<String>,
+    /// RFC 3339 times
+    pub started: String,
+    /// Missing while the session runs, or if Huhnitor didn't end properly
+    pub stopped: Option<String>,
+    pub notes: Vec<String>,
+}
+
+/// Metadata of the running log, saved whenever something new is known and once more with the stop time when dropped
+pub struct LoggedSession {
+    path: PathBuf,
+    meta: SessionMeta,
+    /// Lines left to look for the version in
+    version_lines: usize,
+}
+
+/// Sidecar of a log, e.g. `survey.log.json`
+fn sidecar(log: &Path) -> PathBuf {
+    let mut name = log.as_os_str().to_os_string();
+    name.push(".json");
+    PathBuf::from(name)
+}
+
+/// List of the sidecars written, e.g. `~/.local/share/huhnitor/session-index`, so they're found wherever the logs are
+fn index() -> Option<PathBuf> {
+    Some(dirs::data_dir()?.join("huhnitor").join("session-index"))
+}
+
+/// Sidecars listed in the index, each once
+fn indexed() -> Vec<PathBuf> {
+    let text = index().and_then(|index| fs::read_to_string(index).ok()).unwrap_or_default();
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for path in text.lines().map(PathBuf::from) {
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+/// Add a sidecar to the index, dropping the ones that are gone and the oldest beyond `INDEX_SIZE`
+fn add_to_index(sidecar: &Path) -> io::Result<()> {
+    let index = match index() {
+        Some(index) => index,
+        None => return Ok(()),
+    };
+    let paths = with_added(indexed(), sidecar);
+    if let Some(dir) = index.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).write(true).truncate(true).open(index)?;
+    for path in paths {
+        writeln!(file, "{}", path.display())?;
+    }
+    Ok(())
+}
+
+/// The index with a sidecar added at the end, without the ones that are gone and the oldest beyond `INDEX_SIZE`
+fn with_added(mut paths: Vec<PathBuf>, sidecar: &Path) -> Vec<PathBuf> {
+    paths.retain(|path| path != sidecar && path.exists());
+    paths.push(sidecar.to_path_buf());
+    let skip = paths.len().saturating_sub(INDEX_SIZE);
+    paths.split_off(skip)
+}
+
+/// A log was moved by rotation, its sidecar follows it and the session it holds is over.
+/// Missing sidecars are fine, not every log has one.
+pub fn moved(from: &Path, to: &Path) -> io::Result<()> {
+    match move_sidecar(from, to)? {
+        Some(moved) => add_to_index(&moved),
+        None => Ok(()),
+    }
+}
+
+/// Move the sidecar of a log, returns where it went if there was one
+fn move_sidecar(from: &Path, to: &Path) -> io::Result<Option<PathBuf>> {
+    let json = match fs::read(sidecar(from)) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    fs::remove_file(sidecar(from))?;
+    let mut meta: SessionMeta = serde_json::from_slice(&json).map_err(io::Error::other)?;
+    // Absolute like the log of a new session, the log itself is there already
+    meta.log = fs::canonicalize(to).unwrap_or_else(|_| to.to_path_buf());
+    meta.stopped.get_or_insert_with(|| Local::now().to_rfc3339());
+    let moved = sidecar(&meta.log);
+    fs::write(&moved, serde_json::to_vec_pretty(&meta).map_err(io::Error::other)?)?;
+    Ok(Some(moved))
+}
+
+/// A log was deleted by rotation, its sidecar goes with it
+pub fn removed(log: &Path) -> io::Result<()> {
+    match fs::remove_file(sidecar(log)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+impl LoggedSession {
+    /// Start the metadata of a log that was just opened
+    pub fn start(log: &Path, name: Option<String>, notes: Option<String>) -> io::Result<Self> {
+        // Absolute, so `huhnitor sessions` finds the log from anywhere
+        let log = fs::canonicalize(log).unwrap_or_else(|_| log.to_path_buf());
+        let session = Self {
+            path: sidecar(&log),
+            meta: SessionMeta {
+                name,
+                log,
+                started: Local::now().to_rfc3339(),
+                notes: notes.into_iter().collect(),
+                ..SessionMeta::default()
+            },
+            version_lines: 0,
+        };
+        session.save()?;
+        add_to_index(&session.path)?;
+        Ok(session)
+    }
+
+    /// The log was rotated and its earlier part moved away with the metadata so far,
+    /// the part in the new file starts now with what is known about the device
+    pub fn rotated(&mut self) -> io::Result<()> {
+        self.meta.started = Local::now().to_rfc3339();
+        self.meta.stopped = None;
+        self.meta.notes.clear();
+        self.save()?;
+        add_to_index(&self.path)
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.meta.name.as_deref()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(&self.meta).map_err(io::Error::other)?;
+        fs::write(&self.path, json)
+    }
+
+    /// The port was opened
+    pub fn connected(&mut self, port: &str, device: Option<String>) -> io::Result<()> {
+        self.meta.port = Some(port.to_string());
+        self.meta.device = device;
+        self.version_lines = VERSION_LINES;
+        self.save()
+    }
+
+    /// The firmware of the device is known
+    pub fn firmware(&mut self, name: &str) -> io::Result<()> {
+        self.meta.firmware = Some(name.to_string());
+        self.save()
+    }
+
+    /// A note was added to the log
+    pub fn note(&mut self, note: String) -> io::Result<()> {
+        self.meta.notes.push(note);
+        self.save()
+    }
+
+    /// Look for the firmware version in a received line
+    pub fn line(&mut self, line: &str) -> io::Result<()> {
+        if self.version_lines == 0 {
+            return Ok(());
+        }
+        self.version_lines -= 1;
+        match VERSION.captures(line) {
+            Some(caps) if self.meta.version.as_deref() != Some(&caps[1]) => {
+                self.version_lines = 0;
+                self.meta.version = Some(caps[1].to_string());
+                self.save()
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for LoggedSession {
+    fn drop(&mut self) {
+        self.meta.stopped = Some(Local::now().to_rfc3339());
+        let _ = self.save();
+    }
+}
+
+/// Metadata of all sessions whose sidecar still exists, the oldest first
+pub fn list() -> Vec<SessionMeta> {
+    let mut sessions: Vec<SessionMeta> = indexed()
+        .iter()
+        .filter_map(|path| fs::read(path).ok())
+        .filter_map(|json| serde_json::from_slice(&json).ok())
+        .collect();
+    by_start(&mut sessions);
+    sessions
+}
+
+/// Sort sessions by when they started. The offsets of local times change with daylight saving, so the text doesn't sort.
+fn by_start(sessions: &mut [SessionMeta]) {
+    sessions.sort_by_key(|meta| DateTime::parse_from_rfc3339(&meta.started).ok());
+}
+
+/// Lines of `huhnitor sessions`
+pub fn table(sessions: &[SessionMeta]) -> Vec<String> {
+    let time = |text: &str| DateTime::parse_from_rfc3339(text).ok();
+    sessions
+        .iter()
+        .map(|meta| {
+            let started = time(&meta.started);
+            let when = started.map_or_else(|| meta.started.clone(), |t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string());
+            let duration = match (started, meta.stopped.as_deref().and_then(time)) {
+                (Some(start), Some(stop)) => output::duration((stop - start).to_std().unwrap_or_default()),
+                _ => "-".to_string(),
+            };
+            let device = meta.device.as_deref().or(meta.port.as_deref()).unwrap_or("-");
+            let firmware = match (&meta.firmware, &meta.version) {
+                (Some(firmware), Some(version)) => format!("{} {}", firmware, version),
+                (Some(firmware), None) => firmware.clone(),
+                (None, _) => "-".to_string(),
+            };
+            format!(
+                "{}  {:>8}  {:<20}  {:<24}  {:<16}  {}",
+                when,
+                duration,
+                meta.name.as_deref().unwrap_or("-"),
+                device,
+                firmware,
+                meta.log.display()
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn meta(started: &str) -> SessionMeta {
+        SessionMeta { started: started.to_string(), ..SessionMeta::default() }
+    }
+
+    #[test]
+    fn sorts_by_the_time_not_the_text() {
+        // 01:30 in summer time is before 01:10 in winter time on the night the clocks go back
+        let mut sessions = vec![meta("2024-10-27T01:10:00+01:00"), meta("2024-10-27T01:30:00+02:00")];
+        by_start(&mut sessions);
+        assert_eq!(sessions[0].started, "2024-10-27T01:30:00+02:00");
+    }
+
+    #[test]
+    fn bounds_the_index() {
+        let dir = env::temp_dir();
+        let paths = vec![dir.clone(); INDEX_SIZE];
+        let gone = dir.join("huhnitor-no-such-sidecar.json");
+        let mut with_gone = paths.clone();
+        with_gone.insert(0, gone.clone());
+
+        let index = with_added(with_gone, &dir.join("new.json"));
+        assert_eq!(index.len(), INDEX_SIZE);
+        assert!(!index.contains(&gone));
+        assert_eq!(index.last(), Some(&dir.join("new.json")));
+    }
+
+    #[test]
+    fn moves_the_sidecar_with_its_log() {
+        let dir = env::temp_dir().join(format!("huhnitor-sessions-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (log, rotated) = (dir.join("survey.log"), dir.join("survey.log.1"));
+        fs::write(&rotated, "").unwrap();
+        let started = SessionMeta { name: Some("roof".to_string()), log: log.clone(), ..meta("2024-05-01T12:00:00+02:00") };
+        fs::write(sidecar(&log), serde_json::to_vec(&started).unwrap()).unwrap();
+
+        let moved = move_sidecar(&log, &rotated).unwrap().unwrap();
+        let meta: SessionMeta = serde_json::from_slice(&fs::read(&moved).unwrap()).unwrap();
+        assert!(!sidecar(&log).exists());
+        assert_eq!((meta.name.as_deref(), meta.log.file_name()), (Some("roof"), rotated.file_name()));
+        assert!(meta.stopped.is_some());
+        assert_eq!(move_sidecar(&log, &rotated).unwrap(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,117 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Whether the terminal UI can be suspended like other programs with Ctrl-Z
+pub const CAN_SUSPEND: bool = cfg!(unix);
+
+/// Ways of handing the terminal to the shell for a while
+#[derive(Debug, Clone, PartialEq)]
+pub enum Leave {
+    /// Stop Huhnitor until the shell continues it with `fg`
+    Suspend,
+    /// Run a command, or an interactive shell with `None`
+    Run(Option<String>),
+    /// Write commands in the editor, starting with this text
+    Edit(String),
+}
+
+/// Stop the process like Ctrl-Z does in a cooked terminal, returns once it is continued
+#[cfg(unix)]
+pub fn suspend() -> io::Result<()> {
+    use nix::sys::signal::{raise, Signal};
+    raise(Signal::SIGTSTP).map_err(io::Error::from)
+}
+
+#[cfg(not(unix))]
+pub fn suspend() -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Suspending isn't supported on this platform"))
+}
+
+/// The user's shell and its flag to run a command, `$SHELL -c` on unix and `%COMSPEC% /C` on Windows
+pub fn shell() -> (String, &'static str) {
+    if cfg!(windows) {
+        (env::var("COMSPEC").unwrap_or_else(|_| "cmd".to_string()), "/C")
+    } else {
+        (env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string()), "-c")
+    }
+}
+
+/// The user's editor, `$VISUAL` or `$EDITOR`, otherwise notepad or vi
+fn editor() -> String {
+    let fallback = if cfg!(windows) { "notepad" } else { "vi" };
+    env::var("VISUAL").or_else(|_| env::var("EDITOR")).unwrap_or_else(|_| fallback.to_string())
+}
+
+/// A new directory in the temp directory that only the user can enter.
+/// Creating it fails if anything is in the way, so another user can't plant a symlink for our files.
+fn private_dir() -> io::Result<PathBuf> {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.subsec_nanos());
+    let dir = env::temp_dir().join(format!("huhnitor-{}-{}", std::process::id(), nanos));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    }
+    #[cfg(not(unix))]
+    fs::create_dir(&dir)?;
+    Ok(dir)
+}
+
+/// Open the text in the editor and return it once the editor is closed
+pub fn edit(text: &str) -> io::Result<String> {
+    let dir = private_dir()?;
+    let path = dir.join("commands.txt");
+    let written = OpenOptions::new().write(true).create_new(true).open(&path).and_then(|mut file| file.write_all(text.as_bytes()));
+    let status = written.and_then(|()| {
+        // Run by the shell, since editors are often set with arguments, e.g. `code --wait`
+        let (shell, flag) = shell();
+        Command::new(shell).arg(flag).arg(format!("{} \"{}\"", editor(), path.display())).status()
+    });
+    let text = fs::read_to_string(&path);
+    let _ = fs::remove_dir_all(&dir);
+    match status? {
+        status if status.success() => text,
+        status => Err(io::Error::other(format!("{} ended with {}", editor(), status))),
+    }
+}
+
+/// Run a command in the shell, or the shell itself, on the normal screen.
+/// After a command the output stays until Enter is pressed, an interactive shell ends with `exit`.
+pub fn run(command: Option<&str>) -> io::Result<ExitStatus> {
+    let (shell, flag) = shell();
+    let status = match command {
+        Some(command) => {
+            println!("$ {}", command);
+            Command::new(&shell).arg(flag).arg(command).status()?
+        }
+        None => {
+            println!("Type exit to return to Huhnitor");
+            return Command::new(&shell).status();
+        }
+    };
+    print!("Press Enter to return to Huhnitor");
+    io::stdout().flush()?;
+    io::stdin().lock().read_line(&mut String::new())?;
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn creates_a_fresh_private_dir() {
+        let dir = private_dir().unwrap();
+        assert!(fs::read_dir(&dir).unwrap().next().is_none());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+        fs::remove_dir(&dir).unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+use regex::Regex;
+
+// The packet monitor prints one summary per frame, e.g.
+// `Deauth aa:bb:cc:dd:ee:ff -> 11:22:33:44:55:66 ch 1 -56 reason 7` or `Beacon aa:bb:cc:dd:ee:ff "MyWifi" ch 6`
+lazy_static::lazy_static! {
+    static ref FRAME: Regex = Regex::new(r"(?i)^\W*(deauth\w*|disassoc\w*|beacon|probe\w*)\b").unwrap();
+    // Channel activity also counts lines that only mention the frame type, e.g. `[ch 6] deauth aa:bb:cc:dd:ee:ff`
+    static ref KIND: Regex = Regex::new(r"(?i)\b(deauth\w*|disassoc\w*|beacon|probe\w*)\b").unwrap();
+    static ref MAC: Regex = Regex::new(r"(?i)\b([0-9a-f]{2}(?::[0-9a-f]{2}){5})\b").unwrap();
+    static ref CHANNEL: Regex = Regex::new(r"(?i)\bch(?:annel)?:? *(\d{1,2})\b").unwrap();
+    static ref RSSI: Regex = Regex::new(r"(?:^|\s)(-\d{1,3})(?:\s*dBm)?(?:\s|$)").unwrap();
+    static ref REASON: Regex = Regex::new(r"(?i)\breason:? *(\d{1,5})\b").unwrap();
+    static ref SSID: Regex = Regex::new(r#""([^"]{0,32})""#).unwrap();
+    static ref PACKETS: Regex = Regex::new(r"(?i)\b(\d+) *(?:pkts|packets)\b").unwrap();
+    static ref DEAUTHS: Regex = Regex::new(r"(?i)\b(\d+) *deauths?\b").unwrap();
+}
+
+pub const BROADCAST: [u8; 6] = [0xff; 6];
+
+/// Management frame subtypes the deauther reports
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Deauth,
+    Disassoc,
+    Beacon,
+    ProbeRequest,
+}
+
+/// A frame reconstructed from its summary
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub kind: Kind,
+    pub source: [u8; 6],
+    pub destination: [u8; 6],
+    pub bssid: [u8; 6],
+    pub channel: Option<u8>,
+    pub rssi: Option<i8>,
+    pub reason: u16,
+    pub ssid: Option<String>,
+}
+
+/// Bytes of a MAC like `aa:bb:cc:dd:ee:ff`, anything that isn't hex becomes 0
+pub fn mac(text: &str) -> [u8; 6] {
+    let mut mac = [0; 6];
+    for (byte, hex) in mac.iter_mut().zip(text.split(':')) {
+        *byte = u8::from_str_radix(hex, 16).unwrap_or_default();
+    }
+    mac
+}
+
+/// Parse a frame summary of the packet monitor
+pub fn parse(line: &str) -> Option<Frame> {
+    frame(line, &FRAME)
+}
+
+/// Parse a frame summary, the type of frame found with `kind`
+fn frame(line: &str, kind: &Regex) -> Option<Frame> {
+    let kind = kind.captures(line)?[1].to_lowercase();
+    let kind = if kind.starts_with("deauth") {
+        Kind::Deauth
+    } else if kind.starts_with("disassoc") {
+        Kind::Disassoc
+    } else if kind == "beacon" {
+        Kind::Beacon
+    } else {
+        Kind::ProbeRequest
+    };
+
+    // Addresses are listed as source, destination, BSSID
+    let macs: Vec<[u8; 6]> = MAC.captures_iter(line).map(|caps| mac(&caps[1])).collect();
+    let source = *macs.first()?;
+    let destination = macs.get(1).copied().unwrap_or(BROADCAST);
+    let bssid = macs.get(2).copied().unwrap_or(match kind {
+        Kind::ProbeRequest => BROADCAST,
+        _ => source,
+    });
+
+    // MACs are removed so their digits aren't taken for a signal strength
+    let rest = MAC.replace_all(line, " ");
+
+    Some(Frame {
+        kind,
+        source,
+        destination,
+        bssid,
+        channel: CHANNEL.captures(&rest).and_then(|caps| caps[1].parse().ok()).filter(|ch| (1..=14).contains(ch)),
+        rssi: RSSI.captures(&rest).and_then(|caps| caps[1].parse().ok()),
+        // 1 = unspecified reason
+        reason: REASON.captures(&rest).and_then(|caps| caps[1].parse().ok()).unwrap_or(1),
+        ssid: SSID.captures(line).map(|caps| caps[1].to_string()),
+    })
+}
+
+/// Traffic seen on a channel, from a frame summary or a per-channel packet count
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Activity {
+    pub channel: u8,
+    pub packets: u64,
+    /// Deauth and disassoc frames among the packets
+    pub deauths: u64,
+}
+
+/// Parse channel activity, e.g. `ch 6: 42 pkts, 3 deauths` or a frame summary with a channel
+pub fn activity(line: &str) -> Option<Activity> {
+    if let Some(frame) = frame(line, &KIND) {
+        return Some(Activity {
+            channel: frame.channel?,
+            packets: 1,
+            deauths: matches!(frame.kind, Kind::Deauth | Kind::Disassoc) as u64,
+        });
+    }
+
+    let channel = CHANNEL.captures(line)?[1].parse().ok().filter(|ch| (1..=14).contains(ch))?;
+    Some(Activity {
+        channel,
+        packets: PACKETS.captures(line)?[1].parse().ok()?,
+        deauths: DEAUTHS.captures(line).and_then(|caps| caps[1].parse().ok()).unwrap_or(0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_frame_summaries() {
+        let frame = parse("Deauth aa:bb:cc:dd:ee:ff -> 11:22:33:44:55:66 ch 1 -56 reason 7").unwrap();
+        assert_eq!(frame.kind, Kind::Deauth);
+        assert_eq!(frame.source, [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+        assert_eq!(frame.destination, [0x11, 0x22, 0x33, 0x44, 0x55, 0x66]);
+        assert_eq!(frame.bssid, frame.source);
+        assert_eq!(frame.channel, Some(1));
+        assert_eq!(frame.rssi, Some(-56));
+        assert_eq!(frame.reason, 7);
+
+        let frame = parse(r#"Beacon aa:bb:cc:dd:ee:ff "MyWifi" ch 6"#).unwrap();
+        assert_eq!(frame.kind, Kind::Beacon);
+        assert_eq!(frame.ssid.as_deref(), Some("MyWifi"));
+    }
+
+    #[test]
+    fn takes_only_frames_for_pcap() {
+        assert_eq!(parse("Added aa:bb:cc:dd:ee:ff to the deauth targets"), None);
+        assert_eq!(parse("[ch 6] deauth aa:bb:cc:dd:ee:ff"), None);
+        assert_eq!(parse("Deauth attack: 3 targets"), None);
+    }
+
+    #[test]
+    fn counts_channel_activity() {
+        assert_eq!(channel_activity("ch 6: 42 pkts, 3 deauths"), Some((6, 42, 3)));
+        assert_eq!(channel_activity("[ch 6] deauth aa:bb:cc:dd:ee:ff"), Some((6, 1, 1)));
+        assert_eq!(channel_activity("ch 20: 42 pkts"), None);
+    }
+
+    fn channel_activity(line: &str) -> Option<(u8, u64, u64)> {
+        activity(line).map(|activity| (activity.channel, activity.packets, activity.deauths))
+    }
+}
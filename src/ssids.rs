@@ -0,0 +1,232 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Constraint, Rect},
+    style::Style,
+    widgets::{Block, Borders, Cell, Row, Table},
+    Frame,
+};
+use regex::Regex;
+
+use crate::command;
+use crate::i18n;
+use crate::listing::{self, Listing};
+use crate::picker;
+use crate::theme::Theme;
+
+// `show ssids` prints one entry per line, e.g. ` 0  "MyWifi"  WPA2`
+lazy_static::lazy_static! {
+    static ref ENTRY: Regex = Regex::new(r#"^\s*(\d+)\s+"([^"]{0,32})"\s*(.*)$"#).unwrap();
+}
+
+/// Default interval of `:ssid random` in seconds
+pub const RANDOM_INTERVAL: u64 = 10;
+
+/// Changes to the SSID list, typed as `:ssid ...`
+#[derive(Debug, PartialEq)]
+pub enum SsidCommand {
+    /// Open the SSID list
+    Show,
+    Add { name: String, wpa2: bool },
+    Remove(usize),
+    /// Replace an entry, it moves to the end of the list
+    Rename { id: usize, name: String, wpa2: bool },
+    Clear,
+    /// Let the deauther generate random SSIDs every given seconds
+    Random(u64),
+}
+
+/// Parse the arguments of `:ssid`
+pub fn parse(args: &str) -> Result<SsidCommand, String> {
+    let usage = || format!("Usage: {0}ssid [add <name> [-wpa2] | remove <id> | rename <id> <name> [-wpa2] | clear | random [seconds]]", command::PREFIX);
+    let (action, rest) = args.split_once(' ').map_or((args, ""), |(action, rest)| (action, rest.trim()));
+
+    match action.to_lowercase().as_str() {
+        "" => Ok(SsidCommand::Show),
+        "add" if !rest.is_empty() => {
+            let (name, wpa2) = wpa2(rest);
+            Ok(SsidCommand::Add { name: ssid(name)?, wpa2 })
+        }
+        "remove" => rest.parse().map(SsidCommand::Remove).map_err(|_| usage()),
+        "rename" => match rest.split_once(' ').map(|(id, name)| (id.parse(), wpa2(name.trim()))) {
+            Some((Ok(id), (name, wpa2))) if !name.is_empty() => Ok(SsidCommand::Rename { id, name: ssid(name)?, wpa2 }),
+            _ => Err(usage()),
+        },
+        "clear" => Ok(SsidCommand::Clear),
+        "random" if rest.is_empty() => Ok(SsidCommand::Random(RANDOM_INTERVAL)),
+        "random" => rest.parse().map(SsidCommand::Random).map_err(|_| usage()),
+        _ => Err(usage()),
+    }
+}
+
+/// Split the `-wpa2` flag off a name
+fn wpa2(name: &str) -> (&str, bool) {
+    match name.strip_suffix("-wpa2") {
+        Some(name) => (name.trim(), true),
+        None => (name, false),
+    }
+}
+
+fn ssid(name: &str) -> Result<String, String> {
+    let name = name.trim_matches('"');
+    if name.len() > 32 {
+        return Err(format!("'{}' is too long, SSIDs have at most 32 bytes", name));
+    }
+    Ok(name.to_string())
+}
+
+fn add(name: &str, wpa2: bool) -> String {
+    if wpa2 {
+        format!("add ssid \"{}\" -wpa2", name)
+    } else {
+        format!("add ssid \"{}\"", name)
+    }
+}
+
+/// Deauther commands carrying out the change, followed by a refresh of the list
+pub fn firmware_commands(cmd: &SsidCommand) -> Vec<String> {
+    let mut commands = match cmd {
+        SsidCommand::Show => vec![],
+        SsidCommand::Add { name, wpa2 } => vec![add(name, *wpa2)],
+        SsidCommand::Remove(id) => vec![format!("remove ssid {}", id)],
+        SsidCommand::Rename { id, name, wpa2 } => vec![format!("remove ssid {}", id), add(name, *wpa2)],
+        SsidCommand::Clear => vec!["remove ssids".to_string()],
+        SsidCommand::Random(secs) => vec![format!("enable random {}", secs)],
+    };
+    commands.push("show ssids".to_string());
+    commands
+}
+
+/// What the user did in the SSID list
+pub enum Action {
+    None,
+    Close,
+    /// Run a change
+    Run(SsidCommand),
+    /// Put a command into the input box to be completed
+    Edit(String),
+}
+
+struct Entry {
+    id: usize,
+    name: String,
+    /// Encryption and other flags as printed by the deauther
+    info: String,
+}
+
+impl Entry {
+    fn parse(line: &str) -> Option<Self> {
+        let caps = ENTRY.captures(line)?;
+        Some(Self {
+            id: caps[1].parse().unwrap_or_default(),
+            name: caps[2].to_string(),
+            info: caps[3].trim().to_string(),
+        })
+    }
+}
+
+/// Popup showing the SSID list of the deauther
+pub struct SsidManager {
+    entries: Listing<Entry>,
+}
+
+impl SsidManager {
+    pub fn new() -> Self {
+        Self { entries: Listing::new() }
+    }
+
+    /// Pick up entries from the output of `show ssids`
+    pub fn update(&mut self, line: &str) {
+        self.entries.update(line, Entry::parse);
+    }
+
+    /// Forget the entries and wait for the list to be printed again, so an emptied list shows up empty
+    pub fn reset(&mut self) {
+        self.entries.expect(true);
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if self.entries.handle_key(key) {
+            return Action::None;
+        }
+        match key.code {
+            KeyCode::Char('a') => return Action::Edit(format!("{}ssid add ", command::PREFIX)),
+            KeyCode::Char('e') => {
+                if let Some(entry) = self.entries.selected() {
+                    let wpa2 = if entry.info.to_uppercase().contains("WPA2") { " -wpa2" } else { "" };
+                    return Action::Edit(format!("{}ssid rename {} {}{}", command::PREFIX, entry.id, entry.name, wpa2));
+                }
+            }
+            KeyCode::Char('d') | KeyCode::Delete => {
+                if let Some(entry) = self.entries.selected() {
+                    return Action::Run(SsidCommand::Remove(entry.id));
+                }
+            }
+            KeyCode::Char('r') => return Action::Run(SsidCommand::Random(RANDOM_INTERVAL)),
+            KeyCode::Char('R') => return Action::Run(SsidCommand::Show),
+            KeyCode::Esc | KeyCode::Char('q') => return Action::Close,
+            _ => (),
+        }
+        Action::None
+    }
+
+    pub fn render(&mut self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.focus))
+            .title("SSIDs")
+            .title_bottom(i18n::text("ssids_keys"));
+
+        let rows: Vec<Row> = self
+            .entries
+            .items
+            .iter()
+            .map(|entry| {
+                Row::new(vec![
+                    Cell::from(entry.id.to_string()),
+                    Cell::from(entry.name.clone()),
+                    Cell::from(entry.info.clone()).style(Style::default().fg(theme.accent)),
+                ])
+            })
+            .collect();
+
+        let table = Table::new(rows, [Constraint::Length(4), Constraint::Length(34), Constraint::Min(4)])
+            .header(listing::header(vec!["ID", "SSID", ""]))
+            .block(block);
+        self.entries.render(f, picker::centered(area, 80, 60), table);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_changes() {
+        assert_eq!(parse(""), Ok(SsidCommand::Show));
+        assert_eq!(parse("add \"My Wifi\" -wpa2"), Ok(SsidCommand::Add { name: "My Wifi".to_string(), wpa2: true }));
+        assert_eq!(parse("remove 3"), Ok(SsidCommand::Remove(3)));
+        assert_eq!(parse("random"), Ok(SsidCommand::Random(RANDOM_INTERVAL)));
+        assert!(parse("add").is_err());
+        assert!(parse("add 123456789012345678901234567890123").is_err());
+    }
+
+    #[test]
+    fn keeps_wpa2_when_renaming() {
+        let rename = parse("rename 2 Guest -wpa2").unwrap();
+        assert_eq!(rename, SsidCommand::Rename { id: 2, name: "Guest".to_string(), wpa2: true });
+        assert_eq!(firmware_commands(&rename), ["remove ssid 2", "add ssid \"Guest\" -wpa2", "show ssids"]);
+    }
+
+    #[test]
+    fn collects_the_list() {
+        let mut manager = SsidManager::new();
+        manager.reset();
+        for line in ["SSIDs:", " 0  \"MyWifi\"  WPA2", " 1  \"Open\"", ""] {
+            manager.update(line);
+        }
+        // Scan results after the list look alike, but aren't entries
+        manager.update(" 2  \"Neighbor\"  -56");
+        let names: Vec<&str> = manager.entries.items.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["MyWifi", "Open"]);
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Time the rate of events is measured over
+pub const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Counters for the session summary, shared between the UI and the serial backend
+pub struct Stats {
+    started: Instant,
+    rx_bytes: AtomicU64,
+    tx_bytes: AtomicU64,
+    errors: AtomicU64,
+    connects: AtomicU64,
+    events: AtomicU64,
+    /// When the events within the last `RATE_WINDOW` happened
+    recent_events: Mutex<VecDeque<Instant>>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            rx_bytes: AtomicU64::new(0),
+            tx_bytes: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            connects: AtomicU64::new(0),
+            events: AtomicU64::new(0),
+            recent_events: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn add_rx(&self, bytes: usize) {
+        self.rx_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_tx(&self, bytes: usize) {
+        self.tx_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_connect(&self) {
+        self.connects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_event(&self) {
+        self.events.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut recent) = self.recent_events.lock() {
+            let now = Instant::now();
+            recent.push_back(now);
+            forget_old(&mut recent, now);
+        }
+    }
+
+    pub fn rx_bytes(&self) -> u64 {
+        self.rx_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn tx_bytes(&self) -> u64 {
+        self.tx_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    pub fn connects(&self) -> u64 {
+        self.connects.load(Ordering::Relaxed)
+    }
+
+    pub fn events(&self) -> u64 {
+        self.events.load(Ordering::Relaxed)
+    }
+
+    /// Events per second within the last `RATE_WINDOW`, or since the start if that's shorter
+    pub fn events_per_second(&self) -> f64 {
+        let now = Instant::now();
+        let count = match self.recent_events.lock() {
+            Ok(mut recent) => {
+                forget_old(&mut recent, now);
+                recent.len()
+            }
+            Err(_) => return 0.0,
+        };
+        let secs = self.elapsed().min(RATE_WINDOW).as_secs_f64();
+        if secs > 0.0 {
+            count as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+/// Drop the events that are older than `RATE_WINDOW`
+fn forget_old(recent: &mut VecDeque<Instant>, now: Instant) {
+    while recent.front().is_some_and(|time| now.duration_since(*time) > RATE_WINDOW) {
+        recent.pop_front();
+    }
+}
@@ -0,0 +1,43 @@
+use std::sync::{Arc, Mutex};
+
+use crate::firmware::Profile;
+use crate::serial::LinkSettings;
+
+/// Connection state shown in the status bar, updated by the serial backend
+#[derive(Clone, Debug, Default)]
+pub struct Status {
+    /// Port of the open connection
+    pub port: Option<String>,
+    /// Current line settings
+    pub link: LinkSettings,
+    /// Firmware of the device, once probed
+    pub firmware: Option<&'static dyn Profile>,
+    /// Resets of the device seen in its output
+    pub resets: u64,
+    /// Opening the port failed, the UI lets the user pick another one
+    pub pick_port: bool,
+    /// Nothing has been received for a while
+    pub stale: bool,
+    /// Nothing is written to the port
+    pub dry_run: bool,
+    /// Typed commands also go to the other running Huhnitors
+    pub broadcast: bool,
+    /// Broadcasts of other Huhnitors are ignored
+    pub skip_broadcasts: bool,
+    /// Typed commands that couldn't be written, for the UI to mark
+    pub failed: Vec<String>,
+}
+
+pub type SharedStatus = Arc<Mutex<Status>>;
+
+/// Change the status, ignoring a poisoned lock since the status is only informational
+pub fn update<F: FnOnce(&mut Status)>(status: &SharedStatus, f: F) {
+    if let Ok(mut status) = status.lock() {
+        f(&mut status);
+    }
+}
+
+/// Copy of the current status
+pub fn get(status: &SharedStatus) -> Status {
+    status.lock().map(|status| status.clone()).unwrap_or_default()
+}
@@ -0,0 +1,255 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+use regex::Regex;
+use std::collections::VecDeque;
+
+use crate::i18n;
+use crate::picker;
+use crate::theme::Theme;
+
+lazy_static::lazy_static! {
+    // `{mac}`, `{ssid}` or `{target_2}`, and `\{` for a literal brace
+    static ref PLACEHOLDER: Regex = Regex::new(r"\\\{|\{([A-Za-z_][A-Za-z0-9_-]*)\}").unwrap();
+    static ref MAC: Regex = Regex::new(r"(?i)\b[0-9a-f]{2}(?::[0-9a-f]{2}){5}\b").unwrap();
+}
+
+/// MAC addresses offered for a placeholder
+const RECENT_MACS: usize = 20;
+
+/// Names of the placeholders in a line, each once and in order
+pub fn placeholders(line: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for name in PLACEHOLDER.captures_iter(line).filter_map(|caps| caps.get(1)) {
+        if !names.iter().any(|known| known == name.as_str()) {
+            names.push(name.as_str().to_string());
+        }
+    }
+    names
+}
+
+/// Escape the braces of text that must not be taken for placeholders, e.g. a path
+pub fn escape(text: &str) -> String {
+    text.replace('{', "\\{")
+}
+
+/// The line to send for one without placeholders, `\{` becomes a brace
+pub fn literal(line: &str) -> String {
+    line.replace("\\{", "{")
+}
+
+/// Placeholders that take an address get the recently seen ones to pick from
+fn takes_mac(name: &str) -> bool {
+    let name = name.to_lowercase();
+    ["mac", "bssid", "station", "target"].iter().any(|word| name.contains(word))
+}
+
+/// MAC addresses seen in the output, the latest first
+#[derive(Default)]
+pub struct RecentMacs {
+    macs: VecDeque<String>,
+}
+
+impl RecentMacs {
+    pub fn update(&mut self, line: &str) {
+        for mac in MAC.find_iter(line) {
+            let mac = mac.as_str().to_lowercase();
+            self.macs.retain(|known| *known != mac);
+            self.macs.push_front(mac);
+        }
+        self.macs.truncate(RECENT_MACS);
+    }
+}
+
+pub enum Action {
+    /// All placeholders are filled in, send the line
+    Done(String),
+    Cancel,
+    None,
+}
+
+/// Asks for the value of each placeholder of a line in turn
+pub struct Form {
+    line: String,
+    names: Vec<String>,
+    values: Vec<String>,
+    /// Value typed for the current placeholder
+    input: String,
+    /// Recently seen MACs, offered for placeholders that take one
+    choices: Vec<String>,
+    state: ListState,
+}
+
+impl Form {
+    /// A form for the line, `None` if it has no placeholders
+    pub fn new(line: &str, recent: &RecentMacs) -> Option<Self> {
+        let names = placeholders(line);
+        if names.is_empty() {
+            return None;
+        }
+        let mut form = Self {
+            line: line.to_string(),
+            names,
+            values: Vec::new(),
+            input: String::new(),
+            choices: recent.macs.iter().cloned().collect(),
+            state: ListState::default(),
+        };
+        form.reset_choice();
+        Some(form)
+    }
+
+    fn name(&self) -> &str {
+        &self.names[self.values.len()]
+    }
+
+    fn choices(&self) -> &[String] {
+        if takes_mac(self.name()) {
+            &self.choices
+        } else {
+            &[]
+        }
+    }
+
+    fn reset_choice(&mut self) {
+        let any = !self.choices().is_empty();
+        self.state.select(if any { Some(0) } else { None });
+    }
+
+    /// What is asked for right now, for the inline and plain UI
+    pub fn question(&self) -> String {
+        match self.choices().first() {
+            Some(latest) => format!("{{{}}} ({}): ", self.name(), i18n::format("enter_for", &[&latest])),
+            None => format!("{{{}}}: ", self.name()),
+        }
+    }
+
+    /// Take the value of the current placeholder, returns the finished line once all are filled in.
+    /// An empty value picks the selected choice.
+    pub fn answer(&mut self, value: &str) -> Option<String> {
+        let value = match value.trim() {
+            "" => self.state.selected().and_then(|i| self.choices().get(i)).cloned().unwrap_or_default(),
+            value => value.to_string(),
+        };
+        self.values.push(value);
+        self.input.clear();
+        if self.values.len() < self.names.len() {
+            self.reset_choice();
+            return None;
+        }
+        let line = PLACEHOLDER.replace_all(&self.line, |caps: &regex::Captures| match caps.get(1) {
+            Some(placeholder) => {
+                let index = self.names.iter().position(|name| name == placeholder.as_str()).unwrap_or_default();
+                self.values[index].clone()
+            }
+            None => "{".to_string(),
+        });
+        Some(line.into_owned())
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Up => {
+                if let Some(i) = self.state.selected() {
+                    self.state.select(Some(i.saturating_sub(1)));
+                }
+            }
+            KeyCode::Down => {
+                if let Some(i) = self.state.selected() {
+                    self.state.select(Some((i + 1).min(self.choices().len().saturating_sub(1))));
+                }
+            }
+            KeyCode::Enter => {
+                let input = std::mem::take(&mut self.input);
+                if let Some(line) = self.answer(&input) {
+                    return Action::Done(line);
+                }
+            }
+            KeyCode::Esc => return Action::Cancel,
+            _ => (),
+        }
+        Action::None
+    }
+
+    /// Popup with the line, an input for the current placeholder and the MACs to pick from
+    pub fn render(&mut self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let area = picker::centered(area, 60, 50);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.focus))
+            .title(i18n::format("fill_in", &[&format!("{{{}}}", self.name()), &(self.values.len() + 1), &self.names.len()]))
+            .title_bottom(if self.choices().is_empty() {
+                i18n::text("fill_in_keys")
+            } else {
+                i18n::text("fill_in_pick_keys")
+            });
+        let inner = block.inner(area);
+        f.render_widget(Clear, area);
+        f.render_widget(block, area);
+
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Length(1), Constraint::Min(0)])
+            .split(inner);
+        f.render_widget(Paragraph::new(self.line.as_str()).style(Style::default().fg(theme.border)), rows[0]);
+        let input = Line::from(vec![
+            Span::styled(format!("{{{}}} ", self.name()), Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled(format!("{}_", self.input), Style::default().fg(theme.input)),
+        ]);
+        f.render_widget(Paragraph::new(input), rows[1]);
+
+        let items: Vec<ListItem> = self.choices().iter().map(|mac| ListItem::new(mac.clone())).collect();
+        let list = List::new(items)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        f.render_stateful_widget(list, rows[2], &mut self.state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(line: &str, values: &[&str]) -> Option<String> {
+        let mut form = Form::new(line, &RecentMacs::default())?;
+        values.iter().find_map(|value| form.answer(value))
+    }
+
+    #[test]
+    fn fills_in_each_placeholder_once() {
+        assert_eq!(placeholders("attack -b {mac} -t {time} {mac}"), ["mac", "time"]);
+        assert_eq!(fill("attack -b {mac} -t {time} {mac}", &["aa", "5"]).unwrap(), "attack -b aa -t 5 aa");
+    }
+
+    #[test]
+    fn leaves_escaped_braces() {
+        assert!(placeholders(r"add ssid \{mac}").is_empty());
+        assert_eq!(literal(r"add ssid \{mac}"), "add ssid {mac}");
+        assert_eq!(fill(r"add ssid \{x} {name}", &["y"]).unwrap(), "add ssid {x} y");
+    }
+
+    #[test]
+    fn escapes_paths() {
+        let line = format!("{} {{session_name}}", escape("huhn log /tmp/{date}.log"));
+        assert_eq!(placeholders(&line), ["session_name"]);
+        assert_eq!(fill(&line, &["survey"]).unwrap(), "huhn log /tmp/{date}.log survey");
+    }
+
+    #[test]
+    fn offers_recent_macs() {
+        let mut recent = RecentMacs::default();
+        recent.update("AA:BB:CC:DD:EE:01 and aa:bb:cc:dd:ee:02");
+        recent.update("aa:bb:cc:dd:ee:01");
+        let mut form = Form::new("deauth {bssid}", &recent).unwrap();
+        assert_eq!(form.answer(""), Some("deauth aa:bb:cc:dd:ee:01".to_string()));
+    }
+}
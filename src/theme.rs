@@ -0,0 +1,359 @@
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+use std::env;
+use std::str::FromStr;
+
+use crate::highlight::{self, Token};
+
+/// Names of the built-in themes
+pub const NAMES: [&str; 4] = ["default", "solarized", "high-contrast", "monochrome"];
+
+/// Colors of the TUI and of highlighted output
+#[derive(Clone, Copy, Debug)]
+pub struct Theme {
+    /// Style of each highlight category, in the order of the rules in `highlight`
+    pub categories: [Style; highlight::COUNT],
+    /// Lines that match no category
+    pub text: Style,
+    /// Sent commands when echo highlighting is on
+    pub echo: Style,
+    /// Border of the focused box
+    pub focus: Color,
+    /// Border of the other boxes
+    pub border: Color,
+    /// Text in the input box
+    pub input: Color,
+    /// Known commands, unknown commands, flags and placeholders in the input box
+    pub tokens: [Style; 4],
+    /// Secondary information like port metadata
+    pub accent: Color,
+}
+
+/// How many colors the terminal can show
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+pub enum ColorDepth {
+    /// The 16 named ANSI colors
+    #[serde(rename = "16")]
+    Ansi16,
+    /// The xterm 256 color palette
+    #[serde(rename = "256")]
+    Ansi256,
+    /// 24 bit RGB colors
+    #[serde(rename = "truecolor")]
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// Guess the color depth from the environment of the terminal
+    pub fn detect() -> Self {
+        let colorterm = env::var("COLORTERM").unwrap_or_default().to_lowercase();
+        let term = env::var("TERM").unwrap_or_default().to_lowercase();
+
+        if colorterm == "truecolor" || colorterm == "24bit" || env::var_os("WT_SESSION").is_some() {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+}
+
+/// Colors of the theme that can be overridden in the config file.
+/// Values can be color names, xterm palette indices or `#rrggbb`.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Overrides {
+    pub text: Option<String>,
+    pub echo: Option<String>,
+    pub focus: Option<String>,
+    pub border: Option<String>,
+    pub input: Option<String>,
+    pub accent: Option<String>,
+}
+
+/// RGB values of the 16 ANSI colors, in palette order
+const ANSI: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// Channel values of the 6x6x6 color cube of the xterm palette
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// RGB value of an xterm palette entry
+fn indexed_rgb(i: u8) -> (u8, u8, u8) {
+    match i {
+        0..=15 => ANSI[i as usize].1,
+        16..=231 => {
+            let i = i - 16;
+            (CUBE[(i / 36) as usize], CUBE[(i / 6 % 6) as usize], CUBE[(i % 6) as usize])
+        }
+        _ => {
+            let level = 8 + (i - 232) * 10;
+            (level, level, level)
+        }
+    }
+}
+
+/// Closest entry of the xterm 256 color palette, ignoring the 16 user-configurable ones
+fn nearest_indexed(rgb: (u8, u8, u8)) -> u8 {
+    (16..=255u8)
+        .min_by_key(|&i| distance(rgb, indexed_rgb(i)))
+        .unwrap_or(16)
+}
+
+/// Closest of the 16 named colors
+fn nearest_ansi(rgb: (u8, u8, u8)) -> Color {
+    ANSI.iter()
+        .min_by_key(|(_, ansi)| distance(rgb, *ansi))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::Reset)
+}
+
+/// Reduce a color to what the terminal can show
+fn downsample(color: Color, depth: ColorDepth) -> Color {
+    match (color, depth) {
+        (Color::Rgb(r, g, b), ColorDepth::Ansi256) => Color::Indexed(nearest_indexed((r, g, b))),
+        (Color::Rgb(r, g, b), ColorDepth::Ansi16) => nearest_ansi((r, g, b)),
+        (Color::Indexed(i), ColorDepth::Ansi16) => nearest_ansi(indexed_rgb(i)),
+        (color, _) => color,
+    }
+}
+
+fn downsample_style(style: Style, depth: ColorDepth) -> Style {
+    Style {
+        fg: style.fg.map(|c| downsample(c, depth)),
+        bg: style.bg.map(|c| downsample(c, depth)),
+        ..style
+    }
+}
+
+fn rgb(hex: u32) -> Color {
+    Color::Rgb((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+}
+
+fn fg(color: Color) -> Style {
+    Style::default().fg(color)
+}
+
+fn bold(color: Color) -> Style {
+    fg(color).add_modifier(Modifier::BOLD)
+}
+
+impl Theme {
+    /// Look up a built-in theme by name
+    pub fn by_name(name: &str) -> Option<Theme> {
+        match name.to_lowercase().as_str() {
+            "default" => Some(Self::default()),
+            "solarized" => Some(Self::solarized()),
+            "high-contrast" => Some(Self::high_contrast()),
+            "monochrome" => Some(Self::monochrome()),
+            _ => None,
+        }
+    }
+
+    /// Ethan Schoonover's Solarized palette, on a dark background
+    pub fn solarized() -> Self {
+        let base0 = rgb(0x839496);
+        let base01 = rgb(0x586e75);
+        let base1 = rgb(0x93a1a1);
+        let yellow = rgb(0xb58900);
+        let red = rgb(0xdc322f);
+        let magenta = rgb(0xd33682);
+        let violet = rgb(0x6c71c4);
+        let blue = rgb(0x268bd2);
+        let cyan = rgb(0x2aa198);
+        let green = rgb(0x859900);
+
+        Self {
+            categories: [
+                fg(base0),                // ASCII Chicken
+                bold(base1),              // # command
+                fg(blue),                 // ================
+                bold(yellow),             // [ ===== Headline ====== ]
+                fg(cyan),                 // > Finished job
+                fg(red),                  // ERROR: something went wrong :(
+                fg(green),                // -arg value
+                bold(violet),             // [default=something]
+                fg(yellow),               // command [-arg <value>] [-flag]
+            ],
+            text: fg(base0),
+            echo: fg(magenta).add_modifier(Modifier::ITALIC),
+            focus: yellow,
+            border: base01,
+            input: cyan,
+            tokens: [fg(green), fg(red), fg(yellow), fg(base01)],
+            accent: blue,
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Self {
+            categories: [
+                bold(Color::White),       // ASCII Chicken
+                bold(Color::White),       // # command
+                bold(Color::LightBlue),   // ================
+                bold(Color::LightYellow), // [ ===== Headline ====== ]
+                bold(Color::LightCyan),   // > Finished job
+                bold(Color::LightRed),    // ERROR: something went wrong :(
+                bold(Color::LightGreen),  // -arg value
+                bold(Color::LightGreen),  // [default=something]
+                bold(Color::LightYellow), // command [-arg <value>] [-flag]
+            ],
+            text: bold(Color::White),
+            echo: bold(Color::LightMagenta).add_modifier(Modifier::UNDERLINED),
+            focus: Color::LightYellow,
+            border: Color::White,
+            input: Color::LightYellow,
+            tokens: [
+                bold(Color::LightGreen),
+                bold(Color::LightRed),
+                bold(Color::LightYellow),
+                fg(Color::Gray),
+            ],
+            accent: Color::LightCyan,
+        }
+    }
+
+    /// No colors at all, categories are only told apart by text modifiers
+    pub fn monochrome() -> Self {
+        let plain = Style::default();
+        let strong = plain.add_modifier(Modifier::BOLD);
+        Self {
+            categories: [
+                plain,                                     // ASCII Chicken
+                strong,                                    // # command
+                plain.add_modifier(Modifier::DIM),         // ================
+                strong.add_modifier(Modifier::UNDERLINED), // [ ===== Headline ====== ]
+                plain,                                     // > Finished job
+                strong.add_modifier(Modifier::REVERSED),   // ERROR: something went wrong :(
+                plain,                                     // -arg value
+                strong,                                    // [default=something]
+                plain,                                     // command [-arg <value>] [-flag]
+            ],
+            text: plain,
+            echo: plain.add_modifier(Modifier::ITALIC),
+            focus: Color::Reset,
+            border: Color::Reset,
+            input: Color::Reset,
+            tokens: [
+                strong,
+                plain.add_modifier(Modifier::UNDERLINED),
+                plain,
+                plain.add_modifier(Modifier::DIM),
+            ],
+            accent: Color::Reset,
+        }
+    }
+
+    /// Apply the color overrides from the config file
+    pub fn with_overrides(mut self, overrides: &Overrides) -> Result<Self, String> {
+        fn parse(value: &Option<String>) -> Result<Option<Color>, String> {
+            value
+                .as_deref()
+                .map(|v| Color::from_str(v).map_err(|_| format!("Invalid color '{}'", v)))
+                .transpose()
+        }
+
+        if let Some(c) = parse(&overrides.text)? {
+            self.text = self.text.fg(c);
+        }
+        if let Some(c) = parse(&overrides.echo)? {
+            self.echo = self.echo.fg(c);
+        }
+        self.focus = parse(&overrides.focus)?.unwrap_or(self.focus);
+        self.border = parse(&overrides.border)?.unwrap_or(self.border);
+        self.input = parse(&overrides.input)?.unwrap_or(self.input);
+        self.accent = parse(&overrides.accent)?.unwrap_or(self.accent);
+
+        Ok(self)
+    }
+
+    /// Reduce all colors to what a terminal with the given color depth can show
+    pub fn fit(mut self, depth: ColorDepth) -> Self {
+        for style in self.categories.iter_mut() {
+            *style = downsample_style(*style, depth);
+        }
+        self.text = downsample_style(self.text, depth);
+        self.echo = downsample_style(self.echo, depth);
+        for style in self.tokens.iter_mut() {
+            *style = downsample_style(*style, depth);
+        }
+        self.focus = downsample(self.focus, depth);
+        self.border = downsample(self.border, depth);
+        self.input = downsample(self.input, depth);
+        self.accent = downsample(self.accent, depth);
+        self
+    }
+
+    /// Style of a part of the input line
+    pub fn token(&self, token: Token) -> Style {
+        match token {
+            Token::Command => self.tokens[0],
+            Token::Unknown => self.tokens[1],
+            Token::Flag => self.tokens[2],
+            Token::Placeholder => self.tokens[3],
+            Token::Text => fg(self.input),
+        }
+    }
+
+    /// Style of a line of output
+    pub fn style(&self, line: &str) -> Style {
+        self.category(highlight::classify(line))
+    }
+
+    /// Style of a highlight category, `None` for lines that match no rule
+    pub fn category(&self, category: Option<usize>) -> Style {
+        category.map(|i| self.categories[i]).unwrap_or(self.text)
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            categories: [
+                fg(Color::White),         // ASCII Chicken
+                bold(Color::White),       // # command
+                fg(Color::Blue),          // ================
+                bold(Color::Yellow),      // [ ===== Headline ====== ]
+                fg(Color::Cyan),          // > Finished job
+                fg(Color::Red),           // ERROR: something went wrong :(
+                fg(Color::Green),         // -arg value
+                bold(Color::Green),       // [default=something]
+                fg(Color::Yellow),        // command [-arg <value>] [-flag]
+            ],
+            text: fg(Color::White),
+            echo: fg(Color::Magenta).add_modifier(Modifier::ITALIC),
+            focus: Color::Yellow,
+            border: Color::White,
+            input: Color::Yellow,
+            tokens: [
+                fg(Color::Green),
+                fg(Color::Red),
+                fg(Color::Yellow),
+                fg(Color::Gray).add_modifier(Modifier::DIM),
+            ],
+            accent: Color::Cyan,
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use crossterm::execute;
+use crossterm::terminal::SetTitle;
+use std::env;
+use std::io::{self, Write};
+use std::process::Command;
+
+use crate::i18n;
+
+/// Keeps the terminal title, and the tmux window name inside tmux, up to date with the session
+pub struct TerminalTitle {
+    /// Title shown right now
+    current: String,
+    /// The tmux window as it was, `None` outside tmux
+    tmux: Option<TmuxWindow>,
+}
+
+/// What naming a tmux window changes
+struct TmuxWindow {
+    /// Pane of the Huhnitor, so the right window is restored even if another one is active by then
+    pane: String,
+    name: String,
+    /// `automatic-rename` of the window, `None` if it uses the global setting
+    automatic_rename: Option<String>,
+}
+
+impl TmuxWindow {
+    fn save() -> Option<Self> {
+        let pane = env::var("TMUX_PANE").ok().filter(|_| env::var_os("TMUX").is_some())?;
+        let name = tmux(&["display-message", "-p", "-t", &pane, "#W"])?;
+        // Only options set on the window itself are listed, e.g. `automatic-rename off`
+        let automatic_rename = tmux(&["show-window-options", "-t", &pane, "automatic-rename"])
+            .and_then(|line| line.split_whitespace().nth(1).map(str::to_string));
+        Some(Self { pane, name, automatic_rename })
+    }
+
+    fn restore(&self) {
+        tmux(&["rename-window", "-t", &self.pane, &self.name]);
+        // Naming the window turned off automatic renaming, put back what the user had
+        match &self.automatic_rename {
+            Some(value) => tmux(&["set-window-option", "-t", &self.pane, "automatic-rename", value]),
+            None => tmux(&["set-window-option", "-u", "-t", &self.pane, "automatic-rename"]),
+        };
+    }
+}
+
+/// Run a tmux command, returns what it printed if it succeeded
+fn tmux(args: &[&str]) -> Option<String> {
+    let output = Command::new("tmux").args(args).output().ok().filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Title for the connection state and the running attack
+pub fn text(port: Option<&str>, stale: bool, attack: Option<&str>) -> String {
+    let mut title = match port {
+        Some(port) if stale => i18n::format("title_stale", &[&port]),
+        Some(port) => format!("Huhnitor - {}", port),
+        None => i18n::text("title_not_connected").to_string(),
+    };
+    if let Some(kind) = attack {
+        title.push_str(&i18n::format("title_attack", &[&kind]));
+    }
+    title
+}
+
+impl TerminalTitle {
+    /// Remember the title of the terminal so it can be put back later
+    pub fn save() -> Self {
+        // Push the title onto the stack of xterm compatible terminals, others ignore it
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(b"\x1b[22;0t").and_then(|_| stdout.flush());
+        Self {
+            current: String::new(),
+            tmux: TmuxWindow::save(),
+        }
+    }
+
+    pub fn set(&mut self, title: String) -> io::Result<()> {
+        if title == self.current {
+            return Ok(());
+        }
+        let mut stdout = io::stdout();
+        execute!(stdout, SetTitle(&title))?;
+        if self.tmux.is_some() {
+            // Window name shown in the tmux status line
+            write!(stdout, "\x1bk{}\x1b\\", title)?;
+            stdout.flush()?;
+        }
+        self.current = title;
+        Ok(())
+    }
+
+    /// Put back the title from before
+    pub fn restore(&self) {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(b"\x1b[23;0t").and_then(|_| stdout.flush());
+        if let Some(window) = self.tmux.as_ref().filter(|_| !self.current.is_empty()) {
+            window.restore();
+        }
+    }
+}
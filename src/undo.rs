@@ -0,0 +1,70 @@
+/// Undo steps kept for the input line
+const LIMIT: usize = 100;
+
+/// Kinds of edits, a run of typed or deleted characters is undone as one word
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Edit {
+    Insert,
+    Delete,
+    /// The whole line changed, e.g. by completion or history
+    Replace,
+}
+
+/// Input line and cursor position
+type State = (String, usize);
+
+/// Undo and redo stacks of the input line
+#[derive(Default)]
+pub struct Undo {
+    undo: Vec<State>,
+    redo: Vec<State>,
+    /// Kind of the previous edit, to group runs of typing
+    last: Option<Edit>,
+}
+
+impl Undo {
+    /// Remember the line before an edit
+    pub fn record(&mut self, edit: Edit, line: &str, cursor: usize) {
+        if self.last != Some(edit) || edit == Edit::Replace {
+            self.undo.push((line.to_string(), cursor));
+            if self.undo.len() > LIMIT {
+                self.undo.remove(0);
+            }
+        }
+        self.last = Some(edit);
+        self.redo.clear();
+    }
+
+    /// Start a new undo step with the next edit, e.g. after a space or moving the cursor
+    pub fn split(&mut self) {
+        self.last = None;
+    }
+
+    /// Line before the last edit, given the current one
+    pub fn undo(&mut self, line: &str, cursor: usize) -> Option<State> {
+        let state = self.undo.pop()?;
+        self.redo.push((line.to_string(), cursor));
+        self.last = None;
+        Some(state)
+    }
+
+    /// Line before the last undo, given the current one
+    pub fn redo(&mut self, line: &str, cursor: usize) -> Option<State> {
+        let state = self.redo.pop()?;
+        self.undo.push((line.to_string(), cursor));
+        self.last = None;
+        Some(state)
+    }
+
+    /// Nothing to undo
+    pub fn is_empty(&self) -> bool {
+        self.undo.is_empty()
+    }
+
+    /// Forget everything, e.g. once the line was sent
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.last = None;
+    }
+}
@@ -0,0 +1,165 @@
+use axum::extract::State;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::Html;
+use axum::routing::get;
+use axum::Router;
+use futures::stream::{self, Stream};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+
+use crate::channel;
+use crate::i18n;
+use crate::output;
+use crate::redact::Redactor;
+
+/// Lines a new viewer gets to see from before it connected
+const BACKLOG: usize = 500;
+
+/// Lines that may wait for a slow viewer before it skips ahead
+const VIEWER_BUFFER: usize = 1000;
+
+/// The page follows the session with server-sent events, it can't send anything
+const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Huhnitor</title>
+<style>
+  body { margin: 0; background: #1d1f21; color: #c5c8c6; font: 14px monospace; }
+  header { position: sticky; top: 0; padding: 6px 10px; background: #282a2e; color: #f0c674; }
+  pre { margin: 0; padding: 10px; white-space: pre-wrap; word-break: break-all; }
+</style>
+</head>
+<body>
+<header>Huhnitor, read-only <span id="state">connecting...</span></header>
+<pre id="output"></pre>
+<script>
+  const output = document.getElementById("output");
+  const state = document.getElementById("state");
+  const events = new EventSource("events");
+  events.onopen = () => state.textContent = "live";
+  events.onerror = () => state.textContent = "disconnected, retrying...";
+  events.onmessage = (e) => {
+    const follow = window.innerHeight + window.scrollY >= document.body.scrollHeight - 20;
+    output.append(e.data + "\n");
+    if (follow) window.scrollTo(0, document.body.scrollHeight);
+  };
+</script>
+</body>
+</html>
+"#;
+
+#[derive(Clone)]
+struct Shared {
+    backlog: Arc<Mutex<VecDeque<String>>>,
+    lines: broadcast::Sender<String>,
+}
+
+impl Shared {
+    fn new() -> Self {
+        let (lines, _) = broadcast::channel(VIEWER_BUFFER);
+        Self { backlog: Arc::new(Mutex::new(VecDeque::with_capacity(BACKLOG))), lines }
+    }
+}
+
+/// Read-only live view of the session output in the browser
+pub struct Watch {
+    shared: Shared,
+    /// Viewers only get to see the redacted output
+    redactor: Redactor,
+}
+
+impl Watch {
+    pub async fn bind(addr: SocketAddr, notify_tx: &channel::Sender, redactor: Redactor) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let _ = notify_tx.notify(format!("> {}", i18n::format("watching", &[&listener.local_addr()?])));
+
+        let shared = Shared::new();
+        let app = Router::new()
+            .route("/", get(|| async { Html(PAGE) }))
+            .route("/events", get(events))
+            .with_state(shared.clone());
+
+        let notify_tx = notify_tx.downgrade();
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                output::notify(&notify_tx, format!("ERROR: {}", i18n::format("watch_stopped", &[&e])));
+            }
+        });
+
+        Ok(Self { shared, redactor })
+    }
+
+    /// Show a line of output to the viewers
+    pub fn push(&self, line: &str) {
+        // Carriage returns can't be sent as server-sent events
+        let line = self.redactor.line(line.trim_end()).replace('\r', "");
+        // Sent under the lock, so a new viewer gets each line either in the backlog or live
+        if let Ok(mut backlog) = self.shared.backlog.lock() {
+            if backlog.len() == BACKLOG {
+                backlog.pop_front();
+            }
+            backlog.push_back(line.clone());
+            // Nobody watching
+            let _ = self.shared.lines.send(line);
+        }
+    }
+}
+
+/// The backlog, then every new line
+async fn events(State(shared): State<Shared>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let (backlog, live) = match shared.backlog.lock() {
+        Ok(backlog) => (backlog.clone(), shared.lines.subscribe()),
+        Err(_) => (VecDeque::new(), shared.lines.subscribe()),
+    };
+
+    let stream = stream::unfold((backlog, live), |(mut backlog, mut live)| async move {
+        let line = match backlog.pop_front() {
+            Some(line) => line,
+            None => match live.recv().await {
+                Ok(line) => line,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => i18n::format("lines_skipped", &[&skipped]),
+                Err(broadcast::error::RecvError::Closed) => return None,
+            },
+        };
+        Some((Ok(Event::default().data(line)), (backlog, live)))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
+    use futures::StreamExt;
+
+    async fn event(body: &mut axum::body::BodyDataStream) -> String {
+        String::from_utf8(body.next().await.unwrap().unwrap().to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn sends_lines_as_events() {
+        let watch = Watch { shared: Shared::new(), redactor: Redactor::default() };
+        watch.push("Scanning...\r\n");
+
+        let response = events(State(watch.shared.clone())).await.into_response();
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        let mut body = response.into_body().into_data_stream();
+        assert_eq!(event(&mut body).await, "data: Scanning...\n\n");
+
+        watch.push("a\rb");
+        assert_eq!(event(&mut body).await, "data: ab\n\n");
+
+        // A viewer that falls behind is told what it missed, the buffer holds a power of two lines
+        for i in 0..=VIEWER_BUFFER.next_power_of_two() {
+            watch.push(&i.to_string());
+        }
+        assert_eq!(event(&mut body).await, format!("data: {}\n\n", i18n::format("lines_skipped", &[&1])));
+        assert_eq!(event(&mut body).await, "data: 1\n\n");
+    }
+}
@@ -0,0 +1,167 @@
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+use regex::Regex;
+use std::collections::VecDeque;
+
+use crate::command;
+use crate::theme::Theme;
+
+/// Values kept for the sparkline of a watch
+const HISTORY: usize = 24;
+
+/// Width of the sidebar with the watches
+pub const WIDTH: u16 = 32;
+
+/// Bars of the sparkline, from the lowest value to the highest
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Changes to the watch expressions, typed as `:watch ...`
+#[derive(Debug, PartialEq)]
+pub enum WatchCommand {
+    /// List the watches with their latest value
+    List,
+    /// Follow the first capture group of a regex
+    Add(String),
+    Remove(usize),
+    Clear,
+}
+
+/// Parse the arguments of `:watch`
+pub fn parse(args: &str) -> Result<WatchCommand, String> {
+    let usage = || format!("Usage: {0}watch [<regex> | remove <number> | clear]", command::PREFIX);
+    let (action, rest) = args.split_once(' ').map_or((args, ""), |(action, rest)| (action, rest.trim()));
+    match action.to_lowercase().as_str() {
+        "" => Ok(WatchCommand::List),
+        "remove" => rest.parse().map(WatchCommand::Remove).map_err(|_| usage()),
+        "clear" if rest.is_empty() => Ok(WatchCommand::Clear),
+        _ => Ok(WatchCommand::Add(args.to_string())),
+    }
+}
+
+/// A value extracted from the output, e.g. the free heap or a packet counter
+struct Watch {
+    regex: Regex,
+    /// The first named capture group, or the first one
+    group: usize,
+    /// Name of the capture group, or the regex without one
+    label: String,
+    latest: Option<String>,
+    /// Latest numeric values for the sparkline
+    history: VecDeque<f64>,
+}
+
+impl Watch {
+    fn new(pattern: &str) -> Result<Self, String> {
+        let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+        if regex.captures_len() < 2 {
+            return Err(format!("'{}' has no capture group for the value, e.g. heap: (\\d+)", pattern));
+        }
+        let (group, label) = regex
+            .capture_names()
+            .enumerate()
+            .find_map(|(i, name)| Some((i, name?.to_string())))
+            .unwrap_or((1, pattern.to_string()));
+        Ok(Self { regex, group, label, latest: None, history: VecDeque::with_capacity(HISTORY) })
+    }
+
+    fn update(&mut self, line: &str) {
+        let value = match self.regex.captures(line).and_then(|caps| caps.get(self.group)) {
+            Some(value) => value.as_str().trim(),
+            None => return,
+        };
+        if let Ok(number) = value.parse() {
+            if self.history.len() == HISTORY {
+                self.history.pop_front();
+            }
+            self.history.push_back(number);
+        }
+        self.latest = Some(value.to_string());
+    }
+
+    fn sparkline(&self) -> String {
+        let min = self.history.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = self.history.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        self.history
+            .iter()
+            .map(|value| match max - min {
+                // All the same, so in the middle
+                range if range <= 0.0 => BARS[BARS.len() / 2],
+                range => BARS[(((value - min) / range) * (BARS.len() - 1) as f64).round() as usize],
+            })
+            .collect()
+    }
+}
+
+/// Watch expressions shown in a sidebar next to the messages
+#[derive(Default)]
+pub struct Watches {
+    watches: Vec<Watch>,
+}
+
+impl Watches {
+    pub fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+
+    pub fn add(&mut self, pattern: &str) -> Result<(), String> {
+        self.watches.push(Watch::new(pattern)?);
+        Ok(())
+    }
+
+    /// Remove a watch by its number in the list, starting at 1
+    pub fn remove(&mut self, number: usize) -> Result<(), String> {
+        match number.checked_sub(1).filter(|i| *i < self.watches.len()) {
+            Some(i) => {
+                self.watches.remove(i);
+                Ok(())
+            }
+            None => Err(format!("There is no watch {}", number)),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.watches.clear();
+    }
+
+    /// Take the values out of a received line
+    pub fn update(&mut self, line: &str) {
+        for watch in &mut self.watches {
+            watch.update(line);
+        }
+    }
+
+    /// The watches with their number and latest value, for `:watch` and where there is no sidebar
+    pub fn lines(&self) -> Vec<String> {
+        if self.watches.is_empty() {
+            return vec![format!("> No watches, add one with {}watch <regex>", command::PREFIX)];
+        }
+        self.watches
+            .iter()
+            .enumerate()
+            .map(|(i, watch)| {
+                let value = watch.latest.as_deref().unwrap_or("-");
+                format!("> {} {} = {} {}", i + 1, watch.label, value, watch.sparkline())
+            })
+            .collect()
+    }
+
+    /// Sidebar with the label, latest value and sparkline of every watch
+    pub fn render(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let mut lines = Vec::new();
+        for watch in &self.watches {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{} ", watch.label), Style::default().add_modifier(Modifier::BOLD)),
+                Span::styled(watch.latest.clone().unwrap_or_else(|| "-".to_string()), Style::default().fg(theme.accent)),
+            ]));
+            lines.push(Line::styled(watch.sparkline(), Style::default().fg(theme.focus)));
+        }
+        let sidebar = Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(theme.border)).title("Watches"));
+        f.render_widget(sidebar, area);
+    }
+}
@@ -0,0 +1,286 @@
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    Frame, Terminal,
+};
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+
+use crate::i18n;
+use crate::picker;
+use crate::theme::{self, Theme};
+
+/// Baud rates to choose from, with what they're for
+const BAUDS: [(&str, Option<&str>); 7] = [
+    ("115200", Some("baud_deauther")),
+    ("auto", Some("baud_auto")),
+    ("9600", Some("baud_gps")),
+    ("74880", Some("baud_boot")),
+    ("230400", None),
+    ("460800", None),
+    ("921600", None),
+];
+
+/// Where the options of the config file are described
+const README: &str = "https://github.com/SpacehuhnTech/Huhnitor#config-file";
+
+/// First lines of the written config, so it's clear where it came from
+fn header() -> String {
+    i18n::format("setup_header", &[&README])
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Step {
+    Baud,
+    Theme,
+    LogDir,
+    AutoConnect,
+    Save,
+}
+
+impl Step {
+    const ALL: [Step; 5] = [Step::Baud, Step::Theme, Step::LogDir, Step::AutoConnect, Step::Save];
+
+    fn question(self) -> &'static str {
+        i18n::text(match self {
+            Step::Baud => "setup_baud",
+            Step::Theme => "setup_theme",
+            Step::LogDir => "setup_log_dir",
+            Step::AutoConnect => "setup_auto_connect",
+            Step::Save => "setup_save",
+        })
+    }
+}
+
+/// Answers of the wizard, written to the config file
+struct Wizard {
+    step: Step,
+    /// Selected entry of the current list
+    list: ListState,
+    baud: &'static str,
+    theme: &'static str,
+    log_dir: String,
+    auto_connect: bool,
+    /// Colors of the wizard itself, the chosen theme once there is one
+    colors: Theme,
+}
+
+/// Whether to offer the setup: on the first start in a terminal, when there is no config file yet
+pub fn wanted(config: Option<&Path>, path: &Path) -> bool {
+    config.is_none() && !path.exists() && io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+/// Ask for the basic settings and write them to the config file at `path`.
+/// Skipping writes a config file as well, so the setup isn't offered again.
+pub fn run(path: &Path, colors: Theme) -> io::Result<()> {
+    let mut wizard = Wizard::new(colors);
+
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    let result = Terminal::new(CrosstermBackend::new(io::stdout())).and_then(|mut terminal| wizard.event_loop(&mut terminal));
+    let _ = disable_raw_mode();
+    let _ = execute!(io::stdout(), LeaveAlternateScreen);
+
+    let text = match result? {
+        true => wizard.config(),
+        false => format!("{}# {}\n", header(), i18n::text("setup_skipped")),
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, text)
+}
+
+impl Wizard {
+    fn new(colors: Theme) -> Self {
+        Self {
+            step: Step::Baud,
+            list: ListState::default().with_selected(Some(0)),
+            baud: BAUDS[0].0,
+            theme: theme::NAMES[0],
+            // Logging stays off unless a directory is typed
+            log_dir: String::new(),
+            auto_connect: true,
+            colors,
+        }
+    }
+
+    /// Returns true once the answers are to be saved, false if the setup was skipped
+    fn event_loop(&mut self, terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> io::Result<bool> {
+        loop {
+            terminal.draw(|f| self.render(f))?;
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => match self.handle_key(key) {
+                    Some(save) => return Ok(save),
+                    None => continue,
+                },
+                _ => continue,
+            }
+        }
+    }
+
+    /// Number of entries to choose from in the current step
+    fn choices(&self) -> usize {
+        match self.step {
+            Step::Baud => BAUDS.len(),
+            Step::Theme => theme::NAMES.len(),
+            Step::AutoConnect => 2,
+            Step::LogDir | Step::Save => 0,
+        }
+    }
+
+    /// Returns whether to save once the wizard is done
+    fn handle_key(&mut self, key: KeyEvent) -> Option<bool> {
+        let selected = self.list.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Esc => return Some(false),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Some(false),
+            KeyCode::Up => self.list.select(Some(selected.saturating_sub(1))),
+            KeyCode::Down => self.list.select(Some((selected + 1).min(self.choices().saturating_sub(1)))),
+            KeyCode::Char(c) if self.step == Step::LogDir => self.log_dir.push(c),
+            KeyCode::Backspace if self.step == Step::LogDir => {
+                self.log_dir.pop();
+            }
+            KeyCode::Char('r') if self.step == Step::Save => self.go(Step::Baud),
+            KeyCode::Enter => {
+                match self.step {
+                    Step::Baud => self.baud = BAUDS[selected].0,
+                    Step::Theme => self.theme = theme::NAMES[selected],
+                    Step::AutoConnect => self.auto_connect = selected == 0,
+                    Step::LogDir => (),
+                    Step::Save => return Some(true),
+                }
+                let next = Step::ALL.iter().position(|step| *step == self.step).map_or(Step::Save, |i| Step::ALL[i + 1]);
+                self.go(next);
+            }
+            _ => (),
+        }
+        // The theme step previews the colors
+        if self.step == Step::Theme {
+            self.colors = Theme::by_name(theme::NAMES[self.list.selected().unwrap_or(0)]).unwrap_or(self.colors);
+        }
+        None
+    }
+
+    fn go(&mut self, step: Step) {
+        self.step = step;
+        self.list.select(Some(0));
+    }
+
+    /// The config file with the answers
+    fn config(&self) -> String {
+        let mut text = header();
+        text.push_str(&format!("\n# {}\n", i18n::text("setup_baud_comment")));
+        match self.baud {
+            "auto" => text.push_str("baud = \"auto\"\n"),
+            rate => text.push_str(&format!("baud = {}\n", rate)),
+        }
+        text.push_str(&format!("\n# {}\ntheme = \"{}\"\n", i18n::format("setup_theme_comment", &[&theme::NAMES.join(", ")]), self.theme));
+        text.push_str(&format!("\n# {}\n", i18n::text("setup_log_dir_comment")));
+        match self.log_dir.trim() {
+            "" => text.push_str("# log-dir = \"\"\n"),
+            dir => text.push_str(&format!("log-dir = {}\n", toml::Value::String(dir.to_string()))),
+        }
+        text.push_str(&format!("\n# {}\n", i18n::text("setup_auto_connect_comment")));
+        text.push_str(&format!("auto-connect = {}\n", self.auto_connect));
+        text
+    }
+
+    fn render(&mut self, f: &mut Frame) {
+        let area = picker::centered(f.size(), 70, 60);
+        let number = Step::ALL.iter().position(|step| *step == self.step).unwrap_or(0) + 1;
+        let hint = i18n::text(match self.step {
+            Step::LogDir => "setup_input_keys",
+            Step::Save => "setup_save_keys",
+            _ => "setup_keys",
+        });
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(self.colors.focus))
+            .title(i18n::format("setup_title", &[&number, &Step::ALL.len()]))
+            .title_bottom(hint);
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(0)])
+            .split(inner);
+        f.render_widget(Paragraph::new(self.step.question()).wrap(Wrap { trim: true }), chunks[0]);
+
+        let accent = Style::default().fg(self.colors.accent);
+        let items: Vec<ListItem> = match self.step {
+            Step::Baud => BAUDS
+                .iter()
+                .map(|(rate, info)| {
+                    let info = info.map(i18n::text).unwrap_or_default();
+                    ListItem::new(Line::from(vec![Span::raw(format!("{:<8}", rate)), Span::styled(info, accent)]))
+                })
+                .collect(),
+            Step::Theme => theme::NAMES.iter().map(|name| ListItem::new(*name)).collect(),
+            Step::AutoConnect => [("yes", "setup_auto_connect_yes"), ("no", "setup_auto_connect_no")]
+                .iter()
+                .map(|(answer, info)| {
+                    let answer = Span::raw(format!("{:<6}", i18n::text(answer)));
+                    ListItem::new(Line::from(vec![answer, Span::styled(i18n::text(info), accent)]))
+                })
+                .collect(),
+            Step::LogDir => {
+                let input = Paragraph::new(format!("{}_", self.log_dir))
+                    .style(Style::default().fg(self.colors.input))
+                    .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(self.colors.border)));
+                f.render_widget(input, Layout::default().constraints([Constraint::Length(3), Constraint::Min(0)]).split(chunks[1])[0]);
+                return;
+            }
+            Step::Save => {
+                f.render_widget(Paragraph::new(self.config()).style(accent), chunks[1]);
+                return;
+            }
+        };
+        let list = List::new(items).highlight_style(Style::default().add_modifier(Modifier::REVERSED)).highlight_symbol("> ");
+        f.render_stateful_widget(list, chunks[1], &mut self.list);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::serial::Baud;
+
+    fn config(wizard: &Wizard) -> Config {
+        let text = wizard.config();
+        assert!(text.starts_with(&header()));
+        toml::from_str(&text).unwrap()
+    }
+
+    #[test]
+    fn writes_the_defaults() {
+        let config = config(&Wizard::new(Theme::default()));
+        assert_eq!(config.baud, Some(Baud::Fixed(115200)));
+        assert_eq!(config.theme.as_deref(), Some(theme::NAMES[0]));
+        assert_eq!(config.log_dir, None);
+        assert_eq!(config.auto_connect, Some(true));
+    }
+
+    #[test]
+    fn writes_the_answers() {
+        let mut wizard = Wizard::new(Theme::default());
+        wizard.baud = "auto";
+        wizard.log_dir = " ~/Documents/\"huhnitor\" logs ".to_string();
+        wizard.auto_connect = false;
+        let config = config(&wizard);
+        assert_eq!(config.baud, Some(Baud::Auto));
+        assert_eq!(config.log_dir, Some("~/Documents/\"huhnitor\" logs".into()));
+        assert_eq!(config.auto_connect, Some(false));
+    }
+}